@@ -2,15 +2,18 @@
 // description: commandline application entry point with command handling
 // reference: application bootstrap and orchestration
 
+use crate::database::runs::{self, FileStatus};
 use crate::mcp::GitSummarizeMcp;
+use crate::pipeline::{IngestContext, maintain_after_ingest, process_files};
 use crate::{
-    AnswerGenerator, BatchInserter, ChunkOptions, Config, EmbeddingClient, FileScanner,
-    JsonExporter, LanceDbClient, MarkdownNormalizer, MarkdownParser, RepositorySync, SchemaManager,
-    Validator,
+    AnswerGenerator, BatchInserter, CachePruneOptions, ChunkOptions, CommitStore, Config, Embedder,
+    EmbeddingCache, EmbeddingProvenance, FileScanner, JsonExporter, LanceDbClient,
+    MaintenanceOptions, Reembedder, RefDiff, RepositorySync, Retriever, RunLog, RunRecorder,
+    RunStatus, SchemaManager, SearchFilter, StaleFinder, StatsCollector, TableMaintainer,
+    VectorIndexKind, VectorSearchOptions,
 };
 use anyhow::{Context, Result};
 use clap::{ArgAction, Parser, Subcommand};
-use futures::stream::{self, StreamExt};
 use rmcp::service::ServiceExt;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -58,14 +61,84 @@ enum Commands {
 
         #[arg(long, value_name = "NUM")]
         limit: Option<usize>,
+
+        /// Continue the last interrupted run, skipping files it already committed
+        #[arg(long, conflicts_with = "force")]
+        resume: bool,
+
+        /// Also index commit history (see repository.history)
+        #[arg(long)]
+        history: bool,
+
+        /// Ingest this branch, tag or commit next to the checked-out version
+        #[arg(long, value_name = "REF")]
+        at: Option<String>,
     },
 
     Verify {
         #[arg(long)]
         create_schema: bool,
+
+        /// Apply pending schema migrations (add missing columns with defaults)
+        #[arg(long)]
+        migrate: bool,
     },
 
-    Stats,
+    /// Report chunks and files per repository, language, extension and size
+    Stats {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Build or rebuild the ANN vector index on the embedding column
+    Index {
+        /// Rebuild even if an index already exists
+        #[arg(long)]
+        replace: bool,
+
+        /// Index type, overriding database.index.kind
+        #[arg(long, value_enum)]
+        kind: Option<VectorIndexKind>,
+
+        /// IVF partition count, overriding database.index.num_partitions
+        #[arg(long)]
+        num_partitions: Option<u32>,
+    },
+
+    /// Compact fragments, prune old table versions and optimize the index
+    Maintain {
+        /// Prune versions older than this many days (default: database.maintenance.retention_days)
+        #[arg(long, value_name = "DAYS")]
+        retention_days: Option<u64>,
+
+        #[arg(long)]
+        skip_compact: bool,
+
+        #[arg(long)]
+        skip_prune: bool,
+
+        #[arg(long)]
+        skip_reindex: bool,
+    },
+
+    /// Re-embed every stored chunk with the configured embedding model
+    Reembed {
+        #[arg(long)]
+        confirm: bool,
+    },
+
+    /// List, inspect or retry recorded ingestion runs
+    Runs {
+        #[command(subcommand)]
+        action: RunsAction,
+    },
+
+    /// Inspect or prune the embedding cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
 
     Reset {
         #[arg(long)]
@@ -102,6 +175,26 @@ enum Commands {
 
         #[arg(short, long)]
         repository: Option<String>,
+
+        /// Metadata filter, e.g. 'path:docs/ and ext:md and modified>30d'
+        #[arg(short, long)]
+        filter: Option<String>,
+
+        /// IVF partitions to probe (indexed tables only)
+        #[arg(long)]
+        nprobes: Option<usize>,
+
+        /// Re-rank limit * N candidates with full vectors (indexed tables only)
+        #[arg(long)]
+        refine_factor: Option<u32>,
+
+        /// Only search chunks ingested at this branch, tag or commit
+        #[arg(long, value_name = "REF")]
+        at: Option<String>,
+
+        /// Boost recently changed sections by this weight (overrides retrieval.recency_weight)
+        #[arg(long, value_name = "WEIGHT")]
+        recency: Option<f32>,
     },
 
     /// Ask a natural-language question; retrieves context and synthesizes a cited answer
@@ -115,6 +208,129 @@ enum Commands {
 
         #[arg(short, long)]
         repository: Option<String>,
+
+        /// Metadata filter, e.g. 'path:docs/ and not tag:draft'
+        #[arg(short, long)]
+        filter: Option<String>,
+
+        /// Answer from the chunks ingested at this branch, tag or commit
+        #[arg(long, value_name = "REF")]
+        at: Option<String>,
+
+        /// Prefer recently changed sections by this weight (overrides retrieval.recency_weight)
+        #[arg(long, value_name = "WEIGHT")]
+        recency: Option<f32>,
+    },
+
+    /// Compare the sections ingested at two refs: added, removed, modified and moved
+    Diff {
+        /// The older branch, tag or commit
+        from: String,
+
+        /// The newer branch, tag or commit
+        to: String,
+
+        /// The repository whose refs to compare (default: the configured one)
+        #[arg(short, long)]
+        repository: Option<String>,
+
+        /// Cosine similarity at which a removed and an added section count as moved
+        #[arg(long, value_name = "SIMILARITY")]
+        move_threshold: Option<f32>,
+
+        /// Also write a changelog of the differences with the generation model
+        #[arg(long)]
+        changelog: bool,
+
+        /// Print the report as JSON
+        #[arg(long, conflicts_with = "changelog")]
+        json: bool,
+    },
+
+    /// List sections whose lines no commit has changed in a number of months
+    Stale {
+        /// Months without a change before a section counts as stale
+        #[arg(long, default_value_t = 6)]
+        months: u32,
+
+        #[arg(short, long)]
+        repository: Option<String>,
+
+        /// Metadata filter, e.g. 'path:docs/ and not tag:archive'
+        #[arg(short, long)]
+        filter: Option<String>,
+
+        /// Only check chunks ingested at this branch, tag or commit
+        #[arg(long, value_name = "REF")]
+        at: Option<String>,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// What `ingest` (or `runs retry`) should do.
+#[derive(Default)]
+struct IngestOptions<'a> {
+    force: bool,
+    skip_sync: bool,
+    limit: Option<usize>,
+    /// Only re-ingest the files that failed in this run.
+    retry_of: Option<&'a str>,
+    resume: bool,
+    history: bool,
+    /// Ingest a snapshot of this ref instead of the checkout.
+    at: Option<String>,
+}
+
+/// How `diff` compares and reports.
+struct DiffOptions {
+    repository: Option<String>,
+    move_threshold: Option<f32>,
+    changelog: bool,
+    json: bool,
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Show how many embeddings are cached
+    Stats,
+
+    /// Remove entries for other models/dimensions, and optionally old ones
+    Prune {
+        /// Also remove entries cached more than this many days ago
+        #[arg(long, value_name = "DAYS")]
+        older_than_days: Option<u64>,
+
+        /// Remove every entry
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RunsAction {
+    /// Show the most recent runs
+    List {
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Show a run and its per-file events
+    Show {
+        /// Run id (or a unique prefix)
+        id: String,
+
+        /// Only show files with this status (success, failed, skipped)
+        #[arg(long)]
+        status: Option<String>,
+    },
+
+    /// Re-ingest only the files that failed in a previous run
+    Retry {
+        /// Run id (or a unique prefix)
+        id: String,
     },
 }
 
@@ -147,14 +363,60 @@ pub async fn run() -> Result<()> {
             force,
             skip_sync,
             limit,
+            resume,
+            history,
+            at,
         } => {
-            cmd_ingest(&config, force, skip_sync, limit).await?;
+            let options = IngestOptions {
+                force,
+                skip_sync,
+                limit,
+                retry_of: None,
+                resume,
+                history,
+                at,
+            };
+            cmd_ingest(&config, options).await?;
         }
-        Commands::Verify { create_schema } => {
-            cmd_verify(&config, create_schema).await?;
+        Commands::Verify {
+            create_schema,
+            migrate,
+        } => {
+            cmd_verify(&config, create_schema, migrate).await?;
+        }
+        Commands::Stats { json } => {
+            cmd_stats(&config, json).await?;
+        }
+        Commands::Index {
+            replace,
+            kind,
+            num_partitions,
+        } => {
+            cmd_index(&config, replace, kind, num_partitions).await?;
+        }
+        Commands::Maintain {
+            retention_days,
+            skip_compact,
+            skip_prune,
+            skip_reindex,
+        } => {
+            let options = MaintenanceOptions {
+                compact: !skip_compact,
+                prune: !skip_prune,
+                reindex: !skip_reindex,
+                retention_days: retention_days
+                    .unwrap_or(config.database.maintenance.retention_days),
+            };
+            cmd_maintain(&config, options).await?;
+        }
+        Commands::Reembed { confirm } => {
+            cmd_reembed(&config, confirm).await?;
+        }
+        Commands::Runs { action } => {
+            cmd_runs(&config, action).await?;
         }
-        Commands::Stats => {
-            cmd_stats(&config).await?;
+        Commands::Cache { action } => {
+            cmd_cache(&config, action).await?;
         }
         Commands::Reset { confirm } => {
             cmd_reset(&config, confirm).await?;
@@ -174,15 +436,90 @@ pub async fn run() -> Result<()> {
             query,
             limit,
             repository,
+            filter,
+            nprobes,
+            refine_factor,
+            at,
+            recency,
         } => {
-            cmd_search(&config, &query, limit, repository.as_deref()).await?;
+            let options = VectorSearchOptions {
+                nprobes,
+                refine_factor,
+            };
+            let filter = filter.as_deref().map(SearchFilter::parse).transpose()?;
+            let filter = SearchFilter::all(
+                filter
+                    .into_iter()
+                    .chain(at.as_deref().map(SearchFilter::at)),
+            );
+            cmd_search(
+                &config,
+                &query,
+                limit,
+                repository.as_deref(),
+                filter,
+                options,
+                recency,
+            )
+            .await?;
         }
         Commands::Ask {
             question,
             limit,
             repository,
+            filter,
+            at,
+            recency,
         } => {
-            cmd_ask(&config, &question, limit, repository.as_deref()).await?;
+            let filter = filter.as_deref().map(SearchFilter::parse).transpose()?;
+            let filter = SearchFilter::all(
+                filter
+                    .into_iter()
+                    .chain(at.as_deref().map(SearchFilter::at)),
+            );
+            cmd_ask(
+                &config,
+                &question,
+                limit,
+                repository.as_deref(),
+                filter,
+                recency,
+            )
+            .await?;
+        }
+        Commands::Diff {
+            from,
+            to,
+            repository,
+            move_threshold,
+            changelog,
+            json,
+        } => {
+            let options = DiffOptions {
+                repository,
+                move_threshold,
+                changelog,
+                json,
+            };
+            cmd_diff(&config, &from, &to, options).await?;
+        }
+        Commands::Stale {
+            months,
+            repository,
+            filter,
+            at,
+            json,
+        } => {
+            let filter = filter.as_deref().map(SearchFilter::parse).transpose()?;
+            let filter = SearchFilter::all(
+                repository
+                    .as_deref()
+                    .map(SearchFilter::repository)
+                    .into_iter()
+                    .chain(filter)
+                    .chain(at.as_deref().map(SearchFilter::at)),
+            );
+            cmd_stale(&config, months, filter, json).await?;
         }
     }
 
@@ -195,9 +532,22 @@ async fn cmd_sync(config: &Config, force: bool) -> Result<()> {
     let sync = RepositorySync::new(config.repository.clone());
 
     if force || config.repository.sync_on_start {
-        sync.sync().context("Repository sync failed")?;
-        let commit = sync.get_current_commit()?;
-        info!("Current commit: {}", commit);
+        let report = sync.sync().context("Repository sync failed")?;
+        if let Some(previous) = &report.previous_commit
+            && *previous != report.reference.commit
+        {
+            info!("Updated from {}", previous);
+        }
+        info!("Current commit: {}", report.reference.commit);
+        if !report.changes.is_empty() {
+            info!("Files changed: {}", report.changes);
+        }
+        if report.diverged {
+            warn!("Local branch has diverged from the remote and was left as is");
+        }
+        if report.dirty {
+            warn!("Working tree has uncommitted changes");
+        }
     } else {
         info!("Sync skipped (use --force to sync anyway)");
     }
@@ -205,14 +555,32 @@ async fn cmd_sync(config: &Config, force: bool) -> Result<()> {
     Ok(())
 }
 
-async fn cmd_ingest(
-    config: &Config,
-    force: bool,
-    skip_sync: bool,
-    limit: Option<usize>,
-) -> Result<()> {
+async fn cmd_ingest(config: &Config, options: IngestOptions<'_>) -> Result<()> {
     info!("Starting ingestion pipeline");
     let start_time = Instant::now();
+    let IngestOptions {
+        force,
+        skip_sync,
+        limit,
+        retry_of,
+        resume,
+        history,
+        at,
+    } = options;
+
+    if resume && !config.database.run_log.enabled {
+        return Err(anyhow::anyhow!(
+            "--resume needs the run log (database.run_log.enabled)"
+        ));
+    }
+
+    // Ctrl-C stops scheduling new files; in-flight files are still written
+    // and checkpointed. A second Ctrl-C terminates immediately.
+    // SAFETY: the handler only sets gix's interrupt flag.
+    let _interrupt_handler = unsafe { gix::interrupt::init_handler(1, || {}) }
+        .map(|handler| handler.auto_deregister())
+        .map_err(|e| warn!("Failed to install interrupt handler: {}", e))
+        .ok();
 
     if !skip_sync && config.repository.sync_on_start {
         info!("Syncing repository first");
@@ -237,23 +605,208 @@ async fn cmd_ingest(
             .context("Failed to initialize schema")?;
     }
 
+    schema_manager
+        .check_embedding_provenance(&config.embedding)
+        .await
+        .context("Refusing to ingest into a table embedded with a different model")?;
+    schema_manager
+        .ensure_migrated(config.embedding.dimension)
+        .await
+        .context("Failed to migrate the documents table")?;
+
+    // Chunks are stored per ref; a directory source has no refs.
+    let sync = RepositorySync::new(config.repository.clone());
+    let snapshot = match &at {
+        Some(name) => Some(
+            sync.snapshot(name)
+                .with_context(|| format!("Failed to export {}", name))?,
+        ),
+        None => None,
+    };
+    let reference = match &snapshot {
+        Some(snapshot) => Some(snapshot.reference.clone()),
+        None => match sync.current_reference() {
+            Ok(reference) => Some(reference),
+            Err(e) => {
+                warn!(
+                    "Failed to resolve the checked-out ref; storing chunks without one: {}",
+                    e
+                );
+                None
+            }
+        },
+    };
+    let context = IngestContext::new(reference);
+    if let Some(reference) = &context.reference {
+        info!("Ingesting {}", reference);
+    }
+
+    let root = match &snapshot {
+        Some(snapshot) => snapshot.path().to_path_buf(),
+        None => config.repository.working_dir(),
+    };
     let scanner = FileScanner::new(config.pipeline.clone());
-    let files = scanner
-        .scan_directory(&config.repository.local_path)
+    let mut files = scanner
+        .scan_directory(&root)
         .context("Failed to scan directory")?;
 
+    let context = if snapshot.is_some() {
+        if config.repository.submodules.enabled {
+            warn!("Submodules are not part of ref snapshots; ingesting the superproject only");
+        }
+        context
+    } else {
+        context
+            .with_submodules(&config.repository, &root, &mut files)
+            .context("Failed to read submodules")?
+    };
+
     info!("Found {} files to process", files.len());
 
+    let run_log = RunLog::new(&client, &config.database.run_log);
+    let files = match retry_of {
+        Some(id) => {
+            let failed: std::collections::HashSet<String> = run_log
+                .file_events(id, Some(FileStatus::Failed))
+                .await?
+                .into_iter()
+                .map(|event| event.relative_path)
+                .collect();
+            let files: Vec<_> = files
+                .into_iter()
+                .filter(|file| failed.contains(&file.relative_path))
+                .collect();
+            if files.len() < failed.len() {
+                warn!(
+                    "{} failed file(s) no longer exist in the checkout",
+                    failed.len() - files.len()
+                );
+            }
+            files
+        }
+        None => files,
+    };
+
+    let commit = match &context.reference {
+        Some(reference) => Some(reference.commit.clone()),
+        None => sync.get_current_commit().ok(),
+    };
+    let config_hash = runs::config_hash(config);
+
+    let resumed = if resume {
+        match run_log
+            .latest_unfinished(&config.repository.repository_id())
+            .await?
+        {
+            Some(run) if run.commit == commit && run.config_hash == config_hash => {
+                Some(RunRecorder::resume(run_log.clone(), run).await?)
+            }
+            Some(run) => {
+                warn!(
+                    "Run {} was for a different commit or configuration; starting a new run",
+                    run.run_id
+                );
+                None
+            }
+            None => {
+                info!("No unfinished run to resume; starting a new run");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let files = match &resumed {
+        Some(recorder) => {
+            let completed = recorder.completed_paths().await;
+            let remaining: Vec<_> = files
+                .into_iter()
+                .filter(|file| !completed.contains(&file.relative_path))
+                .collect();
+            info!(
+                "Skipping {} already committed file(s), {} remaining",
+                completed.len(),
+                remaining.len()
+            );
+            remaining
+        }
+        None => files,
+    };
+
     let files_to_process = if let Some(limit) = limit {
         files.into_iter().take(limit).collect()
     } else {
         files
     };
 
+    let mut context = context.with_blame(
+        &config.repository,
+        &config.repository.working_dir(),
+        &files_to_process,
+    );
+
     let mut config_modified = config.clone();
     config_modified.pipeline.force_reprocess = force;
 
-    let processed = process_files(&client, &config_modified, files_to_process).await?;
+    let recorder = match resumed {
+        Some(recorder) => Some(Arc::new(recorder)),
+        None if config.database.run_log.enabled => match RunRecorder::start(
+            run_log,
+            &config.repository.repository_id(),
+            commit,
+            config_hash,
+            retry_of.map(str::to_string),
+        )
+        .await
+        {
+            Ok(recorder) => Some(Arc::new(recorder)),
+            Err(e) => {
+                warn!("Failed to start run log: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    context.recorder = recorder.clone();
+    let result = process_files(&client, &config_modified, files_to_process, context).await;
+
+    let interrupted = gix::interrupt::is_triggered();
+    if let Some(recorder) = &recorder {
+        let status = if interrupted {
+            RunStatus::Interrupted
+        } else if result.is_ok() {
+            RunStatus::Completed
+        } else {
+            RunStatus::Failed
+        };
+        match recorder.finish(status).await {
+            Ok(run) => info!(
+                "Run {}: {} succeeded, {} failed",
+                run.run_id, run.files_succeeded, run.files_failed
+            ),
+            Err(e) => warn!("Failed to record run: {}", e),
+        }
+    }
+    let processed = result?.processed;
+
+    if interrupted {
+        warn!("Interrupted after {} file(s)", processed);
+        return Err(anyhow::anyhow!(
+            "Ingestion interrupted; run `ingest --resume` to continue"
+        ));
+    }
+
+    maintain_after_ingest(&client, config).await;
+
+    if history || config.repository.history.enabled {
+        let commits = CommitStore::new(&client, &config.repository.history)
+            .index(config, force)
+            .await
+            .context("Failed to index commit history")?;
+        info!("Indexed {} commit(s)", commits);
+    }
 
     let elapsed = start_time.elapsed();
     info!("Ingestion complete in {:.2}s", elapsed.as_secs_f64());
@@ -297,131 +850,195 @@ async fn cmd_export(
     Ok(())
 }
 
-async fn process_files(
-    client: &LanceDbClient,
-    config: &Config,
-    files: Vec<crate::ScannedFile>,
-) -> Result<usize> {
-    let client = Arc::new(client.clone());
-    let markdown_parser = Arc::new(MarkdownParser::new());
-    let normalizer = Arc::new(MarkdownNormalizer::new());
-    let embedding = Arc::new(EmbeddingClient::new(config.embedding.clone()));
-    let config = Arc::new(config.clone());
-
-    let parallel_workers = config.pipeline.parallel_workers.max(1);
-
-    let results = stream::iter(files.into_iter().map(|file| {
-        let client = Arc::clone(&client);
-        let markdown_parser = Arc::clone(&markdown_parser);
-        let normalizer = Arc::clone(&normalizer);
-        let embedding = Arc::clone(&embedding);
-        let config = Arc::clone(&config);
-
-        async move {
-            let file_start = Instant::now();
-            let inserter = BatchInserter::new(client.as_ref(), embedding).with_options(
-                ChunkOptions::default(),
-                config.embedding.allow_fallback,
-            );
+async fn cmd_verify(config: &Config, create_schema: bool, migrate: bool) -> Result<()> {
+    info!("Verifying database schema");
 
-            let result = process_single_file(
-                &inserter,
-                markdown_parser.as_ref(),
-                normalizer.as_ref(),
-                config.as_ref(),
-                &file,
-            )
-            .await;
+    let client = LanceDbClient::new(config.database.clone())
+        .await
+        .context("Failed to create LanceDB client")?;
 
-            let processing_time = file_start.elapsed().as_millis() as u32;
-            let status = if result.is_ok() { "success" } else { "failed" };
-            let error_message = match &result {
-                Ok(_) => String::new(),
-                Err(err) => err.to_string(),
-            };
+    if !client.ping().await? {
+        error!("Cannot connect to LanceDB");
+        return Err(anyhow::anyhow!("Database connection failed"));
+    }
+
+    info!("Database connection successful");
+
+    let schema_manager = SchemaManager::new(&client);
+
+    if schema_manager.verify_schema().await? {
+        info!("Schema verification passed - all tables exist");
+    } else {
+        warn!("Schema verification failed - some tables are missing");
+
+        if create_schema {
+            info!("Creating schema");
+            schema_manager
+                .initialize()
+                .await
+                .context("Failed to create schema")?;
+            info!("Schema created successfully");
+        } else {
+            info!("Use --create-schema to create missing tables");
+        }
+    }
 
-            if let Err(log_err) = inserter
-                .log_processing(
-                    &file.path.display().to_string(),
-                    status,
-                    &error_message,
-                    processing_time,
-                )
+    if let Some(plan) = schema_manager
+        .migration_plan(config.embedding.dimension)
+        .await?
+    {
+        if plan.is_up_to_date() {
+            info!("Schema is up to date (version {})", plan.to_version);
+        } else if migrate {
+            info!("{}", plan);
+            schema_manager
+                .apply_migrations(&plan)
                 .await
-            {
-                error!(
-                    "Failed to log processing result for {}: {}",
-                    file.relative_path, log_err
-                );
+                .context("Schema migration failed")?;
+        } else {
+            warn!("{}", plan);
+            if plan.can_apply() {
+                info!("Use --migrate to apply pending migrations");
             }
-
-            (file, result, processing_time)
         }
-    }))
-    .buffer_unordered(parallel_workers)
-    .collect::<Vec<_>>()
-    .await;
+    }
 
-    let mut total_processed = 0;
+    log_index_status(config, &schema_manager).await?;
+    log_embedding_provenance(config, &schema_manager).await?;
 
-    for (file, result, processing_time) in results {
-        match result {
-            Ok(_) => {
-                total_processed += 1;
-                info!("Processed: {} ({} ms)", file.relative_path, processing_time);
+    Ok(())
+}
+
+async fn log_embedding_provenance(
+    config: &Config,
+    schema_manager: &SchemaManager<'_>,
+) -> Result<()> {
+    match schema_manager.embedding_provenance().await? {
+        Some(provenance) => {
+            info!("Embeddings: {}", provenance);
+            if let Err(e) = schema_manager
+                .check_embedding_provenance(&config.embedding)
+                .await
+            {
+                warn!("{}", e);
             }
-            Err(e) => {
-                error!("Failed to process {}: {}", file.relative_path, e);
+        }
+        None => info!("Embeddings: provenance not recorded (written before tracking)"),
+    }
+    Ok(())
+}
+
+async fn log_index_status(config: &Config, schema_manager: &SchemaManager<'_>) -> Result<()> {
+    match schema_manager.vector_index_status().await? {
+        Some(status) => {
+            info!("Vector index: {}", status);
+            let configured = format!("{:?}", config.database.index.metric).to_lowercase();
+            if status.metric.as_deref().is_some_and(|m| m != configured) {
+                warn!(
+                    "Index metric differs from database.index.metric ({}); rebuild with `index --replace`",
+                    configured
+                );
+            }
+            if status.unindexed_rows > 0 {
+                info!(
+                    "{} row(s) are not yet indexed and are searched by brute force",
+                    status.unindexed_rows
+                );
             }
         }
+        None => info!("Vector index: none (searches scan the full table)"),
+    }
+    Ok(())
+}
+
+async fn cmd_stats(config: &Config, json: bool) -> Result<()> {
+    info!("Gathering statistics");
+
+    let client = LanceDbClient::new(config.database.clone())
+        .await
+        .context("Failed to create LanceDB client")?;
+
+    if !client.ping().await? {
+        error!("Cannot connect to LanceDB");
+        return Err(anyhow::anyhow!("Database connection failed"));
+    }
+
+    let mut collector = StatsCollector::new(&client);
+    let local_path = config.repository.working_dir();
+    if local_path.exists() {
+        let files = FileScanner::new(config.pipeline.clone()).scan_directory(&local_path)?;
+        collector = collector.with_source_files(
+            &config.repository.repository_id(),
+            files.into_iter().map(|f| f.relative_path).collect(),
+        );
+    }
+    let stats = collector
+        .collect()
+        .await
+        .context("Failed to collect statistics")?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
     }
 
-    Ok(total_processed)
+    println!("\n{}", stats);
+    let schema_manager = SchemaManager::new(&client);
+    log_index_status(config, &schema_manager).await?;
+    log_embedding_provenance(config, &schema_manager).await?;
+
+    Ok(())
 }
 
-async fn process_single_file(
-    inserter: &BatchInserter<'_>,
-    markdown_parser: &MarkdownParser,
-    normalizer: &MarkdownNormalizer,
+async fn cmd_index(
     config: &Config,
-    file: &crate::ScannedFile,
+    replace: bool,
+    kind: Option<VectorIndexKind>,
+    num_partitions: Option<u32>,
 ) -> Result<()> {
-    Validator::validate_file_path(&file.path)?;
+    info!("Building vector index");
 
-    let content = std::fs::read_to_string(&file.path).context("Failed to read file")?;
+    let client = LanceDbClient::new(config.database.clone())
+        .await
+        .context("Failed to create LanceDB client")?;
 
-    Validator::validate_content_not_empty(&content)?;
+    if !client.ping().await? {
+        error!("Cannot connect to LanceDB");
+        return Err(anyhow::anyhow!("Database connection failed"));
+    }
 
-    let normalized_content = if config.extraction.normalize_markdown {
-        normalizer.normalize(&content)?
-    } else {
-        content
-    };
+    let mut index_config = config.database.index.clone();
+    if let Some(kind) = kind {
+        index_config.kind = kind;
+    }
+    if num_partitions.is_some() {
+        index_config.num_partitions = num_partitions;
+    }
 
-    // Validate markdown structure
-    markdown_parser.parse(&normalized_content)?;
-
-    let chunks_inserted = inserter
-        .insert_file(
-            &file.path.display().to_string(),
-            &file.relative_path,
-            &normalized_content,
-            file.modified,
-            &config.repository.source_url,
-            config.extraction.normalize_markdown,
-        )
-        .await?;
+    let schema_manager = SchemaManager::new(&client);
+    if !replace && let Some(status) = schema_manager.vector_index_status().await? {
+        info!("Vector index already exists: {}", status);
+        info!("Use --replace to rebuild it");
+        return Ok(());
+    }
+
+    let start_time = Instant::now();
+    let status = schema_manager
+        .create_vector_index(&index_config, replace)
+        .await
+        .context("Failed to create vector index")?;
 
     info!(
-        "Inserted {} chunk(s) for {}",
-        chunks_inserted, file.relative_path
+        "Vector index ready in {:.2}s: {}",
+        start_time.elapsed().as_secs_f64(),
+        status
     );
 
     Ok(())
 }
 
-async fn cmd_verify(config: &Config, create_schema: bool) -> Result<()> {
-    info!("Verifying database schema");
+async fn cmd_maintain(config: &Config, options: MaintenanceOptions) -> Result<()> {
+    info!("Running table maintenance");
 
     let client = LanceDbClient::new(config.database.clone())
         .await
@@ -432,33 +1049,139 @@ async fn cmd_verify(config: &Config, create_schema: bool) -> Result<()> {
         return Err(anyhow::anyhow!("Database connection failed"));
     }
 
-    info!("Database connection successful");
+    let start_time = Instant::now();
+    let report = TableMaintainer::new(&client)
+        .run(options)
+        .await
+        .context("Table maintenance failed")?;
 
-    let schema_manager = SchemaManager::new(&client);
+    println!(
+        "\nMaintenance complete in {:.2}s",
+        start_time.elapsed().as_secs_f64()
+    );
+    println!("{}", report);
 
-    if schema_manager.verify_schema().await? {
-        info!("Schema verification passed - all tables exist");
+    Ok(())
+}
+
+async fn cmd_reembed(config: &Config, confirm: bool) -> Result<()> {
+    let client = LanceDbClient::new(config.database.clone())
+        .await
+        .context("Failed to create LanceDB client")?;
+
+    if !client.ping().await? {
+        error!("Cannot connect to LanceDB");
+        return Err(anyhow::anyhow!("Database connection failed"));
+    }
+
+    let target = EmbeddingProvenance::from_config(&config.embedding);
+    let current = SchemaManager::new(&client).embedding_provenance().await?;
+    let rows = client.get_document_count().await?;
+
+    if !confirm {
+        match &current {
+            Some(current) => info!("Table embedded with: {}", current),
+            None => info!("Table embedding provenance: not recorded"),
+        }
+        info!("Configured model: {}", target);
+        error!(
+            "This will re-embed {} chunk(s) with the configured model. Use --confirm to proceed",
+            rows
+        );
+        return Ok(());
+    }
+
+    let start_time = Instant::now();
+    let embedder = Embedder::from_config(&client, &config.embedding);
+    let inserter = BatchInserter::from_embedder(&client, &embedder)
+        .with_options(ChunkOptions::default(), false);
+
+    let report = Reembedder::new(&client, inserter, config.embedding.clone())
+        .run()
+        .await
+        .context("Re-embedding failed")?;
+
+    // Commit rows are searched with the same query vectors, so they move to
+    // the new model with the documents.
+    let commits = CommitStore::new(&client, &config.repository.history);
+    let commit_rows = if client.table_exists(commits.table_name()).await? {
+        let inserter = BatchInserter::from_embedder(&client, &embedder)
+            .with_options(ChunkOptions::default(), false);
+        Reembedder::new(&client, inserter, config.embedding.clone())
+            .with_table(commits.table_name())
+            .run()
+            .await
+            .context("Re-embedding the commits table failed")?
+            .rows
     } else {
-        warn!("Schema verification failed - some tables are missing");
+        0
+    };
 
-        if create_schema {
-            info!("Creating schema");
-            schema_manager
-                .initialize()
+    embedder.flush_cache().await;
+
+    println!(
+        "\nRe-embedded {} chunk(s) and {} commit row(s) in {:.2}s",
+        report.rows,
+        commit_rows,
+        start_time.elapsed().as_secs_f64()
+    );
+    match &report.from {
+        Some(from) => println!("{} -> {}", from, report.to),
+        None => println!("Now: {}", report.to),
+    }
+
+    Ok(())
+}
+
+async fn cmd_cache(config: &Config, action: CacheAction) -> Result<()> {
+    let client = LanceDbClient::new(config.database.clone())
+        .await
+        .context("Failed to create LanceDB client")?;
+
+    if !client.ping().await? {
+        error!("Cannot connect to LanceDB");
+        return Err(anyhow::anyhow!("Database connection failed"));
+    }
+
+    let cache = EmbeddingCache::new(&client, &config.embedding);
+
+    match action {
+        CacheAction::Stats => {
+            let (total, current) = cache
+                .entry_counts()
                 .await
-                .context("Failed to create schema")?;
-            info!("Schema created successfully");
-        } else {
-            info!("Use --create-schema to create missing tables");
+                .context("Failed to read embedding cache")?;
+            println!("\n=== Embedding Cache ===");
+            println!("Table: {}", config.embedding.cache.table_name);
+            println!("Entries: {}", total);
+            println!(
+                "Current model ({}, {} dims): {}",
+                config.embedding.model, config.embedding.dimension, current
+            );
+            println!("Other models/dimensions: {}", total - current);
+            if !config.embedding.cache.enabled {
+                println!("(cache disabled in embedding.cache.enabled)");
+            }
+        }
+        CacheAction::Prune {
+            older_than_days,
+            all,
+        } => {
+            let removed = cache
+                .prune(CachePruneOptions {
+                    older_than_days,
+                    all,
+                })
+                .await
+                .context("Failed to prune embedding cache")?;
+            println!("Removed {} embedding cache entries", removed);
         }
     }
 
     Ok(())
 }
 
-async fn cmd_stats(config: &Config) -> Result<()> {
-    info!("Gathering statistics");
-
+async fn cmd_runs(config: &Config, action: RunsAction) -> Result<()> {
     let client = LanceDbClient::new(config.database.clone())
         .await
         .context("Failed to create LanceDB client")?;
@@ -468,8 +1191,83 @@ async fn cmd_stats(config: &Config) -> Result<()> {
         return Err(anyhow::anyhow!("Database connection failed"));
     }
 
-    let doc_count = client.get_document_count().await?;
-    info!("Total documents: {}", doc_count);
+    let run_log = RunLog::new(&client, &config.database.run_log);
+
+    match action {
+        RunsAction::List { limit } => {
+            let runs = run_log.list_runs(limit).await?;
+            if runs.is_empty() {
+                println!("No ingestion runs recorded");
+                return Ok(());
+            }
+            println!(
+                "{:<36}  {:<23}  {:<9}  {:>6}  {:>6}  {:>7}  COMMIT",
+                "RUN", "STARTED", "STATUS", "FILES", "FAILED", "CHUNKS"
+            );
+            for run in runs {
+                println!(
+                    "{:<36}  {:<23}  {:<9}  {:>6}  {:>6}  {:>7}  {}",
+                    run.run_id,
+                    runs::format_time(run.started_at),
+                    run.status.as_str(),
+                    run.files_total,
+                    run.files_failed,
+                    run.chunks_written,
+                    run.commit.as_deref().map_or("-", |c| &c[..c.len().min(12)])
+                );
+            }
+        }
+        RunsAction::Show { id, status } => {
+            let status = status
+                .as_deref()
+                .map(|s| {
+                    FileStatus::parse(s).with_context(|| {
+                        format!("Unknown status '{}' (use success, failed or skipped)", s)
+                    })
+                })
+                .transpose()?;
+            let run = run_log
+                .get_run(&id)
+                .await?
+                .with_context(|| format!("No run matches '{}'", id))?;
+            println!("{}", run);
+
+            let events = run_log.file_events(&run.run_id, status).await?;
+            if !events.is_empty() {
+                println!();
+                for event in events {
+                    println!(
+                        "{:<8} {:>4} chunk(s) {:>6} ms  {}{}",
+                        event.status.as_str(),
+                        event.chunks,
+                        event.duration_ms,
+                        event.relative_path,
+                        event.error.map(|e| format!(": {}", e)).unwrap_or_default()
+                    );
+                }
+            }
+        }
+        RunsAction::Retry { id } => {
+            let run = run_log
+                .get_run(&id)
+                .await?
+                .with_context(|| format!("No run matches '{}'", id))?;
+            if run.files_failed == 0 {
+                println!("Run {} has no failed files to retry", run.run_id);
+                return Ok(());
+            }
+            info!(
+                "Retrying {} failed file(s) from run {}",
+                run.files_failed, run.run_id
+            );
+            let options = IngestOptions {
+                skip_sync: true,
+                retry_of: Some(&run.run_id),
+                ..Default::default()
+            };
+            cmd_ingest(config, options).await?;
+        }
+    }
 
     Ok(())
 }
@@ -491,6 +1289,10 @@ async fn cmd_reset(config: &Config, confirm: bool) -> Result<()> {
         .drop_all_tables()
         .await
         .context("Failed to drop tables")?;
+    CommitStore::new(&client, &config.repository.history)
+        .drop_table()
+        .await
+        .context("Failed to drop the commits table")?;
 
     info!("All tables dropped");
 
@@ -537,6 +1339,9 @@ async fn cmd_search(
     query: &str,
     limit: usize,
     repository_filter: Option<&str>,
+    filter: Option<SearchFilter>,
+    options: VectorSearchOptions,
+    recency: Option<f32>,
 ) -> Result<()> {
     info!("Searching for: {}", query);
 
@@ -549,11 +1354,11 @@ async fn cmd_search(
         return Err(anyhow::anyhow!("Database connection failed"));
     }
 
-    let query_embedding = embed_query(config, query).await?;
-
-    // Perform search
-    let results = client
-        .vector_search(query_embedding, limit, repository_filter)
+    let results = Retriever::new(&client, config)?
+        .with_search_options(options)
+        .with_filter(filter)
+        .with_recency(recency)
+        .retrieve(query, limit, repository_filter)
         .await
         .context("Vector search failed")?;
 
@@ -562,7 +1367,7 @@ async fn cmd_search(
         println!("\nNo results found for query: \"{}\"\n", query);
         println!("Try:");
         println!("  - Using different search terms");
-        println!("  - Removing repository filter");
+        println!("  - Removing the repository or metadata filter");
         println!("  - Checking that documents have been ingested");
         return Ok(());
     }
@@ -572,12 +1377,20 @@ async fn cmd_search(
     println!("{}", "=".repeat(80));
 
     for (idx, result) in results.iter().enumerate() {
-        println!("\n{}. {} (Score: {:.4})", idx + 1, result.location(), result.score);
+        println!(
+            "\n{}. {} (Score: {:.4})",
+            idx + 1,
+            result.location(),
+            result.score
+        );
         println!("   Repository: {}", result.repository_url);
 
         if let Some(distance) = result.distance {
             println!("   Distance: {:.4}", distance);
         }
+        if let Some(change) = result.last_change() {
+            println!("   Last {}", change);
+        }
 
         let preview: String = result.content.chars().take(300).collect();
         println!("   Preview:");
@@ -592,28 +1405,13 @@ async fn cmd_search(
     Ok(())
 }
 
-/// Embed a query string using the configured embedding provider. Honors the
-/// `allow_fallback` flag; otherwise a failed call surfaces as an error.
-async fn embed_query(config: &Config, query: &str) -> Result<Vec<f32>> {
-    let embedding = EmbeddingClient::new(config.embedding.clone());
-    match embedding.generate_embedding(query).await {
-        Ok(v) => Ok(v),
-        Err(e) if config.embedding.allow_fallback => {
-            warn!("Query embedding failed ({e}); using non-semantic fallback (degraded)");
-            Ok(EmbeddingClient::generate_fallback_embedding(
-                query,
-                config.embedding.dimension,
-            ))
-        }
-        Err(e) => Err(e).context("Failed to embed query"),
-    }
-}
-
 async fn cmd_ask(
     config: &Config,
     question: &str,
     limit: usize,
     repository_filter: Option<&str>,
+    filter: Option<SearchFilter>,
+    recency: Option<f32>,
 ) -> Result<()> {
     info!("Answering: {}", question);
 
@@ -626,19 +1424,22 @@ async fn cmd_ask(
         return Err(anyhow::anyhow!("Database connection failed"));
     }
 
-    let query_embedding = embed_query(config, question).await?;
-
-    let results = client
-        .vector_search(query_embedding, limit, repository_filter)
+    let results = Retriever::new(&client, config)?
+        .with_filter(filter)
+        .with_recency(recency)
+        .with_history(config.repository.history.context_results)
+        .retrieve(question, limit, repository_filter)
         .await
         .context("Vector search failed")?;
 
     if results.is_empty() {
-        println!("\nNo relevant context found. Ingest a repository first, or broaden the question.\n");
+        println!(
+            "\nNo relevant context found. Ingest a repository first, or broaden the question.\n"
+        );
         return Ok(());
     }
 
-    let generator = AnswerGenerator::new(config.generation.clone());
+    let generator = AnswerGenerator::new(config.generation.clone())?;
     let answer = generator
         .answer(question, results)
         .await
@@ -656,6 +1457,87 @@ async fn cmd_ask(
             source.score
         );
     }
+    match answer.usage {
+        Some(usage) => println!(
+            "\nTokens: {} context, {} prompt, {} completion",
+            answer.context_tokens, usage.prompt_tokens, usage.completion_tokens
+        ),
+        None => println!("\nTokens: {} context", answer.context_tokens),
+    }
+
+    Ok(())
+}
+
+async fn cmd_diff(config: &Config, from: &str, to: &str, options: DiffOptions) -> Result<()> {
+    info!("Comparing {} with {}", from, to);
+
+    let client = LanceDbClient::new(config.database.clone())
+        .await
+        .context("Failed to create LanceDB client")?;
+
+    if !client.ping().await? {
+        error!("Cannot connect to LanceDB");
+        return Err(anyhow::anyhow!("Database connection failed"));
+    }
+
+    let repository = options
+        .repository
+        .unwrap_or_else(|| config.repository.repository_id());
+    let mut diff = RefDiff::new(&client, &repository);
+    if let Some(threshold) = options.move_threshold {
+        diff = diff.with_move_threshold(threshold);
+    }
+    let report = diff.compare(from, to).await.context("Diff failed")?;
 
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("\n{}", report);
+    if options.changelog {
+        let generator = AnswerGenerator::new(config.generation.clone())?;
+        let changelog = report
+            .changelog(&generator)
+            .await
+            .context("Changelog generation failed")?;
+        println!("{}", "-".repeat(80));
+        println!("{}\n", changelog);
+    }
+
+    Ok(())
+}
+
+async fn cmd_stale(
+    config: &Config,
+    months: u32,
+    filter: Option<SearchFilter>,
+    json: bool,
+) -> Result<()> {
+    let cutoff = chrono::Utc::now()
+        .checked_sub_months(chrono::Months::new(months))
+        .map_or(0, |t| t.timestamp().max(0) as u64);
+    info!("Looking for sections unchanged in {} month(s)", months);
+
+    let client = LanceDbClient::new(config.database.clone())
+        .await
+        .context("Failed to create LanceDB client")?;
+
+    if !client.ping().await? {
+        error!("Cannot connect to LanceDB");
+        return Err(anyhow::anyhow!("Database connection failed"));
+    }
+
+    let report = StaleFinder::new(&client)
+        .with_filter(filter)
+        .find(cutoff)
+        .await
+        .context("Failed to read chunk dates")?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("\n{}", report);
+    }
     Ok(())
 }
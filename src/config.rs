@@ -3,8 +3,10 @@
 // reference: https://docs.rs/config
 
 use crate::error::{PipelineError, Result};
+use crate::repository::{HistoryWalker, RepositorySource, SubmoduleIndex};
 use dotenvy::dotenv;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -13,16 +15,197 @@ pub struct Config {
     pub database: DatabaseConfig,
     pub embedding: EmbeddingConfig,
     pub generation: GenerationConfig,
+    #[serde(default)]
+    pub retrieval: RetrievalConfig,
     pub pipeline: PipelineConfig,
     pub extraction: ExtractionConfig,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RepositoryConfig {
+    /// A URL to clone, or a `file://` URL / path of a local git checkout or
+    /// plain directory, which is read in place.
     pub source_url: String,
+    /// Where remote sources are cloned; unused for local sources.
     pub local_path: PathBuf,
     pub branch: String,
     pub sync_on_start: bool,
+    /// Fetch only this many commits of history (a shallow clone); unset
+    /// fetches everything.
+    #[serde(default)]
+    pub depth: Option<u32>,
+    /// Directories to check out (cone-mode sparse checkout); empty checks
+    /// out the whole tree. Applies when cloning.
+    #[serde(default)]
+    pub sparse_paths: Vec<String>,
+    /// What to do when the local branch has commits the remote does not.
+    #[serde(default)]
+    pub on_divergence: DivergencePolicy,
+    #[serde(default)]
+    pub submodules: SubmoduleConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub blame: BlameConfig,
+}
+
+/// Git submodules, cloned into the checkout on sync and indexed as part of
+/// the parent repository.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SubmoduleConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Also initialize submodules of submodules.
+    #[serde(default)]
+    pub recursive: bool,
+    /// Submodule paths (relative to the repository root) not to initialize.
+    #[serde(default)]
+    pub skip: Vec<String>,
+    /// File filters keyed by submodule path.
+    #[serde(default)]
+    pub filters: BTreeMap<String, SubmoduleFilter>,
+}
+
+/// Globs matched against paths relative to the submodule root. An empty
+/// `include` keeps every file; `exclude` wins over `include`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SubmoduleFilter {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Commit history, embedded into its own table so `ask` can answer "why" and
+/// "when" questions from commit messages and diffs.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistoryConfig {
+    /// Index history on every `ingest`; `ingest --history` does it once.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Revision range to walk, e.g. `v1.4.0..HEAD`; unset walks back from
+    /// HEAD.
+    #[serde(default)]
+    pub range: Option<String>,
+    /// Walk at most this many commits, newest first.
+    #[serde(default = "default_history_max_commits")]
+    pub max_commits: usize,
+    /// Also embed the diff hunks of each changed text file.
+    #[serde(default)]
+    pub include_diffs: bool,
+    /// Globs limiting which files are diffed; empty diffs every text file.
+    #[serde(default)]
+    pub diff_paths: Vec<String>,
+    /// Files larger than this (either version) are listed but not diffed.
+    #[serde(default = "default_history_max_diff_bytes")]
+    pub max_diff_bytes: u64,
+    #[serde(default = "default_commits_table")]
+    pub table_name: String,
+    /// Commits retrieved next to document chunks by `ask`; 0 searches
+    /// documents only.
+    #[serde(default = "default_history_context_results")]
+    pub context_results: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            range: None,
+            max_commits: default_history_max_commits(),
+            include_diffs: false,
+            diff_paths: Vec::new(),
+            max_diff_bytes: default_history_max_diff_bytes(),
+            table_name: default_commits_table(),
+            context_results: default_history_context_results(),
+        }
+    }
+}
+
+fn default_history_max_commits() -> usize {
+    1000
+}
+
+fn default_history_max_diff_bytes() -> u64 {
+    256 * 1024
+}
+
+fn default_commits_table() -> String {
+    "commits".to_string()
+}
+
+fn default_history_context_results() -> usize {
+    3
+}
+
+/// Per-line git blame, recording on every chunk the last commit that changed
+/// its lines, with that commit's author and date.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BlameConfig {
+    /// Blame on every `ingest`. Off by default: it walks the history of
+    /// every ingested file.
+    #[serde(default)]
+    pub enabled: bool,
+    /// First-parent commits walked back from the ingested commit. Lines
+    /// older than that are left undated.
+    #[serde(default = "default_blame_max_commits")]
+    pub max_commits: usize,
+    /// Larger files (and binary ones) are not blamed.
+    #[serde(default = "default_blame_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+impl Default for BlameConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_commits: default_blame_max_commits(),
+            max_file_bytes: default_blame_max_file_bytes(),
+        }
+    }
+}
+
+fn default_blame_max_commits() -> usize {
+    5000
+}
+
+fn default_blame_max_file_bytes() -> u64 {
+    1024 * 1024
+}
+
+impl RepositoryConfig {
+    pub fn source(&self) -> RepositorySource {
+        RepositorySource::parse(&self.source_url)
+    }
+
+    /// The directory that is scanned: a local source in place, otherwise
+    /// the clone at `local_path`.
+    pub fn working_dir(&self) -> PathBuf {
+        match self.source() {
+            RepositorySource::Local(path) => path,
+            RepositorySource::Remote(_) => self.local_path.clone(),
+        }
+    }
+
+    /// Stable identifier stored in the `repository_url` column.
+    pub fn repository_id(&self) -> String {
+        self.source().id()
+    }
+}
+
+/// How `sync` handles a local branch that can no longer be fast-forwarded
+/// to the remote one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergencePolicy {
+    /// Keep the local commit checked out and warn.
+    #[default]
+    Skip,
+    /// Discard local commits: move the branch to the remote commit and
+    /// check it out.
+    Reset,
+    /// Fail the sync.
+    Fail,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -30,6 +213,144 @@ pub struct DatabaseConfig {
     pub uri: String,
     pub table_name: String,
     pub batch_size: usize,
+    #[serde(default)]
+    pub index: VectorIndexConfig,
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    #[serde(default)]
+    pub run_log: RunLogConfig,
+}
+
+/// Persistent record of ingestion runs (`runs` table) and per-file outcomes
+/// (`file_events` table). Like the embedding cache these live beside the
+/// documents table and survive `reset`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RunLogConfig {
+    #[serde(default = "default_run_log_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_runs_table")]
+    pub runs_table: String,
+    #[serde(default = "default_file_events_table")]
+    pub file_events_table: String,
+}
+
+impl Default for RunLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_run_log_enabled(),
+            runs_table: default_runs_table(),
+            file_events_table: default_file_events_table(),
+        }
+    }
+}
+
+fn default_run_log_enabled() -> bool {
+    true
+}
+
+fn default_runs_table() -> String {
+    "runs".to_string()
+}
+
+fn default_file_events_table() -> String {
+    "file_events".to_string()
+}
+
+/// Table maintenance: fragment compaction, old-version pruning and index
+/// optimization. Every write creates new fragments and a new table version,
+/// so an ingest run leaves many small files behind until this runs.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MaintenanceConfig {
+    /// Run maintenance automatically at the end of `ingest`.
+    #[serde(default)]
+    pub after_ingest: bool,
+    /// Table versions older than this are pruned and their files deleted.
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            after_ingest: false,
+            retention_days: default_retention_days(),
+        }
+    }
+}
+
+fn default_retention_days() -> u64 {
+    7
+}
+
+/// ANN index on the `embedding` column. Without one every search is a
+/// brute-force scan, which is exact but grows linearly with the table.
+///
+/// `metric` is used both to build the index and at search time, so changing
+/// it requires rebuilding the index (`index --replace`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VectorIndexConfig {
+    #[serde(default)]
+    pub kind: VectorIndexKind,
+    #[serde(default)]
+    pub metric: DistanceMetric,
+    /// Build the index automatically after ingestion once the table holds at
+    /// least this many rows. `0` disables automatic indexing.
+    #[serde(default = "default_auto_index_threshold")]
+    pub auto_threshold: usize,
+    /// IVF partitions; derived from the row count when unset.
+    #[serde(default)]
+    pub num_partitions: Option<u32>,
+    /// PQ sub-vectors (IVF-PQ only); derived from the dimension when unset.
+    #[serde(default)]
+    pub num_sub_vectors: Option<u32>,
+    /// IVF partitions probed per query. More is slower with better recall.
+    #[serde(default)]
+    pub nprobes: Option<usize>,
+    /// Re-rank `limit * refine_factor` candidates with full vectors, recovering
+    /// precision lost to quantization.
+    #[serde(default)]
+    pub refine_factor: Option<u32>,
+}
+
+impl Default for VectorIndexConfig {
+    fn default() -> Self {
+        Self {
+            kind: VectorIndexKind::default(),
+            metric: DistanceMetric::default(),
+            auto_threshold: default_auto_index_threshold(),
+            num_partitions: None,
+            num_sub_vectors: None,
+            nprobes: None,
+            refine_factor: None,
+        }
+    }
+}
+
+fn default_auto_index_threshold() -> usize {
+    50_000
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorIndexKind {
+    /// IVF partitions with product-quantized vectors: compact, fast to build.
+    #[default]
+    #[value(name = "ivf_pq")]
+    IvfPq,
+    /// IVF partitions with an HNSW graph per partition over scalar-quantized
+    /// vectors: higher recall, larger index.
+    Hnsw,
+}
+
+/// Vector distance used for indexing and search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    /// Euclidean distance; the search default before any index existed.
+    #[default]
+    L2,
+    Cosine,
+    Dot,
 }
 
 /// Embedding provider configuration.
@@ -50,10 +371,130 @@ pub struct EmbeddingConfig {
     /// poison the index with vectors that share no space with real embeddings.
     #[serde(default)]
     pub allow_fallback: bool,
+    #[serde(default)]
+    pub limits: EmbeddingLimits,
+    #[serde(default)]
+    pub cache: EmbeddingCacheConfig,
+}
+
+/// On-disk cache of embeddings keyed by `(model, dimension, sha256(text))`,
+/// kept in its own table next to the documents table so `reset` does not
+/// discard it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EmbeddingCacheConfig {
+    #[serde(default = "default_cache_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_cache_table_name")]
+    pub table_name: String,
+    /// Pending entries buffered in memory before they are written.
+    #[serde(default = "default_cache_flush_size")]
+    pub flush_size: usize,
+}
+
+impl Default for EmbeddingCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_cache_enabled(),
+            table_name: default_cache_table_name(),
+            flush_size: default_cache_flush_size(),
+        }
+    }
+}
+
+fn default_cache_enabled() -> bool {
+    true
+}
+
+fn default_cache_table_name() -> String {
+    "embedding_cache".to_string()
+}
+
+fn default_cache_flush_size() -> usize {
+    1024
+}
+
+/// Request shaping for the embeddings API. During ingestion chunks from many
+/// files are coalesced into requests of up to `batch_size` inputs and
+/// `max_batch_tokens` (estimated) tokens, paced by the per-minute limits.
+/// Retries apply to every embedding call, including query embedding.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EmbeddingLimits {
+    #[serde(default = "default_embedding_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_max_batch_tokens")]
+    pub max_batch_tokens: usize,
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub tokens_per_minute: Option<u32>,
+    /// Requests allowed in flight at once.
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// How long to wait for more chunks before sending a partial batch.
+    #[serde(default = "default_linger_ms")]
+    pub linger_ms: u64,
+    /// Retries for 429, 5xx and connection errors, with exponential backoff
+    /// starting at `initial_backoff_ms`. A `Retry-After` header takes precedence.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl Default for EmbeddingLimits {
+    fn default() -> Self {
+        Self {
+            batch_size: default_embedding_batch_size(),
+            max_batch_tokens: default_max_batch_tokens(),
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            max_concurrent_requests: default_max_concurrent_requests(),
+            linger_ms: default_linger_ms(),
+            max_retries: default_max_retries(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+fn default_embedding_batch_size() -> usize {
+    96
+}
+
+fn default_max_batch_tokens() -> usize {
+    100_000
+}
+
+fn default_max_concurrent_requests() -> usize {
+    4
+}
+
+fn default_linger_ms() -> u64 {
+    20
+}
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
 }
 
 /// Answer-generation (LLM) configuration for the RAG question-answering step.
-/// Defaults to Groq's OpenAI-compatible chat completions endpoint.
+/// Defaults to Groq's OpenAI-compatible chat completions endpoint; `backend`
+/// selects Ollama's native API or a llama.cpp server instead. `api_key` is
+/// optional and only sent when set, so unauthenticated local servers work.
+///
+/// Context is packed best-first until either `max_context_chunks` chunks are
+/// included or the token budget runs out. The budget is `max_context_tokens`
+/// minus `reserved_answer_tokens` minus the prompt scaffolding.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GenerationConfig {
     pub base_url: String,
@@ -62,12 +503,214 @@ pub struct GenerationConfig {
     pub api_key: Option<String>,
     #[serde(default = "default_max_context_chunks")]
     pub max_context_chunks: usize,
+    /// Tokens the whole request may use (the model's usable context window).
+    #[serde(default = "default_max_context_tokens")]
+    pub max_context_tokens: usize,
+    /// Tokens held back for the answer; also sent as the completion `max_tokens`.
+    #[serde(default = "default_reserved_answer_tokens")]
+    pub reserved_answer_tokens: usize,
+    #[serde(default)]
+    pub tokenizer: TokenizerKind,
+    #[serde(default)]
+    pub backend: GenerationBackend,
+    #[serde(default)]
+    pub options: GenerationOptions,
+}
+
+/// Wire protocol used to talk to the generation server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationBackend {
+    /// `POST {base_url}/chat/completions` (Groq, OpenAI, vLLM, LM Studio, ...).
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// Ollama's native `POST {base_url}/api/chat`.
+    Ollama,
+    /// llama.cpp `llama-server`, via its OpenAI-compatible
+    /// `POST {base_url}/chat/completions` plus llama.cpp-specific fields.
+    LlamaCpp,
+}
+
+/// Backend-specific request options. Options a backend does not understand
+/// are not sent to it.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct GenerationOptions {
+    /// Ollama: context window to load the model with. Defaults to
+    /// `max_context_tokens`, since Ollama's own default silently truncates
+    /// longer prompts.
+    #[serde(default)]
+    pub num_ctx: Option<usize>,
+    /// Ollama: how long the model stays loaded after the request (e.g. "5m").
+    #[serde(default)]
+    pub keep_alive: Option<String>,
+    /// llama.cpp: reuse the KV cache from the previous request's common prefix.
+    #[serde(default)]
+    pub cache_prompt: Option<bool>,
+    /// Request timeout in seconds; local models on CPU can be slow.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 fn default_max_context_chunks() -> usize {
     8
 }
 
+fn default_max_context_tokens() -> usize {
+    8192
+}
+
+fn default_reserved_answer_tokens() -> usize {
+    1024
+}
+
+/// Tokenizer used to measure context against the token budget. Exact counts
+/// depend on the serving model; the BPE encodings are close enough for
+/// budgeting on most modern models.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenizerKind {
+    /// GPT-4o / gpt-oss family encoding.
+    #[default]
+    O200kBase,
+    /// GPT-4 / GPT-3.5 family encoding.
+    Cl100kBase,
+    /// ~4 characters per token; no vocabulary needed.
+    Approximate,
+}
+
+/// How the user's query is turned into one or more search vectors before
+/// hitting the index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryStrategy {
+    /// Embed the raw query once.
+    #[default]
+    Direct,
+    /// Ask the generation model for `num_queries` paraphrases and union the hits.
+    MultiQuery,
+    /// Embed a hypothetical answer (HyDE) alongside the raw query.
+    Hyde,
+    /// Also search a broader "step-back" version of the question.
+    StepBack,
+}
+
+/// Retrieval-layer configuration: everything between embedding the query and
+/// handing candidates to the answer generator. Query rewriting strategies use
+/// the `[generation]` model, so they need a working generation endpoint.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RetrievalConfig {
+    #[serde(default)]
+    pub strategy: QueryStrategy,
+    /// Number of paraphrases requested for `multi_query`.
+    #[serde(default = "default_num_queries")]
+    pub num_queries: usize,
+    #[serde(default)]
+    pub rerank: RerankConfig,
+    /// Merge consecutive chunks of the same file into one result, removing the
+    /// overlap between neighbouring windows.
+    #[serde(default)]
+    pub merge_adjacent: bool,
+    /// Cap on results taken from any single file.
+    #[serde(default)]
+    pub max_per_file: Option<usize>,
+    /// Maximal marginal relevance trade-off (1.0 = pure relevance, lower =
+    /// more diverse). Unset disables MMR.
+    #[serde(default)]
+    pub mmr_lambda: Option<f32>,
+    /// Weight of the freshness boost for recently changed sections. Unset
+    /// disables it.
+    #[serde(default)]
+    pub recency_weight: Option<f32>,
+    /// Age at which a section's freshness boost is halved.
+    #[serde(default = "default_recency_half_life_days")]
+    pub recency_half_life_days: f32,
+}
+
+fn default_num_queries() -> usize {
+    3
+}
+
+fn default_recency_half_life_days() -> f32 {
+    180.0
+}
+
+impl RetrievalConfig {
+    /// Whether any post-search selection step needs more candidates than the
+    /// caller's `limit`.
+    pub fn needs_overfetch(&self) -> bool {
+        self.rerank.kind != RerankerKind::None
+            || self.merge_adjacent
+            || self.max_per_file.is_some()
+            || self.mmr_lambda.is_some()
+            || self.recency_weight.is_some()
+    }
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            strategy: QueryStrategy::default(),
+            num_queries: default_num_queries(),
+            rerank: RerankConfig::default(),
+            merge_adjacent: false,
+            max_per_file: None,
+            mmr_lambda: None,
+            recency_weight: None,
+            recency_half_life_days: default_recency_half_life_days(),
+        }
+    }
+}
+
+/// Which reranker re-scores vector search candidates before generation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RerankerKind {
+    /// Keep vector distance order.
+    #[default]
+    None,
+    /// A Cohere/Jina-style `POST {base_url}/rerank` cross-encoder endpoint.
+    Http,
+    /// Ask the `[generation]` model to grade each candidate.
+    Llm,
+    /// Local query/chunk term overlap blended with the vector score. No network.
+    Lexical,
+}
+
+/// Reranking stage configuration (`[retrieval.rerank]`). When a reranker or any
+/// diversity option is enabled, `limit * overfetch_factor` candidates are pulled
+/// from the index and narrowed down to `limit`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RerankConfig {
+    #[serde(default)]
+    pub kind: RerankerKind,
+    #[serde(default = "default_overfetch_factor")]
+    pub overfetch_factor: usize,
+    /// Base URL of the rerank endpoint (`http` only), e.g. `https://api.cohere.com/v2`.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+fn default_overfetch_factor() -> usize {
+    5
+}
+
+impl Default for RerankConfig {
+    fn default() -> Self {
+        Self {
+            kind: RerankerKind::default(),
+            overfetch_factor: default_overfetch_factor(),
+            base_url: None,
+            model: None,
+            api_key: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PipelineConfig {
     pub parallel_workers: usize,
@@ -135,6 +778,11 @@ impl Config {
                 .or_else(|_| std::env::var("GROQ_API_KEY"))
                 .ok();
         }
+        if config.retrieval.rerank.api_key.is_none() {
+            config.retrieval.rerank.api_key = std::env::var("RERANK_API_KEY")
+                .or_else(|_| std::env::var("COHERE_API_KEY"))
+                .ok();
+        }
 
         config.validate()?;
         Ok(config)
@@ -147,11 +795,20 @@ impl Config {
                 local_path: PathBuf::from("./data_repo"),
                 branch: "main".to_string(),
                 sync_on_start: true,
+                depth: None,
+                sparse_paths: Vec::new(),
+                on_divergence: DivergencePolicy::default(),
+                submodules: SubmoduleConfig::default(),
+                history: HistoryConfig::default(),
+                blame: BlameConfig::default(),
             },
             database: DatabaseConfig {
                 uri: "data/lancedb".to_string(),
                 table_name: "documents".to_string(),
                 batch_size: 100,
+                index: VectorIndexConfig::default(),
+                maintenance: MaintenanceConfig::default(),
+                run_log: RunLogConfig::default(),
             },
             embedding: EmbeddingConfig {
                 base_url: "https://api.openai.com/v1".to_string(),
@@ -159,13 +816,21 @@ impl Config {
                 dimension: 768,
                 api_key: None,
                 allow_fallback: false,
+                limits: EmbeddingLimits::default(),
+                cache: EmbeddingCacheConfig::default(),
             },
             generation: GenerationConfig {
                 base_url: "https://api.groq.com/openai/v1".to_string(),
                 model: "openai/gpt-oss-120b".to_string(),
                 api_key: None,
                 max_context_chunks: default_max_context_chunks(),
+                max_context_tokens: default_max_context_tokens(),
+                reserved_answer_tokens: default_reserved_answer_tokens(),
+                tokenizer: TokenizerKind::default(),
+                backend: GenerationBackend::default(),
+                options: GenerationOptions::default(),
             },
+            retrieval: RetrievalConfig::default(),
             pipeline: PipelineConfig {
                 parallel_workers: 4,
                 skip_patterns: vec![
@@ -185,6 +850,41 @@ impl Config {
     }
 
     fn validate(&self) -> Result<()> {
+        if self.repository.depth == Some(0) {
+            return Err(PipelineError::Config(
+                "repository.depth must be greater than 0 when set".to_string(),
+            ));
+        }
+
+        if let Some(path) =
+            self.repository.sparse_paths.iter().find(|p| {
+                p.split('/').any(|part| part == "..") || p.contains(['*', '?', '[', '\\'])
+            })
+        {
+            return Err(PipelineError::Config(format!(
+                "repository.sparse_paths entries must be plain directories inside the \
+                 repository, got '{}'",
+                path
+            )));
+        }
+
+        // Compiling the filter globs is the check.
+        SubmoduleIndex::new(Vec::new(), &self.repository.submodules)?;
+
+        let history = &self.repository.history;
+        if history.max_commits == 0 {
+            return Err(PipelineError::Config(
+                "repository.history.max_commits must be greater than 0".to_string(),
+            ));
+        }
+        HistoryWalker::new(history.clone())?;
+
+        if self.repository.blame.max_commits == 0 {
+            return Err(PipelineError::Config(
+                "repository.blame.max_commits must be greater than 0".to_string(),
+            ));
+        }
+
         if self.pipeline.parallel_workers == 0 {
             return Err(PipelineError::Config(
                 "parallel_workers must be greater than 0".to_string(),
@@ -197,12 +897,127 @@ impl Config {
             ));
         }
 
+        if self.database.index.nprobes == Some(0) || self.database.index.refine_factor == Some(0) {
+            return Err(PipelineError::Config(
+                "database.index.nprobes and refine_factor must be greater than 0".to_string(),
+            ));
+        }
+
+        let limits = &self.embedding.limits;
+        if limits.batch_size == 0
+            || limits.max_batch_tokens == 0
+            || limits.max_concurrent_requests == 0
+        {
+            return Err(PipelineError::Config(
+                "embedding.limits batch_size, max_batch_tokens and max_concurrent_requests \
+                 must be greater than 0"
+                    .to_string(),
+            ));
+        }
+
+        if limits.requests_per_minute == Some(0) || limits.tokens_per_minute == Some(0) {
+            return Err(PipelineError::Config(
+                "embedding.limits per-minute limits must be greater than 0 when set".to_string(),
+            ));
+        }
+
+        if self.embedding.cache.enabled
+            && self.embedding.cache.table_name == self.database.table_name
+        {
+            return Err(PipelineError::Config(
+                "embedding.cache.table_name must differ from database.table_name".to_string(),
+            ));
+        }
+
+        let run_log = &self.database.run_log;
+        if run_log.enabled {
+            let others = [&self.database.table_name, &self.embedding.cache.table_name];
+            if run_log.runs_table == run_log.file_events_table
+                || others.contains(&&run_log.runs_table)
+                || others.contains(&&run_log.file_events_table)
+            {
+                return Err(PipelineError::Config(
+                    "database.run_log tables must differ from each other, the documents table \
+                     and the embedding cache table"
+                        .to_string(),
+                ));
+            }
+        }
+
         if self.embedding.dimension == 0 {
             return Err(PipelineError::Config(
                 "embedding.dimension must be greater than 0".to_string(),
             ));
         }
 
+        if self.generation.reserved_answer_tokens >= self.generation.max_context_tokens {
+            return Err(PipelineError::Config(
+                "generation.reserved_answer_tokens must be less than max_context_tokens"
+                    .to_string(),
+            ));
+        }
+
+        // Only Ollama loads the model with `num_ctx`; other backends ignore it.
+        if self.generation.backend == GenerationBackend::Ollama
+            && let Some(num_ctx) = self.generation.options.num_ctx
+            && num_ctx < self.generation.max_context_tokens
+        {
+            return Err(PipelineError::Config(
+                "generation.options.num_ctx must be at least max_context_tokens".to_string(),
+            ));
+        }
+
+        if self.generation.options.timeout_secs == Some(0) {
+            return Err(PipelineError::Config(
+                "generation.options.timeout_secs must be greater than 0".to_string(),
+            ));
+        }
+
+        let retrieval = &self.retrieval;
+        if retrieval.strategy == QueryStrategy::MultiQuery && retrieval.num_queries == 0 {
+            return Err(PipelineError::Config(
+                "retrieval.num_queries must be greater than 0 for multi_query".to_string(),
+            ));
+        }
+
+        if retrieval.needs_overfetch() && retrieval.rerank.overfetch_factor == 0 {
+            return Err(PipelineError::Config(
+                "retrieval.rerank.overfetch_factor must be greater than 0".to_string(),
+            ));
+        }
+
+        if retrieval.max_per_file == Some(0) {
+            return Err(PipelineError::Config(
+                "retrieval.max_per_file must be greater than 0".to_string(),
+            ));
+        }
+
+        if let Some(lambda) = retrieval.mmr_lambda
+            && !(0.0..=1.0).contains(&lambda)
+        {
+            return Err(PipelineError::Config(
+                "retrieval.mmr_lambda must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+
+        if retrieval.recency_weight.is_some_and(|weight| weight < 0.0) {
+            return Err(PipelineError::Config(
+                "retrieval.recency_weight must not be negative".to_string(),
+            ));
+        }
+
+        if retrieval.recency_half_life_days <= 0.0 {
+            return Err(PipelineError::Config(
+                "retrieval.recency_half_life_days must be greater than 0".to_string(),
+            ));
+        }
+
+        if retrieval.rerank.kind == RerankerKind::Http && retrieval.rerank.base_url.is_none() {
+            return Err(PipelineError::Config(
+                "retrieval.rerank.base_url is required for the http reranker".to_string(),
+            ));
+        }
+
         Ok(())
     }
 }
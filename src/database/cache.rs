@@ -0,0 +1,431 @@
+// file: src/database/cache.rs
+// description: content-addressed on-disk embedding cache stored as a LanceDB table
+// reference: https://docs.rs/lancedb/latest/lancedb/table/struct.MergeInsertBuilder.html
+
+use crate::config::EmbeddingConfig;
+use crate::database::client::{LanceDbClient, escape_sql_literal};
+use crate::error::{PipelineError, Result};
+use arrow_array::types::Float32Type;
+use arrow_array::{
+    Array, ArrayRef, Float32Array, ListArray, RecordBatch, RecordBatchIterator, StringArray,
+    UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use futures::StreamExt;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+/// Keys per `IN (...)` lookup query.
+const LOOKUP_CHUNK: usize = 256;
+
+/// Hits and misses recorded by one [`EmbeddingCache`] since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hit(s), {} miss(es) ({:.1}% hit rate)",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0
+        )
+    }
+}
+
+/// What `cache prune` removes. Entries for a different model or dimension
+/// than the current configuration are always removed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CachePruneOptions {
+    /// Also remove entries cached more than this many days ago.
+    pub older_than_days: Option<u64>,
+    /// Remove every entry.
+    pub all: bool,
+}
+
+/// Embeddings keyed by `sha256(model, dimension, embedding_text)`, so
+/// byte-identical chunks are never embedded twice by the same model, across
+/// runs, `reset`s and branches. New entries are buffered and written in
+/// batches; call [`flush`](Self::flush) when done.
+pub struct EmbeddingCache {
+    client: LanceDbClient,
+    table_name: String,
+    model: String,
+    dimension: usize,
+    flush_size: usize,
+    pending: Mutex<HashMap<String, Vec<f32>>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl EmbeddingCache {
+    pub fn new(client: &LanceDbClient, config: &EmbeddingConfig) -> Self {
+        Self {
+            client: client.clone(),
+            table_name: config.cache.table_name.clone(),
+            model: config.model.clone(),
+            dimension: config.dimension,
+            flush_size: config.cache.flush_size.max(1),
+            pending: Mutex::new(HashMap::new()),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn key(&self, text: &str) -> String {
+        cache_key(&self.model, self.dimension, text)
+    }
+
+    /// Cached vectors for `texts`, in input order; `None` marks a miss.
+    pub async fn lookup(&self, texts: &[String]) -> Result<Vec<Option<Vec<f32>>>> {
+        let keys: Vec<String> = texts.iter().map(|t| self.key(t)).collect();
+        let mut found: HashMap<String, Vec<f32>> = HashMap::new();
+
+        {
+            let pending = self.pending.lock().await;
+            for key in &keys {
+                if let Some(vector) = pending.get(key) {
+                    found.insert(key.clone(), vector.clone());
+                }
+            }
+        }
+
+        let missing: Vec<&String> = keys.iter().filter(|k| !found.contains_key(*k)).collect();
+        if !missing.is_empty() && self.client.table_exists(&self.table_name).await? {
+            let table = self.client.get_table(&self.table_name).await?;
+            for chunk in missing.chunks(LOOKUP_CHUNK) {
+                let predicate = format!(
+                    "key IN ({})",
+                    chunk
+                        .iter()
+                        .map(|k| format!("'{}'", escape_sql_literal(k)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                let mut stream = table
+                    .query()
+                    .only_if(predicate)
+                    .select(Select::columns(&["key", "embedding"]))
+                    .execute()
+                    .await
+                    .map_err(|e| {
+                        PipelineError::Database(format!("Embedding cache lookup failed: {}", e))
+                    })?;
+
+                while let Some(batch) = stream.next().await {
+                    let batch = batch.map_err(|e| {
+                        PipelineError::Database(format!("Failed to read cache batch: {}", e))
+                    })?;
+                    for (key, vector) in read_entries(&batch)? {
+                        if vector.len() == self.dimension {
+                            found.insert(key, vector);
+                        }
+                    }
+                }
+            }
+        }
+
+        let vectors: Vec<Option<Vec<f32>>> =
+            keys.iter().map(|key| found.get(key).cloned()).collect();
+        let hits = vectors.iter().filter(|v| v.is_some()).count();
+        self.hits.fetch_add(hits, Ordering::Relaxed);
+        self.misses
+            .fetch_add(vectors.len() - hits, Ordering::Relaxed);
+        Ok(vectors)
+    }
+
+    /// Queue freshly embedded vectors, writing once `flush_size` are pending.
+    pub async fn insert(&self, texts: &[String], vectors: &[Vec<f32>]) -> Result<()> {
+        let pending_len = {
+            let mut pending = self.pending.lock().await;
+            for (text, vector) in texts.iter().zip(vectors) {
+                pending.insert(self.key(text), vector.clone());
+            }
+            pending.len()
+        };
+
+        if pending_len >= self.flush_size {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Write all pending entries. Returns how many were written.
+    pub async fn flush(&self) -> Result<usize> {
+        // Holding the lock across the write keeps a single cache writer.
+        let mut pending = self.pending.lock().await;
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        let entries: Vec<(String, Vec<f32>)> = pending.drain().collect();
+        let count = entries.len();
+        let schema = cache_schema();
+        let batch = self.record_batch(schema.clone(), entries)?;
+
+        if !self.client.table_exists(&self.table_name).await? {
+            self.client
+                .get_connection()
+                .create_table(&self.table_name, vec![batch])
+                .execute()
+                .await
+                .map_err(|e| {
+                    PipelineError::Database(format!("Failed to create embedding cache: {}", e))
+                })?;
+            info!("Created embedding cache table: {}", self.table_name);
+        } else {
+            let table = self.client.get_table(&self.table_name).await?;
+            let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+            let mut merge = table.merge_insert(&["key"]);
+            merge.when_not_matched_insert_all();
+            merge.execute(Box::new(reader)).await.map_err(|e| {
+                PipelineError::Database(format!("Failed to write embedding cache: {}", e))
+            })?;
+        }
+
+        debug!("Wrote {} embedding cache entries", count);
+        Ok(count)
+    }
+
+    /// Total entries, and how many belong to the configured model and dimension.
+    pub async fn entry_counts(&self) -> Result<(usize, usize)> {
+        if !self.client.table_exists(&self.table_name).await? {
+            return Ok((0, 0));
+        }
+        let table = self.client.get_table(&self.table_name).await?;
+        let count = |filter: Option<String>| {
+            let table = table.clone();
+            async move {
+                table.count_rows(filter).await.map_err(|e| {
+                    PipelineError::Database(format!("Failed to count cache entries: {}", e))
+                })
+            }
+        };
+        let total = count(None).await?;
+        let current = count(Some(self.current_model_predicate())).await?;
+        Ok((total, current))
+    }
+
+    /// Delete stale entries and return how many were removed.
+    pub async fn prune(&self, options: CachePruneOptions) -> Result<usize> {
+        if !self.client.table_exists(&self.table_name).await? {
+            return Ok(0);
+        }
+        let (before, _) = self.entry_counts().await?;
+
+        if options.all {
+            self.client
+                .get_connection()
+                .drop_table(&self.table_name, &[])
+                .await
+                .map_err(|e| {
+                    PipelineError::Database(format!("Failed to drop embedding cache: {}", e))
+                })?;
+            return Ok(before);
+        }
+
+        let predicate = prune_predicate(
+            &self.current_model_predicate(),
+            options
+                .older_than_days
+                .map(|days| now_secs().saturating_sub(days * 86_400)),
+        );
+        let table = self.client.get_table(&self.table_name).await?;
+        table.delete(&predicate).await.map_err(|e| {
+            PipelineError::Database(format!("Failed to prune embedding cache: {}", e))
+        })?;
+
+        let (after, _) = self.entry_counts().await?;
+        Ok(before.saturating_sub(after))
+    }
+
+    fn current_model_predicate(&self) -> String {
+        format!(
+            "model = '{}' AND dimension = {}",
+            escape_sql_literal(&self.model),
+            self.dimension
+        )
+    }
+
+    fn record_batch(
+        &self,
+        schema: Arc<Schema>,
+        entries: Vec<(String, Vec<f32>)>,
+    ) -> Result<RecordBatch> {
+        let len = entries.len();
+        let now = now_secs();
+        let keys: StringArray = entries.iter().map(|(k, _)| Some(k.as_str())).collect();
+        let models: StringArray = (0..len).map(|_| Some(self.model.as_str())).collect();
+        let dimensions = UInt32Array::from(vec![self.dimension as u32; len]);
+        let cached_at = UInt64Array::from(vec![now; len]);
+        let embeddings = ListArray::from_iter_primitive::<Float32Type, _, _>(
+            entries
+                .iter()
+                .map(|(_, v)| Some(v.iter().copied().map(Some))),
+        );
+
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(keys) as ArrayRef,
+                Arc::new(models),
+                Arc::new(dimensions),
+                Arc::new(cached_at),
+                Arc::new(embeddings),
+            ],
+        )
+        .map_err(|e| PipelineError::Database(format!("Failed to build cache batch: {}", e)))
+    }
+}
+
+/// Vectors are stored as variable-length lists so entries for several
+/// dimensions can share the table.
+fn cache_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("key", DataType::Utf8, false),
+        Field::new("model", DataType::Utf8, false),
+        Field::new("dimension", DataType::UInt32, false),
+        Field::new("cached_at", DataType::UInt64, false),
+        Field::new(
+            "embedding",
+            DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+            false,
+        ),
+    ]))
+}
+
+fn cache_key(model: &str, dimension: usize, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(dimension.to_le_bytes());
+    hasher.update([0]);
+    hasher.update(text.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn prune_predicate(current_model: &str, cached_before: Option<u64>) -> String {
+    match cached_before {
+        Some(cutoff) => format!("NOT ({}) OR cached_at < {}", current_model, cutoff),
+        None => format!("NOT ({})", current_model),
+    }
+}
+
+fn read_entries(batch: &RecordBatch) -> Result<Vec<(String, Vec<f32>)>> {
+    let keys = batch
+        .column_by_name("key")
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+        .ok_or_else(|| PipelineError::Database("Invalid 'key' cache column".to_string()))?;
+    let embeddings = batch
+        .column_by_name("embedding")
+        .and_then(|c| c.as_any().downcast_ref::<ListArray>())
+        .ok_or_else(|| PipelineError::Database("Invalid 'embedding' cache column".to_string()))?;
+
+    let mut entries = Vec::with_capacity(batch.num_rows());
+    for i in 0..batch.num_rows() {
+        if embeddings.is_null(i) {
+            continue;
+        }
+        let values = embeddings.value(i);
+        let Some(values) = values.as_any().downcast_ref::<Float32Array>() else {
+            continue;
+        };
+        entries.push((keys.value(i).to_string(), values.values().to_vec()));
+    }
+    Ok(entries)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_depend_on_model_dimension_and_text() {
+        let key = cache_key("text-embedding-3-small", 768, "hello");
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, cache_key("text-embedding-3-small", 768, "hello"));
+        assert_ne!(key, cache_key("text-embedding-3-small", 512, "hello"));
+        assert_ne!(key, cache_key("text-embedding-3-large", 768, "hello"));
+        assert_ne!(key, cache_key("text-embedding-3-small", 768, "hello "));
+    }
+
+    #[test]
+    fn entries_round_trip_through_a_record_batch() {
+        let batch = RecordBatch::try_new(
+            cache_schema(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b"])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["m", "m"])),
+                Arc::new(UInt32Array::from(vec![2, 2])),
+                Arc::new(UInt64Array::from(vec![0, 0])),
+                Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(vec![
+                    Some(vec![Some(1.0), Some(2.0)]),
+                    Some(vec![Some(3.0), Some(4.0)]),
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let entries = read_entries(&batch).unwrap();
+        assert_eq!(entries[1], ("b".to_string(), vec![3.0, 4.0]));
+    }
+
+    #[test]
+    fn prune_keeps_only_recent_entries_for_the_current_model() {
+        assert_eq!(
+            prune_predicate("model = 'm' AND dimension = 8", Some(100)),
+            "NOT (model = 'm' AND dimension = 8) OR cached_at < 100"
+        );
+        assert_eq!(
+            prune_predicate("model = 'm' AND dimension = 8", None),
+            "NOT (model = 'm' AND dimension = 8)"
+        );
+    }
+
+    #[test]
+    fn hit_rate_handles_no_lookups() {
+        assert_eq!(CacheStats::default().hit_rate(), 0.0);
+        let stats = CacheStats { hits: 3, misses: 1 };
+        assert_eq!(stats.to_string(), "3 hit(s), 1 miss(es) (75.0% hit rate)");
+    }
+}
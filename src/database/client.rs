@@ -2,17 +2,22 @@
 // description: LanceDB client wrapper with connection management
 // reference: https://docs.rs/lancedb
 
-use crate::config::DatabaseConfig;
+use crate::config::{DatabaseConfig, DistanceMetric, VectorIndexConfig};
+use crate::database::filter::SearchFilter;
 use crate::error::{PipelineError, Result};
 use crate::models::{
     SearchResult, SearchResultFileMetadata, SearchResultPaths, SearchResultScoring,
 };
-use arrow_array::{Float32Array, StringArray, UInt64Array};
+use arrow_array::{Array, FixedSizeListArray, Float32Array, StringArray, UInt32Array, UInt64Array};
 use futures::StreamExt;
-use lancedb::query::{ExecutableQuery, QueryBase};
-use lancedb::{Connection, Table, connect};
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use lancedb::{Connection, DistanceType, Table, connect};
+use std::collections::HashMap;
 use tracing::{debug, info, warn};
 
+/// Content hashes per `IN (...)` lookup query.
+const LOOKUP_CHUNK: usize = 256;
+
 #[derive(Clone)]
 pub struct LanceDbClient {
     connection: Connection,
@@ -20,10 +25,36 @@ pub struct LanceDbClient {
 }
 
 /// Escape single quotes for safe interpolation into a SQL string literal.
-fn escape_sql_literal(value: &str) -> String {
+pub(crate) fn escape_sql_literal(value: &str) -> String {
     value.replace('\'', "''")
 }
 
+/// Per-query ANN tuning. Unset fields fall back to `database.index`; both
+/// are ignored by brute-force search when no index exists.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VectorSearchOptions {
+    pub nprobes: Option<usize>,
+    pub refine_factor: Option<u32>,
+}
+
+pub(crate) fn distance_type(metric: DistanceMetric) -> DistanceType {
+    match metric {
+        DistanceMetric::L2 => DistanceType::L2,
+        DistanceMetric::Cosine => DistanceType::Cosine,
+        DistanceMetric::Dot => DistanceType::Dot,
+    }
+}
+
+/// Map a raw distance to a higher-is-better similarity score. Cosine and dot
+/// distances are `1 - similarity`, so they invert directly; L2 is unbounded
+/// and squashed into (0, 1].
+pub(crate) fn distance_to_score(metric: DistanceMetric, distance: f32) -> f32 {
+    match metric {
+        DistanceMetric::L2 => 1.0 / (1.0 + distance),
+        DistanceMetric::Cosine | DistanceMetric::Dot => 1.0 - distance,
+    }
+}
+
 impl LanceDbClient {
     pub async fn new(config: DatabaseConfig) -> Result<Self> {
         info!("Connecting to LanceDB at {}", config.uri);
@@ -95,6 +126,10 @@ impl LanceDbClient {
         self.config.batch_size
     }
 
+    pub fn index_config(&self) -> &VectorIndexConfig {
+        &self.config.index
+    }
+
     pub fn table_name(&self) -> &str {
         &self.config.table_name
     }
@@ -149,12 +184,109 @@ impl LanceDbClient {
         Ok(())
     }
 
+    /// Whether `repository_url` has chunks stored under a ref other than
+    /// `git_ref`.
+    pub async fn has_other_refs(&self, repository_url: &str, git_ref: &str) -> Result<bool> {
+        if !self.table_exists(&self.config.table_name).await? {
+            return Ok(false);
+        }
+
+        let table = self.get_table(&self.config.table_name).await?;
+        let predicate = format!(
+            "repository_url = '{}' AND git_ref IS NOT NULL AND git_ref <> '{}'",
+            escape_sql_literal(repository_url),
+            escape_sql_literal(git_ref)
+        );
+        let count = table
+            .count_rows(Some(predicate))
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to count rows: {}", e)))?;
+        Ok(count > 0)
+    }
+
+    /// Stored vectors of `repository_url`'s chunks whose `content_hash` is in
+    /// `content_hashes`, keyed by `(content_hash, heading_path)` (the heading
+    /// is part of the embedded text). Any ref's rows will do.
+    pub async fn stored_vectors(
+        &self,
+        repository_url: &str,
+        content_hashes: &[String],
+    ) -> Result<HashMap<(String, String), Vec<f32>>> {
+        let mut vectors = HashMap::new();
+        if content_hashes.is_empty() || !self.table_exists(&self.config.table_name).await? {
+            return Ok(vectors);
+        }
+
+        let table = self.get_table(&self.config.table_name).await?;
+        for chunk in content_hashes.chunks(LOOKUP_CHUNK) {
+            let predicate = format!(
+                "repository_url = '{}' AND content_hash IN ({})",
+                escape_sql_literal(repository_url),
+                chunk
+                    .iter()
+                    .map(|h| format!("'{}'", escape_sql_literal(h)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            let mut stream = table
+                .query()
+                .only_if(predicate)
+                .select(Select::columns(&[
+                    "content_hash",
+                    "heading_path",
+                    "embedding",
+                ]))
+                .execute()
+                .await
+                .map_err(|e| {
+                    PipelineError::Database(format!("Stored vector lookup failed: {}", e))
+                })?;
+
+            while let Some(batch) = stream.next().await {
+                let batch = batch.map_err(|e| {
+                    PipelineError::Database(format!("Failed to read result batch: {}", e))
+                })?;
+                let hashes = batch
+                    .column_by_name("content_hash")
+                    .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+                let headings = batch
+                    .column_by_name("heading_path")
+                    .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+                let embeddings = batch
+                    .column_by_name("embedding")
+                    .and_then(|c| c.as_any().downcast_ref::<FixedSizeListArray>());
+                let (Some(hashes), Some(headings), Some(embeddings)) =
+                    (hashes, headings, embeddings)
+                else {
+                    return Err(PipelineError::Database(
+                        "Stored vector lookup returned unexpected columns".to_string(),
+                    ));
+                };
+                for i in 0..batch.num_rows() {
+                    if embeddings.is_null(i) {
+                        continue;
+                    }
+                    if let Some(values) =
+                        embeddings.value(i).as_any().downcast_ref::<Float32Array>()
+                    {
+                        vectors.insert(
+                            (hashes.value(i).to_string(), headings.value(i).to_string()),
+                            values.values().to_vec(),
+                        );
+                    }
+                }
+            }
+        }
+        Ok(vectors)
+    }
+
     /// Search for documents by vector similarity
     ///
     /// # Arguments
     /// * `query_embedding` - The query vector to search for
     /// * `limit` - Maximum number of results to return (default: 10)
-    /// * `repository_filter` - Optional repository URL to filter results
+    /// * `filter` - Optional metadata filter applied before ranking
+    /// * `options` - ANN tuning (`nprobes`, `refine_factor`) for indexed tables
     ///
     /// # Returns
     /// Vector of SearchResult ordered by similarity (highest first)
@@ -162,7 +294,8 @@ impl LanceDbClient {
         &self,
         query_embedding: Vec<f32>,
         limit: usize,
-        repository_filter: Option<&str>,
+        filter: Option<&SearchFilter>,
+        options: VectorSearchOptions,
     ) -> Result<Vec<SearchResult>> {
         if !self.table_exists(&self.config.table_name).await? {
             warn!("Table does not exist, returning empty results");
@@ -173,17 +306,26 @@ impl LanceDbClient {
 
         info!("Performing vector search with limit {}", limit);
 
+        let index = &self.config.index;
+
         // Create the search query
         let mut query = table
             .vector_search(query_embedding)
             .map_err(|e| PipelineError::Database(format!("Failed to create vector search: {}", e)))?
+            .distance_type(distance_type(index.metric))
             .limit(limit);
 
-        // Add repository filter if provided
-        if let Some(repo_url) = repository_filter {
-            let filter = format!("repository_url = '{}'", escape_sql_literal(repo_url));
-            query = query.only_if(&filter);
-            debug!("Applied filter: {}", filter);
+        if let Some(nprobes) = options.nprobes.or(index.nprobes) {
+            query = query.nprobes(nprobes);
+        }
+        if let Some(refine_factor) = options.refine_factor.or(index.refine_factor) {
+            query = query.refine_factor(refine_factor);
+        }
+
+        if let Some(filter) = filter {
+            let predicate = filter.to_predicate();
+            debug!("Applied filter: {}", predicate);
+            query = query.only_if(predicate);
         }
 
         // Execute the search
@@ -260,6 +402,15 @@ impl LanceDbClient {
                     PipelineError::Database("Invalid 'repository_url' column type".to_string())
                 })?;
 
+            let chunk_indices = batch
+                .column_by_name("chunk_index")
+                .ok_or_else(|| PipelineError::Database("Missing 'chunk_index' column".to_string()))?
+                .as_any()
+                .downcast_ref::<UInt32Array>()
+                .ok_or_else(|| {
+                    PipelineError::Database("Invalid 'chunk_index' column type".to_string())
+                })?;
+
             let file_sizes = batch
                 .column_by_name("file_size")
                 .ok_or_else(|| PipelineError::Database("Missing 'file_size' column".to_string()))?
@@ -285,12 +436,36 @@ impl LanceDbClient {
                 .column_by_name("_distance")
                 .and_then(|col| col.as_any().downcast_ref::<Float32Array>());
 
+            // Absent from tables created before refs were recorded
+            let git_refs = batch
+                .column_by_name("git_ref")
+                .and_then(|col| col.as_any().downcast_ref::<StringArray>());
+            let git_commits = batch
+                .column_by_name("git_commit")
+                .and_then(|col| col.as_any().downcast_ref::<StringArray>());
+            // Absent from tables created before blame was recorded
+            let last_commits = batch
+                .column_by_name("last_commit")
+                .and_then(|col| col.as_any().downcast_ref::<StringArray>());
+            let last_authors = batch
+                .column_by_name("last_author")
+                .and_then(|col| col.as_any().downcast_ref::<StringArray>());
+            let last_changed_ats = batch
+                .column_by_name("last_changed_at")
+                .and_then(|col| col.as_any().downcast_ref::<UInt64Array>());
+
+            // Stored vectors, kept on results for diversity-aware selection
+            let embeddings = batch
+                .column_by_name("embedding")
+                .and_then(|col| col.as_any().downcast_ref::<FixedSizeListArray>());
+
             // Convert rows to SearchResult
             for i in 0..num_rows {
                 let id = ids.value(i).to_string();
                 let file_path = file_paths.value(i).to_string();
                 let relative_path = relative_paths.value(i).to_string();
                 let heading_path = heading_paths.value(i).to_string();
+                let chunk_index = chunk_indices.value(i);
                 let content = contents.value(i).to_string();
                 let repository_url = repository_urls.value(i).to_string();
                 let file_size = file_sizes.value(i);
@@ -300,20 +475,19 @@ impl LanceDbClient {
                 let (score, distance) = if let Some(dist_array) = distances {
                     let dist = dist_array.value(i);
                     // Convert distance to similarity (lower distance = higher similarity)
-                    // Common approach: score = 1 / (1 + distance)
-                    let similarity = 1.0 / (1.0 + dist);
-                    (similarity, Some(dist))
+                    (distance_to_score(index.metric, dist), Some(dist))
                 } else {
                     // If no distance column, use default
                     (1.0, None)
                 };
 
-                search_results.push(SearchResult::new(
+                let mut result = SearchResult::new(
                     id,
                     SearchResultPaths {
                         file_path,
                         relative_path,
                         heading_path,
+                        chunk_index,
                     },
                     content,
                     repository_url,
@@ -322,7 +496,30 @@ impl LanceDbClient {
                         file_size,
                         last_modified,
                     },
-                ));
+                );
+
+                let optional = |column: Option<&StringArray>| {
+                    column
+                        .filter(|c| !c.is_null(i))
+                        .map(|c| c.value(i).to_string())
+                };
+                result = result.with_reference(optional(git_refs), optional(git_commits));
+                result = result.with_last_change(
+                    optional(last_commits),
+                    optional(last_authors),
+                    last_changed_ats
+                        .filter(|c| !c.is_null(i))
+                        .map(|c| c.value(i)),
+                );
+
+                if let Some(vectors) = embeddings
+                    && !vectors.is_null(i)
+                    && let Some(values) = vectors.value(i).as_any().downcast_ref::<Float32Array>()
+                {
+                    result = result.with_embedding(values.values().to_vec());
+                }
+
+                search_results.push(result);
             }
         }
 
@@ -341,9 +538,25 @@ mod tests {
             uri: "memory://test".to_string(),
             table_name: "test_table".to_string(),
             batch_size: 100,
+            index: Default::default(),
+            maintenance: Default::default(),
+            run_log: Default::default(),
         };
 
         assert_eq!(config.uri, "memory://test");
         assert_eq!(config.table_name, "test_table");
     }
+
+    #[test]
+    fn distance_scores_are_higher_for_closer_vectors() {
+        for metric in [
+            DistanceMetric::L2,
+            DistanceMetric::Cosine,
+            DistanceMetric::Dot,
+        ] {
+            assert!(distance_to_score(metric, 0.1) > distance_to_score(metric, 0.5));
+        }
+        assert_eq!(distance_to_score(DistanceMetric::L2, 0.0), 1.0);
+        assert_eq!(distance_to_score(DistanceMetric::Cosine, 0.25), 0.75);
+    }
 }
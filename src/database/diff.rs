@@ -0,0 +1,780 @@
+// file: src/database/diff.rs
+// description: semantic diff of the sections stored for two refs of a repository
+// reference: https://git-scm.com/docs/git-diff
+
+use crate::database::client::LanceDbClient;
+use crate::database::filter::SearchFilter;
+use crate::error::{PipelineError, Result};
+use crate::generation::AnswerGenerator;
+use crate::retrieval::diversity::cosine;
+use arrow_array::{Array, FixedSizeListArray, Float32Array, RecordBatch, StringArray, UInt32Array};
+use futures::StreamExt;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use tracing::{debug, info};
+
+/// Cosine similarity above which an unmatched removed and added section are
+/// taken to be the same section, moved or reworded.
+pub const DEFAULT_MOVE_THRESHOLD: f32 = 0.9;
+
+/// Changes described to the model when writing a changelog.
+const CHANGELOG_MAX_CHANGES: usize = 40;
+
+/// Characters of each version's text shown to the model.
+const CHANGELOG_EXCERPT_CHARS: usize = 600;
+
+const CHANGELOG_PROMPT: &str = "You write changelogs for software documentation. \
+You are given numbered changes between two versions of a repository's docs; each shows the \
+section before and/or after the change. Summarize what changed for a reader upgrading from the \
+old version to the new one, grouped by topic, most important first. Cite every statement with \
+the bracketed change numbers it is based on, like [1] or [2][5]. Only describe what the changes \
+show; do not speculate. Be concise.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// Same file and heading, different text.
+    Modified,
+    /// Found under another file or heading, with the same or similar text.
+    Moved,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Modified => "modified",
+            Self::Moved => "moved",
+        }
+    }
+}
+
+/// One chunk as stored at a ref.
+#[derive(Debug, Clone, Serialize)]
+pub struct Section {
+    pub repository_url: String,
+    pub relative_path: String,
+    pub heading_path: String,
+    pub chunk_index: u32,
+    pub content: String,
+    pub content_hash: String,
+    pub git_ref: String,
+    pub git_commit: Option<String>,
+    #[serde(skip)]
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl Section {
+    /// "relative/path.md # Heading @ ref", as in search citations.
+    pub fn location(&self) -> String {
+        if self.heading_path.is_empty() {
+            format!("{} @ {}", self.relative_path, self.git_ref)
+        } else {
+            format!(
+                "{} # {} @ {}",
+                self.relative_path, self.heading_path, self.git_ref
+            )
+        }
+    }
+
+    fn similarity(&self, other: &Section) -> Option<f32> {
+        Some(cosine(
+            self.embedding.as_deref()?,
+            other.embedding.as_deref()?,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionChange {
+    pub kind: ChangeKind,
+    /// The section at the old ref; `None` when added.
+    pub before: Option<Section>,
+    /// The section at the new ref; `None` when removed.
+    pub after: Option<Section>,
+    /// Cosine similarity of the two versions' vectors, when both exist.
+    pub similarity: Option<f32>,
+}
+
+impl SectionChange {
+    /// The newer side when there is one.
+    pub fn section(&self) -> &Section {
+        self.after
+            .as_ref()
+            .or(self.before.as_ref())
+            .expect("a change has at least one side")
+    }
+}
+
+/// What changed between the sections stored for two refs.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffReport {
+    pub from: String,
+    pub to: String,
+    pub from_commit: Option<String>,
+    pub to_commit: Option<String>,
+    /// Sections with the same file, heading and text at both refs.
+    pub unchanged: usize,
+    pub changes: Vec<SectionChange>,
+}
+
+impl DiffReport {
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|c| c.kind == kind).count()
+    }
+
+    /// An LLM-written summary of the changes, citing them by number as
+    /// listed in the report. Only the first changes are described when
+    /// there are many.
+    pub async fn changelog(&self, generator: &AnswerGenerator) -> Result<String> {
+        if self.changes.is_empty() {
+            return Ok(format!(
+                "No documentation changes between {} and {}.",
+                self.from, self.to
+            ));
+        }
+        let user = format!(
+            "Changes from {} to {}:\n\n{}",
+            self.from,
+            self.to,
+            changelog_context(&self.changes)
+        );
+        generator.complete(CHANGELOG_PROMPT, &user, 0.2).await
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} → {}: {} added, {} removed, {} modified, {} moved, {} unchanged",
+            self.from,
+            self.to,
+            self.count(ChangeKind::Added),
+            self.count(ChangeKind::Removed),
+            self.count(ChangeKind::Modified),
+            self.count(ChangeKind::Moved),
+            self.unchanged
+        )?;
+        for (idx, change) in self.changes.iter().enumerate() {
+            let similarity = change
+                .similarity
+                .map(|s| format!(" (similarity {:.2})", s))
+                .unwrap_or_default();
+            match (&change.before, &change.after) {
+                (Some(before), Some(after)) if change.kind == ChangeKind::Moved => writeln!(
+                    f,
+                    "[{}] moved    {} → {}{}",
+                    idx + 1,
+                    before.location(),
+                    after.location(),
+                    similarity
+                )?,
+                _ => writeln!(
+                    f,
+                    "[{}] {:<8} {}{}",
+                    idx + 1,
+                    change.kind.as_str(),
+                    change.section().location(),
+                    similarity
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares the sections stored for two refs of one repository in the
+/// documents table.
+pub struct RefDiff<'a> {
+    client: &'a LanceDbClient,
+    repository_url: String,
+    move_threshold: f32,
+}
+
+impl<'a> RefDiff<'a> {
+    /// Refs are per repository, so a diff only ever reads one repository's
+    /// sections; otherwise moves could pair sections across repositories.
+    pub fn new(client: &'a LanceDbClient, repository_url: &str) -> Self {
+        Self {
+            client,
+            repository_url: repository_url.to_string(),
+            move_threshold: DEFAULT_MOVE_THRESHOLD,
+        }
+    }
+
+    pub fn with_move_threshold(mut self, threshold: f32) -> Self {
+        self.move_threshold = threshold;
+        self
+    }
+
+    /// Diff the sections ingested at `from` against those at `to`. Each is a
+    /// branch, tag or commit as accepted by the `at:` filter.
+    pub async fn compare(&self, from: &str, to: &str) -> Result<DiffReport> {
+        let (from_commit, before) = self.sections_at(from).await?;
+        let (to_commit, after) = self.sections_at(to).await?;
+        for (name, sections) in [(from, &before), (to, &after)] {
+            if sections.is_empty() {
+                return Err(PipelineError::Validation(format!(
+                    "No chunks of {} are stored at '{}'; ingest it first (ingest --at {})",
+                    self.repository_url, name, name
+                )));
+            }
+        }
+        info!(
+            "Comparing {} section(s) at {} with {} at {}",
+            before.len(),
+            from,
+            after.len(),
+            to
+        );
+
+        let (changes, unchanged) = align(before, after, self.move_threshold);
+        Ok(DiffReport {
+            from: from.to_string(),
+            to: to.to_string(),
+            from_commit,
+            to_commit,
+            unchanged,
+            changes,
+        })
+    }
+
+    /// The commit `at` resolves to and the sections stored at it.
+    async fn sections_at(&self, at: &str) -> Result<(Option<String>, Vec<Section>)> {
+        let table_name = self.client.table_name();
+        if !self.client.table_exists(table_name).await? {
+            return Ok((None, Vec::new()));
+        }
+        let predicate = SearchFilter::repository(&self.repository_url)
+            .and(SearchFilter::at(at))
+            .to_predicate();
+        debug!("Reading sections where {}", predicate);
+
+        let table = self.client.get_table(table_name).await?;
+        let mut stream = table
+            .query()
+            .only_if(predicate)
+            .select(Select::columns(&[
+                "repository_url",
+                "relative_path",
+                "heading_path",
+                "chunk_index",
+                "content",
+                "content_hash",
+                "git_ref",
+                "git_commit",
+                "embedding",
+            ]))
+            .execute()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read sections: {}", e)))?;
+
+        let mut sections = Vec::new();
+        while let Some(batch) = stream.next().await {
+            let batch = batch
+                .map_err(|e| PipelineError::Database(format!("Failed to read batch: {}", e)))?;
+            read_sections(&batch, &mut sections)?;
+        }
+
+        let commit = resolved_commit(at, &sections)?;
+        if let Some(commit) = &commit {
+            sections.retain(|s| s.git_commit.as_ref() == Some(commit));
+        }
+
+        // A commit can be stored under several refs; keep one copy of each chunk.
+        let mut seen = HashSet::new();
+        sections.retain(|s| {
+            seen.insert((
+                s.repository_url.clone(),
+                s.relative_path.clone(),
+                s.chunk_index,
+            ))
+        });
+        Ok((commit, sections))
+    }
+}
+
+/// The commit `at` names among the sections the `at:` filter matched: the
+/// commit its ref was ingested at when `at` is a ref, otherwise the commit
+/// whose SHA it abbreviates. A prefix shared by several ingested commits is
+/// ambiguous.
+fn resolved_commit(at: &str, sections: &[Section]) -> Result<Option<String>> {
+    let commits = |named: bool| -> BTreeSet<&str> {
+        sections
+            .iter()
+            .filter(|s| !named || s.git_ref == at)
+            .filter_map(|s| s.git_commit.as_deref())
+            .collect()
+    };
+    let mut commits = match commits(true) {
+        named if named.is_empty() => commits(false),
+        named => named,
+    };
+    if commits.len() > 1 {
+        return Err(PipelineError::Validation(format!(
+            "'{}' matches several ingested commits ({}); use a ref name or a longer SHA",
+            at,
+            commits.into_iter().collect::<Vec<_>>().join(", ")
+        )));
+    }
+    Ok(commits.pop_first().map(str::to_string))
+}
+
+fn read_sections(batch: &RecordBatch, sections: &mut Vec<Section>) -> Result<()> {
+    let strings = |name: &str| {
+        batch
+            .column_by_name(name)
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            .ok_or_else(|| PipelineError::Database(format!("Missing '{}' column", name)))
+    };
+    let repositories = strings("repository_url")?;
+    let paths = strings("relative_path")?;
+    let headings = strings("heading_path")?;
+    let contents = strings("content")?;
+    let hashes = strings("content_hash")?;
+    let refs = strings("git_ref")?;
+    let commits = strings("git_commit")?;
+    let indices = batch
+        .column_by_name("chunk_index")
+        .and_then(|c| c.as_any().downcast_ref::<UInt32Array>())
+        .ok_or_else(|| PipelineError::Database("Missing 'chunk_index' column".to_string()))?;
+    let embeddings = batch
+        .column_by_name("embedding")
+        .and_then(|c| c.as_any().downcast_ref::<FixedSizeListArray>());
+
+    for row in 0..batch.num_rows() {
+        let embedding = embeddings.filter(|e| !e.is_null(row)).and_then(|e| {
+            e.value(row)
+                .as_any()
+                .downcast_ref::<Float32Array>()
+                .map(|v| v.values().to_vec())
+        });
+        sections.push(Section {
+            repository_url: repositories.value(row).to_string(),
+            relative_path: paths.value(row).to_string(),
+            heading_path: headings.value(row).to_string(),
+            chunk_index: indices.value(row),
+            content: contents.value(row).to_string(),
+            content_hash: hashes.value(row).to_string(),
+            git_ref: refs.value(row).to_string(),
+            git_commit: (!commits.is_null(row)).then(|| commits.value(row).to_string()),
+            embedding,
+        });
+    }
+    Ok(())
+}
+
+/// Where a section sits: repository, file, heading, and its position among
+/// the file's chunks under that heading (long sections span several).
+type SectionKey = (String, String, String, usize);
+
+fn keyed(mut sections: Vec<Section>) -> BTreeMap<SectionKey, Section> {
+    sections.sort_by(|a, b| {
+        (&a.repository_url, &a.relative_path, a.chunk_index).cmp(&(
+            &b.repository_url,
+            &b.relative_path,
+            b.chunk_index,
+        ))
+    });
+    let mut ordinals: HashMap<(String, String, String), usize> = HashMap::new();
+    sections
+        .into_iter()
+        .map(|section| {
+            let ordinal = ordinals
+                .entry((
+                    section.repository_url.clone(),
+                    section.relative_path.clone(),
+                    section.heading_path.clone(),
+                ))
+                .or_default();
+            let key = (
+                section.repository_url.clone(),
+                section.relative_path.clone(),
+                section.heading_path.clone(),
+                *ordinal,
+            );
+            *ordinal += 1;
+            (key, section)
+        })
+        .collect()
+}
+
+/// Align `before` with `after` by file and heading. Sections at the same
+/// place are unchanged or modified (by `content_hash`). Of the rest, pairs
+/// with equal `content_hash`, then pairs whose vectors are at least
+/// `move_threshold` similar (closest first), are moved; what is left was
+/// removed or added. Returns the changes in file order and the number of
+/// unchanged sections.
+pub fn align(
+    before: Vec<Section>,
+    after: Vec<Section>,
+    move_threshold: f32,
+) -> (Vec<SectionChange>, usize) {
+    let mut before = keyed(before);
+    let after = keyed(after);
+    let mut changes = Vec::new();
+    let mut unchanged = 0;
+    let mut added = Vec::new();
+
+    for (key, new) in after {
+        match before.remove(&key) {
+            Some(old) if old.content_hash == new.content_hash => unchanged += 1,
+            Some(old) => changes.push(SectionChange {
+                kind: ChangeKind::Modified,
+                similarity: old.similarity(&new),
+                before: Some(old),
+                after: Some(new),
+            }),
+            None => added.push(Some(new)),
+        }
+    }
+    let mut removed: Vec<Option<Section>> = before.into_values().map(Some).collect();
+
+    // Identical text under another file or heading.
+    let mut by_hash: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, old) in removed.iter().enumerate() {
+        if let Some(old) = old {
+            by_hash.entry(old.content_hash.clone()).or_default().push(i);
+        }
+    }
+    for slot in added.iter_mut() {
+        let Some(new) = slot else { continue };
+        let Some(i) = by_hash.get_mut(&new.content_hash).and_then(|c| c.pop()) else {
+            continue;
+        };
+        let old = removed[i].take();
+        changes.push(SectionChange {
+            kind: ChangeKind::Moved,
+            before: old,
+            after: slot.take(),
+            similarity: Some(1.0),
+        });
+    }
+
+    // Reworded and moved: the closest remaining pairs above the threshold.
+    let mut candidates = Vec::new();
+    for (i, old) in removed.iter().enumerate() {
+        for (j, new) in added.iter().enumerate() {
+            if let (Some(old), Some(new)) = (old, new)
+                && let Some(similarity) = old.similarity(new)
+                && similarity >= move_threshold
+            {
+                candidates.push((similarity, i, j));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (similarity, i, j) in candidates {
+        if removed[i].is_none() || added[j].is_none() {
+            continue;
+        }
+        changes.push(SectionChange {
+            kind: ChangeKind::Moved,
+            before: removed[i].take(),
+            after: added[j].take(),
+            similarity: Some(similarity),
+        });
+    }
+
+    changes.extend(removed.into_iter().flatten().map(|old| SectionChange {
+        kind: ChangeKind::Removed,
+        before: Some(old),
+        after: None,
+        similarity: None,
+    }));
+    changes.extend(added.into_iter().flatten().map(|new| SectionChange {
+        kind: ChangeKind::Added,
+        before: None,
+        after: Some(new),
+        similarity: None,
+    }));
+
+    changes.sort_by(|a, b| {
+        let (a, b) = (a.section(), b.section());
+        (&a.repository_url, &a.relative_path, a.chunk_index).cmp(&(
+            &b.repository_url,
+            &b.relative_path,
+            b.chunk_index,
+        ))
+    });
+    (changes, unchanged)
+}
+
+/// Numbered changes with both versions' text, as shown to the model.
+fn changelog_context(changes: &[SectionChange]) -> String {
+    let excerpt = |section: &Section| -> String {
+        section
+            .content
+            .chars()
+            .take(CHANGELOG_EXCERPT_CHARS)
+            .collect()
+    };
+    let mut context = changes
+        .iter()
+        .take(CHANGELOG_MAX_CHANGES)
+        .enumerate()
+        .map(|(idx, change)| {
+            let mut entry = format!("[{}] {}", idx + 1, change.kind.as_str());
+            if let Some(before) = &change.before {
+                entry.push_str(&format!(
+                    "\nBefore ({}):\n{}",
+                    before.location(),
+                    excerpt(before)
+                ));
+            }
+            if let Some(after) = &change.after {
+                entry.push_str(&format!(
+                    "\nAfter ({}):\n{}",
+                    after.location(),
+                    excerpt(after)
+                ));
+            }
+            entry
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if changes.len() > CHANGELOG_MAX_CHANGES {
+        context.push_str(&format!(
+            "\n\n({} further change(s) not shown)",
+            changes.len() - CHANGELOG_MAX_CHANGES
+        ));
+    }
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(path: &str, heading: &str, index: u32, content: &str, embedding: &[f32]) -> Section {
+        Section {
+            repository_url: "https://github.com/x/y".to_string(),
+            relative_path: path.to_string(),
+            heading_path: heading.to_string(),
+            chunk_index: index,
+            content: content.to_string(),
+            content_hash: format!("hash:{content}"),
+            git_ref: "v1".to_string(),
+            git_commit: None,
+            embedding: Some(embedding.to_vec()),
+        }
+    }
+
+    fn kinds(changes: &[SectionChange]) -> Vec<(ChangeKind, String)> {
+        changes
+            .iter()
+            .map(|c| (c.kind, c.section().location()))
+            .collect()
+    }
+
+    #[test]
+    fn sections_are_aligned_by_file_and_heading() {
+        let before = vec![
+            section("a.md", "Intro", 0, "same", &[1.0, 0.0]),
+            section("a.md", "Setup", 1, "old setup", &[0.0, 1.0]),
+            section("a.md", "Legacy", 2, "gone", &[1.0, 1.0]),
+        ];
+        let after = vec![
+            section("a.md", "Intro", 0, "same", &[1.0, 0.0]),
+            section("a.md", "Setup", 1, "new setup", &[0.1, 1.0]),
+            section("a.md", "Plugins", 2, "brand new", &[-1.0, 0.0]),
+        ];
+
+        let (changes, unchanged) = align(before, after, DEFAULT_MOVE_THRESHOLD);
+        assert_eq!(unchanged, 1);
+        assert_eq!(
+            kinds(&changes),
+            vec![
+                (ChangeKind::Modified, "a.md # Setup @ v1".to_string()),
+                (ChangeKind::Removed, "a.md # Legacy @ v1".to_string()),
+                (ChangeKind::Added, "a.md # Plugins @ v1".to_string()),
+            ]
+        );
+        assert!(changes[0].similarity.unwrap() > 0.9);
+    }
+
+    #[test]
+    fn moved_and_reworded_sections_are_paired() {
+        let before = vec![
+            section("old.md", "Install", 0, "pip install x", &[1.0, 0.0, 0.0]),
+            section("a.md", "Retry", 0, "retries three times", &[0.0, 1.0, 0.0]),
+        ];
+        let after = vec![
+            section(
+                "guide/install.md",
+                "Install",
+                0,
+                "pip install x",
+                &[1.0, 0.0, 0.0],
+            ),
+            section("a.md", "Retries", 0, "retries 3 times", &[0.0, 0.99, 0.05]),
+        ];
+
+        let (changes, unchanged) = align(before, after, DEFAULT_MOVE_THRESHOLD);
+        assert_eq!(unchanged, 0);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|c| c.kind == ChangeKind::Moved));
+        let install = changes
+            .iter()
+            .find(|c| c.section().relative_path == "guide/install.md")
+            .unwrap();
+        assert_eq!(install.before.as_ref().unwrap().relative_path, "old.md");
+        assert_eq!(install.similarity, Some(1.0));
+
+        // Below the threshold the reworded section is a removal plus an addition.
+        let (changes, _) = align(
+            vec![section(
+                "a.md",
+                "Retry",
+                0,
+                "retries three times",
+                &[0.0, 1.0, 0.0],
+            )],
+            vec![section(
+                "a.md",
+                "Retries",
+                0,
+                "retries 3 times",
+                &[0.0, 0.5, 0.5],
+            )],
+            DEFAULT_MOVE_THRESHOLD,
+        );
+        assert_eq!(
+            changes.iter().map(|c| c.kind).collect::<Vec<_>>(),
+            vec![ChangeKind::Removed, ChangeKind::Added]
+        );
+    }
+
+    #[test]
+    fn refs_resolve_to_the_commit_they_were_ingested_at() {
+        let at = |git_ref: &str, commit: &str| {
+            let mut s = section("a.md", "Intro", 0, "text", &[1.0]);
+            s.git_ref = git_ref.to_string();
+            s.git_commit = Some(commit.to_string());
+            s
+        };
+        // "abc" is a branch, and also a prefix of another ref's commit.
+        let sections = vec![at("abc", "1111"), at("v1", "abc123"), at("v2", "abc456")];
+        assert_eq!(
+            resolved_commit("abc", &sections).unwrap(),
+            Some("1111".to_string())
+        );
+        assert_eq!(
+            resolved_commit("abc1", &sections[1..2]).unwrap(),
+            Some("abc123".to_string())
+        );
+        assert!(matches!(
+            resolved_commit("ab", &sections[1..]),
+            Err(PipelineError::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn sections_are_only_compared_within_one_repository() {
+        use crate::config::DatabaseConfig;
+        use crate::database::DocumentWriter;
+        use crate::database::insert::PreparedFile;
+        use crate::models::Document;
+        use crate::parser::Chunk;
+        use crate::repository::{ReferenceKind, ResolvedReference};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let client = LanceDbClient::new(DatabaseConfig {
+            uri: dir.path().display().to_string(),
+            table_name: "documents".to_string(),
+            batch_size: 100,
+            index: Default::default(),
+            maintenance: Default::default(),
+            run_log: Default::default(),
+        })
+        .await
+        .unwrap();
+        let file =
+            |repository: &str, git_ref: &str, path: &str, content: &str, vector: [f32; 2]| {
+                let reference = ResolvedReference {
+                    name: git_ref.to_string(),
+                    kind: ReferenceKind::Tag,
+                    commit: format!("{repository}-{git_ref}"),
+                };
+                let chunk = Chunk {
+                    index: 0,
+                    heading_path: vec!["Install".to_string()],
+                    content: content.to_string(),
+                };
+                let document = Document::from_chunk(path, path, &chunk, 0, repository, false)
+                    .with_reference(Some(&reference));
+                PreparedFile {
+                    repository_url: repository.to_string(),
+                    relative_path: path.to_string(),
+                    git_ref: Some(git_ref.to_string()),
+                    documents: vec![document],
+                    embeddings: vec![vector.to_vec()],
+                    degraded: false,
+                }
+            };
+        let mut writer = DocumentWriter::new(&client, 2);
+        // A section leaves "one" at v2 while "two" gains the same text and
+        // vector at v2; across repositories they would pair as a move.
+        for file in [
+            file("one", "v1", "install.md", "pip install x", [1.0, 0.0]),
+            file("one", "v2", "other.md", "something else", [0.0, 1.0]),
+            file("two", "v1", "readme.md", "unrelated", [0.0, 1.0]),
+            file("two", "v2", "guide/install.md", "pip install x", [1.0, 0.0]),
+        ] {
+            writer.push(file).await.unwrap();
+        }
+        writer.finish().await.unwrap();
+
+        let report = RefDiff::new(&client, "one")
+            .compare("v1", "v2")
+            .await
+            .unwrap();
+        assert_eq!(report.from_commit.as_deref(), Some("one-v1"));
+        assert_eq!(report.to_commit.as_deref(), Some("one-v2"));
+        assert_eq!(
+            kinds(&report.changes),
+            vec![
+                (ChangeKind::Removed, "install.md # Install @ v1".to_string()),
+                (ChangeKind::Added, "other.md # Install @ v2".to_string()),
+            ]
+        );
+
+        let report = RefDiff::new(&client, "two")
+            .compare("v1", "v2")
+            .await
+            .unwrap();
+        assert_eq!(report.from_commit.as_deref(), Some("two-v1"));
+        assert_eq!(
+            kinds(&report.changes),
+            vec![
+                (
+                    ChangeKind::Added,
+                    "guide/install.md # Install @ v2".to_string()
+                ),
+                (ChangeKind::Removed, "readme.md # Install @ v1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn changelog_context_cites_both_versions() {
+        let mut after = section("a.md", "Setup", 1, "new setup", &[0.1, 1.0]);
+        after.git_ref = "v2".to_string();
+        let changes = vec![SectionChange {
+            kind: ChangeKind::Modified,
+            before: Some(section("a.md", "Setup", 1, "old setup", &[0.0, 1.0])),
+            after: Some(after),
+            similarity: Some(0.99),
+        }];
+        assert_eq!(
+            changelog_context(&changes),
+            "[1] modified\nBefore (a.md # Setup @ v1):\nold setup\nAfter (a.md # Setup @ v2):\nnew setup"
+        );
+    }
+}
@@ -2,10 +2,12 @@
 // description: text embedding client for an OpenAI-compatible embeddings endpoint
 // reference: https://platform.openai.com/docs/api-reference/embeddings
 
-use crate::config::EmbeddingConfig;
+use crate::config::{EmbeddingConfig, EmbeddingLimits};
 use crate::error::{PipelineError, Result};
-use reqwest::Client;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use tracing::{debug, warn};
 
 #[derive(Debug, Serialize)]
@@ -52,6 +54,10 @@ impl EmbeddingClient {
         self.config.dimension
     }
 
+    pub fn limits(&self) -> &EmbeddingLimits {
+        &self.config.limits
+    }
+
     fn endpoint(&self) -> String {
         format!("{}/embeddings", self.config.base_url.trim_end_matches('/'))
     }
@@ -59,9 +65,8 @@ impl EmbeddingClient {
     /// Embed a single string. Convenience wrapper over [`Self::embed_batch`].
     pub async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
         let mut out = self.embed_batch(&[text.to_string()]).await?;
-        out.pop().ok_or_else(|| {
-            PipelineError::Database("Embedding API returned no vectors".to_string())
-        })
+        out.pop()
+            .ok_or_else(|| PipelineError::Database("Embedding API returned no vectors".to_string()))
     }
 
     /// Embed many strings in a single request, returning vectors in input order.
@@ -70,12 +75,6 @@ impl EmbeddingClient {
             return Ok(Vec::new());
         }
 
-        let api_key = self.config.api_key.as_ref().ok_or_else(|| {
-            PipelineError::Config(
-                "No embedding API key configured (set embedding.api_key, EMBEDDING_API_KEY, or OPENAI_API_KEY)".to_string(),
-            )
-        })?;
-
         let request = EmbeddingRequest {
             input: texts.to_vec(),
             model: self.config.model.clone(),
@@ -89,29 +88,7 @@ impl EmbeddingClient {
             self.config.model
         );
 
-        let response = self
-            .client
-            .post(self.endpoint())
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| {
-                PipelineError::Database(format!("Failed to send embedding request: {}", e))
-            })?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(PipelineError::Database(format!(
-                "Embedding request failed with status {}: {}",
-                status, error_text
-            )));
-        }
+        let response = self.send_with_retry(&request).await?;
 
         let mut parsed: EmbeddingResponse = response.json().await.map_err(|e| {
             PipelineError::Database(format!("Failed to parse embedding response: {}", e))
@@ -144,6 +121,79 @@ impl EmbeddingClient {
         Ok(vectors)
     }
 
+    /// POST `request`, retrying 429, 5xx and connection failures with
+    /// exponential backoff. A `retry-after-ms` or `Retry-After` header
+    /// overrides the computed delay.
+    async fn send_with_retry(&self, request: &EmbeddingRequest) -> Result<reqwest::Response> {
+        let limits = &self.config.limits;
+        let mut attempt = 0;
+
+        loop {
+            // Local embedding servers usually run without auth, so the key is optional.
+            let mut builder = self.client.post(self.endpoint());
+            if let Some(api_key) = &self.config.api_key {
+                builder = builder.header("Authorization", format!("Bearer {}", api_key));
+            }
+
+            let outcome = builder
+                .header("Content-Type", "application/json")
+                .json(request)
+                .send()
+                .await;
+
+            let (error, retry_after) = match outcome {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = retry_after(
+                        response.headers(),
+                        Duration::from_millis(limits.max_backoff_ms),
+                    );
+                    let error_text = response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string());
+                    let hint = if status == StatusCode::UNAUTHORIZED
+                        && self.config.api_key.is_none()
+                    {
+                        " (no API key configured; set embedding.api_key, EMBEDDING_API_KEY, or OPENAI_API_KEY)"
+                    } else {
+                        ""
+                    };
+                    let error = PipelineError::Database(format!(
+                        "Embedding request failed with status {}: {}{}",
+                        status, error_text, hint
+                    ));
+                    if !is_retryable(status) {
+                        return Err(error);
+                    }
+                    (error, retry_after)
+                }
+                Err(e) => (
+                    PipelineError::Database(format!("Failed to send embedding request: {}", e)),
+                    None,
+                ),
+            };
+
+            if attempt >= limits.max_retries {
+                return Err(error);
+            }
+
+            let delay = retry_after.unwrap_or_else(|| {
+                backoff_delay(attempt, limits.initial_backoff_ms, limits.max_backoff_ms)
+            });
+            warn!(
+                "{}; retrying in {:?} (attempt {}/{})",
+                error,
+                delay,
+                attempt + 1,
+                limits.max_retries
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
     /// Deterministic, NON-SEMANTIC fallback. Only suitable for offline tests or
     /// when `embedding.allow_fallback` is explicitly enabled. It carries no
     /// meaning, so search results built on it are effectively random. Never mix
@@ -157,6 +207,29 @@ impl EmbeddingClient {
     }
 }
 
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// `initial * 2^attempt`, capped at `max`.
+fn backoff_delay(attempt: u32, initial_ms: u64, max_ms: u64) -> Duration {
+    let factor = 1u64.checked_shl(attempt).unwrap_or(u64::MAX);
+    Duration::from_millis(initial_ms.saturating_mul(factor).min(max_ms))
+}
+
+/// Server-requested delay: OpenAI's `retry-after-ms`, else `Retry-After` in
+/// seconds, capped at `max`. HTTP-date and out-of-range values are ignored in
+/// favour of the computed backoff.
+fn retry_after(headers: &HeaderMap, max: Duration) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<f64>().ok();
+    let seconds = header("retry-after-ms")
+        .map(|ms| ms / 1000.0)
+        .or_else(|| header("retry-after"))?;
+    Duration::try_from_secs_f64(seconds.max(0.0))
+        .ok()
+        .map(|delay| delay.min(max))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -183,7 +256,58 @@ mod tests {
             dimension: 768,
             api_key: Some("k".to_string()),
             allow_fallback: false,
+            limits: Default::default(),
+            cache: Default::default(),
         });
         assert_eq!(client.endpoint(), "https://api.openai.com/v1/embeddings");
     }
+
+    #[test]
+    fn backoff_doubles_and_caps() {
+        assert_eq!(backoff_delay(0, 500, 30_000), Duration::from_millis(500));
+        assert_eq!(backoff_delay(3, 500, 30_000), Duration::from_millis(4000));
+        assert_eq!(
+            backoff_delay(40, 500, 30_000),
+            Duration::from_millis(30_000)
+        );
+        assert_eq!(
+            backoff_delay(70, 500, 30_000),
+            Duration::from_millis(30_000)
+        );
+    }
+
+    #[test]
+    fn retry_after_prefers_milliseconds_header() {
+        let max = Duration::from_secs(30);
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "2".parse().unwrap());
+        assert_eq!(retry_after(&headers, max), Some(Duration::from_secs(2)));
+
+        headers.insert("retry-after-ms", "250".parse().unwrap());
+        assert_eq!(retry_after(&headers, max), Some(Duration::from_millis(250)));
+
+        let mut dated = HeaderMap::new();
+        dated.insert(
+            "retry-after",
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&dated, max), None);
+
+        // A misbehaving server can neither crash the ingest nor stall it.
+        for (value, expected) in [
+            ("1e300", None),
+            ("inf", None),
+            ("NaN", Some(Duration::ZERO)),
+            ("-5", Some(Duration::ZERO)),
+            ("3600", Some(max)),
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert("retry-after", value.parse().unwrap());
+            assert_eq!(retry_after(&headers, max), expected, "Retry-After: {value}");
+        }
+
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+    }
 }
@@ -0,0 +1,667 @@
+// file: src/database/filter.rs
+// description: metadata filter expressions for search, compiled to LanceDB predicates
+// reference: https://lancedb.github.io/lancedb/sql/
+
+use crate::database::client::escape_sql_literal;
+use crate::error::{PipelineError, Result};
+use crate::repository::reference::looks_like_commit;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Longest filter expression accepted, in bytes.
+const MAX_FILTER_LENGTH: usize = 4096;
+
+/// Deepest nesting of parentheses and `not` accepted; the parser recurses
+/// once per level.
+const MAX_FILTER_DEPTH: usize = 32;
+
+/// A boolean filter over chunk metadata, e.g.
+/// `path:docs/** and (ext:md or lang:en) and not tag:draft and modified>30d`.
+///
+/// Values only ever reach the predicate as escaped SQL string literals or
+/// integers, so a filter string cannot inject SQL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchFilter {
+    And(Box<SearchFilter>, Box<SearchFilter>),
+    Or(Box<SearchFilter>, Box<SearchFilter>),
+    Not(Box<SearchFilter>),
+    Condition(Condition),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// Exact `repository_url`.
+    Repository(String),
+    /// `relative_path` prefix, or a glob when the value contains `*` or `?`.
+    Path(String),
+    /// File extension without the dot; case-insensitive.
+    Extension(String),
+    /// Document language from frontmatter (`lang`/`language`).
+    Language(String),
+    /// Case-insensitive substring of `heading_path`.
+    Heading(String),
+    /// `last_modified` compared with a Unix timestamp in seconds.
+    Modified(Comparison, u64),
+    /// `last_changed_at` (author time of the last commit touching the chunk)
+    /// compared with a Unix timestamp in seconds.
+    Changed(Comparison, u64),
+    Category(String),
+    Tag(String),
+    /// Ingested at this `git_ref`, or at a commit with this (abbreviated) sha.
+    At(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Before,
+    AtOrBefore,
+    After,
+    AtOrAfter,
+}
+
+impl SearchFilter {
+    /// Parse a filter expression. Relative times (`30d`) count back from now.
+    pub fn parse(input: &str) -> Result<Self> {
+        Self::parse_at(input, now_secs())
+    }
+
+    /// Parse with relative times counted back from `now` (Unix seconds).
+    pub fn parse_at(input: &str, now: u64) -> Result<Self> {
+        if input.len() > MAX_FILTER_LENGTH {
+            return Err(invalid(format!("longer than {} bytes", MAX_FILTER_LENGTH)));
+        }
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            return Err(invalid("filter is empty"));
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            depth: 0,
+            now,
+        };
+        let filter = parser.or_expr()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(invalid(format!("unexpected {}", token))),
+        }
+    }
+
+    pub fn repository(url: &str) -> Self {
+        Self::Condition(Condition::Repository(url.to_string()))
+    }
+
+    /// Rows ingested at a ref (branch or tag) or commit.
+    pub fn at(reference: &str) -> Self {
+        Self::Condition(Condition::At(reference.to_string()))
+    }
+
+    pub fn and(self, other: Self) -> Self {
+        Self::And(Box::new(self), Box::new(other))
+    }
+
+    /// Combine optional filters with AND.
+    pub fn all(filters: impl IntoIterator<Item = Self>) -> Option<Self> {
+        filters.into_iter().reduce(Self::and)
+    }
+
+    /// The predicate passed to LanceDB's `only_if`.
+    pub fn to_predicate(&self) -> String {
+        match self {
+            Self::And(a, b) => format!("({} AND {})", a.to_predicate(), b.to_predicate()),
+            Self::Or(a, b) => format!("({} OR {})", a.to_predicate(), b.to_predicate()),
+            Self::Not(inner) => format!("(NOT {})", inner.to_predicate()),
+            Self::Condition(condition) => condition.to_predicate(),
+        }
+    }
+}
+
+impl Condition {
+    /// Conditions on nullable columns test `IS NOT NULL` first so that NULL
+    /// rows are plainly false, and `not` selects them.
+    fn to_predicate(&self) -> String {
+        match self {
+            Self::Repository(url) => format!("repository_url = {}", literal(url)),
+            Self::Path(pattern) if is_glob(pattern) => {
+                format!("relative_path LIKE {}", literal(&glob_to_like(pattern)))
+            }
+            Self::Path(prefix) => {
+                format!(
+                    "relative_path LIKE {}",
+                    literal(&format!("{}%", escape_like(prefix)))
+                )
+            }
+            Self::Extension(ext) => format!(
+                "lower(relative_path) LIKE {}",
+                literal(&format!("%.{}", escape_like(ext)))
+            ),
+            Self::Language(language) => format!(
+                "(language IS NOT NULL AND language = {})",
+                literal(language)
+            ),
+            Self::Heading(text) => format!(
+                "lower(heading_path) LIKE {}",
+                literal(&format!("%{}%", escape_like(text)))
+            ),
+            Self::Modified(comparison, timestamp) => {
+                format!("last_modified {} {}", comparison.operator(), timestamp)
+            }
+            Self::Changed(comparison, timestamp) => format!(
+                "(last_changed_at IS NOT NULL AND last_changed_at {} {})",
+                comparison.operator(),
+                timestamp
+            ),
+            Self::Category(category) => format!(
+                "(category IS NOT NULL AND category = {})",
+                literal(category)
+            ),
+            Self::Tag(tag) => format!(
+                "(tags IS NOT NULL AND tags LIKE {})",
+                literal(&format!("%,{},%", escape_like(tag)))
+            ),
+            Self::At(reference) if looks_like_commit(reference) => format!(
+                "(git_ref IS NOT NULL AND (git_ref = {} OR git_commit LIKE {}))",
+                literal(reference),
+                literal(&format!("{}%", escape_like(&reference.to_lowercase())))
+            ),
+            Self::At(reference) => {
+                format!("(git_ref IS NOT NULL AND git_ref = {})", literal(reference))
+            }
+        }
+    }
+}
+
+impl Comparison {
+    fn operator(self) -> &'static str {
+        match self {
+            Self::Before => "<",
+            Self::AtOrBefore => "<=",
+            Self::After => ">",
+            Self::AtOrAfter => ">=",
+        }
+    }
+}
+
+impl fmt::Display for SearchFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_predicate())
+    }
+}
+
+/// Encode tags for the `tags` column as `,a,b,` so a single tag matches
+/// with `LIKE '%,tag,%'`. Tags are lower-cased; commas are dropped.
+pub(crate) fn encode_tags(tags: &[String]) -> Option<String> {
+    let tags: Vec<String> = tags
+        .iter()
+        .map(|t| t.trim().to_lowercase().replace(',', ""))
+        .filter(|t| !t.is_empty())
+        .collect();
+    if tags.is_empty() {
+        None
+    } else {
+        Some(format!(",{},", tags.join(",")))
+    }
+}
+
+fn literal(value: &str) -> String {
+    format!("'{}'", escape_sql_literal(value))
+}
+
+/// Escape LIKE wildcards; backslash is the LIKE escape character.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// `*` and `**` become `%` (LIKE cannot stop at `/`, so `*` may match across
+/// directories), `?` becomes `_`.
+fn glob_to_like(pattern: &str) -> String {
+    let mut like = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => {
+                while chars.peek() == Some(&'*') {
+                    chars.next();
+                }
+                like.push('%');
+            }
+            '?' => like.push('_'),
+            '%' | '_' | '\\' => {
+                like.push('\\');
+                like.push(c);
+            }
+            c => like.push(c),
+        }
+    }
+    like
+}
+
+fn invalid(message: impl fmt::Display) -> PipelineError {
+    PipelineError::Validation(format!("Invalid search filter: {}", message))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    /// `field:value` or `field<op>value`; quoted parts already unescaped.
+    Term(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LParen => write!(f, "'('"),
+            Self::RParen => write!(f, "')'"),
+            Self::And => write!(f, "'and'"),
+            Self::Or => write!(f, "'or'"),
+            Self::Not => write!(f, "'not'"),
+            Self::Term(term) => write!(f, "'{}'", term),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '!' => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    return Err(invalid(format!("expected '{c}{c}'")));
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            _ => {
+                let mut word = String::new();
+                let mut quoted = false;
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    if c != '"' {
+                        word.push(c);
+                        continue;
+                    }
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some(escaped) => word.push(escaped),
+                                None => return Err(invalid("unterminated quote")),
+                            },
+                            Some(c) => word.push(c),
+                            None => return Err(invalid("unterminated quote")),
+                        }
+                    }
+                }
+
+                let keyword = if quoted {
+                    None
+                } else {
+                    match word.to_lowercase().as_str() {
+                        "and" => Some(Token::And),
+                        "or" => Some(Token::Or),
+                        "not" => Some(Token::Not),
+                        _ => None,
+                    }
+                };
+                tokens.push(keyword.unwrap_or(Token::Term(word)));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent: `not` binds tightest, then `and` (also implied between
+/// adjacent terms), then `or`.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// Parentheses and `not`s enclosing the current position.
+    depth: usize,
+    now: u64,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_FILTER_DEPTH {
+            return Err(invalid(format!(
+                "nested deeper than {} levels",
+                MAX_FILTER_DEPTH
+            )));
+        }
+        Ok(())
+    }
+
+    fn or_expr(&mut self) -> Result<SearchFilter> {
+        let mut left = self.and_expr()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            let right = self.and_expr()?;
+            left = SearchFilter::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<SearchFilter> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Not | Token::LParen | Token::Term(_)) => {}
+                _ => break,
+            }
+            let right = self.unary()?;
+            left = left.and(right);
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<SearchFilter> {
+        match self.next() {
+            Some(Token::Not) => {
+                self.enter()?;
+                let inner = self.unary()?;
+                self.depth -= 1;
+                Ok(SearchFilter::Not(Box::new(inner)))
+            }
+            Some(Token::LParen) => {
+                self.enter()?;
+                let inner = self.or_expr()?;
+                self.depth -= 1;
+                match self.next() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err(invalid("missing ')'")),
+                }
+            }
+            Some(Token::Term(term)) => {
+                parse_condition(&term, self.now).map(SearchFilter::Condition)
+            }
+            Some(token) => Err(invalid(format!("unexpected {}", token))),
+            None => Err(invalid("expression ends early")),
+        }
+    }
+}
+
+fn parse_condition(term: &str, now: u64) -> Result<Condition> {
+    let split = term
+        .find([':', '<', '>', '='])
+        .ok_or_else(|| invalid(format!("'{}' is not of the form field:value", term)))?;
+    let field = term[..split].to_lowercase();
+    let rest = &term[split..];
+    let (operator, value) = ["<=", ">=", ":", "<", ">", "="]
+        .iter()
+        .find_map(|op| rest.strip_prefix(op).map(|value| (*op, value)))
+        .unwrap_or((":", rest));
+
+    if value.is_empty() {
+        return Err(invalid(format!("'{}' has no value", term)));
+    }
+
+    if field == "modified" || field == "changed" {
+        let comparison = match operator {
+            "<" => Comparison::Before,
+            "<=" => Comparison::AtOrBefore,
+            ">" => Comparison::After,
+            ">=" => Comparison::AtOrAfter,
+            _ => {
+                return Err(invalid(format!(
+                    "{field} takes <, <=, > or >=, e.g. {field}>2024-01-01 or {field}>30d"
+                )));
+            }
+        };
+        let timestamp = parse_time(value, now)?;
+        return Ok(if field == "changed" {
+            Condition::Changed(comparison, timestamp)
+        } else {
+            Condition::Modified(comparison, timestamp)
+        });
+    }
+
+    if operator != ":" && operator != "=" {
+        return Err(invalid(format!("'{}' only supports field:value", field)));
+    }
+
+    let value = value.to_string();
+    Ok(match field.as_str() {
+        "repo" | "repository" => Condition::Repository(value),
+        "path" => Condition::Path(value),
+        "ext" | "extension" => Condition::Extension(value.trim_start_matches('.').to_lowercase()),
+        "lang" | "language" => Condition::Language(value.to_lowercase()),
+        "heading" => Condition::Heading(value.to_lowercase()),
+        "category" => Condition::Category(value),
+        "tag" => {
+            if value.contains(',') {
+                return Err(invalid(
+                    "a tag cannot contain ','; combine tags with and/or",
+                ));
+            }
+            Condition::Tag(value.to_lowercase())
+        }
+        "at" | "ref" => Condition::At(value),
+        other => {
+            return Err(invalid(format!(
+                "unknown field '{}' (expected repo, path, ext, lang, heading, modified, \
+                 changed, category, tag or at)",
+                other
+            )));
+        }
+    })
+}
+
+/// `YYYY-MM-DD` (UTC midnight), RFC 3339, a Unix timestamp, or an age such
+/// as `12h`, `30d` or `6w` counted back from `now`.
+fn parse_time(value: &str, now: u64) -> Result<u64> {
+    if let Some(unit) = value
+        .chars()
+        .last()
+        .filter(|c| matches!(c, 'h' | 'd' | 'w'))
+        && let Ok(amount) = value[..value.len() - 1].parse::<u64>()
+    {
+        let seconds = match unit {
+            'h' => 3_600,
+            'd' => 86_400,
+            _ => 7 * 86_400,
+        };
+        return Ok(now.saturating_sub(amount.saturating_mul(seconds)));
+    }
+    if let Ok(timestamp) = value.parse::<u64>() {
+        return Ok(timestamp);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date
+            .and_hms_opt(0, 0, 0)
+            .map(|t| t.and_utc().timestamp().max(0) as u64)
+            .unwrap_or(0));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc).timestamp().max(0) as u64);
+    }
+    Err(invalid(format!(
+        "'{}' is not a date (YYYY-MM-DD), timestamp or age like 30d",
+        value
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn predicate(input: &str) -> String {
+        SearchFilter::parse_at(input, NOW).unwrap().to_predicate()
+    }
+
+    #[test]
+    fn precedence_and_implicit_and() {
+        assert_eq!(
+            predicate("path:docs/ ext:md or not lang:en"),
+            "((relative_path LIKE 'docs/%' AND lower(relative_path) LIKE '%.md') OR \
+             (NOT (language IS NOT NULL AND language = 'en')))"
+        );
+        assert_eq!(
+            predicate("category:guides && (tag:Draft || heading:Install)"),
+            "((category IS NOT NULL AND category = 'guides') AND \
+             ((tags IS NOT NULL AND tags LIKE '%,draft,%') OR \
+             lower(heading_path) LIKE '%install%'))"
+        );
+    }
+
+    #[test]
+    fn values_cannot_inject_sql() {
+        assert_eq!(
+            predicate(r#"repo:"x' OR '1'='1""#),
+            "repository_url = 'x'' OR ''1''=''1'"
+        );
+        assert_eq!(
+            predicate("path:docs/100%_done/**/*.md"),
+            r"relative_path LIKE 'docs/100\%\_done/%/%.md'"
+        );
+        assert_eq!(
+            predicate(r#"heading:"and (or)""#),
+            "lower(heading_path) LIKE '%and (or)%'"
+        );
+    }
+
+    #[test]
+    fn modified_accepts_dates_and_ages() {
+        assert_eq!(
+            predicate("modified>=2024-01-01 modified<1704153600"),
+            "(last_modified >= 1704067200 AND last_modified < 1704153600)"
+        );
+        assert_eq!(
+            predicate("modified>30d"),
+            format!("last_modified > {}", NOW - 30 * 86_400)
+        );
+        assert_eq!(
+            predicate("changed<26w"),
+            format!(
+                "(last_changed_at IS NOT NULL AND last_changed_at < {})",
+                NOW - 26 * 7 * 86_400
+            )
+        );
+    }
+
+    #[test]
+    fn at_matches_refs_and_commit_prefixes() {
+        assert_eq!(
+            predicate("at:v1.4"),
+            "(git_ref IS NOT NULL AND git_ref = 'v1.4')"
+        );
+        assert_eq!(
+            predicate("ref:A1B2C3D"),
+            "(git_ref IS NOT NULL AND (git_ref = 'A1B2C3D' OR git_commit LIKE 'a1b2c3d%'))"
+        );
+        assert_eq!(
+            SearchFilter::at("release/2.0").to_predicate(),
+            "(git_ref IS NOT NULL AND git_ref = 'release/2.0')"
+        );
+    }
+
+    #[test]
+    fn malformed_filters_are_rejected() {
+        for input in [
+            "",
+            "docs",
+            "color:red",
+            "path:",
+            "(ext:md",
+            "ext:md)",
+            "modified:2024-01-01",
+            "changed=30d",
+            "modified>yesterday",
+            "tag:a,b",
+            "ext:md and",
+            r#"path:"docs"#,
+        ] {
+            assert!(SearchFilter::parse_at(input, NOW).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn oversized_and_deeply_nested_filters_are_rejected() {
+        let nested = |depth: usize| format!("{}ext:md{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(SearchFilter::parse_at(&nested(MAX_FILTER_DEPTH), NOW).is_ok());
+        for input in [
+            nested(MAX_FILTER_DEPTH + 1),
+            nested(100_000),
+            format!("{}ext:md", "not ".repeat(MAX_FILTER_DEPTH + 1)),
+            "ext:md and ".repeat(MAX_FILTER_LENGTH / 10) + "ext:md",
+        ] {
+            assert!(
+                matches!(
+                    SearchFilter::parse_at(&input, NOW),
+                    Err(PipelineError::Validation(_))
+                ),
+                "{}",
+                &input[..20]
+            );
+        }
+    }
+
+    #[test]
+    fn tags_are_encoded_for_like_matching() {
+        assert_eq!(
+            encode_tags(&["Rust".to_string(), " ".to_string(), "a,b".to_string()]),
+            Some(",rust,ab,".to_string())
+        );
+        assert_eq!(encode_tags(&[]), None);
+    }
+}
@@ -0,0 +1,531 @@
+// file: src/database/history.rs
+// description: commits table of embedded commit messages and diff hunks
+// reference: https://docs.rs/lancedb/latest/lancedb/query/struct.VectorQuery.html
+
+use crate::config::{Config, DistanceMetric, HistoryConfig};
+use crate::database::client::{
+    LanceDbClient, distance_to_score, distance_type, escape_sql_literal,
+};
+use crate::database::insert::{BatchInserter, Embedder};
+use crate::database::schema::SchemaManager;
+use crate::error::{PipelineError, Result};
+use crate::models::{
+    SearchResult, SearchResultFileMetadata, SearchResultPaths, SearchResultScoring,
+};
+use crate::parser::ChunkOptions;
+use crate::repository::{CommitEntry, HistoryWalker};
+use arrow_array::{
+    Array, ArrayRef, Float32Array, RecordBatch, RecordBatchIterator, StringArray, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use futures::StreamExt;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// `kind` of the row holding a commit's message.
+pub const MESSAGE_KIND: &str = "message";
+/// `kind` of a row holding one diff hunk.
+pub const HUNK_KIND: &str = "diff";
+
+/// One row of the commits table, before embedding.
+#[derive(Debug, Clone)]
+pub struct CommitRow {
+    /// The commit sha for message rows, `<sha>:<path>:<n>` for hunks.
+    pub id: String,
+    pub commit: String,
+    pub kind: &'static str,
+    pub author: String,
+    pub author_email: String,
+    pub authored_at: u64,
+    pub summary: String,
+    /// The file a hunk belongs to; `None` for message rows.
+    pub relative_path: Option<String>,
+    /// The embedded text.
+    pub content: String,
+}
+
+impl CommitRow {
+    /// The message row of `entry` followed by one row per diff hunk.
+    pub fn from_entry(entry: &CommitEntry) -> Vec<Self> {
+        let row = |id: String, kind, relative_path, content| Self {
+            id,
+            commit: entry.commit.clone(),
+            kind,
+            author: entry.author.clone(),
+            author_email: entry.author_email.clone(),
+            authored_at: entry.authored_at,
+            summary: entry.summary().to_string(),
+            relative_path,
+            content,
+        };
+        let mut rows = vec![row(
+            entry.commit.clone(),
+            MESSAGE_KIND,
+            None,
+            entry.message_text(),
+        )];
+        rows.extend(entry.hunks.iter().enumerate().map(|(n, hunk)| {
+            row(
+                format!("{}:{}:{}", entry.commit, hunk.path, n),
+                HUNK_KIND,
+                Some(hunk.path.clone()),
+                entry.hunk_text(hunk),
+            )
+        }));
+        rows
+    }
+}
+
+/// Reads and writes the commits table. Like the run log it lives beside the
+/// documents table; `reset` drops it with the documents.
+#[derive(Clone)]
+pub struct CommitStore {
+    client: LanceDbClient,
+    table_name: String,
+}
+
+impl CommitStore {
+    pub fn new(client: &LanceDbClient, config: &HistoryConfig) -> Self {
+        Self {
+            client: client.clone(),
+            table_name: config.table_name.clone(),
+        }
+    }
+
+    /// Embed the commits of the configured repository that are not indexed
+    /// yet (all of them with `force`), `database.batch_size` commits at a
+    /// time. Returns the number of commits written.
+    pub async fn index(&self, config: &Config, force: bool) -> Result<usize> {
+        let workdir = config.repository.working_dir();
+        if !workdir.join(".git").exists() {
+            warn!(
+                "{} is not a git repository; no history to index",
+                workdir.display()
+            );
+            return Ok(0);
+        }
+
+        // Commit vectors are compared with query vectors of the configured
+        // model, so the table is held to the same provenance as the documents.
+        self.schema()
+            .check_embedding_provenance(&config.embedding)
+            .await?;
+
+        let repository_url = config.repository.repository_id();
+        if force {
+            self.delete_repository(&repository_url).await?;
+        }
+        let known = self.known_commits(&repository_url).await?;
+        let commits =
+            HistoryWalker::new(config.repository.history.clone())?.walk(&workdir, &known)?;
+
+        let embedder = Embedder::from_config(&self.client, &config.embedding);
+        let inserter = BatchInserter::from_embedder(&self.client, &embedder)
+            .with_options(ChunkOptions::default(), config.embedding.allow_fallback);
+
+        let mut written = 0;
+        let mut any_degraded = false;
+        for batch in commits.chunks(config.database.batch_size) {
+            if gix::interrupt::is_triggered() {
+                warn!("Interrupted; {} commit(s) indexed so far", written);
+                break;
+            }
+            let rows: Vec<CommitRow> = batch.iter().flat_map(CommitRow::from_entry).collect();
+            let texts: Vec<String> = rows.iter().map(|row| row.content.clone()).collect();
+            let (embeddings, degraded) = inserter.embed(&texts).await?;
+            if degraded {
+                warn!("Commit history embedded with non-semantic fallback vectors");
+            }
+            any_degraded |= degraded;
+            self.add(&repository_url, &rows, &embeddings).await?;
+            written += batch.len();
+            debug!("Indexed {}/{} commit(s)", written, commits.len());
+        }
+
+        if written > 0 {
+            self.schema()
+                .update_embedding_provenance(&config.embedding, any_degraded)
+                .await?;
+        }
+        embedder.flush_cache().await;
+        Ok(written)
+    }
+
+    /// Schema operations, including embedding provenance, on the commits table.
+    pub fn schema(&self) -> SchemaManager<'_> {
+        SchemaManager::for_table(&self.client, &self.table_name)
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// Commits of `repository_url` that are already indexed.
+    pub async fn known_commits(&self, repository_url: &str) -> Result<HashSet<String>> {
+        let mut known = HashSet::new();
+        if !self.client.table_exists(&self.table_name).await? {
+            return Ok(known);
+        }
+        let table = self.client.get_table(&self.table_name).await?;
+        let mut stream = table
+            .query()
+            .only_if(format!(
+                "repository_url = '{}' AND kind = '{}'",
+                escape_sql_literal(repository_url),
+                MESSAGE_KIND
+            ))
+            .select(Select::columns(&["commit"]))
+            .execute()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read commits: {}", e)))?;
+        while let Some(batch) = stream.next().await {
+            let batch = batch
+                .map_err(|e| PipelineError::Database(format!("Failed to read batch: {}", e)))?;
+            let commits = string_column(&batch, "commit")?;
+            known.extend((0..batch.num_rows()).map(|i| commits.value(i).to_string()));
+        }
+        Ok(known)
+    }
+
+    pub async fn delete_repository(&self, repository_url: &str) -> Result<()> {
+        if !self.client.table_exists(&self.table_name).await? {
+            return Ok(());
+        }
+        self.client
+            .get_table(&self.table_name)
+            .await?
+            .delete(&format!(
+                "repository_url = '{}'",
+                escape_sql_literal(repository_url)
+            ))
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to delete commits: {}", e)))?;
+        Ok(())
+    }
+
+    /// Append `rows` with their `embeddings` (same order).
+    pub async fn add(
+        &self,
+        repository_url: &str,
+        rows: &[CommitRow],
+        embeddings: &[Vec<f32>],
+    ) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        if rows.len() != embeddings.len() {
+            return Err(PipelineError::Database(format!(
+                "{} commit rows but {} embeddings",
+                rows.len(),
+                embeddings.len()
+            )));
+        }
+        let dimension = embeddings[0].len();
+        let batch = commits_batch(commits_schema(dimension), repository_url, rows, embeddings)?;
+
+        if !self.client.table_exists(&self.table_name).await? {
+            self.client
+                .get_connection()
+                .create_table(&self.table_name, vec![batch])
+                .execute()
+                .await
+                .map_err(|e| {
+                    PipelineError::Database(format!(
+                        "Failed to create table {}: {}",
+                        self.table_name, e
+                    ))
+                })?;
+            info!("Created table: {}", self.table_name);
+            return Ok(());
+        }
+
+        let table = self.client.get_table(&self.table_name).await?;
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], commits_schema(dimension));
+        let mut merge = table.merge_insert(&["id"]);
+        merge
+            .when_matched_update_all(None)
+            .when_not_matched_insert_all();
+        merge
+            .execute(Box::new(reader))
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to write commits: {}", e)))?;
+        debug!("Wrote {} commit row(s)", rows.len());
+        Ok(())
+    }
+
+    /// Commit messages and hunks closest to `embedding`, as search results:
+    /// `relative_path` reads `commit <sha>` or `<path> @ <sha>`, the heading
+    /// is the commit summary and `last_modified` the author date.
+    pub async fn search(
+        &self,
+        embedding: Vec<f32>,
+        limit: usize,
+        repository_url: Option<&str>,
+    ) -> Result<Vec<SearchResult>> {
+        if limit == 0 || !self.client.table_exists(&self.table_name).await? {
+            return Ok(Vec::new());
+        }
+        let metric = self.client.index_config().metric;
+        let table = self.client.get_table(&self.table_name).await?;
+        let mut query = table
+            .vector_search(embedding)
+            .map_err(|e| PipelineError::Database(format!("Failed to create vector search: {}", e)))?
+            .distance_type(distance_type(metric))
+            .limit(limit);
+        if let Some(repository_url) = repository_url {
+            query = query.only_if(format!(
+                "repository_url = '{}'",
+                escape_sql_literal(repository_url)
+            ));
+        }
+        let mut stream = query
+            .execute()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Commit search failed: {}", e)))?;
+
+        let mut results = Vec::new();
+        while let Some(batch) = stream.next().await {
+            let batch = batch
+                .map_err(|e| PipelineError::Database(format!("Failed to read batch: {}", e)))?;
+            results.extend(read_results(&batch, metric)?);
+        }
+        Ok(results)
+    }
+
+    pub async fn drop_table(&self) -> Result<()> {
+        if !self.client.table_exists(&self.table_name).await? {
+            return Ok(());
+        }
+        self.client
+            .get_connection()
+            .drop_table(&self.table_name, &[])
+            .await
+            .map_err(|e| {
+                PipelineError::Database(format!("Failed to drop table {}: {}", self.table_name, e))
+            })?;
+        info!("Dropped table: {}", self.table_name);
+        Ok(())
+    }
+}
+
+fn commits_schema(dimension: usize) -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("repository_url", DataType::Utf8, false),
+        Field::new("commit", DataType::Utf8, false),
+        Field::new("kind", DataType::Utf8, false),
+        Field::new("author", DataType::Utf8, false),
+        Field::new("author_email", DataType::Utf8, false),
+        Field::new("authored_at", DataType::UInt64, false),
+        Field::new("summary", DataType::Utf8, false),
+        Field::new("relative_path", DataType::Utf8, true),
+        Field::new("content", DataType::Utf8, false),
+        Field::new(
+            "embedding",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                dimension as i32,
+            ),
+            false,
+        ),
+    ]))
+}
+
+fn commits_batch(
+    schema: Arc<Schema>,
+    repository_url: &str,
+    rows: &[CommitRow],
+    embeddings: &[Vec<f32>],
+) -> Result<RecordBatch> {
+    let strings = |f: fn(&CommitRow) -> Option<&str>| -> ArrayRef {
+        Arc::new(rows.iter().map(f).collect::<StringArray>())
+    };
+
+    RecordBatch::try_new(
+        schema,
+        vec![
+            strings(|r| Some(r.id.as_str())),
+            Arc::new(StringArray::from(vec![repository_url; rows.len()])),
+            strings(|r| Some(r.commit.as_str())),
+            strings(|r| Some(r.kind)),
+            strings(|r| Some(r.author.as_str())),
+            strings(|r| Some(r.author_email.as_str())),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|r| r.authored_at),
+            )),
+            strings(|r| Some(r.summary.as_str())),
+            strings(|r| r.relative_path.as_deref()),
+            strings(|r| Some(r.content.as_str())),
+            Arc::new(BatchInserter::embedding_array(embeddings)?),
+        ],
+    )
+    .map_err(|e| PipelineError::Database(format!("Failed to build commit batch: {}", e)))
+}
+
+fn string_column<'b>(batch: &'b RecordBatch, name: &str) -> Result<&'b StringArray> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+        .ok_or_else(|| PipelineError::Database(format!("Missing '{}' column", name)))
+}
+
+fn read_results(batch: &RecordBatch, metric: DistanceMetric) -> Result<Vec<SearchResult>> {
+    let ids = string_column(batch, "id")?;
+    let repositories = string_column(batch, "repository_url")?;
+    let commits = string_column(batch, "commit")?;
+    let summaries = string_column(batch, "summary")?;
+    let paths = string_column(batch, "relative_path")?;
+    let contents = string_column(batch, "content")?;
+    let authored = batch
+        .column_by_name("authored_at")
+        .and_then(|c| c.as_any().downcast_ref::<UInt64Array>())
+        .ok_or_else(|| PipelineError::Database("Missing 'authored_at' column".to_string()))?;
+    let distances = batch
+        .column_by_name("_distance")
+        .and_then(|c| c.as_any().downcast_ref::<Float32Array>());
+
+    Ok((0..batch.num_rows())
+        .map(|i| {
+            let commit = commits.value(i);
+            let short = &commit[..commit.len().min(10)];
+            let relative_path = if paths.is_null(i) {
+                format!("commit {}", short)
+            } else {
+                format!("{} @ {}", paths.value(i), short)
+            };
+            let distance = distances.map(|d| d.value(i));
+            SearchResult::new(
+                ids.value(i).to_string(),
+                SearchResultPaths {
+                    file_path: commit.to_string(),
+                    relative_path,
+                    heading_path: summaries.value(i).to_string(),
+                    chunk_index: 0,
+                },
+                contents.value(i).to_string(),
+                repositories.value(i).to_string(),
+                SearchResultScoring {
+                    score: distance.map_or(1.0, |d| distance_to_score(metric, d)),
+                    distance,
+                },
+                SearchResultFileMetadata {
+                    file_size: 0,
+                    last_modified: authored.value(i),
+                },
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DatabaseConfig, EmbeddingConfig};
+    use crate::repository::FileHunk;
+
+    #[test]
+    fn commits_round_trip_into_search_results() {
+        let entry = CommitEntry {
+            commit: "0123456789abcdef0123456789abcdef01234567".to_string(),
+            author: "Ada".to_string(),
+            author_email: "ada@example.com".to_string(),
+            authored_at: 1_700_000_000,
+            message: "Retry on 503\n\nThe gateway sheds load with 503s.".to_string(),
+            changed_paths: vec!["docs/retry.md".to_string()],
+            hunks: vec![FileHunk {
+                path: "docs/retry.md".to_string(),
+                text: "@@ -1,1 +1,1 @@\n-429\n+429, 503".to_string(),
+            }],
+        };
+        let rows = CommitRow::from_entry(&entry);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].id, format!("{}:docs/retry.md:0", entry.commit));
+
+        let embeddings = vec![vec![0.0, 1.0], vec![1.0, 0.0]];
+        let batch = commits_batch(
+            commits_schema(2),
+            "https://github.com/x/y",
+            &rows,
+            &embeddings,
+        )
+        .unwrap();
+        let results = read_results(&batch, DistanceMetric::Cosine).unwrap();
+        assert_eq!(results[0].relative_path, "commit 0123456789");
+        assert_eq!(results[0].heading_path, "Retry on 503");
+        assert_eq!(results[0].last_modified, 1_700_000_000);
+        assert_eq!(
+            results[1].location(),
+            "docs/retry.md @ 0123456789 # Retry on 503"
+        );
+    }
+
+    #[tokio::test]
+    async fn commits_table_records_and_checks_its_own_provenance() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let client = LanceDbClient::new(DatabaseConfig {
+            uri: dir.path().display().to_string(),
+            table_name: "documents".to_string(),
+            batch_size: 100,
+            index: Default::default(),
+            maintenance: Default::default(),
+            run_log: Default::default(),
+        })
+        .await
+        .unwrap();
+        let store = CommitStore::new(&client, &HistoryConfig::default());
+        let entry = CommitEntry {
+            commit: "0123456789abcdef0123456789abcdef01234567".to_string(),
+            author: "Ada".to_string(),
+            author_email: "ada@example.com".to_string(),
+            authored_at: 1_700_000_000,
+            message: "Retry on 503".to_string(),
+            changed_paths: Vec::new(),
+            hunks: Vec::new(),
+        };
+        let rows = CommitRow::from_entry(&entry);
+        store
+            .add("https://github.com/x/y", &rows, &[vec![0.5; 4]])
+            .await
+            .unwrap();
+
+        let config = EmbeddingConfig {
+            model: "old".to_string(),
+            dimension: 4,
+            ..Config::default_config().embedding
+        };
+        store
+            .schema()
+            .update_embedding_provenance(&config, false)
+            .await
+            .unwrap();
+        let stored = store.schema().embedding_provenance().await.unwrap();
+        assert_eq!(stored.unwrap().model, "old");
+        // The documents table keeps its own provenance.
+        assert_eq!(
+            SchemaManager::new(&client)
+                .embedding_provenance()
+                .await
+                .unwrap(),
+            None
+        );
+
+        store
+            .schema()
+            .check_embedding_provenance(&config)
+            .await
+            .unwrap();
+        let switched = EmbeddingConfig {
+            model: "new".to_string(),
+            ..config
+        };
+        assert!(
+            store
+                .schema()
+                .check_embedding_provenance(&switched)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -2,17 +2,24 @@
 // description: LanceDB batch insertion operations with vector embeddings
 // reference: https://docs.rs/lancedb
 
+use crate::config::EmbeddingConfig;
+use crate::database::cache::EmbeddingCache;
 use crate::database::client::LanceDbClient;
 use crate::database::embeddings::EmbeddingClient;
-use crate::database::schema::SchemaManager;
+use crate::database::filter::encode_tags;
+use crate::database::runs::{FileEvent, FileStatus, RunRecorder};
+use crate::database::scheduler::EmbeddingScheduler;
+use crate::database::writer::{DocumentWriter, WriteStats};
 use crate::error::{PipelineError, Result};
-use crate::models::Document;
+use crate::models::{Document, DocumentMetadata};
 use crate::parser::{ChunkOptions, chunk_markdown};
+use crate::repository::{BlameIndex, FileClassifier, ResolvedReference, SubmoduleIndex};
 use arrow_array::{
     ArrayRef, BooleanArray, FixedSizeListArray, Float32Array, RecordBatch, StringArray,
     UInt32Array, UInt64Array,
 };
 use arrow_schema::{DataType, Field};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{debug, info, warn};
 
@@ -21,6 +28,28 @@ pub struct BatchInserter<'a> {
     embedding: Arc<EmbeddingClient>,
     chunk_opts: ChunkOptions,
     allow_fallback: bool,
+    scheduler: Option<EmbeddingScheduler>,
+    cache: Option<Arc<EmbeddingCache>>,
+    classifier: Option<Arc<FileClassifier>>,
+    submodules: Option<Arc<SubmoduleIndex>>,
+    run: Option<Arc<RunRecorder>>,
+    reference: Option<ResolvedReference>,
+    reuse_stored: bool,
+    blame: Option<Arc<BlameIndex>>,
+}
+
+/// One file's chunks and their vectors, ready to be written.
+#[derive(Debug, Clone)]
+pub struct PreparedFile {
+    pub repository_url: String,
+    pub relative_path: String,
+    /// The ref the file was read from; its stale chunks are looked up under
+    /// that ref only.
+    pub git_ref: Option<String>,
+    pub documents: Vec<Document>,
+    pub embeddings: Vec<Vec<f32>>,
+    /// True when the embeddings are non-semantic fallback vectors.
+    pub degraded: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -29,6 +58,41 @@ pub struct InsertStats {
     pub errors: usize,
 }
 
+/// The embedding client, shared scheduler and optional cache of one run.
+/// Cloning is cheap; every clone feeds the same scheduler and cache.
+#[derive(Clone)]
+pub struct Embedder {
+    pub client: Arc<EmbeddingClient>,
+    pub scheduler: EmbeddingScheduler,
+    pub cache: Option<Arc<EmbeddingCache>>,
+}
+
+impl Embedder {
+    /// Build the client and scheduler for `config`, with the embedding
+    /// cache when `config.cache.enabled`.
+    pub fn from_config(client: &LanceDbClient, config: &EmbeddingConfig) -> Self {
+        let embedding = Arc::new(EmbeddingClient::new(config.clone()));
+        Self {
+            scheduler: EmbeddingScheduler::spawn(Arc::clone(&embedding)),
+            cache: config
+                .cache
+                .enabled
+                .then(|| Arc::new(EmbeddingCache::new(client, config))),
+            client: embedding,
+        }
+    }
+
+    /// Write the cache's new entries and log its hit rate.
+    pub async fn flush_cache(&self) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.flush().await {
+                warn!("Failed to write embedding cache: {}", e);
+            }
+            info!("Embedding cache: {}", cache.stats());
+        }
+    }
+}
+
 impl<'a> BatchInserter<'a> {
     pub fn new(client: &'a LanceDbClient, embedding: Arc<EmbeddingClient>) -> Self {
         Self {
@@ -36,7 +100,25 @@ impl<'a> BatchInserter<'a> {
             embedding,
             chunk_opts: ChunkOptions::default(),
             allow_fallback: false,
+            scheduler: None,
+            cache: None,
+            classifier: None,
+            submodules: None,
+            run: None,
+            reference: None,
+            reuse_stored: false,
+            blame: None,
+        }
+    }
+
+    /// An inserter embedding through `embedder`'s scheduler and cache.
+    pub fn from_embedder(client: &'a LanceDbClient, embedder: &Embedder) -> Self {
+        let mut inserter = Self::new(client, Arc::clone(&embedder.client))
+            .with_scheduler(embedder.scheduler.clone());
+        if let Some(cache) = &embedder.cache {
+            inserter = inserter.with_cache(Arc::clone(cache));
         }
+        inserter
     }
 
     pub fn with_options(mut self, chunk_opts: ChunkOptions, allow_fallback: bool) -> Self {
@@ -45,11 +127,65 @@ impl<'a> BatchInserter<'a> {
         self
     }
 
-    /// Chunk a file's (normalized) content, embed every chunk, and insert all
-    /// chunks as rows. Existing rows for the same file are removed first so
-    /// reprocessing does not leave stale chunks. Returns the number of chunks
-    /// inserted.
-    pub async fn insert_file(
+    /// Route embedding calls through a shared scheduler so chunks from many
+    /// files are coalesced into full, rate-limited requests.
+    pub fn with_scheduler(mut self, scheduler: EmbeddingScheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Consult (and fill) a content-addressed embedding cache before calling
+    /// the API.
+    pub fn with_cache(mut self, cache: Arc<EmbeddingCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Derive each file's category (and a topic tag) from the configured
+    /// path rules; frontmatter values take precedence.
+    pub fn with_classifier(mut self, classifier: Arc<FileClassifier>) -> Self {
+        self.classifier = Some(classifier);
+        self
+    }
+
+    /// Record the submodule (path and commit) of files inside one.
+    pub fn with_submodules(mut self, submodules: Arc<SubmoduleIndex>) -> Self {
+        self.submodules = Some(submodules);
+        self
+    }
+
+    /// Record processing results in the run log as well as tracing them.
+    pub fn with_run_recorder(mut self, run: Arc<RunRecorder>) -> Self {
+        self.run = Some(run);
+        self
+    }
+
+    /// Tag every chunk with the ref and commit it was read from, keeping
+    /// each ref's chunks apart from the others'.
+    pub fn with_reference(mut self, reference: ResolvedReference) -> Self {
+        self.reference = Some(reference);
+        self
+    }
+
+    /// When `enabled`, copy the vector of a stored chunk of the same
+    /// repository with the same `content_hash` and heading instead of
+    /// embedding it again, so sections unchanged between refs are embedded
+    /// once. See
+    /// [`SchemaManager::stored_vectors_reusable`](crate::database::SchemaManager::stored_vectors_reusable).
+    pub fn with_stored_vectors(mut self, enabled: bool) -> Self {
+        self.reuse_stored = enabled;
+        self
+    }
+
+    /// Record on each chunk the last commit that changed its lines.
+    pub fn with_blame(mut self, blame: Arc<BlameIndex>) -> Self {
+        self.blame = Some(blame);
+        self
+    }
+
+    /// Chunk a file's (normalized) content and embed every chunk, without
+    /// touching the database. The result is handed to a [`DocumentWriter`].
+    pub async fn prepare_file(
         &self,
         file_path: &str,
         relative_path: &str,
@@ -57,16 +193,16 @@ impl<'a> BatchInserter<'a> {
         last_modified: u64,
         repository_url: &str,
         normalized: bool,
-    ) -> Result<usize> {
+    ) -> Result<PreparedFile> {
         let chunks = chunk_markdown(content, &self.chunk_opts);
         if chunks.is_empty() {
             debug!("No chunks produced for {}", relative_path);
-            return Ok(0);
         }
 
-        let embedding_texts: Vec<String> = chunks.iter().map(|c| c.embedding_text()).collect();
-        let embeddings = self.embed(&embedding_texts).await?;
-
+        let metadata =
+            DocumentMetadata::from_file(relative_path, content, self.classifier.as_deref());
+        let submodule = self.submodules.as_ref().and_then(|s| s.find(relative_path));
+        let blame = self.blame.as_ref().and_then(|b| b.get(relative_path));
         let documents: Vec<Document> = chunks
             .iter()
             .map(|chunk| {
@@ -78,60 +214,188 @@ impl<'a> BatchInserter<'a> {
                     repository_url,
                     normalized,
                 )
+                .with_metadata(&metadata)
+                .with_submodule(submodule)
+                .with_reference(self.reference.as_ref())
+                .with_last_change(blame.and_then(|b| b.last_change(&chunk.content)))
             })
             .collect();
 
-        let dim = self.embedding.dimension();
-        let schema = SchemaManager::get_documents_schema(dim);
-        let record_batch = Self::create_record_batch(schema, &documents, &embeddings)?;
-
-        let table_name = self.client.table_name();
-
-        if !self.client.table_exists(table_name).await? {
-            self.client
-                .get_connection()
-                .create_table(table_name, vec![record_batch])
-                .execute()
-                .await
-                .map_err(|e| PipelineError::Database(format!("Failed to create table: {}", e)))?;
-            info!("Created new table: {}", table_name);
+        let mut stored = if self.reuse_stored && !documents.is_empty() {
+            let hashes: Vec<String> = documents.iter().map(|d| d.content_hash.clone()).collect();
+            match self.client.stored_vectors(repository_url, &hashes).await {
+                Ok(stored) => stored,
+                Err(e) => {
+                    warn!("Failed to look up stored vectors: {}", e);
+                    HashMap::new()
+                }
+            }
         } else {
-            // Remove any prior chunks for this file before re-inserting.
-            self.client
-                .delete_by_file(repository_url, relative_path)
-                .await?;
-            let table = self.client.get_table(table_name).await?;
-            table
-                .add(vec![record_batch])
-                .execute()
-                .await
-                .map_err(|e| PipelineError::Database(format!("Failed to insert chunks: {}", e)))?;
+            HashMap::new()
+        };
+        let dimension = self.embedding.dimension();
+        stored.retain(|_, vector| vector.len() == dimension);
+
+        let reused: Vec<Option<Vec<f32>>> = documents
+            .iter()
+            .map(|d| {
+                stored
+                    .get(&(d.content_hash.clone(), d.heading_path.clone()))
+                    .cloned()
+            })
+            .collect();
+        let embedding_texts: Vec<String> = chunks
+            .iter()
+            .zip(&reused)
+            .filter(|(_, vector)| vector.is_none())
+            .map(|(chunk, _)| chunk.embedding_text())
+            .collect();
+        if embedding_texts.len() < chunks.len() {
+            debug!(
+                "Reusing {} stored vector(s) for {}",
+                chunks.len() - embedding_texts.len(),
+                relative_path
+            );
         }
+        let (fresh, degraded) = self.embed(&embedding_texts).await?;
+        let mut fresh = fresh.into_iter();
+        let embeddings = reused
+            .into_iter()
+            .map(|vector| vector.or_else(|| fresh.next()).unwrap_or_default())
+            .collect();
+
+        Ok(PreparedFile {
+            repository_url: repository_url.to_string(),
+            relative_path: relative_path.to_string(),
+            git_ref: self.reference.as_ref().map(|r| r.name.clone()),
+            documents,
+            embeddings,
+            degraded,
+        })
+    }
 
-        debug!("Inserted {} chunk(s) for {}", documents.len(), relative_path);
-        Ok(documents.len())
+    /// Chunk, embed and write a single file immediately. Prefer feeding
+    /// [`prepare_file`](Self::prepare_file) results to one shared
+    /// [`DocumentWriter`] when processing many files.
+    pub async fn insert_file(
+        &self,
+        file_path: &str,
+        relative_path: &str,
+        content: &str,
+        last_modified: u64,
+        repository_url: &str,
+        normalized: bool,
+    ) -> Result<WriteStats> {
+        let prepared = self
+            .prepare_file(
+                file_path,
+                relative_path,
+                content,
+                last_modified,
+                repository_url,
+                normalized,
+            )
+            .await?;
+
+        let mut writer = DocumentWriter::new(self.client, self.embedding.dimension());
+        writer.push(prepared).await?;
+        let stats = writer.finish().await?;
+
+        debug!(
+            "Inserted {} chunk(s) for {}",
+            stats.rows_written, relative_path
+        );
+        Ok(stats)
     }
 
     /// Embed `texts`, applying the configured fallback only when explicitly
     /// enabled. By default a failed call propagates as an error rather than
-    /// silently filling the index with non-semantic vectors.
-    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
-        match self.embedding.embed_batch(texts).await {
-            Ok(vectors) => Ok(vectors),
+    /// silently filling the index with non-semantic vectors. The flag is true
+    /// when the fallback was used.
+    pub(crate) async fn embed(&self, texts: &[String]) -> Result<(Vec<Vec<f32>>, bool)> {
+        if texts.is_empty() {
+            return Ok((Vec::new(), false));
+        }
+        match self.embed_cached(texts).await {
+            Ok(vectors) => Ok((vectors, false)),
             Err(e) if self.allow_fallback => {
                 warn!("Embedding API failed ({e}); using non-semantic fallback (degraded)");
                 let dim = self.embedding.dimension();
-                Ok(texts
+                let vectors = texts
                     .iter()
                     .map(|t| EmbeddingClient::generate_fallback_embedding(t, dim))
-                    .collect())
+                    .collect();
+                Ok((vectors, true))
             }
             Err(e) => Err(e),
         }
     }
 
+    /// Serve what the cache has and request only the misses. Fallback vectors
+    /// never reach the cache. Cache failures degrade to plain API calls.
+    async fn embed_cached(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let Some(cache) = &self.cache else {
+            return self.request(texts).await;
+        };
+
+        let cached = match cache.lookup(texts).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                warn!("Embedding cache lookup failed: {}", e);
+                vec![None; texts.len()]
+            }
+        };
+
+        let misses: Vec<String> = texts
+            .iter()
+            .zip(&cached)
+            .filter(|(_, hit)| hit.is_none())
+            .map(|(text, _)| text.clone())
+            .collect();
+        if misses.is_empty() {
+            return Ok(cached.into_iter().flatten().collect());
+        }
+
+        let fresh = self.request(&misses).await?;
+        if let Err(e) = cache.insert(&misses, &fresh).await {
+            warn!("Failed to update embedding cache: {}", e);
+        }
+
+        let mut fresh = fresh.into_iter();
+        Ok(cached
+            .into_iter()
+            .map(|hit| hit.or_else(|| fresh.next()).unwrap_or_default())
+            .collect())
+    }
+
+    async fn request(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        match &self.scheduler {
+            Some(scheduler) => scheduler.embed(texts.to_vec()).await,
+            None => self.embedding.embed_batch(texts).await,
+        }
+    }
+
+    /// Build the `embedding` column (FixedSizeList of Float32). All vectors
+    /// must share the first vector's length.
+    pub(crate) fn embedding_array(embeddings: &[Vec<f32>]) -> Result<FixedSizeListArray> {
+        let dimension = embeddings.first().map_or(0, |e| e.len());
+        let embedding_values: Float32Array = embeddings
+            .iter()
+            .flat_map(|emb| emb.iter().copied())
+            .collect();
+
+        let value_field = Arc::new(Field::new("item", DataType::Float32, true));
+        FixedSizeListArray::try_new(
+            value_field,
+            dimension as i32,
+            Arc::new(embedding_values) as ArrayRef,
+            None,
+        )
+        .map_err(|e| PipelineError::Database(format!("Failed to create embedding array: {}", e)))
+    }
+
     /// Create an Arrow RecordBatch from documents and embeddings
-    fn create_record_batch(
+    pub(crate) fn create_record_batch(
         schema: Arc<arrow_schema::Schema>,
         documents: &[Document],
         embeddings: &[Vec<f32>],
@@ -188,25 +452,12 @@ impl<'a> BatchInserter<'a> {
 
         let normalized: BooleanArray = documents.iter().map(|doc| Some(doc.normalized)).collect();
 
-        // Build embedding array (FixedSizeList of Float32)
-        let embedding_values: Float32Array = embeddings
-            .iter()
-            .flat_map(|emb| emb.iter().copied())
-            .collect();
-
-        let value_field = Arc::new(Field::new("item", DataType::Float32, true));
-        let embedding_list = FixedSizeListArray::try_new(
-            value_field,
-            embeddings[0].len() as i32,
-            Arc::new(embedding_values) as ArrayRef,
-            None,
-        )
-        .map_err(|e| PipelineError::Database(format!("Failed to create embedding array: {}", e)))?;
+        let embedding_list = Self::embedding_array(embeddings)?;
 
         // Optional metadata fields
         let titles: StringArray = (0..len).map(|_| None::<String>).collect();
         let descriptions: StringArray = (0..len).map(|_| None::<String>).collect();
-        let languages: StringArray = (0..len).map(|_| None::<String>).collect();
+        let languages: StringArray = documents.iter().map(|doc| doc.language.clone()).collect();
 
         // Repository URL is required for deletion tracking
         let repository_urls: StringArray = documents
@@ -214,6 +465,26 @@ impl<'a> BatchInserter<'a> {
             .map(|doc| Some(doc.repository_url.clone()))
             .collect();
 
+        let categories: StringArray = documents.iter().map(|doc| doc.category.clone()).collect();
+        let tags: StringArray = documents.iter().map(|doc| encode_tags(&doc.tags)).collect();
+        let submodules: StringArray = documents.iter().map(|doc| doc.submodule.clone()).collect();
+        let submodule_commits: StringArray = documents
+            .iter()
+            .map(|doc| doc.submodule_commit.clone())
+            .collect();
+        let git_refs: StringArray = documents.iter().map(|doc| doc.git_ref.clone()).collect();
+        let git_commits: StringArray = documents.iter().map(|doc| doc.git_commit.clone()).collect();
+        let last_commits: StringArray = documents
+            .iter()
+            .map(|doc| doc.last_commit.clone())
+            .collect();
+        let last_authors: StringArray = documents
+            .iter()
+            .map(|doc| doc.last_author.clone())
+            .collect();
+        let last_changed_ats: UInt64Array =
+            documents.iter().map(|doc| doc.last_changed_at).collect();
+
         RecordBatch::try_new(
             schema,
             vec![
@@ -233,30 +504,39 @@ impl<'a> BatchInserter<'a> {
                 Arc::new(descriptions),
                 Arc::new(languages),
                 Arc::new(repository_urls),
+                Arc::new(categories),
+                Arc::new(tags),
+                Arc::new(submodules),
+                Arc::new(submodule_commits),
+                Arc::new(git_refs),
+                Arc::new(git_commits),
+                Arc::new(last_commits),
+                Arc::new(last_authors),
+                Arc::new(last_changed_ats),
             ],
         )
         .map_err(|e| PipelineError::Database(format!("Failed to create record batch: {}", e)))
     }
 
-    pub async fn log_processing(
-        &self,
-        file_path: &str,
-        status: &str,
-        error_message: &str,
-        processing_time_ms: u32,
-    ) -> Result<()> {
-        // For LanceDB, we could log to a separate table or just use tracing
-        if status == "failed" {
+    pub async fn log_processing(&self, event: FileEvent) -> Result<()> {
+        if event.status == FileStatus::Failed {
             warn!(
                 "Processing failed for {}: {} (took {}ms)",
-                file_path, error_message, processing_time_ms
+                event.relative_path,
+                event.error.as_deref().unwrap_or("unknown error"),
+                event.duration_ms
             );
         } else {
             debug!(
-                "Processing succeeded for {} (took {}ms)",
-                file_path, processing_time_ms
+                "Processing {} for {} (took {}ms)",
+                event.status.as_str(),
+                event.relative_path,
+                event.duration_ms
             );
         }
+        if let Some(run) = &self.run {
+            run.record(event).await;
+        }
         Ok(())
     }
 }
@@ -264,6 +544,9 @@ impl<'a> BatchInserter<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{DatabaseConfig, EmbeddingConfig, EmbeddingLimits};
+    use crate::database::SchemaManager;
+    use crate::repository::ReferenceKind;
 
     #[test]
     fn test_insert_stats_default() {
@@ -278,4 +561,83 @@ mod tests {
         assert_eq!(embedding.len(), 384);
         assert!(embedding.iter().all(|&x| (0.0..=1.0).contains(&x)));
     }
+
+    #[tokio::test]
+    async fn degraded_table_vectors_are_re_embedded() {
+        const URL: &str = "https://github.com/x/y";
+        let dir = tempfile::TempDir::new().unwrap();
+        let client = LanceDbClient::new(DatabaseConfig {
+            uri: dir.path().display().to_string(),
+            table_name: "documents".to_string(),
+            batch_size: 100,
+            index: Default::default(),
+            maintenance: Default::default(),
+            run_log: Default::default(),
+        })
+        .await
+        .unwrap();
+        // Nothing listens on the discard port, so every chunk falls back.
+        let config = EmbeddingConfig {
+            base_url: "http://127.0.0.1:9".to_string(),
+            model: "test".to_string(),
+            dimension: 8,
+            api_key: None,
+            allow_fallback: true,
+            limits: EmbeddingLimits {
+                max_retries: 0,
+                ..Default::default()
+            },
+            cache: Default::default(),
+        };
+        let embedding = Arc::new(EmbeddingClient::new(config.clone()));
+        let schema = SchemaManager::new(&client);
+        let prepare = |git_ref: &str, reuse: bool| {
+            BatchInserter::new(&client, Arc::clone(&embedding))
+                .with_options(ChunkOptions::default(), true)
+                .with_reference(ResolvedReference {
+                    name: git_ref.to_string(),
+                    kind: ReferenceKind::Tag,
+                    commit: format!("{git_ref}-commit"),
+                })
+                .with_stored_vectors(reuse)
+        };
+        let content = "# Setup\n\nInstall the tool and run it once.\n";
+
+        let mut v1 = prepare("v1", false)
+            .prepare_file("/repo/a.md", "a.md", content, 0, URL, false)
+            .await
+            .unwrap();
+        let stored = vec![0.5; 8];
+        v1.embeddings = vec![stored.clone(); v1.documents.len()];
+        let mut writer = DocumentWriter::new(&client, 8);
+        writer.push(v1).await.unwrap();
+        writer.finish().await.unwrap();
+
+        schema
+            .update_embedding_provenance(&config, false)
+            .await
+            .unwrap();
+        let reuse = schema.stored_vectors_reusable(URL, "v2").await.unwrap();
+        assert!(reuse);
+        assert!(!schema.stored_vectors_reusable(URL, "v1").await.unwrap());
+        let v2 = prepare("v2", reuse)
+            .prepare_file("/repo/a.md", "a.md", content, 0, URL, false)
+            .await
+            .unwrap();
+        assert!(!v2.degraded);
+        assert_eq!(v2.embeddings[0], stored);
+
+        schema
+            .update_embedding_provenance(&config, true)
+            .await
+            .unwrap();
+        let reuse = schema.stored_vectors_reusable(URL, "v2").await.unwrap();
+        assert!(!reuse);
+        let v2 = prepare("v2", reuse)
+            .prepare_file("/repo/a.md", "a.md", content, 0, URL, false)
+            .await
+            .unwrap();
+        assert!(v2.degraded);
+        assert_ne!(v2.embeddings[0], stored);
+    }
 }
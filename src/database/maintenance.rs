@@ -0,0 +1,235 @@
+// file: src/database/maintenance.rs
+// description: table maintenance: fragment compaction, version pruning and reindexing
+// reference: https://lancedb.github.io/lancedb/concepts/data_management/
+
+use crate::database::client::LanceDbClient;
+use crate::database::schema::SchemaManager;
+use crate::error::{PipelineError, Result};
+use crate::utils::text::format_bytes;
+use lancedb::Table;
+use lancedb::table::{CompactionOptions, Duration, OptimizeAction, OptimizeOptions};
+use std::fmt;
+use tracing::info;
+
+/// Which maintenance steps to run, in order: compact, prune, reindex.
+#[derive(Debug, Clone, Copy)]
+pub struct MaintenanceOptions {
+    pub compact: bool,
+    pub prune: bool,
+    pub reindex: bool,
+    /// Versions older than this many days are pruned.
+    pub retention_days: u64,
+}
+
+/// Fragment layout and size of the table at one point in time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableSnapshot {
+    pub version: u64,
+    pub rows: usize,
+    pub fragments: usize,
+    pub small_fragments: usize,
+    pub total_bytes: u64,
+}
+
+/// Outcome of one maintenance run.
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceReport {
+    pub before: TableSnapshot,
+    pub after: TableSnapshot,
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+    pub versions_pruned: u64,
+    /// Bytes deleted from storage by pruning old versions.
+    pub bytes_reclaimed: u64,
+    pub reindexed: bool,
+}
+
+impl fmt::Display for MaintenanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Fragments: {} -> {} ({} small -> {} small; {} compacted into {})",
+            self.before.fragments,
+            self.after.fragments,
+            self.before.small_fragments,
+            self.after.small_fragments,
+            self.fragments_removed,
+            self.fragments_added
+        )?;
+        writeln!(
+            f,
+            "Live data: {} -> {}",
+            format_bytes(self.before.total_bytes),
+            format_bytes(self.after.total_bytes)
+        )?;
+        writeln!(
+            f,
+            "Pruned {} old version(s), reclaimed {}",
+            self.versions_pruned,
+            format_bytes(self.bytes_reclaimed)
+        )?;
+        write!(
+            f,
+            "Version: {} -> {}{}",
+            self.before.version,
+            self.after.version,
+            if self.reindexed {
+                " (index optimized)"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// Runs compaction, version pruning and index optimization on the documents
+/// table.
+pub struct TableMaintainer<'a> {
+    client: &'a LanceDbClient,
+}
+
+impl<'a> TableMaintainer<'a> {
+    pub fn new(client: &'a LanceDbClient) -> Self {
+        Self { client }
+    }
+
+    /// Current fragment layout and size of the documents table.
+    pub async fn snapshot(&self) -> Result<TableSnapshot> {
+        let table = self.table().await?;
+        snapshot(&table).await
+    }
+
+    pub async fn run(&self, options: MaintenanceOptions) -> Result<MaintenanceReport> {
+        let table = self.table().await?;
+        let mut report = MaintenanceReport {
+            before: snapshot(&table).await?,
+            ..Default::default()
+        };
+
+        if options.compact {
+            info!("Compacting fragments");
+            let stats = table
+                .optimize(OptimizeAction::Compact {
+                    options: CompactionOptions::default(),
+                    remap_options: None,
+                })
+                .await
+                .map_err(|e| PipelineError::Database(format!("Compaction failed: {}", e)))?;
+            if let Some(compaction) = stats.compaction {
+                report.fragments_removed = compaction.fragments_removed;
+                report.fragments_added = compaction.fragments_added;
+            }
+        }
+
+        if options.prune {
+            info!(
+                "Pruning versions older than {} day(s)",
+                options.retention_days
+            );
+            let stats = table
+                .optimize(OptimizeAction::Prune {
+                    older_than: Some(Duration::days(options.retention_days as i64)),
+                    delete_unverified: None,
+                    error_if_tagged_old_versions: None,
+                })
+                .await
+                .map_err(|e| PipelineError::Database(format!("Version pruning failed: {}", e)))?;
+            if let Some(prune) = stats.prune {
+                report.versions_pruned = prune.old_versions;
+                report.bytes_reclaimed = prune.bytes_removed;
+            }
+        }
+
+        if options.reindex {
+            report.reindexed = self.reindex(&table).await?;
+        }
+
+        report.after = snapshot(&table).await?;
+        Ok(report)
+    }
+
+    /// Fold unindexed rows into the existing vector index, or build the index
+    /// if the table has crossed the auto-index threshold. Returns whether an
+    /// index was touched.
+    async fn reindex(&self, table: &Table) -> Result<bool> {
+        let schema_manager = SchemaManager::new(self.client);
+        if schema_manager.vector_index_status().await?.is_none() {
+            return Ok(schema_manager
+                .ensure_vector_index(self.client.index_config())
+                .await?
+                .is_some());
+        }
+
+        info!("Optimizing indices");
+        table
+            .optimize(OptimizeAction::Index(OptimizeOptions::default()))
+            .await
+            .map_err(|e| PipelineError::Database(format!("Index optimization failed: {}", e)))?;
+        Ok(true)
+    }
+
+    async fn table(&self) -> Result<Table> {
+        let table_name = self.client.table_name();
+        if !self.client.table_exists(table_name).await? {
+            return Err(PipelineError::Database(format!(
+                "Table '{}' does not exist; nothing to maintain",
+                table_name
+            )));
+        }
+        self.client.get_table(table_name).await
+    }
+}
+
+async fn snapshot(table: &Table) -> Result<TableSnapshot> {
+    let stats = table
+        .stats()
+        .await
+        .map_err(|e| PipelineError::Database(format!("Failed to read table stats: {}", e)))?;
+    let version = table
+        .version()
+        .await
+        .map_err(|e| PipelineError::Database(format!("Failed to read table version: {}", e)))?;
+
+    Ok(TableSnapshot {
+        version,
+        rows: stats.num_rows,
+        fragments: stats.fragment_stats.num_fragments,
+        small_fragments: stats.fragment_stats.num_small_fragments,
+        total_bytes: stats.total_bytes as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_shows_before_and_after() {
+        let report = MaintenanceReport {
+            before: TableSnapshot {
+                version: 40,
+                rows: 1000,
+                fragments: 120,
+                small_fragments: 118,
+                total_bytes: 4 * 1024 * 1024,
+            },
+            after: TableSnapshot {
+                version: 43,
+                rows: 1000,
+                fragments: 1,
+                small_fragments: 0,
+                total_bytes: 3 * 1024 * 1024,
+            },
+            fragments_removed: 120,
+            fragments_added: 1,
+            versions_pruned: 38,
+            bytes_reclaimed: 2048,
+            reindexed: true,
+        };
+
+        let text = report.to_string();
+        assert!(text.contains("Fragments: 120 -> 1"));
+        assert!(text.contains("reclaimed 2.0 KiB"));
+        assert!(text.contains("(index optimized)"));
+    }
+}
@@ -0,0 +1,319 @@
+// file: src/database/migrations.rs
+// description: versioned schema migrations for the documents table
+// reference: https://docs.rs/lancedb/latest/lancedb/table/enum.NewColumnTransform.html
+
+use arrow_schema::{DataType, Field, Schema};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Schema metadata key holding the documents table's schema version.
+pub const SCHEMA_VERSION_KEY: &str = "git_summarize.schema_version";
+
+/// One step in the documents schema history. Columns listed in `columns`
+/// are added to older tables with the given SQL default expression;
+/// columns of [`super::SchemaManager::get_documents_schema`] not listed in
+/// any migration fall back to a type-derived default.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub columns: &'static [(&'static str, &'static str)],
+}
+
+/// Append-only; `version` must increase by one per entry.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Baseline documents schema",
+        columns: &[],
+    },
+    Migration {
+        version: 2,
+        description: "Add category and tags for search filters",
+        columns: &[
+            ("category", "arrow_cast(NULL, 'Utf8')"),
+            ("tags", "arrow_cast(NULL, 'Utf8')"),
+        ],
+    },
+    Migration {
+        version: 3,
+        description: "Add submodule path and commit",
+        columns: &[
+            ("submodule", "arrow_cast(NULL, 'Utf8')"),
+            ("submodule_commit", "arrow_cast(NULL, 'Utf8')"),
+        ],
+    },
+    Migration {
+        version: 4,
+        description: "Add git ref and commit for time-travel queries",
+        columns: &[
+            ("git_ref", "arrow_cast(NULL, 'Utf8')"),
+            ("git_commit", "arrow_cast(NULL, 'Utf8')"),
+        ],
+    },
+    Migration {
+        version: 5,
+        description: "Add last commit, author and date from git blame",
+        columns: &[
+            ("last_commit", "arrow_cast(NULL, 'Utf8')"),
+            ("last_author", "arrow_cast(NULL, 'Utf8')"),
+            ("last_changed_at", "arrow_cast(NULL, 'UInt64')"),
+        ],
+    },
+];
+
+pub fn current_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Version recorded in schema metadata; `0` for tables that predate
+/// versioning.
+pub fn recorded_version(metadata: &HashMap<String, String>) -> u32 {
+    metadata
+        .get(SCHEMA_VERSION_KEY)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Differences between the live table schema and the schema this build writes.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaDiff {
+    /// Expected columns absent from the table.
+    pub missing: Vec<Field>,
+    /// Table columns this build does not know about; left untouched.
+    pub extra: Vec<String>,
+    /// `(column, live type, expected type)`.
+    pub mismatched: Vec<(String, DataType, DataType)>,
+}
+
+impl SchemaDiff {
+    pub fn between(live: &Schema, expected: &Schema) -> Self {
+        let mut diff = Self::default();
+        for field in expected.fields() {
+            match live.field_with_name(field.name()) {
+                Ok(existing) if existing.data_type() != field.data_type() => {
+                    diff.mismatched.push((
+                        field.name().clone(),
+                        existing.data_type().clone(),
+                        field.data_type().clone(),
+                    ));
+                }
+                Ok(_) => {}
+                Err(_) => diff.missing.push(field.as_ref().clone()),
+            }
+        }
+        diff.extra = live
+            .fields()
+            .iter()
+            .filter(|f| expected.field_with_name(f.name()).is_err())
+            .map(|f| f.name().clone())
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// What it takes to bring a table up to [`current_version`].
+#[derive(Debug, Clone, Default)]
+pub struct MigrationPlan {
+    pub from_version: u32,
+    pub to_version: u32,
+    /// `(column, SQL default)` to add.
+    pub add_columns: Vec<(String, String)>,
+    /// Columns that cannot be migrated in place (type changes, or new
+    /// columns without a usable default); these need `reembed` or a reset.
+    pub blocked: Vec<String>,
+    pub diff: SchemaDiff,
+}
+
+impl MigrationPlan {
+    pub fn new(live: &Schema, expected: &Schema) -> Self {
+        let diff = SchemaDiff::between(live, expected);
+        let mut plan = Self {
+            from_version: recorded_version(live.metadata()),
+            to_version: current_version(),
+            ..Default::default()
+        };
+
+        for field in &diff.missing {
+            match column_default(field) {
+                Some(default) => plan.add_columns.push((field.name().clone(), default)),
+                None => plan.blocked.push(format!(
+                    "{} (no default for {})",
+                    field.name(),
+                    field.data_type()
+                )),
+            }
+        }
+        for (name, live, expected) in &diff.mismatched {
+            plan.blocked.push(format!(
+                "{} ({} in table, {} expected)",
+                name, live, expected
+            ));
+        }
+        plan.diff = diff;
+        plan
+    }
+
+    /// Nothing to add and the version is current.
+    pub fn is_up_to_date(&self) -> bool {
+        self.add_columns.is_empty()
+            && self.blocked.is_empty()
+            && self.from_version >= self.to_version
+    }
+
+    pub fn can_apply(&self) -> bool {
+        self.blocked.is_empty()
+    }
+
+    /// Migrations between the recorded and current version.
+    pub fn steps(&self) -> impl Iterator<Item = &'static Migration> + '_ {
+        MIGRATIONS.iter().filter(|m| m.version > self.from_version)
+    }
+}
+
+impl fmt::Display for MigrationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Schema version: {} (current {})",
+            self.from_version, self.to_version
+        )?;
+        for step in self.steps() {
+            write!(f, "\n  pending v{}: {}", step.version, step.description)?;
+        }
+        for (name, default) in &self.add_columns {
+            write!(f, "\n  add column {} = {}", name, default)?;
+        }
+        for blocked in &self.blocked {
+            write!(f, "\n  cannot migrate {}", blocked)?;
+        }
+        for extra in &self.diff.extra {
+            write!(f, "\n  unknown column {} (kept)", extra)?;
+        }
+        Ok(())
+    }
+}
+
+/// Default for a column added to existing rows: the migration's explicit
+/// expression if one names it, otherwise NULL for nullable columns and a
+/// zero value for non-nullable scalars. Vector columns have no default.
+fn column_default(field: &Field) -> Option<String> {
+    if let Some(explicit) = MIGRATIONS
+        .iter()
+        .flat_map(|m| m.columns.iter())
+        .rev()
+        .find(|(name, _)| name == field.name())
+    {
+        return Some(explicit.1.to_string());
+    }
+
+    let data_type = field.data_type();
+    let scalar = matches!(data_type, DataType::Utf8 | DataType::Boolean)
+        || data_type.is_integer()
+        || data_type.is_floating();
+    if !scalar {
+        return None;
+    }
+    if field.is_nullable() {
+        return Some(format!("arrow_cast(NULL, '{}')", data_type));
+    }
+    Some(match data_type {
+        DataType::Utf8 => "''".to_string(),
+        DataType::Boolean => "false".to_string(),
+        t => format!("arrow_cast(0, '{}')", t),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn expected() -> Schema {
+        Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("chunk_index", DataType::UInt32, false),
+            Field::new("language", DataType::Utf8, true),
+            Field::new(
+                "embedding",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 4),
+                false,
+            ),
+        ])
+    }
+
+    #[test]
+    fn missing_scalar_columns_get_defaults() {
+        let live = Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new(
+                "embedding",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 4),
+                false,
+            ),
+            Field::new("legacy", DataType::Utf8, true),
+        ]);
+
+        let plan = MigrationPlan::new(&live, &expected());
+        assert_eq!(plan.from_version, 0);
+        assert_eq!(
+            plan.add_columns,
+            vec![
+                (
+                    "chunk_index".to_string(),
+                    "arrow_cast(0, 'UInt32')".to_string()
+                ),
+                (
+                    "language".to_string(),
+                    "arrow_cast(NULL, 'Utf8')".to_string()
+                ),
+            ]
+        );
+        assert!(plan.can_apply());
+        assert_eq!(plan.diff.extra, vec!["legacy".to_string()]);
+        assert!(!plan.is_up_to_date());
+    }
+
+    #[test]
+    fn vector_changes_are_blocked() {
+        let live = Schema::new_with_metadata(
+            vec![
+                Field::new("id", DataType::Utf8, false),
+                Field::new("chunk_index", DataType::UInt32, false),
+                Field::new("language", DataType::Utf8, true),
+                Field::new(
+                    "embedding",
+                    DataType::FixedSizeList(
+                        Arc::new(Field::new("item", DataType::Float32, true)),
+                        8,
+                    ),
+                    false,
+                ),
+            ],
+            HashMap::from([(
+                SCHEMA_VERSION_KEY.to_string(),
+                current_version().to_string(),
+            )]),
+        );
+
+        let plan = MigrationPlan::new(&live, &expected());
+        assert!(!plan.can_apply());
+        assert_eq!(plan.blocked.len(), 1);
+        assert!(plan.blocked[0].starts_with("embedding"));
+    }
+
+    #[test]
+    fn matching_schema_at_current_version_is_up_to_date() {
+        let live = Schema::new_with_metadata(
+            expected().fields().clone(),
+            HashMap::from([(
+                SCHEMA_VERSION_KEY.to_string(),
+                current_version().to_string(),
+            )]),
+        );
+        assert!(MigrationPlan::new(&live, &expected()).is_up_to_date());
+    }
+}
@@ -2,12 +2,38 @@
 // description: database operations module exports
 // reference: internal module structure
 
+pub mod cache;
 pub mod client;
+pub mod diff;
 pub mod embeddings;
+pub mod filter;
+pub mod history;
 pub mod insert;
+pub mod maintenance;
+pub mod migrations;
+pub mod provenance;
+pub mod reembed;
+pub mod runs;
+pub mod scheduler;
 pub mod schema;
+pub mod stale;
+pub mod stats;
+pub mod writer;
 
-pub use client::LanceDbClient;
+pub use cache::{CachePruneOptions, CacheStats, EmbeddingCache};
+pub use client::{LanceDbClient, VectorSearchOptions};
+pub use diff::{ChangeKind, DiffReport, RefDiff, Section, SectionChange};
 pub use embeddings::EmbeddingClient;
-pub use insert::{BatchInserter, InsertStats};
-pub use schema::SchemaManager;
+pub use filter::{Comparison, Condition, SearchFilter};
+pub use history::{CommitRow, CommitStore};
+pub use insert::{BatchInserter, Embedder, InsertStats, PreparedFile};
+pub use maintenance::{MaintenanceOptions, MaintenanceReport, TableMaintainer, TableSnapshot};
+pub use migrations::{MigrationPlan, SchemaDiff};
+pub use provenance::EmbeddingProvenance;
+pub use reembed::{ReembedReport, Reembedder};
+pub use runs::{FileEvent, FileStatus, RunLog, RunRecord, RunRecorder, RunStatus};
+pub use scheduler::EmbeddingScheduler;
+pub use schema::{SchemaManager, VectorIndexStatus};
+pub use stale::{StaleFinder, StaleReport, StaleSection};
+pub use stats::{ChunkSizeStats, IndexStats, RepositoryStats, SizeBucket, StatsCollector};
+pub use writer::{DocumentWriter, WriteStats};
@@ -0,0 +1,131 @@
+// file: src/database/provenance.rs
+// description: embedding model provenance recorded in the documents table's schema metadata
+// reference: https://docs.rs/lancedb/latest/lancedb/table/struct.NativeTable.html
+
+use crate::config::EmbeddingConfig;
+use crate::error::{PipelineError, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+const MODEL_KEY: &str = "git_summarize.embedding.model";
+const DIMENSION_KEY: &str = "git_summarize.embedding.dimension";
+const PROVIDER_KEY: &str = "git_summarize.embedding.provider";
+const FALLBACK_KEY: &str = "git_summarize.embedding.fallback";
+
+/// Which model produced the vectors in a table. Vectors from different
+/// models (or dimensions) live in different spaces and must never be mixed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EmbeddingProvenance {
+    pub model: String,
+    pub dimension: usize,
+    /// Embeddings endpoint base URL.
+    pub provider: String,
+    /// Whether any row holds a non-semantic fallback vector.
+    pub fallback: bool,
+}
+
+impl EmbeddingProvenance {
+    pub fn from_config(config: &EmbeddingConfig) -> Self {
+        Self {
+            model: config.model.clone(),
+            dimension: config.dimension,
+            provider: config.base_url.trim_end_matches('/').to_string(),
+            fallback: false,
+        }
+    }
+
+    /// Read provenance from table metadata; `None` for tables written before
+    /// provenance was recorded.
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            model: metadata.get(MODEL_KEY)?.clone(),
+            dimension: metadata.get(DIMENSION_KEY)?.parse().ok()?,
+            provider: metadata.get(PROVIDER_KEY).cloned().unwrap_or_default(),
+            fallback: metadata.get(FALLBACK_KEY).is_some_and(|v| v == "true"),
+        })
+    }
+
+    pub fn to_metadata(&self) -> HashMap<String, String> {
+        HashMap::from([
+            (MODEL_KEY.to_string(), self.model.clone()),
+            (DIMENSION_KEY.to_string(), self.dimension.to_string()),
+            (PROVIDER_KEY.to_string(), self.provider.clone()),
+            (FALLBACK_KEY.to_string(), self.fallback.to_string()),
+        ])
+    }
+
+    /// Whether vectors produced under `other` can be compared with these.
+    /// The provider is informational: the same model may be served by several
+    /// endpoints.
+    pub fn same_space(&self, other: &Self) -> bool {
+        self.model == other.model && self.dimension == other.dimension
+    }
+
+    /// Error unless the configured model matches the one that built the table.
+    pub fn ensure_compatible(&self, configured: &Self) -> Result<()> {
+        if self.same_space(configured) {
+            return Ok(());
+        }
+        Err(PipelineError::Config(format!(
+            "Embedding model mismatch: the table was embedded with {} but embedding is \
+             configured for {}. Revert embedding.model/dimension, or run `reembed` to \
+             migrate the table to the new model",
+            self, configured
+        )))
+    }
+}
+
+impl fmt::Display for EmbeddingProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} dims)", self.model, self.dimension)?;
+        if !self.provider.is_empty() {
+            write!(f, " via {}", self.provider)?;
+        }
+        if self.fallback {
+            write!(f, " [contains fallback vectors]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provenance(model: &str, dimension: usize) -> EmbeddingProvenance {
+        EmbeddingProvenance {
+            model: model.to_string(),
+            dimension,
+            provider: "https://api.openai.com/v1".to_string(),
+            fallback: false,
+        }
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let mut original = provenance("text-embedding-3-small", 768);
+        original.fallback = true;
+        let restored = EmbeddingProvenance::from_metadata(&original.to_metadata()).unwrap();
+        assert_eq!(restored, original);
+        assert!(EmbeddingProvenance::from_metadata(&HashMap::new()).is_none());
+    }
+
+    #[test]
+    fn model_or_dimension_change_is_a_mismatch() {
+        let stored = provenance("text-embedding-3-small", 768);
+        let mut other_provider = stored.clone();
+        other_provider.provider = "http://localhost:8080".to_string();
+
+        assert!(stored.ensure_compatible(&other_provider).is_ok());
+        assert!(
+            stored
+                .ensure_compatible(&provenance("text-embedding-3-small", 512))
+                .is_err()
+        );
+        let err = stored
+            .ensure_compatible(&provenance("nomic-embed-text", 768))
+            .unwrap_err();
+        assert!(err.to_string().contains("reembed"));
+    }
+}
@@ -0,0 +1,350 @@
+// file: src/database/reembed.rs
+// description: migrate the documents table to a new embedding model without re-ingesting
+// reference: https://docs.rs/lancedb/latest/lancedb/connection/struct.Connection.html
+
+use crate::config::EmbeddingConfig;
+use crate::database::client::LanceDbClient;
+use crate::database::insert::BatchInserter;
+use crate::database::provenance::EmbeddingProvenance;
+use crate::database::schema::{EMBEDDING_COLUMN, SchemaManager};
+use crate::error::{PipelineError, Result};
+use arrow_array::{Array, ArrayRef, RecordBatch, StringArray};
+use arrow_schema::{Field, Schema};
+use futures::StreamExt;
+use lancedb::query::ExecutableQuery;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Set on the staging table once it holds every re-embedded row.
+const COMPLETE_KEY: &str = "git_summarize.reembed.complete";
+
+/// Outcome of a `reembed` run.
+#[derive(Debug, Clone)]
+pub struct ReembedReport {
+    pub rows: usize,
+    pub from: Option<EmbeddingProvenance>,
+    pub to: EmbeddingProvenance,
+}
+
+/// Re-embeds every row already in the documents table (or another table
+/// given to [`Self::with_table`]) with the configured model. The table name is kept: rows are written to a staging table, the
+/// original is replaced by a copy of it (LanceDB OSS cannot rename tables),
+/// and the vector index is rebuilt. If the swap is interrupted, the next run
+/// resumes from the completed staging table.
+pub struct Reembedder<'a> {
+    client: &'a LanceDbClient,
+    inserter: BatchInserter<'a>,
+    config: EmbeddingConfig,
+    table_name: String,
+}
+
+impl<'a> Reembedder<'a> {
+    /// `inserter` supplies embedding (including any scheduler and cache); its
+    /// fallback must be disabled so no degraded vectors are written.
+    pub fn new(
+        client: &'a LanceDbClient,
+        inserter: BatchInserter<'a>,
+        config: EmbeddingConfig,
+    ) -> Self {
+        Self {
+            client,
+            inserter,
+            config,
+            table_name: client.table_name().to_string(),
+        }
+    }
+
+    /// Re-embed `table_name`, e.g. the commits table, instead of the
+    /// documents table.
+    pub fn with_table(mut self, table_name: &str) -> Self {
+        self.table_name = table_name.to_string();
+        self
+    }
+
+    fn staging_name(&self) -> String {
+        format!("{}_reembed", self.table_name)
+    }
+
+    pub async fn run(&self) -> Result<ReembedReport> {
+        let table_name = self.table_name.as_str();
+        let staging = self.staging_name();
+        let schema_manager = SchemaManager::for_table(self.client, table_name);
+        let to = EmbeddingProvenance::from_config(&self.config);
+
+        if self.client.table_exists(&staging).await? {
+            if self.staging_complete(&staging).await? {
+                warn!(
+                    "Resuming an interrupted reembed from staging table '{}'",
+                    staging
+                );
+                self.drop_table(table_name).await?;
+                let rows = self.copy_table(&staging, table_name).await?;
+                self.finish(&schema_manager, &to).await?;
+                return Ok(ReembedReport {
+                    rows,
+                    from: None,
+                    to,
+                });
+            }
+            // A partial staging table; the original is still intact.
+            self.drop_table(&staging).await?;
+        }
+
+        if !self.client.table_exists(table_name).await? {
+            return Err(PipelineError::Database(format!(
+                "Table '{}' does not exist; nothing to re-embed",
+                table_name
+            )));
+        }
+
+        let from = schema_manager.embedding_provenance().await?;
+
+        info!("Re-embedding '{}' with {}", table_name, to);
+        let source = self.client.get_table(table_name).await?;
+        let mut stream = source
+            .query()
+            .execute()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read documents: {}", e)))?;
+
+        let mut rows = 0;
+        while let Some(batch) = stream.next().await {
+            let batch = batch
+                .map_err(|e| PipelineError::Database(format!("Failed to read batch: {}", e)))?;
+            if batch.num_rows() == 0 {
+                continue;
+            }
+
+            let texts = embedding_texts(&batch)?;
+            let (vectors, degraded) = self.inserter.embed(&texts).await?;
+            if degraded {
+                return Err(PipelineError::Database(
+                    "Re-embedding produced fallback vectors; aborting".to_string(),
+                ));
+            }
+            if vectors.iter().any(|v| v.len() != self.config.dimension) {
+                return Err(PipelineError::Database(format!(
+                    "Embedding API returned vectors that are not {}-dimensional",
+                    self.config.dimension
+                )));
+            }
+
+            let batch = replace_embeddings(&batch, &vectors)?;
+            rows += batch.num_rows();
+            self.append(&staging, batch).await?;
+            info!("Re-embedded {} row(s)", rows);
+        }
+
+        if rows == 0 {
+            schema_manager.record_embedding_provenance(&to).await?;
+            return Ok(ReembedReport { rows, from, to });
+        }
+
+        // Only once staging holds every row is the original dropped.
+        self.mark_staging_complete(&staging).await?;
+        self.drop_table(table_name).await?;
+        self.copy_table(&staging, table_name).await?;
+        self.finish(&schema_manager, &to).await?;
+
+        Ok(ReembedReport { rows, from, to })
+    }
+
+    async fn staging_complete(&self, staging: &str) -> Result<bool> {
+        let schema = self
+            .client
+            .get_table(staging)
+            .await?
+            .schema()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read schema: {}", e)))?;
+        Ok(schema
+            .metadata()
+            .get(COMPLETE_KEY)
+            .is_some_and(|v| v == "true"))
+    }
+
+    async fn mark_staging_complete(&self, staging: &str) -> Result<()> {
+        let table = self.client.get_table(staging).await?;
+        let mut metadata = table
+            .schema()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read schema: {}", e)))?
+            .metadata()
+            .clone();
+        metadata.insert(COMPLETE_KEY.to_string(), "true".to_string());
+        table
+            .as_native()
+            .ok_or_else(|| {
+                PipelineError::Database("Table metadata requires a local table".to_string())
+            })?
+            .replace_schema_metadata(metadata)
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to mark staging table: {}", e)))
+    }
+
+    async fn finish(
+        &self,
+        schema_manager: &SchemaManager<'_>,
+        to: &EmbeddingProvenance,
+    ) -> Result<()> {
+        schema_manager.record_embedding_provenance(to).await?;
+        self.drop_table(&self.staging_name()).await?;
+
+        match schema_manager
+            .ensure_vector_index(self.client.index_config())
+            .await
+        {
+            Ok(Some(status)) => info!("Vector index: {}", status),
+            Ok(None) => {}
+            Err(e) => warn!("Rebuilding the vector index failed: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn copy_table(&self, from: &str, to: &str) -> Result<usize> {
+        let source = self.client.get_table(from).await?;
+        let mut stream = source
+            .query()
+            .execute()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read {}: {}", from, e)))?;
+
+        let mut rows = 0;
+        while let Some(batch) = stream.next().await {
+            let batch = batch
+                .map_err(|e| PipelineError::Database(format!("Failed to read batch: {}", e)))?;
+            rows += batch.num_rows();
+            self.append(to, batch).await?;
+        }
+        Ok(rows)
+    }
+
+    async fn append(&self, table_name: &str, batch: RecordBatch) -> Result<()> {
+        if self.client.table_exists(table_name).await? {
+            self.client
+                .get_table(table_name)
+                .await?
+                .add(vec![batch])
+                .execute()
+                .await
+                .map_err(|e| {
+                    PipelineError::Database(format!("Failed to write {}: {}", table_name, e))
+                })?;
+        } else {
+            self.client
+                .get_connection()
+                .create_table(table_name, vec![batch])
+                .execute()
+                .await
+                .map_err(|e| {
+                    PipelineError::Database(format!("Failed to create {}: {}", table_name, e))
+                })?;
+        }
+        Ok(())
+    }
+
+    async fn drop_table(&self, table_name: &str) -> Result<()> {
+        if !self.client.table_exists(table_name).await? {
+            return Ok(());
+        }
+        self.client
+            .get_connection()
+            .drop_table(table_name, &[])
+            .await
+            .map_err(|e| {
+                PipelineError::Database(format!("Failed to drop table {}: {}", table_name, e))
+            })
+    }
+}
+
+/// Rebuild each row's embedding text the way [`crate::parser::Chunk`] does:
+/// the heading breadcrumb, a blank line, then the content. Tables without
+/// headings, like the commits table, embed their content as is.
+fn embedding_texts(batch: &RecordBatch) -> Result<Vec<String>> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+    };
+    let headings = column("heading_path");
+    let contents = column("content")
+        .ok_or_else(|| PipelineError::Database("Missing 'content' column".to_string()))?;
+
+    Ok((0..batch.num_rows())
+        .map(|i| {
+            let heading = headings.map_or("", |headings| headings.value(i));
+            let content = contents.value(i);
+            if heading.is_empty() {
+                content.to_string()
+            } else {
+                format!("{}\n\n{}", heading, content)
+            }
+        })
+        .collect())
+}
+
+/// Swap the `embedding` column for `vectors`, adjusting its dimension.
+fn replace_embeddings(batch: &RecordBatch, vectors: &[Vec<f32>]) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    let position = schema
+        .index_of(EMBEDDING_COLUMN)
+        .map_err(|_| PipelineError::Database(format!("Missing '{}' column", EMBEDDING_COLUMN)))?;
+    let embeddings = BatchInserter::embedding_array(vectors)?;
+
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields[position] = Field::new(EMBEDDING_COLUMN, embeddings.data_type().clone(), false);
+    let mut columns: Vec<ArrayRef> = batch.columns().to_vec();
+    columns[position] = Arc::new(embeddings);
+
+    // Never carry a completion marker copied from a previous swap.
+    let mut metadata = schema.metadata().clone();
+    metadata.remove(COMPLETE_KEY);
+
+    RecordBatch::try_new(
+        Arc::new(Schema::new_with_metadata(fields, metadata)),
+        columns,
+    )
+    .map_err(|e| PipelineError::Database(format!("Failed to build batch: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Document;
+    use crate::parser::Chunk;
+
+    #[test]
+    fn embeddings_are_replaced_with_the_new_dimension() {
+        let chunk = Chunk {
+            index: 0,
+            heading_path: vec!["Install".to_string(), "From source".to_string()],
+            content: "cargo build".to_string(),
+        };
+        let document = Document::from_chunk("/r/README.md", "README.md", &chunk, 0, "r", false);
+        let batch = BatchInserter::create_record_batch(
+            SchemaManager::get_documents_schema(4),
+            &[document],
+            &[vec![0.0; 4]],
+        )
+        .unwrap();
+
+        assert_eq!(
+            embedding_texts(&batch).unwrap(),
+            vec![chunk.embedding_text()]
+        );
+
+        let replaced = replace_embeddings(&batch, &[vec![1.0, 2.0]]).unwrap();
+        assert_eq!(replaced.num_columns(), batch.num_columns());
+        assert_eq!(
+            replaced
+                .schema()
+                .field_with_name(EMBEDDING_COLUMN)
+                .unwrap()
+                .data_type(),
+            &arrow_schema::DataType::FixedSizeList(
+                Arc::new(Field::new("item", arrow_schema::DataType::Float32, true)),
+                2
+            )
+        );
+    }
+}
@@ -0,0 +1,882 @@
+// file: src/database/runs.rs
+// description: persistent log of ingestion runs and per-file processing events
+// reference: https://docs.rs/lancedb/latest/lancedb/table/struct.MergeInsertBuilder.html
+
+use crate::config::{Config, RunLogConfig};
+use crate::database::client::{LanceDbClient, escape_sql_literal};
+use crate::error::{PipelineError, Result};
+use arrow_array::{
+    Array, ArrayRef, RecordBatch, RecordBatchIterator, StringArray, UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use futures::StreamExt;
+use lancedb::query::{ExecutableQuery, QueryBase};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    Failed,
+    /// Stopped by Ctrl-C; committed files are checkpointed for `--resume`.
+    Interrupted,
+}
+
+impl RunStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Interrupted => "interrupted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "running" => Some(Self::Running),
+            "completed" => Some(Self::Completed),
+            "failed" => Some(Self::Failed),
+            "interrupted" => Some(Self::Interrupted),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Success,
+    Failed,
+    Skipped,
+}
+
+impl FileStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "success" => Some(Self::Success),
+            "failed" => Some(Self::Failed),
+            "skipped" => Some(Self::Skipped),
+            _ => None,
+        }
+    }
+}
+
+/// One `ingest` invocation.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunRecord {
+    pub run_id: String,
+    pub repository_url: String,
+    /// Checked-out commit, when the source is a git repository.
+    pub commit: Option<String>,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    pub status: RunStatus,
+    pub files_total: u64,
+    pub files_succeeded: u64,
+    pub files_failed: u64,
+    pub files_skipped: u64,
+    pub chunks_written: u64,
+    /// Hash of the settings that affect what gets indexed; see [`config_hash`].
+    pub config_hash: String,
+    /// The run whose failed files this run retried.
+    pub retry_of: Option<String>,
+}
+
+/// What happened to one file during a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileEvent {
+    pub relative_path: String,
+    pub status: FileStatus,
+    pub error: Option<String>,
+    pub chunks: u32,
+    pub duration_ms: u64,
+    pub bytes: u64,
+}
+
+impl fmt::Display for RunRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Run {}", self.run_id)?;
+        writeln!(f, "  Repository: {}", self.repository_url)?;
+        writeln!(f, "  Commit:     {}", self.commit.as_deref().unwrap_or("-"))?;
+        writeln!(f, "  Status:     {}", self.status.as_str())?;
+        writeln!(f, "  Started:    {}", format_time(self.started_at))?;
+        match self.finished_at {
+            Some(finished) => writeln!(
+                f,
+                "  Finished:   {} ({}s)",
+                format_time(finished),
+                finished.saturating_sub(self.started_at)
+            )?,
+            None => writeln!(f, "  Finished:   -")?,
+        }
+        writeln!(
+            f,
+            "  Files:      {} total, {} succeeded, {} failed, {} skipped",
+            self.files_total, self.files_succeeded, self.files_failed, self.files_skipped
+        )?;
+        writeln!(f, "  Chunks:     {}", self.chunks_written)?;
+        write!(f, "  Config:     {}", self.config_hash)?;
+        if let Some(retry_of) = &self.retry_of {
+            write!(f, "\n  Retry of:   {}", retry_of)?;
+        }
+        Ok(())
+    }
+}
+
+/// Hash of the settings that determine what ends up in the index (embedding
+/// model, extraction rules, file selection), so runs with different
+/// settings can be told apart. Credentials are not included.
+pub fn config_hash(config: &Config) -> String {
+    let relevant = serde_json::json!({
+        "table": config.database.table_name,
+        "embedding_model": config.embedding.model,
+        "embedding_dimension": config.embedding.dimension,
+        "embedding_provider": config.embedding.base_url,
+        "extraction": config.extraction,
+        "skip_patterns": config.pipeline.skip_patterns,
+        "max_file_size_mb": config.pipeline.max_file_size_mb,
+    });
+    let digest = Sha256::digest(relevant.to_string().as_bytes());
+    digest.iter().take(8).map(|b| format!("{b:02x}")).collect()
+}
+
+/// Reads and writes the `runs` and `file_events` tables.
+#[derive(Clone)]
+pub struct RunLog {
+    client: LanceDbClient,
+    runs_table: String,
+    events_table: String,
+}
+
+impl RunLog {
+    pub fn new(client: &LanceDbClient, config: &RunLogConfig) -> Self {
+        Self {
+            client: client.clone(),
+            runs_table: config.runs_table.clone(),
+            events_table: config.file_events_table.clone(),
+        }
+    }
+
+    /// Insert or update a run, keyed by `run_id`.
+    pub async fn save_run(&self, run: &RunRecord) -> Result<()> {
+        let schema = runs_schema();
+        let batch = runs_batch(schema.clone(), std::slice::from_ref(run))?;
+
+        if !self.client.table_exists(&self.runs_table).await? {
+            self.create(&self.runs_table, batch).await?;
+            return Ok(());
+        }
+
+        let table = self.client.get_table(&self.runs_table).await?;
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut merge = table.merge_insert(&["run_id"]);
+        merge
+            .when_matched_update_all(None)
+            .when_not_matched_insert_all();
+        merge
+            .execute(Box::new(reader))
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to record run: {}", e)))?;
+        Ok(())
+    }
+
+    pub async fn append_events(&self, run_id: &str, events: &[FileEvent]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let batch = events_batch(events_schema(), run_id, events)?;
+
+        if !self.client.table_exists(&self.events_table).await? {
+            return self.create(&self.events_table, batch).await;
+        }
+        self.client
+            .get_table(&self.events_table)
+            .await?
+            .add(vec![batch])
+            .execute()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to record file events: {}", e)))?;
+        Ok(())
+    }
+
+    /// Remove a run's events for `relative_paths`, before they are re-recorded.
+    pub async fn delete_events(&self, run_id: &str, relative_paths: &[String]) -> Result<()> {
+        if relative_paths.is_empty() || !self.client.table_exists(&self.events_table).await? {
+            return Ok(());
+        }
+        let paths = relative_paths
+            .iter()
+            .map(|p| format!("'{}'", escape_sql_literal(p)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.client
+            .get_table(&self.events_table)
+            .await?
+            .delete(&format!(
+                "run_id = '{}' AND relative_path IN ({})",
+                escape_sql_literal(run_id),
+                paths
+            ))
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to delete file events: {}", e)))?;
+        Ok(())
+    }
+
+    /// Runs, newest first.
+    pub async fn list_runs(&self, limit: usize) -> Result<Vec<RunRecord>> {
+        let mut runs = self.query_runs(None).await?;
+        runs.sort_by_key(|run| std::cmp::Reverse(run.started_at));
+        runs.truncate(limit);
+        Ok(runs)
+    }
+
+    /// The most recent run for `repository_url`, if it did not complete.
+    pub async fn latest_unfinished(&self, repository_url: &str) -> Result<Option<RunRecord>> {
+        let predicate = format!("repository_url = '{}'", escape_sql_literal(repository_url));
+        let latest = self
+            .query_runs(Some(predicate))
+            .await?
+            .into_iter()
+            .max_by_key(|run| run.started_at);
+        Ok(latest.filter(|run| run.status != RunStatus::Completed))
+    }
+
+    /// A run by id, or by a unique id prefix.
+    pub async fn get_run(&self, id: &str) -> Result<Option<RunRecord>> {
+        let pattern = format!(
+            "run_id LIKE '{}%'",
+            escape_sql_literal(&id.replace(['%', '_', '\\'], ""))
+        );
+        let mut runs = self.query_runs(Some(pattern)).await?;
+        if let Some(exact) = runs.iter().position(|r| r.run_id == id) {
+            return Ok(Some(runs.swap_remove(exact)));
+        }
+        match runs.len() {
+            0 => Ok(None),
+            1 => Ok(runs.pop()),
+            n => Err(PipelineError::Validation(format!(
+                "Run id prefix '{}' is ambiguous ({} runs)",
+                id, n
+            ))),
+        }
+    }
+
+    /// A run's file events, optionally only those with `status`, by path.
+    pub async fn file_events(
+        &self,
+        run_id: &str,
+        status: Option<FileStatus>,
+    ) -> Result<Vec<FileEvent>> {
+        if !self.client.table_exists(&self.events_table).await? {
+            return Ok(Vec::new());
+        }
+        let mut predicate = format!("run_id = '{}'", escape_sql_literal(run_id));
+        if let Some(status) = status {
+            predicate.push_str(&format!(" AND status = '{}'", status.as_str()));
+        }
+
+        let mut events = Vec::new();
+        let table = self.client.get_table(&self.events_table).await?;
+        let mut stream = table
+            .query()
+            .only_if(predicate)
+            .execute()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read file events: {}", e)))?;
+        while let Some(batch) = stream.next().await {
+            let batch = batch
+                .map_err(|e| PipelineError::Database(format!("Failed to read batch: {}", e)))?;
+            events.extend(read_events(&batch)?);
+        }
+        events.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        Ok(events)
+    }
+
+    async fn query_runs(&self, predicate: Option<String>) -> Result<Vec<RunRecord>> {
+        if !self.client.table_exists(&self.runs_table).await? {
+            return Ok(Vec::new());
+        }
+        let table = self.client.get_table(&self.runs_table).await?;
+        let mut query = table.query();
+        if let Some(predicate) = predicate {
+            query = query.only_if(predicate);
+        }
+        let mut stream = query
+            .execute()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read runs: {}", e)))?;
+
+        let mut runs = Vec::new();
+        while let Some(batch) = stream.next().await {
+            let batch = batch
+                .map_err(|e| PipelineError::Database(format!("Failed to read batch: {}", e)))?;
+            runs.extend(read_runs(&batch)?);
+        }
+        Ok(runs)
+    }
+
+    async fn create(&self, table_name: &str, batch: RecordBatch) -> Result<()> {
+        self.client
+            .get_connection()
+            .create_table(table_name, vec![batch])
+            .execute()
+            .await
+            .map_err(|e| {
+                PipelineError::Database(format!("Failed to create table {}: {}", table_name, e))
+            })?;
+        info!("Created table: {}", table_name);
+        Ok(())
+    }
+}
+
+/// Tracks one run while it is in progress. Events are kept per path, so a
+/// file that was prepared but then failed to write is recorded once, as
+/// failed. A successful file is only persisted once the writer reports its
+/// chunks committed, which makes the persisted events a checkpoint that
+/// `ingest --resume` can continue from.
+pub struct RunRecorder {
+    log: RunLog,
+    run: Mutex<RunRecord>,
+    state: Mutex<RecorderState>,
+}
+
+#[derive(Default)]
+struct RecorderState {
+    events: BTreeMap<String, FileEvent>,
+    /// Files whose chunks the writer has committed.
+    committed: HashSet<String>,
+    /// Files whose current event is already in the events table.
+    persisted: HashSet<String>,
+    /// Files whose persisted event has since been replaced.
+    superseded: HashSet<String>,
+}
+
+impl RecorderState {
+    /// Events that are final but not yet persisted: failures and skips right
+    /// away, successes once committed.
+    fn pending(&self) -> Vec<FileEvent> {
+        self.events
+            .values()
+            .filter(|e| !self.persisted.contains(&e.relative_path))
+            .filter(|e| {
+                e.status != FileStatus::Success || self.committed.contains(&e.relative_path)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+impl RunRecorder {
+    /// Record a new `running` run.
+    pub async fn start(
+        log: RunLog,
+        repository_url: &str,
+        commit: Option<String>,
+        config_hash: String,
+        retry_of: Option<String>,
+    ) -> Result<Self> {
+        let run = RunRecord {
+            run_id: uuid::Uuid::new_v4().to_string(),
+            repository_url: repository_url.to_string(),
+            commit,
+            started_at: now_secs(),
+            finished_at: None,
+            status: RunStatus::Running,
+            files_total: 0,
+            files_succeeded: 0,
+            files_failed: 0,
+            files_skipped: 0,
+            chunks_written: 0,
+            config_hash,
+            retry_of,
+        };
+        log.save_run(&run).await?;
+        info!("Started run {}", run.run_id);
+
+        Ok(Self {
+            log,
+            run: Mutex::new(run),
+            state: Mutex::new(RecorderState::default()),
+        })
+    }
+
+    /// Reopen an unfinished run, starting from its persisted events.
+    pub async fn resume(log: RunLog, mut run: RunRecord) -> Result<Self> {
+        let events = log.file_events(&run.run_id, None).await?;
+        let mut state = RecorderState::default();
+        for event in events {
+            state.committed.insert(event.relative_path.clone());
+            state.persisted.insert(event.relative_path.clone());
+            state.events.insert(event.relative_path.clone(), event);
+        }
+
+        run.status = RunStatus::Running;
+        run.finished_at = None;
+        log.save_run(&run).await?;
+        info!(
+            "Resuming run {} ({} file(s) already recorded)",
+            run.run_id,
+            state.events.len()
+        );
+
+        Ok(Self {
+            log,
+            run: Mutex::new(run),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub async fn run_id(&self) -> String {
+        self.run.lock().await.run_id.clone()
+    }
+
+    /// Files this run has already committed successfully.
+    pub async fn completed_paths(&self) -> HashSet<String> {
+        let state = self.state.lock().await;
+        state
+            .events
+            .values()
+            .filter(|e| {
+                e.status == FileStatus::Success && state.committed.contains(&e.relative_path)
+            })
+            .map(|e| e.relative_path.clone())
+            .collect()
+    }
+
+    pub async fn record(&self, event: FileEvent) {
+        debug!(
+            "Run event: {} {}",
+            event.relative_path,
+            event.status.as_str()
+        );
+        let mut state = self.state.lock().await;
+        let path = event.relative_path.clone();
+        if state.persisted.remove(&path) {
+            state.superseded.insert(path.clone());
+        }
+        state.committed.remove(&path);
+        state.events.insert(path, event);
+    }
+
+    /// Downgrade a file to failed, e.g. when its prepared chunks could not be
+    /// written.
+    pub async fn mark_failed(&self, relative_path: &str, error: &str) {
+        let mut state = self.state.lock().await;
+        let event = state
+            .events
+            .entry(relative_path.to_string())
+            .or_insert_with(|| FileEvent {
+                relative_path: relative_path.to_string(),
+                status: FileStatus::Failed,
+                error: None,
+                chunks: 0,
+                duration_ms: 0,
+                bytes: 0,
+            });
+        event.status = FileStatus::Failed;
+        event.error = Some(error.to_string());
+        event.chunks = 0;
+    }
+
+    /// Note that the writer committed these files' chunks, and checkpoint.
+    pub async fn commit<'p>(
+        &self,
+        relative_paths: impl IntoIterator<Item = &'p str>,
+    ) -> Result<()> {
+        self.state
+            .lock()
+            .await
+            .committed
+            .extend(relative_paths.into_iter().map(str::to_string));
+        self.checkpoint().await
+    }
+
+    /// Persist every final event not yet written and refresh the run's
+    /// counts, leaving it `running`.
+    pub async fn checkpoint(&self) -> Result<()> {
+        let mut run = self.run.lock().await;
+        self.persist(&mut run).await?;
+        self.log.save_run(&run).await
+    }
+
+    /// Write the remaining file events and the final run row.
+    pub async fn finish(&self, status: RunStatus) -> Result<RunRecord> {
+        let mut run = self.run.lock().await;
+        if status == RunStatus::Completed {
+            // Every file has been through the writer by now.
+            let mut state = self.state.lock().await;
+            let paths: Vec<String> = state.events.keys().cloned().collect();
+            state.committed.extend(paths);
+        }
+        self.persist(&mut run).await?;
+        run.finished_at = Some(now_secs());
+        run.status = status;
+        self.log.save_run(&run).await?;
+        Ok(run.clone())
+    }
+
+    async fn persist(&self, run: &mut RunRecord) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let pending = state.pending();
+        let superseded: Vec<String> = state
+            .superseded
+            .iter()
+            .filter(|path| pending.iter().any(|e| &e.relative_path == *path))
+            .cloned()
+            .collect();
+
+        self.log.delete_events(&run.run_id, &superseded).await?;
+        self.log.append_events(&run.run_id, &pending).await?;
+
+        for path in superseded {
+            state.superseded.remove(&path);
+        }
+        for event in &pending {
+            state.persisted.insert(event.relative_path.clone());
+        }
+        let persisted: Vec<FileEvent> = state
+            .events
+            .values()
+            .filter(|e| state.persisted.contains(&e.relative_path))
+            .cloned()
+            .collect();
+        summarize(run, &persisted);
+        Ok(())
+    }
+}
+
+fn summarize(run: &mut RunRecord, events: &[FileEvent]) {
+    let count = |status: FileStatus| events.iter().filter(|e| e.status == status).count() as u64;
+    run.files_total = events.len() as u64;
+    run.files_succeeded = count(FileStatus::Success);
+    run.files_failed = count(FileStatus::Failed);
+    run.files_skipped = count(FileStatus::Skipped);
+    run.chunks_written = events
+        .iter()
+        .filter(|e| e.status == FileStatus::Success)
+        .map(|e| e.chunks as u64)
+        .sum();
+}
+
+fn runs_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("run_id", DataType::Utf8, false),
+        Field::new("repository_url", DataType::Utf8, false),
+        Field::new("commit", DataType::Utf8, true),
+        Field::new("started_at", DataType::UInt64, false),
+        Field::new("finished_at", DataType::UInt64, true),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("files_total", DataType::UInt64, false),
+        Field::new("files_succeeded", DataType::UInt64, false),
+        Field::new("files_failed", DataType::UInt64, false),
+        Field::new("files_skipped", DataType::UInt64, false),
+        Field::new("chunks_written", DataType::UInt64, false),
+        Field::new("config_hash", DataType::Utf8, false),
+        Field::new("retry_of", DataType::Utf8, true),
+    ]))
+}
+
+fn events_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("run_id", DataType::Utf8, false),
+        Field::new("relative_path", DataType::Utf8, false),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("error", DataType::Utf8, true),
+        Field::new("chunks", DataType::UInt32, false),
+        Field::new("duration_ms", DataType::UInt64, false),
+        Field::new("bytes", DataType::UInt64, false),
+        Field::new("recorded_at", DataType::UInt64, false),
+    ]))
+}
+
+fn runs_batch(schema: Arc<Schema>, runs: &[RunRecord]) -> Result<RecordBatch> {
+    let strings = |f: fn(&RunRecord) -> Option<&str>| -> ArrayRef {
+        Arc::new(runs.iter().map(f).collect::<StringArray>())
+    };
+    let numbers = |f: fn(&RunRecord) -> Option<u64>| -> ArrayRef {
+        Arc::new(runs.iter().map(f).collect::<UInt64Array>())
+    };
+
+    RecordBatch::try_new(
+        schema,
+        vec![
+            strings(|r| Some(r.run_id.as_str())),
+            strings(|r| Some(r.repository_url.as_str())),
+            strings(|r| r.commit.as_deref()),
+            numbers(|r| Some(r.started_at)),
+            numbers(|r| r.finished_at),
+            strings(|r| Some(r.status.as_str())),
+            numbers(|r| Some(r.files_total)),
+            numbers(|r| Some(r.files_succeeded)),
+            numbers(|r| Some(r.files_failed)),
+            numbers(|r| Some(r.files_skipped)),
+            numbers(|r| Some(r.chunks_written)),
+            strings(|r| Some(r.config_hash.as_str())),
+            strings(|r| r.retry_of.as_deref()),
+        ],
+    )
+    .map_err(|e| PipelineError::Database(format!("Failed to build run batch: {}", e)))
+}
+
+fn events_batch(schema: Arc<Schema>, run_id: &str, events: &[FileEvent]) -> Result<RecordBatch> {
+    let now = now_secs();
+    let len = events.len();
+
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from(vec![run_id; len])) as ArrayRef,
+            Arc::new(
+                events
+                    .iter()
+                    .map(|e| Some(e.relative_path.as_str()))
+                    .collect::<StringArray>(),
+            ),
+            Arc::new(
+                events
+                    .iter()
+                    .map(|e| Some(e.status.as_str()))
+                    .collect::<StringArray>(),
+            ),
+            Arc::new(
+                events
+                    .iter()
+                    .map(|e| e.error.as_deref())
+                    .collect::<StringArray>(),
+            ),
+            Arc::new(UInt32Array::from_iter_values(
+                events.iter().map(|e| e.chunks),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                events.iter().map(|e| e.duration_ms),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                events.iter().map(|e| e.bytes),
+            )),
+            Arc::new(UInt64Array::from(vec![now; len])),
+        ],
+    )
+    .map_err(|e| PipelineError::Database(format!("Failed to build file event batch: {}", e)))
+}
+
+fn string_column<'b>(batch: &'b RecordBatch, name: &str) -> Result<&'b StringArray> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+        .ok_or_else(|| PipelineError::Database(format!("Missing '{}' column", name)))
+}
+
+fn u64_column<'b>(batch: &'b RecordBatch, name: &str) -> Result<&'b UInt64Array> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<UInt64Array>())
+        .ok_or_else(|| PipelineError::Database(format!("Missing '{}' column", name)))
+}
+
+fn optional(array: &StringArray, row: usize) -> Option<String> {
+    (!array.is_null(row)).then(|| array.value(row).to_string())
+}
+
+fn read_runs(batch: &RecordBatch) -> Result<Vec<RunRecord>> {
+    let run_ids = string_column(batch, "run_id")?;
+    let repositories = string_column(batch, "repository_url")?;
+    let commits = string_column(batch, "commit")?;
+    let started = u64_column(batch, "started_at")?;
+    let finished = u64_column(batch, "finished_at")?;
+    let statuses = string_column(batch, "status")?;
+    let total = u64_column(batch, "files_total")?;
+    let succeeded = u64_column(batch, "files_succeeded")?;
+    let failed = u64_column(batch, "files_failed")?;
+    let skipped = u64_column(batch, "files_skipped")?;
+    let chunks = u64_column(batch, "chunks_written")?;
+    let hashes = string_column(batch, "config_hash")?;
+    let retries = string_column(batch, "retry_of")?;
+
+    Ok((0..batch.num_rows())
+        .map(|i| RunRecord {
+            run_id: run_ids.value(i).to_string(),
+            repository_url: repositories.value(i).to_string(),
+            commit: optional(commits, i),
+            started_at: started.value(i),
+            finished_at: (!finished.is_null(i)).then(|| finished.value(i)),
+            status: RunStatus::parse(statuses.value(i)).unwrap_or(RunStatus::Failed),
+            files_total: total.value(i),
+            files_succeeded: succeeded.value(i),
+            files_failed: failed.value(i),
+            files_skipped: skipped.value(i),
+            chunks_written: chunks.value(i),
+            config_hash: hashes.value(i).to_string(),
+            retry_of: optional(retries, i),
+        })
+        .collect())
+}
+
+fn read_events(batch: &RecordBatch) -> Result<Vec<FileEvent>> {
+    let paths = string_column(batch, "relative_path")?;
+    let statuses = string_column(batch, "status")?;
+    let errors = string_column(batch, "error")?;
+    let chunks = batch
+        .column_by_name("chunks")
+        .and_then(|c| c.as_any().downcast_ref::<UInt32Array>())
+        .ok_or_else(|| PipelineError::Database("Missing 'chunks' column".to_string()))?;
+    let durations = u64_column(batch, "duration_ms")?;
+    let bytes = u64_column(batch, "bytes")?;
+
+    Ok((0..batch.num_rows())
+        .map(|i| FileEvent {
+            relative_path: paths.value(i).to_string(),
+            status: FileStatus::parse(statuses.value(i)).unwrap_or(FileStatus::Failed),
+            error: optional(errors, i),
+            chunks: chunks.value(i),
+            duration_ms: durations.value(i),
+            bytes: bytes.value(i),
+        })
+        .collect())
+}
+
+pub(crate) fn format_time(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run() -> RunRecord {
+        RunRecord {
+            run_id: "8d3c5b1e-0000-4000-8000-000000000000".to_string(),
+            repository_url: "https://github.com/example/repo".to_string(),
+            commit: Some("abc123".to_string()),
+            started_at: 1_700_000_000,
+            finished_at: None,
+            status: RunStatus::Running,
+            files_total: 0,
+            files_succeeded: 0,
+            files_failed: 0,
+            files_skipped: 0,
+            chunks_written: 0,
+            config_hash: "0011223344556677".to_string(),
+            retry_of: None,
+        }
+    }
+
+    fn event(path: &str, status: FileStatus, chunks: u32) -> FileEvent {
+        FileEvent {
+            relative_path: path.to_string(),
+            status,
+            error: (status == FileStatus::Failed).then(|| "boom".to_string()),
+            chunks,
+            duration_ms: 12,
+            bytes: 300,
+        }
+    }
+
+    #[test]
+    fn runs_and_events_round_trip_through_batches() {
+        let mut original = run();
+        original.finished_at = Some(1_700_000_090);
+        original.status = RunStatus::Completed;
+        let batch = runs_batch(runs_schema(), std::slice::from_ref(&original)).unwrap();
+        let restored = read_runs(&batch).unwrap().remove(0);
+        assert_eq!(restored.run_id, original.run_id);
+        assert_eq!(restored.commit, original.commit);
+        assert_eq!(restored.finished_at, Some(1_700_000_090));
+        assert_eq!(restored.status, RunStatus::Completed);
+        assert_eq!(restored.retry_of, None);
+
+        let events = vec![
+            event("a.md", FileStatus::Success, 3),
+            event("b.md", FileStatus::Failed, 0),
+        ];
+        let batch = events_batch(events_schema(), &original.run_id, &events).unwrap();
+        let restored = read_events(&batch).unwrap();
+        assert_eq!(restored[1].status, FileStatus::Failed);
+        assert_eq!(restored[1].error.as_deref(), Some("boom"));
+        assert_eq!(restored[0].chunks, 3);
+    }
+
+    #[test]
+    fn summary_counts_by_status() {
+        let mut record = run();
+        summarize(
+            &mut record,
+            &[
+                event("a.md", FileStatus::Success, 3),
+                event("b.md", FileStatus::Success, 2),
+                event("c.md", FileStatus::Failed, 0),
+                event("d.md", FileStatus::Skipped, 0),
+            ],
+        );
+        assert_eq!(record.files_total, 4);
+        assert_eq!(
+            (
+                record.files_succeeded,
+                record.files_failed,
+                record.files_skipped
+            ),
+            (2, 1, 1)
+        );
+        assert_eq!(record.chunks_written, 5);
+        assert!(record.to_string().contains("2 succeeded, 1 failed"));
+    }
+
+    #[test]
+    fn successes_are_checkpointed_only_once_committed() {
+        let mut state = RecorderState::default();
+        for e in [
+            event("a.md", FileStatus::Success, 3),
+            event("b.md", FileStatus::Success, 2),
+            event("c.md", FileStatus::Failed, 0),
+        ] {
+            state.events.insert(e.relative_path.clone(), e);
+        }
+        state.committed.insert("a.md".to_string());
+
+        let pending: Vec<String> = state
+            .pending()
+            .into_iter()
+            .map(|e| e.relative_path)
+            .collect();
+        assert_eq!(pending, vec!["a.md", "c.md"]);
+
+        state.persisted.insert("a.md".to_string());
+        state.persisted.insert("c.md".to_string());
+        assert!(state.pending().is_empty());
+    }
+
+    #[test]
+    fn config_hash_ignores_credentials() {
+        let mut config = Config::default_config();
+        let hash = config_hash(&config);
+        config.embedding.api_key = Some("secret".to_string());
+        assert_eq!(config_hash(&config), hash);
+        config.embedding.model = "other-model".to_string();
+        assert_ne!(config_hash(&config), hash);
+    }
+}
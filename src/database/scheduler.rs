@@ -0,0 +1,369 @@
+// file: src/database/scheduler.rs
+// description: shared embedding scheduler coalescing chunks across files under rate limits
+// reference: https://platform.openai.com/docs/guides/rate-limits
+
+use crate::config::{EmbeddingLimits, TokenizerKind};
+use crate::database::embeddings::EmbeddingClient;
+use crate::error::{PipelineError, Result};
+use crate::generation::TokenCounter;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Semaphore, mpsc, oneshot};
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Sliding window the per-minute limits are measured over.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Jobs queued ahead of the dispatcher before `embed` callers wait.
+const JOB_QUEUE_CAPACITY: usize = 64;
+
+/// One caller's texts and where their vectors go.
+struct Job {
+    vectors: Vec<Option<Vec<f32>>>,
+    remaining: usize,
+    reply: Option<oneshot::Sender<Result<Vec<Vec<f32>>>>>,
+}
+
+impl Job {
+    fn is_abandoned(&self) -> bool {
+        self.reply.is_none()
+    }
+
+    fn fail(&mut self, error: String) {
+        if let Some(reply) = self.reply.take() {
+            let _ = reply.send(Err(PipelineError::Database(error)));
+        }
+    }
+}
+
+/// A single text waiting to be embedded.
+struct Item {
+    job: Arc<Mutex<Job>>,
+    position: usize,
+    text: String,
+    tokens: usize,
+}
+
+struct Request {
+    texts: Vec<String>,
+    reply: oneshot::Sender<Result<Vec<Vec<f32>>>>,
+}
+
+/// Coalesces embedding work from many concurrent callers (typically one per
+/// file) into requests of up to `batch_size` inputs and `max_batch_tokens`
+/// estimated tokens, paced by the configured requests- and tokens-per-minute
+/// limits. Cloning is cheap; all clones feed the same dispatcher task, which
+/// exits once every clone is dropped.
+#[derive(Clone)]
+pub struct EmbeddingScheduler {
+    sender: mpsc::Sender<Request>,
+}
+
+impl EmbeddingScheduler {
+    /// Start the dispatcher on the current Tokio runtime.
+    pub fn spawn(client: Arc<EmbeddingClient>) -> Self {
+        let (sender, receiver) = mpsc::channel(JOB_QUEUE_CAPACITY);
+        tokio::spawn(dispatch(client, receiver));
+        Self { sender }
+    }
+
+    /// Embed `texts`, returning vectors in input order once all of them have
+    /// been embedded, possibly across several requests shared with other
+    /// callers.
+    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(Request { texts, reply })
+            .await
+            .map_err(|_| PipelineError::Database("Embedding scheduler stopped".to_string()))?;
+        response.await.map_err(|_| {
+            PipelineError::Database("Embedding scheduler dropped the request".to_string())
+        })?
+    }
+}
+
+async fn dispatch(client: Arc<EmbeddingClient>, mut receiver: mpsc::Receiver<Request>) {
+    let limits = client.limits().clone();
+    let counter = TokenCounter::new(TokenizerKind::Approximate);
+    let in_flight = Arc::new(Semaphore::new(limits.max_concurrent_requests.max(1)));
+    let mut limiter = RateLimiter::new(limits.requests_per_minute, limits.tokens_per_minute);
+    let linger = Duration::from_millis(limits.linger_ms);
+    let mut queue: VecDeque<Item> = VecDeque::new();
+    let mut open = true;
+
+    loop {
+        if queue.is_empty() {
+            match receiver.recv().await {
+                Some(request) => enqueue(&mut queue, request, &counter),
+                None => break,
+            }
+        }
+
+        // Give other files a moment to contribute before sending a partial batch.
+        let deadline = Instant::now() + linger;
+        while open && !batch_full(&queue, &limits) {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(request)) => enqueue(&mut queue, request, &counter),
+                Ok(None) => open = false,
+                Err(_) => break,
+            }
+        }
+
+        let batch = take_batch(&mut queue, &limits);
+        if batch.is_empty() {
+            if !open && queue.is_empty() {
+                break;
+            }
+            continue;
+        }
+
+        let tokens: usize = batch.iter().map(|item| item.tokens).sum();
+        limiter.acquire(tokens).await;
+
+        let permit = match Arc::clone(&in_flight).acquire_owned().await {
+            Ok(permit) => permit,
+            Err(_) => break,
+        };
+        let client = Arc::clone(&client);
+        tokio::spawn(async move {
+            let texts: Vec<String> = batch.iter().map(|item| item.text.clone()).collect();
+            debug!(
+                "Embedding request: {} input(s), ~{} token(s)",
+                texts.len(),
+                tokens
+            );
+            let result = client.embed_batch(&texts).await;
+            drop(permit);
+            deliver(batch, result);
+        });
+
+        if !open && queue.is_empty() {
+            break;
+        }
+    }
+}
+
+fn enqueue(queue: &mut VecDeque<Item>, request: Request, counter: &TokenCounter) {
+    let len = request.texts.len();
+    let job = Arc::new(Mutex::new(Job {
+        vectors: vec![None; len],
+        remaining: len,
+        reply: Some(request.reply),
+    }));
+    for (position, text) in request.texts.into_iter().enumerate() {
+        queue.push_back(Item {
+            job: Arc::clone(&job),
+            position,
+            tokens: counter.count(&text).max(1),
+            text,
+        });
+    }
+}
+
+fn batch_full(queue: &VecDeque<Item>, limits: &EmbeddingLimits) -> bool {
+    queue.len() >= limits.batch_size
+        || queue.iter().map(|item| item.tokens).sum::<usize>() >= limits.max_batch_tokens
+}
+
+/// Pop the next request's worth of items. A single item larger than the
+/// token limit still goes out on its own; the API decides whether it fits.
+fn take_batch(queue: &mut VecDeque<Item>, limits: &EmbeddingLimits) -> Vec<Item> {
+    let mut batch = Vec::new();
+    let mut tokens = 0;
+
+    while let Some(item) = queue.front() {
+        if lock(&item.job).is_abandoned() {
+            queue.pop_front();
+            continue;
+        }
+        if batch.len() >= limits.batch_size
+            || (!batch.is_empty() && tokens + item.tokens > limits.max_batch_tokens)
+        {
+            break;
+        }
+        tokens += item.tokens;
+        if let Some(item) = queue.pop_front() {
+            batch.push(item);
+        }
+    }
+
+    batch
+}
+
+fn deliver(batch: Vec<Item>, result: Result<Vec<Vec<f32>>>) {
+    match result {
+        Ok(vectors) => {
+            for (item, vector) in batch.into_iter().zip(vectors) {
+                let mut job = lock(&item.job);
+                if job.is_abandoned() {
+                    continue;
+                }
+                job.vectors[item.position] = Some(vector);
+                job.remaining -= 1;
+                if job.remaining == 0
+                    && let Some(reply) = job.reply.take()
+                {
+                    let vectors = std::mem::take(&mut job.vectors)
+                        .into_iter()
+                        .map(Option::unwrap_or_default)
+                        .collect();
+                    let _ = reply.send(Ok(vectors));
+                }
+            }
+        }
+        Err(e) => {
+            let message = e.to_string();
+            for item in batch {
+                lock(&item.job).fail(message.clone());
+            }
+        }
+    }
+}
+
+fn lock(job: &Mutex<Job>) -> std::sync::MutexGuard<'_, Job> {
+    job.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Sliding-window limiter over the last minute of requests and tokens.
+struct RateLimiter {
+    requests_per_minute: Option<u32>,
+    tokens_per_minute: Option<u32>,
+    window: VecDeque<(Instant, usize)>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_minute: Option<u32>, tokens_per_minute: Option<u32>) -> Self {
+        Self {
+            requests_per_minute,
+            tokens_per_minute,
+            window: VecDeque::new(),
+        }
+    }
+
+    async fn acquire(&mut self, tokens: usize) {
+        loop {
+            let now = Instant::now();
+            match self.wait_time(now, tokens) {
+                Some(wait) => {
+                    debug!("Embedding rate limit reached; waiting {:?}", wait);
+                    tokio::time::sleep(wait).await;
+                }
+                None => {
+                    self.window.push_back((now, tokens));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// How long until a request of `tokens` fits the limits, or `None` if it
+    /// can go now. A request larger than the whole token budget is let
+    /// through once the window is empty.
+    fn wait_time(&mut self, now: Instant, tokens: usize) -> Option<Duration> {
+        while let Some(&(at, _)) = self.window.front() {
+            if now.duration_since(at) >= RATE_WINDOW {
+                self.window.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        let oldest = self.window.front().map(|&(at, _)| at)?;
+        let until_oldest_expires = RATE_WINDOW.saturating_sub(now.duration_since(oldest));
+
+        if let Some(rpm) = self.requests_per_minute
+            && self.window.len() >= rpm as usize
+        {
+            return Some(until_oldest_expires);
+        }
+
+        if let Some(tpm) = self.tokens_per_minute {
+            let used: usize = self.window.iter().map(|&(_, t)| t).sum();
+            if used + tokens > tpm as usize {
+                return Some(until_oldest_expires);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(batch_size: usize, max_batch_tokens: usize) -> EmbeddingLimits {
+        EmbeddingLimits {
+            batch_size,
+            max_batch_tokens,
+            ..Default::default()
+        }
+    }
+
+    type Response = oneshot::Receiver<Result<Vec<Vec<f32>>>>;
+
+    fn queue_of(texts: &[&str]) -> (VecDeque<Item>, Response) {
+        let (reply, response) = oneshot::channel();
+        let mut queue = VecDeque::new();
+        enqueue(
+            &mut queue,
+            Request {
+                texts: texts.iter().map(|t| t.to_string()).collect(),
+                reply,
+            },
+            &TokenCounter::new(TokenizerKind::Approximate),
+        );
+        (queue, response)
+    }
+
+    #[test]
+    fn batches_respect_count_and_token_limits() {
+        let (mut queue, _response) = queue_of(&["a".repeat(40).as_str(), "b", "c", "d"]);
+
+        // 10 tokens + 1 would exceed the 10-token limit, but a lone item always goes.
+        let batch = take_batch(&mut queue, &limits(8, 10));
+        assert_eq!(batch.len(), 1);
+
+        let batch = take_batch(&mut queue, &limits(2, 10));
+        assert_eq!(batch.len(), 2);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn results_are_reassembled_in_order_across_requests() {
+        let (mut queue, response) = queue_of(&["x", "y", "z"]);
+        let first = take_batch(&mut queue, &limits(2, 100));
+        let second = take_batch(&mut queue, &limits(2, 100));
+
+        deliver(second, Ok(vec![vec![3.0]]));
+        deliver(first, Ok(vec![vec![1.0], vec![2.0]]));
+
+        let vectors = response.await.unwrap().unwrap();
+        assert_eq!(vectors, vec![vec![1.0], vec![2.0], vec![3.0]]);
+    }
+
+    #[test]
+    fn limiter_waits_for_the_window_to_clear() {
+        let mut limiter = RateLimiter::new(Some(2), Some(100));
+        let start = Instant::now();
+        limiter.window.push_back((start, 10));
+        assert_eq!(limiter.wait_time(start, 10), None);
+
+        limiter.window.push_back((start, 10));
+        let wait = limiter
+            .wait_time(start + Duration::from_secs(15), 10)
+            .unwrap();
+        assert_eq!(wait, Duration::from_secs(45));
+
+        let mut tokens = RateLimiter::new(None, Some(100));
+        tokens.window.push_back((start, 95));
+        assert!(tokens.wait_time(start, 10).is_some());
+        assert_eq!(tokens.wait_time(start + RATE_WINDOW, 10), None);
+    }
+}
@@ -2,26 +2,77 @@
 // description: LanceDB schema management for vector storage
 // reference: https://docs.rs/lancedb
 
-use crate::database::client::LanceDbClient;
-use crate::error::Result;
+use crate::config::{EmbeddingConfig, VectorIndexConfig, VectorIndexKind};
+use crate::database::client::{LanceDbClient, distance_type};
+use crate::database::migrations::{MigrationPlan, SCHEMA_VERSION_KEY, current_version};
+use crate::database::provenance::EmbeddingProvenance;
+use crate::error::{PipelineError, Result};
 use arrow_schema::{DataType, Field, Schema};
+use lancedb::index::Index;
+use lancedb::index::vector::{IvfHnswSqIndexBuilder, IvfPqIndexBuilder};
+use lancedb::table::NewColumnTransform;
+use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
 use tracing::{info, warn};
 
+/// Column the ANN index is built on.
+pub const EMBEDDING_COLUMN: &str = "embedding";
+
+/// IVF k-means and PQ codebook training need at least this many vectors
+/// (256 centroids per 8-bit PQ sub-quantizer).
+pub const MIN_INDEX_ROWS: usize = 256;
+
+/// State of the vector index on the documents table.
+#[derive(Debug, Clone)]
+pub struct VectorIndexStatus {
+    pub name: String,
+    pub index_type: String,
+    pub metric: Option<String>,
+    pub indexed_rows: usize,
+    /// Rows added since the index was built; searched by brute force until the
+    /// index is optimized or rebuilt.
+    pub unindexed_rows: usize,
+}
+
+impl fmt::Display for VectorIndexStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, metric {}): {} indexed, {} unindexed",
+            self.name,
+            self.index_type,
+            self.metric.as_deref().unwrap_or("unknown"),
+            self.indexed_rows,
+            self.unindexed_rows
+        )
+    }
+}
+
 pub struct SchemaManager<'a> {
     client: &'a LanceDbClient,
+    table_name: String,
 }
 
 impl<'a> SchemaManager<'a> {
     pub fn new(client: &'a LanceDbClient) -> Self {
-        Self { client }
+        Self::for_table(client, client.table_name())
+    }
+
+    /// Manage `table_name` instead of the documents table, e.g. the commits
+    /// table, which carries its own embedding provenance.
+    pub fn for_table(client: &'a LanceDbClient, table_name: &str) -> Self {
+        Self {
+            client,
+            table_name: table_name.to_string(),
+        }
     }
 
     pub async fn initialize(&self) -> Result<()> {
         info!("Initializing LanceDB schema");
 
         // Check if the main documents table exists
-        if !self.client.table_exists(self.client.table_name()).await? {
+        if !self.client.table_exists(&self.table_name).await? {
             info!("Creating documents table with vector embeddings");
             // Table will be created on first insert with proper schema
         } else {
@@ -33,7 +84,7 @@ impl<'a> SchemaManager<'a> {
     }
 
     pub async fn verify_schema(&self) -> Result<bool> {
-        let table_name = self.client.table_name();
+        let table_name = self.table_name.as_str();
 
         if !self.client.table_exists(table_name).await? {
             warn!("Table '{}' does not exist", table_name);
@@ -44,9 +95,354 @@ impl<'a> SchemaManager<'a> {
         Ok(true)
     }
 
-    /// Returns the Arrow schema for the documents table with vector embeddings
+    /// Compare the live table against [`Self::get_documents_schema`]. `None`
+    /// when the table does not exist yet (it is created at the current version).
+    pub async fn migration_plan(&self, embedding_dim: usize) -> Result<Option<MigrationPlan>> {
+        let table_name = self.table_name.as_str();
+        if !self.client.table_exists(table_name).await? {
+            return Ok(None);
+        }
+
+        let table = self.client.get_table(table_name).await?;
+        let live = table
+            .schema()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read schema: {}", e)))?;
+        Ok(Some(MigrationPlan::new(
+            &live,
+            &Self::get_documents_schema(embedding_dim),
+        )))
+    }
+
+    /// Add missing columns with their defaults and record the current schema
+    /// version. Fails without changing anything if any column is blocked.
+    pub async fn apply_migrations(&self, plan: &MigrationPlan) -> Result<()> {
+        if !plan.can_apply() {
+            return Err(PipelineError::Database(format!(
+                "Schema cannot be migrated in place: {}. Run `reembed` for a dimension \
+                 change, or `reset` and re-ingest",
+                plan.blocked.join(", ")
+            )));
+        }
+
+        let table = self.client.get_table(&self.table_name).await?;
+        if !plan.add_columns.is_empty() {
+            info!(
+                "Adding column(s): {}",
+                plan.add_columns
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            table
+                .add_columns(
+                    NewColumnTransform::SqlExpressions(plan.add_columns.clone()),
+                    None,
+                )
+                .await
+                .map_err(|e| PipelineError::Database(format!("Failed to add columns: {}", e)))?;
+        }
+
+        let mut metadata = table
+            .schema()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read schema: {}", e)))?
+            .metadata()
+            .clone();
+        metadata.insert(SCHEMA_VERSION_KEY.to_string(), plan.to_version.to_string());
+        table
+            .as_native()
+            .ok_or_else(|| {
+                PipelineError::Database("Table metadata requires a local table".to_string())
+            })?
+            .replace_schema_metadata(metadata)
+            .await
+            .map_err(|e| {
+                PipelineError::Database(format!("Failed to record schema version: {}", e))
+            })?;
+
+        info!("Schema migrated to version {}", plan.to_version);
+        Ok(())
+    }
+
+    /// Apply pending migrations before writing; a no-op for up-to-date or
+    /// missing tables.
+    pub async fn ensure_migrated(&self, embedding_dim: usize) -> Result<()> {
+        match self.migration_plan(embedding_dim).await? {
+            Some(plan) if !plan.is_up_to_date() => {
+                info!("{}", plan);
+                self.apply_migrations(&plan).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Embedding provenance recorded on the table, or `None` when the
+    /// table does not exist or predates provenance tracking.
+    pub async fn embedding_provenance(&self) -> Result<Option<EmbeddingProvenance>> {
+        let table_name = self.table_name.as_str();
+        if !self.client.table_exists(table_name).await? {
+            return Ok(None);
+        }
+
+        let table = self.client.get_table(table_name).await?;
+        let schema = table
+            .schema()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read schema: {}", e)))?;
+        Ok(EmbeddingProvenance::from_metadata(schema.metadata()))
+    }
+
+    /// Merge `provenance` into the table's schema metadata.
+    pub async fn record_embedding_provenance(
+        &self,
+        provenance: &EmbeddingProvenance,
+    ) -> Result<()> {
+        let table_name = self.table_name.as_str();
+        if !self.client.table_exists(table_name).await? {
+            return Ok(());
+        }
+
+        let table = self.client.get_table(table_name).await?;
+        let mut metadata = table
+            .schema()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read schema: {}", e)))?
+            .metadata()
+            .clone();
+        metadata.extend(provenance.to_metadata());
+
+        table
+            .as_native()
+            .ok_or_else(|| {
+                PipelineError::Database("Table metadata requires a local table".to_string())
+            })?
+            .replace_schema_metadata(metadata)
+            .await
+            .map_err(|e| {
+                PipelineError::Database(format!("Failed to record embedding provenance: {}", e))
+            })
+    }
+
+    /// Record that rows were just written under `config`. The fallback flag is
+    /// sticky: once a fallback vector is written only `reembed` clears it.
+    pub async fn update_embedding_provenance(
+        &self,
+        config: &EmbeddingConfig,
+        degraded: bool,
+    ) -> Result<()> {
+        let mut provenance = EmbeddingProvenance::from_config(config);
+        let previously_degraded = self
+            .embedding_provenance()
+            .await?
+            .is_some_and(|stored| stored.fallback);
+        provenance.fallback = degraded || previously_degraded;
+        self.record_embedding_provenance(&provenance).await
+    }
+
+    /// Whether chunks ingested at `git_ref` should copy the vectors of
+    /// identical chunks stored at other refs of `repository_url`. Only worth
+    /// the lookup once another ref is stored, and never from a table holding
+    /// fallback vectors, which would be copied as if they were semantic.
+    pub async fn stored_vectors_reusable(
+        &self,
+        repository_url: &str,
+        git_ref: &str,
+    ) -> Result<bool> {
+        if self
+            .embedding_provenance()
+            .await?
+            .is_some_and(|stored| stored.fallback)
+        {
+            info!("Table contains fallback vectors; embedding every chunk again");
+            return Ok(false);
+        }
+        self.client.has_other_refs(repository_url, git_ref).await
+    }
+
+    /// Refuse to continue when the table was embedded with a
+    /// different model or dimension than `config`. Tables without recorded
+    /// provenance are only checked for their vector dimension.
+    pub async fn check_embedding_provenance(&self, config: &EmbeddingConfig) -> Result<()> {
+        let configured = EmbeddingProvenance::from_config(config);
+        if let Some(stored) = self.embedding_provenance().await? {
+            if stored.provider != configured.provider && stored.same_space(&configured) {
+                warn!(
+                    "Table was embedded via {} but embedding.base_url is {}; \
+                     assuming the same model weights",
+                    stored.provider, configured.provider
+                );
+            }
+            if stored.fallback {
+                warn!("Table contains non-semantic fallback vectors; consider `reembed`");
+            }
+            return stored.ensure_compatible(&configured);
+        }
+
+        match self.embedding_dimension().await? {
+            Some(dimension) if dimension != config.dimension => {
+                Err(PipelineError::Config(format!(
+                    "Embedding dimension mismatch: the table stores {}-dim vectors but \
+                     embedding.dimension is {}. Run `reembed` to migrate the table",
+                    dimension, config.dimension
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Size of the `embedding` vectors in the existing table.
+    pub async fn embedding_dimension(&self) -> Result<Option<usize>> {
+        let table_name = self.table_name.as_str();
+        if !self.client.table_exists(table_name).await? {
+            return Ok(None);
+        }
+
+        let table = self.client.get_table(table_name).await?;
+        let schema = table
+            .schema()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read schema: {}", e)))?;
+        Ok(schema
+            .field_with_name(EMBEDDING_COLUMN)
+            .ok()
+            .and_then(|field| match field.data_type() {
+                DataType::FixedSizeList(_, size) => Some(*size as usize),
+                _ => None,
+            }))
+    }
+
+    /// Status of the index on `embedding`, or `None` when the table or the
+    /// index does not exist.
+    pub async fn vector_index_status(&self) -> Result<Option<VectorIndexStatus>> {
+        let table_name = self.table_name.as_str();
+        if !self.client.table_exists(table_name).await? {
+            return Ok(None);
+        }
+
+        let table = self.client.get_table(table_name).await?;
+        let indices = table
+            .list_indices()
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to list indices: {}", e)))?;
+
+        let Some(index) = indices
+            .into_iter()
+            .find(|i| i.columns.iter().any(|c| c == EMBEDDING_COLUMN))
+        else {
+            return Ok(None);
+        };
+
+        let stats = table
+            .index_stats(&index.name)
+            .await
+            .map_err(|e| PipelineError::Database(format!("Failed to read index stats: {}", e)))?;
+
+        Ok(Some(VectorIndexStatus {
+            index_type: index.index_type.to_string(),
+            metric: stats
+                .as_ref()
+                .and_then(|s| s.distance_type)
+                .map(|d| format!("{:?}", d).to_lowercase()),
+            indexed_rows: stats.as_ref().map_or(0, |s| s.num_indexed_rows),
+            unindexed_rows: stats.as_ref().map_or(0, |s| s.num_unindexed_rows),
+            name: index.name,
+        }))
+    }
+
+    /// Build (or with `replace`, rebuild) the ANN index on `embedding`.
+    pub async fn create_vector_index(
+        &self,
+        config: &VectorIndexConfig,
+        replace: bool,
+    ) -> Result<VectorIndexStatus> {
+        let table_name = self.table_name.as_str();
+        if !self.client.table_exists(table_name).await? {
+            return Err(PipelineError::Database(format!(
+                "Table '{}' does not exist; ingest documents before indexing",
+                table_name
+            )));
+        }
+
+        let rows = self.client.get_document_count().await? as usize;
+        if rows < MIN_INDEX_ROWS {
+            return Err(PipelineError::Database(format!(
+                "Table has {} rows; at least {} are needed to train a vector index",
+                rows, MIN_INDEX_ROWS
+            )));
+        }
+
+        let metric = distance_type(config.metric);
+        let index = match config.kind {
+            VectorIndexKind::IvfPq => {
+                let mut builder = IvfPqIndexBuilder::default().distance_type(metric);
+                if let Some(partitions) = config.num_partitions {
+                    builder = builder.num_partitions(partitions);
+                }
+                if let Some(sub_vectors) = config.num_sub_vectors {
+                    builder = builder.num_sub_vectors(sub_vectors);
+                }
+                Index::IvfPq(builder)
+            }
+            VectorIndexKind::Hnsw => {
+                let mut builder = IvfHnswSqIndexBuilder::default().distance_type(metric);
+                if let Some(partitions) = config.num_partitions {
+                    builder = builder.num_partitions(partitions);
+                }
+                Index::IvfHnswSq(builder)
+            }
+        };
+
+        info!(
+            "Building {:?} index on '{}' over {} rows (metric {:?})",
+            config.kind, EMBEDDING_COLUMN, rows, config.metric
+        );
+
+        let table = self.client.get_table(table_name).await?;
+        table
+            .create_index(&[EMBEDDING_COLUMN], index)
+            .replace(replace)
+            .execute()
+            .await
+            .map_err(|e| {
+                PipelineError::Database(format!("Failed to create vector index: {}", e))
+            })?;
+
+        self.vector_index_status().await?.ok_or_else(|| {
+            PipelineError::Database("Vector index was not found after creation".to_string())
+        })
+    }
+
+    /// Create the vector index once the table reaches `auto_threshold` rows.
+    /// Existing indexes are left alone. Returns the index status, if any.
+    pub async fn ensure_vector_index(
+        &self,
+        config: &VectorIndexConfig,
+    ) -> Result<Option<VectorIndexStatus>> {
+        if let Some(status) = self.vector_index_status().await? {
+            return Ok(Some(status));
+        }
+        if config.auto_threshold == 0 || !self.client.table_exists(&self.table_name).await? {
+            return Ok(None);
+        }
+
+        let rows = self.client.get_document_count().await? as usize;
+        if rows < config.auto_threshold.max(MIN_INDEX_ROWS) {
+            return Ok(None);
+        }
+
+        info!(
+            "Table has {} rows (auto-index threshold {}), creating vector index",
+            rows, config.auto_threshold
+        );
+        self.create_vector_index(config, false).await.map(Some)
+    }
+
+    /// Returns the Arrow schema for the documents table with vector embeddings.
+    /// Tables created from it are stamped with the current schema version.
     pub fn get_documents_schema(embedding_dim: usize) -> Arc<Schema> {
-        Arc::new(Schema::new(vec![
+        let fields = vec![
             Field::new("id", DataType::Utf8, false),
             Field::new("file_path", DataType::Utf8, false),
             Field::new("relative_path", DataType::Utf8, false),
@@ -73,13 +469,30 @@ impl<'a> SchemaManager<'a> {
             Field::new("language", DataType::Utf8, true),
             // Required for repository tracking and deletion
             Field::new("repository_url", DataType::Utf8, false),
-        ]))
+            // Search filter metadata; tags are stored as ",a,b,"
+            Field::new("category", DataType::Utf8, true),
+            Field::new("tags", DataType::Utf8, true),
+            // Set for files inside a git submodule
+            Field::new("submodule", DataType::Utf8, true),
+            Field::new("submodule_commit", DataType::Utf8, true),
+            // Ref and commit the chunk was ingested from; NULL for directories
+            Field::new("git_ref", DataType::Utf8, true),
+            Field::new("git_commit", DataType::Utf8, true),
+            Field::new("last_commit", DataType::Utf8, true),
+            Field::new("last_author", DataType::Utf8, true),
+            Field::new("last_changed_at", DataType::UInt64, true),
+        ];
+        let metadata = HashMap::from([(
+            SCHEMA_VERSION_KEY.to_string(),
+            current_version().to_string(),
+        )]);
+        Arc::new(Schema::new_with_metadata(fields, metadata))
     }
 
     pub async fn drop_all_tables(&self) -> Result<()> {
         warn!("Dropping all tables in LanceDB");
 
-        let table_name = self.client.table_name();
+        let table_name = self.table_name.as_str();
 
         // Drop the main table
         if self.client.table_exists(table_name).await? {
@@ -107,7 +520,7 @@ mod tests {
     #[test]
     fn test_schema_generation() {
         let schema = SchemaManager::get_documents_schema(384);
-        assert_eq!(schema.fields().len(), 16);
+        assert_eq!(schema.fields().len(), 25);
 
         let embedding_field = schema.field_with_name("embedding").unwrap();
         assert!(matches!(
@@ -0,0 +1,280 @@
+// file: src/database/stale.rs
+// description: report sections whose lines no commit has changed since a cutoff
+// reference: https://git-scm.com/docs/git-blame
+
+use crate::database::client::LanceDbClient;
+use crate::database::filter::SearchFilter;
+use crate::error::{PipelineError, Result};
+use crate::repository::history::format_date;
+use arrow_array::{Array, RecordBatch, StringArray, UInt64Array};
+use futures::StreamExt;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use tracing::debug;
+
+/// One heading of one file (at one ref), dated by the newest commit among
+/// its chunks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StaleSection {
+    pub repository_url: String,
+    pub relative_path: String,
+    pub heading_path: String,
+    pub git_ref: Option<String>,
+    pub last_commit: Option<String>,
+    pub last_author: Option<String>,
+    pub last_changed_at: u64,
+}
+
+impl StaleSection {
+    /// "path.md # Heading @ ref", as in search results.
+    pub fn location(&self) -> String {
+        let mut location = self.relative_path.clone();
+        if !self.heading_path.is_empty() {
+            location.push_str(&format!(" # {}", self.heading_path));
+        }
+        if let Some(git_ref) = &self.git_ref {
+            location.push_str(&format!(" @ {}", git_ref));
+        }
+        location
+    }
+}
+
+/// Sections untouched since `cutoff`, oldest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleReport {
+    /// Unix seconds; sections last changed before this are stale.
+    pub cutoff: u64,
+    /// Sections matched by the filter.
+    pub checked: usize,
+    /// Sections without blame data (plain directories, blame disabled, or
+    /// lines older than the blame walk), which cannot be judged.
+    pub undated: usize,
+    pub sections: Vec<StaleSection>,
+}
+
+impl fmt::Display for StaleReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} section(s) unchanged since {}",
+            self.sections.len(),
+            self.checked,
+            format_date(self.cutoff)
+        )?;
+        if self.undated > 0 {
+            writeln!(
+                f,
+                "{} section(s) have no commit dates; enable repository.blame or raise \
+                 its max_commits and re-ingest",
+                self.undated
+            )?;
+        }
+        for section in &self.sections {
+            let commit = section
+                .last_commit
+                .as_deref()
+                .map(|c| format!(" ({})", &c[..c.len().min(10)]))
+                .unwrap_or_default();
+            writeln!(
+                f,
+                "{}  {}  {}{}",
+                format_date(section.last_changed_at),
+                section.location(),
+                section.last_author.as_deref().unwrap_or("unknown"),
+                commit
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds sections whose newest chunk was last changed before a cutoff.
+pub struct StaleFinder<'a> {
+    client: &'a LanceDbClient,
+    filter: Option<SearchFilter>,
+}
+
+impl<'a> StaleFinder<'a> {
+    pub fn new(client: &'a LanceDbClient) -> Self {
+        Self {
+            client,
+            filter: None,
+        }
+    }
+
+    /// Only consider chunks matching `filter`.
+    pub fn with_filter(mut self, filter: Option<SearchFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sections whose every chunk was last changed before `cutoff` (Unix
+    /// seconds).
+    pub async fn find(&self, cutoff: u64) -> Result<StaleReport> {
+        let mut rows = Vec::new();
+        let table_name = self.client.table_name();
+        if self.client.table_exists(table_name).await? {
+            let table = self.client.get_table(table_name).await?;
+            let mut query = table.query().select(Select::columns(&[
+                "repository_url",
+                "relative_path",
+                "heading_path",
+                "git_ref",
+                "last_commit",
+                "last_author",
+                "last_changed_at",
+            ]));
+            if let Some(filter) = &self.filter {
+                let predicate = filter.to_predicate();
+                debug!("Reading chunk dates where {}", predicate);
+                query = query.only_if(predicate);
+            }
+            let mut stream = query.execute().await.map_err(|e| {
+                PipelineError::Database(format!("Failed to read chunk dates: {}", e))
+            })?;
+            while let Some(batch) = stream.next().await {
+                let batch = batch
+                    .map_err(|e| PipelineError::Database(format!("Failed to read batch: {}", e)))?;
+                read_rows(&batch, &mut rows)?;
+            }
+        }
+        Ok(report(rows, cutoff))
+    }
+}
+
+/// A chunk's section; `last_changed_at` is `None` without blame data.
+type Row = (SectionKey, Option<StaleSection>);
+type SectionKey = (String, String, String, Option<String>);
+
+fn read_rows(batch: &RecordBatch, rows: &mut Vec<Row>) -> Result<()> {
+    let strings = |name: &str| {
+        batch
+            .column_by_name(name)
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            .ok_or_else(|| PipelineError::Database(format!("Missing '{}' column", name)))
+    };
+    let repositories = strings("repository_url")?;
+    let paths = strings("relative_path")?;
+    let headings = strings("heading_path")?;
+    let refs = strings("git_ref")?;
+    let commits = strings("last_commit")?;
+    let authors = strings("last_author")?;
+    let dates = batch
+        .column_by_name("last_changed_at")
+        .and_then(|c| c.as_any().downcast_ref::<UInt64Array>())
+        .ok_or_else(|| PipelineError::Database("Missing 'last_changed_at' column".to_string()))?;
+
+    let optional = |column: &StringArray, row: usize| {
+        (!column.is_null(row)).then(|| column.value(row).to_string())
+    };
+    for row in 0..batch.num_rows() {
+        let key = (
+            repositories.value(row).to_string(),
+            paths.value(row).to_string(),
+            headings.value(row).to_string(),
+            optional(refs, row),
+        );
+        let section = (!dates.is_null(row)).then(|| StaleSection {
+            repository_url: key.0.clone(),
+            relative_path: key.1.clone(),
+            heading_path: key.2.clone(),
+            git_ref: key.3.clone(),
+            last_commit: optional(commits, row),
+            last_author: optional(authors, row),
+            last_changed_at: dates.value(row),
+        });
+        rows.push((key, section));
+    }
+    Ok(())
+}
+
+/// Group chunks into sections dated by their newest chunk, and keep those
+/// older than `cutoff`. A section with any undated chunk is undated.
+fn report(rows: Vec<Row>, cutoff: u64) -> StaleReport {
+    let mut newest: HashMap<SectionKey, Option<StaleSection>> = HashMap::new();
+    for (key, section) in rows {
+        match newest.get_mut(&key) {
+            None => {
+                newest.insert(key, section);
+            }
+            Some(current) => {
+                *current = match (current.take(), section) {
+                    (Some(a), Some(b)) if b.last_changed_at > a.last_changed_at => Some(b),
+                    (Some(a), Some(_)) => Some(a),
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    let checked = newest.len();
+    let undated = newest.values().filter(|s| s.is_none()).count();
+    let mut sections: Vec<StaleSection> = newest
+        .into_values()
+        .flatten()
+        .filter(|s| s.last_changed_at < cutoff)
+        .collect();
+    sections.sort_by(|a, b| {
+        (a.last_changed_at, &a.relative_path, &a.heading_path).cmp(&(
+            b.last_changed_at,
+            &b.relative_path,
+            &b.heading_path,
+        ))
+    });
+    StaleReport {
+        cutoff,
+        checked,
+        undated,
+        sections,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(path: &str, heading: &str, changed_at: Option<u64>) -> Row {
+        let key = (
+            "https://github.com/x/y".to_string(),
+            path.to_string(),
+            heading.to_string(),
+            None,
+        );
+        let section = changed_at.map(|at| StaleSection {
+            repository_url: key.0.clone(),
+            relative_path: key.1.clone(),
+            heading_path: key.2.clone(),
+            git_ref: None,
+            last_commit: Some(format!("commit-{at}")),
+            last_author: Some("Ada".to_string()),
+            last_changed_at: at,
+        });
+        (key, section)
+    }
+
+    #[test]
+    fn sections_are_dated_by_their_newest_chunk() {
+        let report = report(
+            vec![
+                row("a.md", "Setup", Some(100)),
+                row("a.md", "Setup", Some(900)),
+                row("a.md", "Usage", Some(300)),
+                row("b.md", "", Some(200)),
+                row("c.md", "", Some(100)),
+                row("c.md", "", None),
+            ],
+            500,
+        );
+        assert_eq!(report.checked, 4);
+        assert_eq!(report.undated, 1);
+        let stale: Vec<String> = report.sections.iter().map(|s| s.location()).collect();
+        assert_eq!(stale, vec!["b.md", "a.md # Usage"]);
+        assert!(
+            report
+                .to_string()
+                .contains("1970-01-01  b.md  Ada (commit-200)")
+        );
+    }
+}
@@ -0,0 +1,536 @@
+// file: src/database/stats.rs
+// description: index statistics report with per-repository, language and chunk size breakdowns
+// reference: https://docs.rs/lancedb/latest/lancedb/table/struct.Table.html#method.stats
+
+use crate::database::client::LanceDbClient;
+use crate::database::maintenance::{TableMaintainer, TableSnapshot};
+use crate::database::provenance::EmbeddingProvenance;
+use crate::database::schema::SchemaManager;
+use crate::error::{PipelineError, Result};
+use crate::utils::text::format_bytes;
+use arrow_array::{Array, RecordBatch, StringArray, UInt64Array};
+use futures::StreamExt;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+/// Upper bounds (exclusive, in bytes) of the chunk size histogram buckets;
+/// the last bucket is open-ended.
+const SIZE_BUCKETS: [u64; 5] = [256, 512, 1024, 2048, 4096];
+
+/// Label used for chunks without a language.
+const UNKNOWN: &str = "unknown";
+
+/// Everything `stats` reports about the documents table.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexStats {
+    pub table: String,
+    pub exists: bool,
+    pub chunks: usize,
+    pub files: usize,
+    pub table_version: u64,
+    pub fragments: usize,
+    pub disk_bytes: u64,
+    pub embedding: Option<EmbeddingProvenance>,
+    pub vector_index: Option<String>,
+    pub repositories: Vec<RepositoryStats>,
+    /// Chunks per language (`unknown` when frontmatter names none).
+    pub languages: BTreeMap<String, usize>,
+    /// Chunks per file extension.
+    pub extensions: BTreeMap<String, usize>,
+    pub chunk_sizes: ChunkSizeStats,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepositoryStats {
+    pub url: String,
+    pub chunks: usize,
+    pub files: usize,
+    /// Bytes of chunk text.
+    pub content_bytes: u64,
+    /// The table's on-disk size apportioned by content bytes; Lance does not
+    /// track storage per repository.
+    pub estimated_disk_bytes: u64,
+    /// Latest `parsed_at` of the repository's chunks (Unix seconds).
+    pub last_ingested: Option<u64>,
+    /// Scanned source files with no chunks in the table: empty, failed, or
+    /// not ingested yet. `None` when the checkout was not scanned.
+    pub files_without_chunks: Option<Vec<String>>,
+}
+
+/// Distribution of chunk text sizes in bytes.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChunkSizeStats {
+    pub min: u64,
+    pub max: u64,
+    pub mean: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub buckets: Vec<SizeBucket>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeBucket {
+    pub label: String,
+    pub chunks: usize,
+}
+
+/// Collects [`IndexStats`] in one pass over the metadata columns (vectors
+/// are never read).
+pub struct StatsCollector<'a> {
+    client: &'a LanceDbClient,
+    sources: HashMap<String, Vec<String>>,
+}
+
+impl<'a> StatsCollector<'a> {
+    pub fn new(client: &'a LanceDbClient) -> Self {
+        Self {
+            client,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Relative paths found in a repository's checkout, used to list files
+    /// that produced no chunks.
+    pub fn with_source_files(mut self, repository_url: &str, relative_paths: Vec<String>) -> Self {
+        self.sources
+            .entry(repository_url.to_string())
+            .or_default()
+            .extend(relative_paths);
+        self
+    }
+
+    pub async fn collect(&self) -> Result<IndexStats> {
+        let table_name = self.client.table_name();
+        let mut accumulator = Accumulator::default();
+
+        let exists = self.client.table_exists(table_name).await?;
+        let (snapshot, embedding, vector_index) = if exists {
+            let table = self.client.get_table(table_name).await?;
+            let mut stream = table
+                .query()
+                .select(Select::columns(&[
+                    "repository_url",
+                    "relative_path",
+                    "language",
+                    "file_size",
+                    "parsed_at",
+                ]))
+                .execute()
+                .await
+                .map_err(|e| PipelineError::Database(format!("Failed to scan documents: {}", e)))?;
+            while let Some(batch) = stream.next().await {
+                let batch = batch
+                    .map_err(|e| PipelineError::Database(format!("Failed to read batch: {}", e)))?;
+                accumulator.add_batch(&batch)?;
+            }
+
+            let schema_manager = SchemaManager::new(self.client);
+            (
+                TableMaintainer::new(self.client).snapshot().await?,
+                schema_manager.embedding_provenance().await?,
+                schema_manager
+                    .vector_index_status()
+                    .await?
+                    .map(|status| status.to_string()),
+            )
+        } else {
+            (TableSnapshot::default(), None, None)
+        };
+
+        let mut stats = accumulator.finish(snapshot, &self.sources);
+        stats.table = table_name.to_string();
+        stats.exists = exists;
+        stats.embedding = embedding;
+        stats.vector_index = vector_index;
+        Ok(stats)
+    }
+}
+
+#[derive(Default)]
+struct RepositoryAccumulator {
+    chunks: usize,
+    files: HashSet<String>,
+    content_bytes: u64,
+    last_ingested: Option<u64>,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    repositories: BTreeMap<String, RepositoryAccumulator>,
+    languages: BTreeMap<String, usize>,
+    extensions: BTreeMap<String, usize>,
+    sizes: Vec<u64>,
+}
+
+impl Accumulator {
+    fn add_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        let strings = |name: &str| {
+            batch
+                .column_by_name(name)
+                .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+                .ok_or_else(|| PipelineError::Database(format!("Missing '{}' column", name)))
+        };
+        let numbers = |name: &str| {
+            batch
+                .column_by_name(name)
+                .and_then(|c| c.as_any().downcast_ref::<UInt64Array>())
+                .ok_or_else(|| PipelineError::Database(format!("Missing '{}' column", name)))
+        };
+        let repositories = strings("repository_url")?;
+        let paths = strings("relative_path")?;
+        let languages = strings("language")?;
+        let sizes = numbers("file_size")?;
+        let parsed_ats = numbers("parsed_at")?;
+
+        for row in 0..batch.num_rows() {
+            let language = (!languages.is_null(row)).then(|| languages.value(row));
+            self.add(
+                repositories.value(row),
+                paths.value(row),
+                language,
+                sizes.value(row),
+                parsed_ats.value(row),
+            );
+        }
+        Ok(())
+    }
+
+    fn add(
+        &mut self,
+        repository_url: &str,
+        relative_path: &str,
+        language: Option<&str>,
+        size: u64,
+        parsed_at: u64,
+    ) {
+        let repository = self
+            .repositories
+            .entry(repository_url.to_string())
+            .or_default();
+        repository.chunks += 1;
+        repository.content_bytes += size;
+        repository.last_ingested = repository.last_ingested.max(Some(parsed_at));
+        if !repository.files.contains(relative_path) {
+            repository.files.insert(relative_path.to_string());
+        }
+
+        let language = language.filter(|l| !l.is_empty()).unwrap_or(UNKNOWN);
+        *self.languages.entry(language.to_string()).or_default() += 1;
+        *self.extensions.entry(extension(relative_path)).or_default() += 1;
+        self.sizes.push(size);
+    }
+
+    fn finish(
+        mut self,
+        snapshot: TableSnapshot,
+        sources: &HashMap<String, Vec<String>>,
+    ) -> IndexStats {
+        let total_content: u64 = self.repositories.values().map(|r| r.content_bytes).sum();
+
+        let mut urls: Vec<&String> = self.repositories.keys().collect();
+        urls.extend(
+            sources
+                .keys()
+                .filter(|url| !self.repositories.contains_key(*url)),
+        );
+        urls.sort();
+
+        let repositories = urls
+            .into_iter()
+            .map(|url| {
+                let empty = RepositoryAccumulator::default();
+                let repository = self.repositories.get(url).unwrap_or(&empty);
+                let estimated_disk_bytes = if total_content == 0 {
+                    0
+                } else {
+                    (snapshot.total_bytes as u128 * repository.content_bytes as u128
+                        / total_content as u128) as u64
+                };
+                let files_without_chunks = sources.get(url).map(|paths| {
+                    let mut missing: Vec<String> = paths
+                        .iter()
+                        .filter(|p| !repository.files.contains(*p))
+                        .cloned()
+                        .collect();
+                    missing.sort();
+                    missing.dedup();
+                    missing
+                });
+
+                RepositoryStats {
+                    url: url.clone(),
+                    chunks: repository.chunks,
+                    files: repository.files.len(),
+                    content_bytes: repository.content_bytes,
+                    estimated_disk_bytes,
+                    last_ingested: repository.last_ingested,
+                    files_without_chunks,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.sizes.sort_unstable();
+        IndexStats {
+            chunks: self.sizes.len(),
+            files: repositories.iter().map(|r| r.files).sum(),
+            table_version: snapshot.version,
+            fragments: snapshot.fragments,
+            disk_bytes: snapshot.total_bytes,
+            repositories,
+            languages: self.languages,
+            extensions: self.extensions,
+            chunk_sizes: ChunkSizeStats::from_sorted(&self.sizes),
+            ..Default::default()
+        }
+    }
+}
+
+impl ChunkSizeStats {
+    fn from_sorted(sizes: &[u64]) -> Self {
+        let percentile = |p: usize| {
+            if sizes.is_empty() {
+                0
+            } else {
+                sizes[((sizes.len() - 1) * p) / 100]
+            }
+        };
+
+        let mut buckets: Vec<SizeBucket> = SIZE_BUCKETS
+            .iter()
+            .map(|bound| SizeBucket {
+                label: format!("< {}", format_bytes(*bound)),
+                chunks: 0,
+            })
+            .collect();
+        buckets.push(SizeBucket {
+            label: format!(">= {}", format_bytes(SIZE_BUCKETS[SIZE_BUCKETS.len() - 1])),
+            chunks: 0,
+        });
+        for size in sizes {
+            let bucket = SIZE_BUCKETS
+                .iter()
+                .position(|bound| size < bound)
+                .unwrap_or(SIZE_BUCKETS.len());
+            buckets[bucket].chunks += 1;
+        }
+
+        Self {
+            min: sizes.first().copied().unwrap_or(0),
+            max: sizes.last().copied().unwrap_or(0),
+            mean: if sizes.is_empty() {
+                0
+            } else {
+                sizes.iter().sum::<u64>() / sizes.len() as u64
+            },
+            p50: percentile(50),
+            p90: percentile(90),
+            buckets,
+        }
+    }
+}
+
+fn extension(relative_path: &str) -> String {
+    Path::new(relative_path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+fn format_time(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// Write rows as left-aligned columns under `headers`, indented by two spaces.
+fn write_table(f: &mut fmt::Formatter<'_>, headers: &[&str], rows: &[Vec<String>]) -> fmt::Result {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+            .collect();
+        format!("  {}", padded.join("  ").trim_end())
+    };
+    writeln!(f, "{}", line(headers.to_vec()))?;
+    for row in rows {
+        writeln!(f, "{}", line(row.iter().map(String::as_str).collect()))?;
+    }
+    Ok(())
+}
+
+fn count_rows(counts: &BTreeMap<String, usize>) -> Vec<Vec<String>> {
+    let mut rows: Vec<(&String, &usize)> = counts.iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    rows.into_iter()
+        .map(|(name, count)| vec![name.clone(), count.to_string()])
+        .collect()
+}
+
+impl fmt::Display for IndexStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.exists {
+            return writeln!(
+                f,
+                "Table '{}' does not exist yet; run ingest first",
+                self.table
+            );
+        }
+
+        writeln!(
+            f,
+            "Table: {} (version {}, {} fragment(s), {} on disk)",
+            self.table,
+            self.table_version,
+            self.fragments,
+            format_bytes(self.disk_bytes)
+        )?;
+        writeln!(f, "Chunks: {} from {} file(s)", self.chunks, self.files)?;
+        match &self.embedding {
+            Some(embedding) => writeln!(f, "Embedding model: {}", embedding)?,
+            None => writeln!(f, "Embedding model: not recorded")?,
+        }
+        writeln!(
+            f,
+            "Vector index: {}",
+            self.vector_index.as_deref().unwrap_or("none")
+        )?;
+
+        writeln!(f, "\nRepositories")?;
+        let rows: Vec<Vec<String>> = self
+            .repositories
+            .iter()
+            .map(|r| {
+                vec![
+                    r.url.clone(),
+                    r.files.to_string(),
+                    r.chunks.to_string(),
+                    format!("~{}", format_bytes(r.estimated_disk_bytes)),
+                    r.last_ingested.map(format_time).unwrap_or_default(),
+                ]
+            })
+            .collect();
+        write_table(
+            f,
+            &["REPOSITORY", "FILES", "CHUNKS", "DISK", "LAST INGESTED"],
+            &rows,
+        )?;
+
+        writeln!(f, "\nLanguages")?;
+        write_table(f, &["LANGUAGE", "CHUNKS"], &count_rows(&self.languages))?;
+
+        writeln!(f, "\nExtensions")?;
+        write_table(f, &["EXTENSION", "CHUNKS"], &count_rows(&self.extensions))?;
+
+        let sizes = &self.chunk_sizes;
+        writeln!(
+            f,
+            "\nChunk sizes: min {}, p50 {}, p90 {}, max {}, mean {}",
+            format_bytes(sizes.min),
+            format_bytes(sizes.p50),
+            format_bytes(sizes.p90),
+            format_bytes(sizes.max),
+            format_bytes(sizes.mean)
+        )?;
+        let rows: Vec<Vec<String>> = sizes
+            .buckets
+            .iter()
+            .map(|b| vec![b.label.clone(), b.chunks.to_string()])
+            .collect();
+        write_table(f, &["SIZE", "CHUNKS"], &rows)?;
+
+        for repository in &self.repositories {
+            if let Some(missing) = &repository.files_without_chunks
+                && !missing.is_empty()
+            {
+                writeln!(
+                    f,
+                    "\nFiles without chunks in {} ({}; empty, failed or not yet ingested)",
+                    repository.url,
+                    missing.len()
+                )?;
+                for path in missing {
+                    writeln!(f, "  {}", path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_broken_down_by_repository_language_and_size() {
+        let mut accumulator = Accumulator::default();
+        accumulator.add("repo-a", "docs/a.md", Some("en"), 100, 10);
+        accumulator.add("repo-a", "docs/a.md", Some("en"), 300, 20);
+        accumulator.add("repo-a", "README.MD", None, 5000, 15);
+        accumulator.add("repo-b", "guide.markdown", Some("de"), 700, 5);
+
+        let sources = HashMap::from([(
+            "repo-a".to_string(),
+            vec![
+                "docs/a.md".to_string(),
+                "docs/empty.md".to_string(),
+                "README.MD".to_string(),
+            ],
+        )]);
+        let snapshot = TableSnapshot {
+            version: 7,
+            fragments: 2,
+            total_bytes: 6100,
+            ..Default::default()
+        };
+        let stats = accumulator.finish(snapshot, &sources);
+
+        assert_eq!((stats.chunks, stats.files), (4, 3));
+        let repo_a = &stats.repositories[0];
+        assert_eq!((repo_a.chunks, repo_a.files), (3, 2));
+        assert_eq!(repo_a.estimated_disk_bytes, 5400);
+        assert_eq!(repo_a.last_ingested, Some(20));
+        assert_eq!(
+            repo_a.files_without_chunks,
+            Some(vec!["docs/empty.md".to_string()])
+        );
+        assert_eq!(stats.repositories[1].files_without_chunks, None);
+
+        assert_eq!(stats.languages.get("en"), Some(&2));
+        assert_eq!(stats.languages.get(UNKNOWN), Some(&1));
+        assert_eq!(stats.extensions.get("md"), Some(&3));
+
+        let sizes = &stats.chunk_sizes;
+        assert_eq!((sizes.min, sizes.p50, sizes.max), (100, 300, 5000));
+        let counts: Vec<usize> = sizes.buckets.iter().map(|b| b.chunks).collect();
+        assert_eq!(counts, vec![1, 1, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn report_renders_as_text_and_json() {
+        let mut accumulator = Accumulator::default();
+        accumulator.add("repo-a", "a.md", None, 10, 0);
+        let mut stats = accumulator.finish(TableSnapshot::default(), &HashMap::new());
+        stats.table = "documents".to_string();
+        stats.exists = true;
+
+        let text = stats.to_string();
+        assert!(text.contains("Chunks: 1 from 1 file(s)"));
+        assert!(text.contains("REPOSITORY"));
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["repositories"][0]["url"], "repo-a");
+        assert_eq!(json["languages"]["unknown"], 1);
+    }
+}
@@ -0,0 +1,268 @@
+// file: src/database/writer.rs
+// description: single-writer batched upserts of prepared documents into LanceDB
+// reference: https://docs.rs/lancedb/latest/lancedb/table/struct.MergeInsertBuilder.html
+
+use crate::database::client::{LanceDbClient, escape_sql_literal};
+use crate::database::insert::{BatchInserter, PreparedFile};
+use crate::database::runs::RunRecorder;
+use crate::database::schema::SchemaManager;
+use crate::error::{PipelineError, Result};
+use crate::models::Document;
+use arrow_array::RecordBatchIterator;
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+
+/// Source file whose chunks are part of the pending batch. After the upsert,
+/// any of its rows for the same ref with `chunk_index >= chunks` are stale
+/// (the file shrank), as are rows written before refs were recorded.
+#[derive(Debug, Clone)]
+struct PendingFile {
+    repository_url: String,
+    relative_path: String,
+    git_ref: Option<String>,
+    chunks: usize,
+    degraded: bool,
+}
+
+/// Totals for everything a writer has flushed.
+#[derive(Debug, Clone, Default)]
+pub struct WriteStats {
+    pub rows_written: usize,
+    pub files_written: usize,
+    pub batches: usize,
+    /// Files written with non-semantic fallback vectors.
+    pub degraded_files: usize,
+    /// `(relative_path, error)` for files whose batch failed to write.
+    pub failed_files: Vec<(String, String)>,
+}
+
+/// Accumulates prepared files until `batch_size` rows are pending, then
+/// writes them as one `RecordBatch` with a merge-insert on `id` (chunk ids are
+/// stable, so unchanged chunks are updated in place rather than duplicated)
+/// followed by a single delete of every stale chunk in the batch.
+///
+/// Exactly one writer should exist per table during ingestion; workers only
+/// read, chunk and embed.
+pub struct DocumentWriter<'a> {
+    client: &'a LanceDbClient,
+    dimension: usize,
+    batch_size: usize,
+    documents: Vec<Document>,
+    embeddings: Vec<Vec<f32>>,
+    files: Vec<PendingFile>,
+    stats: WriteStats,
+    run: Option<Arc<RunRecorder>>,
+}
+
+impl<'a> DocumentWriter<'a> {
+    pub fn new(client: &'a LanceDbClient, dimension: usize) -> Self {
+        Self {
+            client,
+            dimension,
+            batch_size: client.batch_size().max(1),
+            documents: Vec::new(),
+            embeddings: Vec::new(),
+            files: Vec::new(),
+            stats: WriteStats::default(),
+            run: None,
+        }
+    }
+
+    /// Checkpoint the run after every committed batch, so an interrupted
+    /// ingest can resume after the last one.
+    pub fn with_run_recorder(mut self, run: Arc<RunRecorder>) -> Self {
+        self.run = Some(run);
+        self
+    }
+
+    /// Queue a file, flushing once `batch_size` rows are pending. A failed
+    /// flush records its files in [`WriteStats::failed_files`] and returns the
+    /// error; the writer stays usable.
+    pub async fn push(&mut self, file: PreparedFile) -> Result<()> {
+        self.files.push(PendingFile {
+            repository_url: file.repository_url,
+            relative_path: file.relative_path,
+            git_ref: file.git_ref,
+            chunks: file.documents.len(),
+            degraded: file.degraded,
+        });
+        self.documents.extend(file.documents);
+        self.embeddings.extend(file.embeddings);
+
+        if self.documents.len() >= self.batch_size {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Write everything still pending and return the totals.
+    pub async fn finish(mut self) -> Result<WriteStats> {
+        self.flush().await?;
+        Ok(self.stats)
+    }
+
+    pub fn stats(&self) -> &WriteStats {
+        &self.stats
+    }
+
+    pub fn into_stats(self) -> WriteStats {
+        self.stats
+    }
+
+    /// Write all pending rows now, regardless of `batch_size`.
+    pub async fn flush(&mut self) -> Result<()> {
+        if self.files.is_empty() {
+            return Ok(());
+        }
+
+        let documents = std::mem::take(&mut self.documents);
+        let embeddings = std::mem::take(&mut self.embeddings);
+        let files = std::mem::take(&mut self.files);
+
+        match self.write(&documents, &embeddings, &files).await {
+            Ok(()) => {
+                self.stats.rows_written += documents.len();
+                self.stats.files_written += files.len();
+                self.stats.batches += 1;
+                self.stats.degraded_files += files.iter().filter(|f| f.degraded).count();
+                debug!(
+                    "Flushed {} row(s) from {} file(s)",
+                    documents.len(),
+                    files.len()
+                );
+                if let Some(run) = &self.run
+                    && let Err(e) = run
+                        .commit(files.iter().map(|f| f.relative_path.as_str()))
+                        .await
+                {
+                    warn!("Failed to checkpoint run: {}", e);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to write batch of {} file(s): {}", files.len(), e);
+                self.stats
+                    .failed_files
+                    .extend(files.into_iter().map(|f| (f.relative_path, e.to_string())));
+                Err(e)
+            }
+        }
+    }
+
+    async fn write(
+        &self,
+        documents: &[Document],
+        embeddings: &[Vec<f32>],
+        files: &[PendingFile],
+    ) -> Result<()> {
+        let table_name = self.client.table_name();
+        let table_exists = self.client.table_exists(table_name).await?;
+
+        if !documents.is_empty() {
+            let schema = SchemaManager::get_documents_schema(self.dimension);
+            let batch = BatchInserter::create_record_batch(schema.clone(), documents, embeddings)?;
+
+            if !table_exists {
+                self.client
+                    .get_connection()
+                    .create_table(table_name, vec![batch])
+                    .execute()
+                    .await
+                    .map_err(|e| {
+                        PipelineError::Database(format!("Failed to create table: {}", e))
+                    })?;
+                info!("Created new table: {}", table_name);
+                // Nothing can be stale in a table that did not exist.
+                return Ok(());
+            }
+
+            let table = self.client.get_table(table_name).await?;
+            let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+            let mut merge = table.merge_insert(&["id"]);
+            merge
+                .when_matched_update_all(None)
+                .when_not_matched_insert_all();
+            merge
+                .execute(Box::new(reader))
+                .await
+                .map_err(|e| PipelineError::Database(format!("Failed to upsert chunks: {}", e)))?;
+        } else if !table_exists {
+            return Ok(());
+        }
+
+        if let Some(predicate) = stale_chunks_predicate(files) {
+            let table = self.client.get_table(table_name).await?;
+            table.delete(&predicate).await.map_err(|e| {
+                PipelineError::Database(format!("Failed to delete stale chunks: {}", e))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// One predicate matching every chunk of `files` beyond its new chunk count
+/// at the same ref. Rows of other refs are kept; rows without a ref are
+/// superseded by a versioned write.
+fn stale_chunks_predicate(files: &[PendingFile]) -> Option<String> {
+    if files.is_empty() {
+        return None;
+    }
+    Some(
+        files
+            .iter()
+            .map(|f| {
+                let stale = match &f.git_ref {
+                    Some(git_ref) => format!(
+                        "((git_ref = '{}' AND chunk_index >= {}) OR git_ref IS NULL)",
+                        escape_sql_literal(git_ref),
+                        f.chunks
+                    ),
+                    None => format!("git_ref IS NULL AND chunk_index >= {}", f.chunks),
+                };
+                format!(
+                    "(repository_url = '{}' AND relative_path = '{}' AND {})",
+                    escape_sql_literal(&f.repository_url),
+                    escape_sql_literal(&f.relative_path),
+                    stale
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" OR "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_predicate_covers_every_file() {
+        let predicate = stale_chunks_predicate(&[
+            PendingFile {
+                repository_url: "https://github.com/x/y".to_string(),
+                relative_path: "docs/it's.md".to_string(),
+                git_ref: None,
+                chunks: 3,
+                degraded: false,
+            },
+            PendingFile {
+                repository_url: "https://github.com/x/y".to_string(),
+                relative_path: "empty.md".to_string(),
+                git_ref: Some("v1.4".to_string()),
+                chunks: 0,
+                degraded: false,
+            },
+        ])
+        .unwrap();
+
+        assert_eq!(
+            predicate,
+            "(repository_url = 'https://github.com/x/y' AND relative_path = 'docs/it''s.md' \
+             AND git_ref IS NULL AND chunk_index >= 3) OR (repository_url = \
+             'https://github.com/x/y' AND relative_path = 'empty.md' AND \
+             ((git_ref = 'v1.4' AND chunk_index >= 0) OR git_ref IS NULL))"
+        );
+        assert!(stale_chunks_predicate(&[]).is_none());
+    }
+}
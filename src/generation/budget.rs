@@ -0,0 +1,288 @@
+// file: src/generation/budget.rs
+// description: token counting and token-budgeted context packing for generation
+// reference: https://github.com/openai/tiktoken
+
+use crate::config::TokenizerKind;
+use crate::models::SearchResult;
+use crate::utils::text::significant_terms;
+use std::collections::HashSet;
+use tiktoken_rs::CoreBPE;
+
+/// Chunks are only trimmed to fit when at least this many tokens remain;
+/// smaller slivers carry too little context to be worth a citation slot.
+const MIN_TRIMMED_TOKENS: usize = 64;
+
+/// Marker placed where a trimmed chunk was cut.
+const ELLIPSIS: &str = "…";
+
+/// Counts tokens with the configured tokenizer.
+pub struct TokenCounter {
+    bpe: Option<&'static CoreBPE>,
+}
+
+impl TokenCounter {
+    pub fn new(kind: TokenizerKind) -> Self {
+        let bpe = match kind {
+            TokenizerKind::O200kBase => Some(tiktoken_rs::o200k_base_singleton()),
+            TokenizerKind::Cl100kBase => Some(tiktoken_rs::cl100k_base_singleton()),
+            TokenizerKind::Approximate => None,
+        };
+        Self { bpe }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self.bpe {
+            Some(bpe) => bpe.encode_ordinary(text).len(),
+            None => text.chars().count().div_ceil(4),
+        }
+    }
+}
+
+/// The sources chosen for a prompt, possibly with trimmed content, plus the
+/// tokens they occupy.
+#[derive(Debug, Clone)]
+pub struct PackedContext {
+    pub sources: Vec<SearchResult>,
+    pub tokens: usize,
+}
+
+/// Greedily pack `results` (best first) into `budget` tokens, taking at most
+/// `max_chunks`. `entry_tokens` measures a source as it will appear in the
+/// prompt, given its 1-based number. The first source that does not fit is
+/// trimmed around its best-matching lines if enough budget remains, after
+/// which packing stops. The packed sources never exceed `budget`.
+pub fn pack_context(
+    query: &str,
+    results: Vec<SearchResult>,
+    counter: &TokenCounter,
+    budget: usize,
+    max_chunks: usize,
+    entry_tokens: impl Fn(usize, &SearchResult) -> usize,
+) -> PackedContext {
+    let query_terms = significant_terms(query);
+    let mut sources = Vec::new();
+    let mut used = 0;
+
+    for mut result in results.into_iter().take(max_chunks) {
+        let number = sources.len() + 1;
+        let cost = entry_tokens(number, &result);
+        if used + cost <= budget {
+            used += cost;
+            sources.push(result);
+            continue;
+        }
+
+        let remaining = budget - used;
+        if remaining >= MIN_TRIMMED_TOKENS {
+            // Scaffolding (number, location) is what the entry costs beyond its content.
+            let scaffolding = cost.saturating_sub(counter.count(&result.content));
+            let mut content_budget = remaining.saturating_sub(scaffolding);
+            let content = std::mem::take(&mut result.content);
+            while let Some(trimmed) =
+                trim_to_budget(&content, &query_terms, counter, content_budget)
+            {
+                result.content = trimmed;
+                let cost = entry_tokens(number, &result);
+                if cost <= remaining {
+                    used += cost;
+                    sources.push(result);
+                    break;
+                }
+                // Token counts are not additive: shrink by the overshoot and retry.
+                content_budget = content_budget.saturating_sub(cost - remaining);
+            }
+        }
+        break;
+    }
+
+    PackedContext {
+        sources,
+        tokens: used,
+    }
+}
+
+/// Cut `content` down to `budget` tokens, keeping the contiguous run of lines
+/// around the line with the most query-term hits. Returns `None` if nothing
+/// useful fits.
+fn trim_to_budget(
+    content: &str,
+    query_terms: &HashSet<String>,
+    counter: &TokenCounter,
+    budget: usize,
+) -> Option<String> {
+    if budget == 0 {
+        return None;
+    }
+
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.is_empty() {
+        return None;
+    }
+
+    let best = lines
+        .iter()
+        .enumerate()
+        .max_by_key(|(i, line)| {
+            let hits = significant_terms(line).intersection(query_terms).count();
+            // Prefer earlier lines on ties.
+            (hits, std::cmp::Reverse(*i))
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+
+    let render = |lo: usize, hi: usize| {
+        let mut text = lines[lo..=hi].join("\n");
+        if lo > 0 {
+            text = format!("{ELLIPSIS}\n{text}");
+        }
+        if hi + 1 < lines.len() {
+            text.push('\n');
+            text.push_str(ELLIPSIS);
+        }
+        text
+    };
+
+    let (mut lo, mut hi) = (best, best);
+    let mut text = render(lo, hi);
+    if counter.count(&text) > budget {
+        return truncate_line(lines[best], counter, budget);
+    }
+
+    // Grow the window downwards then upwards, one line at a time, while it fits.
+    loop {
+        let mut grew = false;
+        if hi + 1 < lines.len() {
+            let candidate = render(lo, hi + 1);
+            if counter.count(&candidate) <= budget {
+                hi += 1;
+                text = candidate;
+                grew = true;
+            }
+        }
+        if lo > 0 {
+            let candidate = render(lo - 1, hi);
+            if counter.count(&candidate) <= budget {
+                lo -= 1;
+                text = candidate;
+                grew = true;
+            }
+        }
+        if !grew {
+            break;
+        }
+    }
+
+    Some(text)
+}
+
+/// Keep the longest char prefix of a single oversized line that fits `budget`.
+fn truncate_line(line: &str, counter: &TokenCounter, budget: usize) -> Option<String> {
+    let chars: Vec<char> = line.chars().collect();
+    let (mut lo, mut hi) = (0, chars.len());
+    while lo < hi {
+        let mid = (lo + hi).div_ceil(2);
+        let candidate: String = chars[..mid].iter().collect::<String>() + ELLIPSIS;
+        if counter.count(&candidate) <= budget {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+
+    if lo == 0 {
+        None
+    } else {
+        Some(chars[..lo].iter().collect::<String>() + ELLIPSIS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx() -> TokenCounter {
+        TokenCounter::new(TokenizerKind::Approximate)
+    }
+
+    #[test]
+    fn bpe_counter_counts_tokens() {
+        let counter = TokenCounter::new(TokenizerKind::O200kBase);
+        let n = counter.count("hello world, this is a test");
+        assert!(n > 3 && n < 12, "unexpected token count {n}");
+    }
+
+    #[test]
+    fn packs_until_budget_and_respects_max_chunks() {
+        let counter = approx();
+        let results = vec![
+            SearchResult::fixture("a").with_content(&"a".repeat(40)),
+            SearchResult::fixture("b").with_content(&"b".repeat(40)),
+        ];
+        let packed = pack_context("q", results.clone(), &counter, 1000, 1, |_, r| {
+            counter.count(&r.content)
+        });
+        assert_eq!(packed.sources.len(), 1);
+
+        let packed = pack_context("q", results, &counter, 15, 8, |_, r| {
+            counter.count(&r.content)
+        });
+        assert_eq!(packed.sources.len(), 1);
+        assert_eq!(packed.tokens, 10);
+    }
+
+    #[test]
+    fn oversized_chunk_is_trimmed_around_best_match() {
+        let counter = approx();
+        let mut lines: Vec<String> = (0..200)
+            .map(|i| format!("filler line number {i}"))
+            .collect();
+        lines[120] = "configure the retry backoff here".to_string();
+        let packed = pack_context(
+            "retry backoff",
+            vec![SearchResult::fixture("a").with_content(&lines.join("\n"))],
+            &counter,
+            80,
+            8,
+            |_, r| counter.count(&r.content),
+        );
+
+        assert_eq!(packed.sources.len(), 1);
+        let content = &packed.sources[0].content;
+        assert!(content.contains("retry backoff"));
+        assert!(content.starts_with(ELLIPSIS));
+        assert!(counter.count(content) <= 80);
+    }
+
+    #[test]
+    fn trimmed_last_chunk_that_overshoots_is_trimmed_again() {
+        let counter = approx();
+        let lines: Vec<String> = (0..200)
+            .map(|i| format!("filler line number {i}"))
+            .collect();
+        // Trimmed entries cost more than their untrimmed scaffolding suggests.
+        let entry_tokens = |_: usize, r: &SearchResult| {
+            counter.count(&r.content) + if r.content.contains(ELLIPSIS) { 30 } else { 0 }
+        };
+        let packed = pack_context(
+            "q",
+            vec![
+                SearchResult::fixture("a").with_content(&"a".repeat(40)),
+                SearchResult::fixture("b").with_content(&lines.join("\n")),
+            ],
+            &counter,
+            120,
+            8,
+            entry_tokens,
+        );
+
+        assert_eq!(packed.sources.len(), 2);
+        let total: usize = packed
+            .sources
+            .iter()
+            .enumerate()
+            .map(|(i, r)| entry_tokens(i + 1, r))
+            .sum();
+        assert_eq!(packed.tokens, total);
+        assert!(packed.tokens <= 120);
+    }
+}
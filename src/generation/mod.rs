@@ -1,13 +1,18 @@
 // file: src/generation/mod.rs
-// description: RAG answer synthesis over retrieved chunks via an OpenAI-compatible chat API
+// description: RAG answer synthesis over retrieved chunks via OpenAI-compatible, Ollama or llama.cpp chat APIs
 // reference: https://console.groq.com/docs/api-reference#chat-create
 
-use crate::config::GenerationConfig;
+pub mod budget;
+
+pub use budget::{PackedContext, TokenCounter, pack_context};
+
+use crate::config::{GenerationBackend, GenerationConfig};
 use crate::error::{PipelineError, Result};
 use crate::models::SearchResult;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use std::time::Duration;
+use tracing::{debug, info};
 
 const SYSTEM_PROMPT: &str = "You are a precise documentation assistant for software repositories. \
 Answer the user's question using ONLY the provided context excerpts. \
@@ -20,13 +25,33 @@ Be concise and technical.";
 pub struct Answer {
     pub text: String,
     pub sources: Vec<SearchResult>,
+    /// Tokens the packed context excerpts occupied, by the configured tokenizer.
+    pub context_tokens: usize,
+    /// Token usage as reported by the provider, when it reports it.
+    pub usage: Option<TokenUsage>,
+}
+
+/// Provider-reported token usage for one completion.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
 }
 
 #[derive(Debug, Serialize)]
-struct ChatRequest {
-    model: String,
+struct ChatRequest<'a> {
+    model: &'a str,
     messages: Vec<ChatMessage>,
     temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<usize>,
+    /// llama.cpp only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_prompt: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,6 +63,8 @@ struct ChatMessage {
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<ChatChoice>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,17 +72,62 @@ struct ChatChoice {
     message: ChatMessage,
 }
 
+#[derive(Debug, Serialize)]
+struct OllamaChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    options: OllamaOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    temperature: f32,
+    num_ctx: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaChatResponse {
+    message: ChatMessage,
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
+}
+
+impl OllamaChatResponse {
+    fn usage(&self) -> Option<TokenUsage> {
+        let (prompt, completion) = match (self.prompt_eval_count, self.eval_count) {
+            (None, None) => return None,
+            (p, c) => (p.unwrap_or(0), c.unwrap_or(0)),
+        };
+        Some(TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+        })
+    }
+}
+
 pub struct AnswerGenerator {
     client: Client,
     config: GenerationConfig,
 }
 
 impl AnswerGenerator {
-    pub fn new(config: GenerationConfig) -> Self {
-        Self {
-            client: Client::new(),
-            config,
+    pub fn new(config: GenerationConfig) -> Result<Self> {
+        let mut builder = Client::builder();
+        if let Some(secs) = config.options.timeout_secs {
+            builder = builder.timeout(Duration::from_secs(secs));
         }
+        let client = builder.build().map_err(|e| {
+            PipelineError::Config(format!("Failed to build generation HTTP client: {}", e))
+        })?;
+        Ok(Self { client, config })
     }
 
     pub fn max_context_chunks(&self) -> usize {
@@ -63,71 +135,159 @@ impl AnswerGenerator {
     }
 
     fn endpoint(&self) -> String {
-        format!(
-            "{}/chat/completions",
-            self.config.base_url.trim_end_matches('/')
-        )
+        let path = match self.config.backend {
+            GenerationBackend::OpenAi | GenerationBackend::LlamaCpp => "chat/completions",
+            GenerationBackend::Ollama => "api/chat",
+        };
+        format!("{}/{}", self.config.base_url.trim_end_matches('/'), path)
     }
 
-    /// Synthesize an answer to `query` grounded in `results`. The top
-    /// `max_context_chunks` results are passed as numbered context.
+    /// Synthesize an answer to `query` grounded in `results`. Results are
+    /// packed best-first into the token budget, up to `max_context_chunks`.
     pub async fn answer(&self, query: &str, results: Vec<SearchResult>) -> Result<Answer> {
-        let api_key = self.config.api_key.as_ref().ok_or_else(|| {
-            PipelineError::Config(
-                "No generation API key configured (set generation.api_key, GENERATION_API_KEY, or GROQ_API_KEY)".to_string(),
-            )
-        })?;
-
         if results.is_empty() {
             return Ok(Answer {
                 text: "No relevant context was found in the index for this question.".to_string(),
                 sources: results,
+                context_tokens: 0,
+                usage: None,
             });
         }
 
-        let sources: Vec<SearchResult> = results
-            .into_iter()
-            .take(self.config.max_context_chunks)
-            .collect();
+        let counter = TokenCounter::new(self.config.tokenizer);
+        let overhead = counter.count(SYSTEM_PROMPT) + counter.count(&user_prompt(query, ""));
+        let budget = self
+            .config
+            .max_context_tokens
+            .saturating_sub(self.config.reserved_answer_tokens)
+            .saturating_sub(overhead);
 
-        let context = build_context(&sources);
-        let user_content = format!(
-            "Context excerpts:\n\n{context}\n\n---\n\nQuestion: {query}\n\n\
-             Answer using only the excerpts above and cite them with [n]."
+        let packed = pack_context(
+            query,
+            results,
+            &counter,
+            budget,
+            self.config.max_context_chunks,
+            // +2 for the blank line separating entries
+            |number, result| counter.count(&format_entry(number, result)) + 2,
         );
 
-        let request = ChatRequest {
-            model: self.config.model.clone(),
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: SYSTEM_PROMPT.to_string(),
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: user_content,
-                },
-            ],
-            temperature: 0.2,
-        };
+        if packed.sources.is_empty() {
+            return Err(PipelineError::Config(format!(
+                "No context fits in the generation token budget ({budget} tokens); \
+                 raise generation.max_context_tokens or lower reserved_answer_tokens"
+            )));
+        }
+
+        let sources = packed.sources;
+        let context = build_context(&sources);
+        let user_content = user_prompt(query, &context);
 
         debug!(
-            "Requesting answer from {} (model {}, {} sources)",
+            "Requesting answer from {} (model {}, {} sources, ~{} context tokens of {})",
             self.endpoint(),
             self.config.model,
-            sources.len()
+            sources.len(),
+            packed.tokens,
+            budget
         );
 
-        let response = self
-            .client
-            .post(self.endpoint())
-            .header("Authorization", format!("Bearer {}", api_key))
+        let (text, usage) = self
+            .chat(
+                SYSTEM_PROMPT,
+                &user_content,
+                0.2,
+                Some(self.config.reserved_answer_tokens),
+            )
+            .await?;
+
+        if let Some(usage) = usage {
+            info!(
+                "Generation used {} prompt + {} completion tokens",
+                usage.prompt_tokens, usage.completion_tokens
+            );
+        }
+
+        Ok(Answer {
+            text,
+            sources,
+            context_tokens: packed.tokens,
+            usage,
+        })
+    }
+
+    /// Run a single system + user chat completion and return the first choice's
+    /// content. Shared by answer synthesis and retrieval-time query rewriting.
+    pub async fn complete(&self, system: &str, user: &str, temperature: f32) -> Result<String> {
+        self.chat(system, user, temperature, None)
+            .await
+            .map(|(text, _)| text)
+    }
+
+    async fn chat(
+        &self,
+        system: &str,
+        user: &str,
+        temperature: f32,
+        max_tokens: Option<usize>,
+    ) -> Result<(String, Option<TokenUsage>)> {
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: system.to_string(),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: user.to_string(),
+            },
+        ];
+
+        let options = &self.config.options;
+        let builder = match self.config.backend {
+            GenerationBackend::OpenAi | GenerationBackend::LlamaCpp => {
+                let cache_prompt = match self.config.backend {
+                    GenerationBackend::LlamaCpp => options.cache_prompt,
+                    _ => None,
+                };
+                self.client.post(self.endpoint()).json(&ChatRequest {
+                    model: &self.config.model,
+                    messages,
+                    temperature,
+                    max_tokens,
+                    cache_prompt,
+                })
+            }
+            GenerationBackend::Ollama => {
+                self.client.post(self.endpoint()).json(&OllamaChatRequest {
+                    model: &self.config.model,
+                    messages,
+                    stream: false,
+                    options: OllamaOptions {
+                        temperature,
+                        num_ctx: options.num_ctx.unwrap_or(self.config.max_context_tokens),
+                        num_predict: max_tokens,
+                    },
+                    keep_alive: options.keep_alive.as_deref(),
+                })
+            }
+        };
+
+        // Local servers usually run without auth, so the key is optional.
+        let builder = match &self.config.api_key {
+            Some(api_key) => builder.header("Authorization", format!("Bearer {}", api_key)),
+            None => builder,
+        };
+
+        let response = builder
             .header("Content-Type", "application/json")
-            .json(&request)
             .send()
             .await
             .map_err(|e| {
-                PipelineError::Database(format!("Failed to send generation request: {}", e))
+                PipelineError::Database(format!(
+                    "Failed to send generation request to {}: {}",
+                    self.endpoint(),
+                    e
+                ))
             })?;
 
         if !response.status().is_success() {
@@ -136,36 +296,73 @@ impl AnswerGenerator {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
+            let hint = if status == reqwest::StatusCode::UNAUTHORIZED
+                && self.config.api_key.is_none()
+            {
+                " (no API key configured; set generation.api_key, GENERATION_API_KEY, or GROQ_API_KEY)"
+            } else {
+                ""
+            };
             return Err(PipelineError::Database(format!(
-                "Generation request failed with status {}: {}",
-                status, error_text
+                "Generation request failed with status {}: {}{}",
+                status, error_text, hint
             )));
         }
 
-        let parsed: ChatResponse = response.json().await.map_err(|e| {
-            PipelineError::Database(format!("Failed to parse generation response: {}", e))
-        })?;
+        match self.config.backend {
+            GenerationBackend::OpenAi | GenerationBackend::LlamaCpp => {
+                let parsed: ChatResponse = response.json().await.map_err(|e| {
+                    PipelineError::Database(format!("Failed to parse generation response: {}", e))
+                })?;
 
-        let text = parsed
-            .choices
-            .into_iter()
-            .next()
-            .map(|c| c.message.content)
-            .ok_or_else(|| {
-                PipelineError::Database("Generation API returned no choices".to_string())
-            })?;
+                let usage = parsed.usage;
+                let text = parsed
+                    .choices
+                    .into_iter()
+                    .next()
+                    .map(|c| c.message.content)
+                    .ok_or_else(|| {
+                        PipelineError::Database("Generation API returned no choices".to_string())
+                    })?;
 
-        Ok(Answer { text, sources })
+                Ok((text, usage))
+            }
+            GenerationBackend::Ollama => {
+                let parsed: OllamaChatResponse = response.json().await.map_err(|e| {
+                    PipelineError::Database(format!("Failed to parse Ollama response: {}", e))
+                })?;
+                let usage = parsed.usage();
+                Ok((parsed.message.content, usage))
+            }
+        }
     }
 }
 
+fn user_prompt(query: &str, context: &str) -> String {
+    format!(
+        "Context excerpts:\n\n{context}\n\n---\n\nQuestion: {query}\n\n\
+         Answer using only the excerpts above and cite them with [n]."
+    )
+}
+
+/// One numbered context excerpt, exactly as it appears in the prompt.
+fn format_entry(number: usize, r: &SearchResult) -> String {
+    format!(
+        "[{}] {} ({})\n{}",
+        number,
+        r.location(),
+        r.repository_url,
+        r.content
+    )
+}
+
 /// Build the numbered context block fed to the model. Numbers here line up with
 /// the `[n]` citations the model is asked to produce and the printed source list.
 fn build_context(sources: &[SearchResult]) -> String {
     sources
         .iter()
         .enumerate()
-        .map(|(i, r)| format!("[{}] {} ({})\n{}", i + 1, r.location(), r.repository_url, r.content))
+        .map(|(i, r)| format_entry(i + 1, r))
         .collect::<Vec<_>>()
         .join("\n\n")
 }
@@ -173,37 +370,52 @@ fn build_context(sources: &[SearchResult]) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{
-        SearchResultFileMetadata, SearchResultPaths, SearchResultScoring,
-    };
-
-    fn result(idx: usize, content: &str) -> SearchResult {
-        SearchResult::new(
-            format!("id{idx}"),
-            SearchResultPaths {
-                file_path: format!("/repo/file{idx}.md"),
-                relative_path: format!("file{idx}.md"),
-                heading_path: "Section".to_string(),
-            },
-            content.to_string(),
-            "https://github.com/x/y".to_string(),
-            SearchResultScoring {
-                score: 0.9,
-                distance: Some(0.1),
-            },
-            SearchResultFileMetadata {
-                file_size: 10,
-                last_modified: 0,
-            },
-        )
-    }
 
     #[test]
     fn context_is_numbered_and_located() {
-        let ctx = build_context(&[result(1, "alpha"), result(2, "beta")]);
+        let section = |id: &str, content: &str| SearchResult {
+            heading_path: "Section".to_string(),
+            ..SearchResult::fixture(id).with_content(content)
+        };
+        let ctx = build_context(&[section("file1", "alpha"), section("file2", "beta")]);
         assert!(ctx.contains("[1] file1.md # Section"));
         assert!(ctx.contains("[2] file2.md # Section"));
         assert!(ctx.contains("alpha"));
         assert!(ctx.contains("beta"));
     }
+
+    fn generator(backend: GenerationBackend, base_url: &str) -> AnswerGenerator {
+        let mut config = crate::config::Config::default_config().generation;
+        config.backend = backend;
+        config.base_url = base_url.to_string();
+        AnswerGenerator::new(config).unwrap()
+    }
+
+    #[test]
+    fn endpoint_follows_backend() {
+        assert_eq!(
+            generator(GenerationBackend::OpenAi, "https://api.groq.com/openai/v1/").endpoint(),
+            "https://api.groq.com/openai/v1/chat/completions"
+        );
+        assert_eq!(
+            generator(GenerationBackend::Ollama, "http://localhost:11434").endpoint(),
+            "http://localhost:11434/api/chat"
+        );
+        assert_eq!(
+            generator(GenerationBackend::LlamaCpp, "http://localhost:8080/v1").endpoint(),
+            "http://localhost:8080/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn ollama_response_maps_eval_counts_to_usage() {
+        let parsed: OllamaChatResponse = serde_json::from_str(
+            r#"{"model":"llama3.1","message":{"role":"assistant","content":"hi"},
+                "done":true,"prompt_eval_count":120,"eval_count":30}"#,
+        )
+        .unwrap();
+        let usage = parsed.usage().unwrap();
+        assert_eq!(parsed.message.content, "hi");
+        assert_eq!(usage.total_tokens, 150);
+    }
 }
@@ -15,23 +15,41 @@ pub mod models;
 pub mod parser;
 pub mod pipeline;
 pub mod repository;
+pub mod retrieval;
 pub mod utils;
 
 pub use config::{
-    Config, DatabaseConfig, EmbeddingConfig, ExtractionConfig, GenerationConfig, PipelineConfig,
-    RepositoryConfig,
+    BlameConfig, Config, DatabaseConfig, DistanceMetric, DivergencePolicy, EmbeddingCacheConfig,
+    EmbeddingConfig, EmbeddingLimits, ExtractionConfig, GenerationBackend, GenerationConfig,
+    GenerationOptions, HistoryConfig, MaintenanceConfig, PipelineConfig, QueryStrategy,
+    RepositoryConfig, RerankConfig, RerankerKind, RetrievalConfig, RunLogConfig, SubmoduleConfig,
+    SubmoduleFilter, TokenizerKind, VectorIndexConfig, VectorIndexKind,
+};
+pub use database::{
+    BatchInserter, CachePruneOptions, CacheStats, ChangeKind, CommitRow, CommitStore, DiffReport,
+    DocumentWriter, Embedder, EmbeddingCache, EmbeddingClient, EmbeddingProvenance,
+    EmbeddingScheduler, IndexStats, InsertStats, LanceDbClient, MaintenanceOptions,
+    MaintenanceReport, MigrationPlan, PreparedFile, ReembedReport, Reembedder, RefDiff,
+    RepositoryStats, RunLog, RunRecord, RunRecorder, RunStatus, SchemaDiff, SchemaManager,
+    SearchFilter, Section, SectionChange, StaleFinder, StaleReport, StaleSection, StatsCollector,
+    TableMaintainer, TableSnapshot, VectorIndexStatus, VectorSearchOptions, WriteStats,
 };
-pub use database::{BatchInserter, EmbeddingClient, InsertStats, LanceDbClient, SchemaManager};
-pub use generation::AnswerGenerator;
 pub use error::{PipelineError, Result};
 pub use exporter::json::{ExportManifest, ExportedDocument, JsonExporter};
-pub use models::{Document, SearchResult};
+pub use generation::{Answer, AnswerGenerator, TokenUsage};
+pub use models::{Document, DocumentMetadata, SearchResult};
 pub use parser::{
     Chunk, ChunkOptions, Frontmatter, FrontmatterParser, MarkdownNormalizer, MarkdownParser,
     ParsedMarkdown, chunk_markdown,
 };
-pub use pipeline::{PipelineStats, ProgressTracker};
-pub use repository::{FileClassifier, FileScanner, RepositorySync, ScannedFile};
+pub use pipeline::{IngestContext, IngestReport, PipelineStats, ProgressTracker};
+pub use repository::{
+    BlameIndex, Blamer, CommitEntry, FileBlame, FileClassifier, FileHunk, FileScanner,
+    HistoryWalker, LineCommit, LocalVersion, RefSnapshot, ReferenceKind, RepositorySource,
+    RepositorySync, ResolvedReference, ScannedFile, SubmoduleIndex, SubmoduleInfo, SyncReport,
+    WorktreeChanges,
+};
+pub use retrieval::{Reranker, Retriever};
 pub use utils::{
     FileTemplate, HealthCheck, HealthReport, HealthStatus, OperationTimer, PerformanceMetrics,
     Validator,
@@ -4,7 +4,7 @@
 
 use crate::error::{PipelineError, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use tokio::fs;
 use tracing::{debug, info, warn};
@@ -19,6 +19,10 @@ pub struct RepositoryMetadata {
     pub subdirectories: Option<Vec<String>>,
     pub file_count: usize,
     pub ingested_at: u64,
+    /// Every ref ingested so far and the commit it was last ingested at.
+    /// Each keeps its own chunks, searchable with `at`.
+    #[serde(default)]
+    pub refs: BTreeMap<String, String>,
 }
 
 pub struct MetadataStore {
@@ -123,6 +127,7 @@ mod tests {
                 subdirectories: None,
                 file_count: 10,
                 ingested_at: 1234567890,
+                refs: BTreeMap::from([("main".to_string(), "abc123".to_string())]),
             };
             store.insert("repo".to_string(), metadata);
             store.save().await.unwrap();
@@ -135,6 +140,17 @@ mod tests {
             let meta = store.get("repo").unwrap();
             assert_eq!(meta.url, "https://github.com/test/repo");
             assert_eq!(meta.file_count, 10);
+            assert_eq!(meta.refs["main"], "abc123");
         }
     }
+
+    #[test]
+    fn test_metadata_without_refs_still_loads() {
+        let meta: RepositoryMetadata = serde_json::from_str(
+            r#"{"url":"u","branch":"main","commit_hash":"abc","local_path":"/r",
+                "subdirectories":null,"file_count":1,"ingested_at":0}"#,
+        )
+        .unwrap();
+        assert!(meta.refs.is_empty());
+    }
 }
@@ -3,16 +3,17 @@
 // reference: https://docs.rs/rmcp
 
 use crate::config::Config;
-use crate::database::{BatchInserter, EmbeddingClient, LanceDbClient, SchemaManager};
+use crate::database::runs::{self, RunLog, RunRecorder, RunStatus};
+use crate::database::{
+    CommitStore, LanceDbClient, RefDiff, SchemaManager, SearchFilter, StaleFinder, StatsCollector,
+};
 use crate::generation::AnswerGenerator;
 use crate::mcp::persistence::RepositoryMetadata;
-use crate::repository::{FileScanner, RepositorySync};
+use crate::pipeline::{IngestContext, IngestReport, maintain_after_ingest, process_files};
+use crate::repository::{FileScanner, ReferenceKind, RepositorySync};
+use crate::retrieval::Retriever;
 use crate::utils::telemetry::{HealthCheck, HealthReport, OperationTimer, PerformanceMetrics};
-use rmcp::handler::server::{
-    ServerHandler,
-    tool::ToolRouter,
-    wrapper::Parameters,
-};
+use rmcp::handler::server::{ServerHandler, tool::ToolRouter, wrapper::Parameters};
 use rmcp::model::*;
 use rmcp::{ErrorData as McpError, tool, tool_handler, tool_router};
 use schemars::JsonSchema;
@@ -23,11 +24,13 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, RwLock};
 use tokio::time::timeout;
-use tracing::{error, info, warn};
+use tracing::{info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 struct IngestRepositoryParams {
-    #[schemars(description = "GitHub repository URL (e.g., https://github.com/user/repo)")]
+    #[schemars(
+        description = "GitHub repository URL (e.g., https://github.com/user/repo), or a file:// URL or path of a local checkout or directory"
+    )]
     repo_url: String,
     #[serde(default)]
     #[schemars(description = "Branch, tag, or commit to checkout (default: main)")]
@@ -57,6 +60,13 @@ struct UpdateRepositoryParams {
     new_reference: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct GetStatsParams {
+    #[serde(default)]
+    #[schemars(description = "Output format: 'text' (default) or 'json'")]
+    format: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 struct SearchDocumentsParams {
     #[schemars(description = "Search query text")]
@@ -67,6 +77,21 @@ struct SearchDocumentsParams {
     #[serde(default)]
     #[schemars(description = "Filter by repository URL (optional)")]
     repository_filter: Option<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Metadata filter, e.g. 'path:docs/ and (ext:md or lang:en) and not tag:draft and modified>30d'. Fields: repo, path (prefix or glob), ext, lang, heading, modified and changed (file mtime and last commit; <, <=, >, >= a date or age like 30d), category, tag, at (branch, tag or commit) (optional)"
+    )]
+    filter: Option<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Only search chunks ingested at this branch, tag or commit, e.g. 'v1.4' (optional)"
+    )]
+    at: Option<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Boost recently changed sections by this weight, e.g. 0.3 (default: from config)"
+    )]
+    recency: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -79,6 +104,62 @@ struct AskQuestionParams {
     #[serde(default)]
     #[schemars(description = "Filter retrieval to a single repository URL (optional)")]
     repository_filter: Option<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Metadata filter, e.g. 'path:docs/ and (ext:md or lang:en) and not tag:draft and modified>30d'. Fields: repo, path (prefix or glob), ext, lang, heading, modified and changed (file mtime and last commit; <, <=, >, >= a date or age like 30d), category, tag, at (branch, tag or commit) (optional)"
+    )]
+    filter: Option<String>,
+    #[serde(default)]
+    #[schemars(description = "Answer as of this branch, tag or commit, e.g. 'v1.4' (optional)")]
+    at: Option<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Prefer recently changed sections by this weight, e.g. 0.3 (default: from config)"
+    )]
+    recency: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct DiffVersionsParams {
+    #[schemars(description = "The older branch, tag or commit, e.g. 'v1.4'")]
+    from: String,
+    #[schemars(description = "The newer branch, tag or commit, e.g. 'v1.5'")]
+    to: String,
+    #[serde(default)]
+    #[schemars(
+        description = "The repository URL whose refs to compare (optional, defaults to the configured repository)"
+    )]
+    repository_filter: Option<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Cosine similarity at which a removed and an added section count as moved (default: 0.9)"
+    )]
+    move_threshold: Option<f32>,
+    #[serde(default)]
+    #[schemars(
+        description = "Also write a changelog citing the changes in both versions (default: false)"
+    )]
+    changelog: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct StaleDocumentsParams {
+    #[serde(default)]
+    #[schemars(
+        description = "Months without a change before a section counts as stale (default: 6)"
+    )]
+    months: Option<u32>,
+    #[serde(default)]
+    #[schemars(description = "Only check this repository URL (optional)")]
+    repository_filter: Option<String>,
+    #[serde(default)]
+    #[schemars(description = "Metadata filter, as for search_documents (optional)")]
+    filter: Option<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Only check chunks ingested at this branch, tag or commit (optional)"
+    )]
+    at: Option<String>,
 }
 
 /// GitSummarizeMcp server with concurrent access controls
@@ -109,6 +190,23 @@ impl GitSummarizeMcp {
         }
     }
 
+    /// The `filter` expression, narrowed to the ref `at` when given.
+    fn parse_filter(
+        filter: Option<&str>,
+        at: Option<&str>,
+    ) -> Result<Option<SearchFilter>, McpError> {
+        let filter = filter
+            .filter(|f| !f.trim().is_empty())
+            .map(SearchFilter::parse)
+            .transpose()
+            .map_err(|e| Self::make_error(-32602, e.to_string()))?;
+        let at = at
+            .map(str::trim)
+            .filter(|at| !at.is_empty())
+            .map(SearchFilter::at);
+        Ok(SearchFilter::all(filter.into_iter().chain(at)))
+    }
+
     pub fn new(config: Config) -> Self {
         Self {
             config: Arc::new(RwLock::new(config)),
@@ -195,7 +293,7 @@ impl GitSummarizeMcp {
 #[tool_router]
 impl GitSummarizeMcp {
     #[tool(
-        description = "Ingest a GitHub repository into the RAG pipeline. Supports branch selection and subdirectory filtering."
+        description = "Ingest a GitHub repository or a local checkout or directory into the RAG pipeline. Supports branch selection and subdirectory filtering."
     )]
     async fn ingest_repository(
         &self,
@@ -233,26 +331,33 @@ impl GitSummarizeMcp {
         );
 
         // Update config with new repository URL
-        let local_path = {
+        // Local paths are stored under their canonical file:// identifier.
+        let (repo_url, local_path) = {
             let mut config = self.write_config().await?;
             config.repository.source_url = repo_url.clone();
             if let Some(ref_name) = reference.clone() {
                 config.repository.branch = ref_name;
             }
-            config.repository.local_path.clone()
+            (
+                config.repository.repository_id(),
+                config.repository.working_dir(),
+            )
         };
 
         // Sync repository
         timer.checkpoint("Starting repository sync");
         let config = self.read_config().await?.clone();
-        let sync = RepositorySync::new(config.repository.clone());
-        sync.sync()
+        let mut repository = config.repository.clone();
+        if let Some(subdirs) = &subdirectories {
+            // Only materialize the requested subdirectories on a fresh clone.
+            repository.sparse_paths = subdirs.clone();
+        }
+        let sync = RepositorySync::new(repository);
+        let report = sync
+            .sync()
             .map_err(|e| Self::make_error(-32603, format!("Repository sync failed: {}", e)))?;
-
-        // Get current commit hash
-        let commit_hash = sync
-            .get_current_commit()
-            .unwrap_or_else(|_| "unknown".to_string());
+        let resolved = &report.reference;
+        let commit_hash = resolved.commit.clone();
         timer.checkpoint("Repository sync completed");
 
         // Ensure DB is connected
@@ -262,7 +367,7 @@ impl GitSummarizeMcp {
         // Scan files
         let scanner = FileScanner::new(config.pipeline.clone());
         let mut files = scanner
-            .scan_directory(&config.repository.local_path)
+            .scan_directory(&local_path)
             .map_err(|e| Self::make_error(-32603, format!("Failed to scan directory: {}", e)))?;
 
         // Filter by subdirectories if specified
@@ -280,6 +385,12 @@ impl GitSummarizeMcp {
             );
         }
 
+        let context = IngestContext::new(Some(resolved.clone()))
+            .with_submodules(&config.repository, &local_path, &mut files)
+            .map_err(|e| Self::make_error(-32603, format!("Failed to read submodules: {}", e)))?;
+
+        let mut context = context.with_blame(&config.repository, &local_path, &files);
+
         let file_count = files.len();
         info!("MCP: Found {} files to process", file_count);
         timer.checkpoint(&format!("Scanned {} files", file_count));
@@ -296,69 +407,70 @@ impl GitSummarizeMcp {
             Self::make_error(-32603, format!("Schema initialization failed: {}", e))
         })?;
 
-        let mut processed = 0;
-        let mut failed = 0;
-
-        // Process files (limit to 100 per request for responsiveness)
-        let limit = file_count.min(100);
+        schema_manager
+            .check_embedding_provenance(&config.embedding)
+            .await
+            .map_err(|e| Self::make_error(-32603, e.to_string()))?;
+        schema_manager
+            .ensure_migrated(config.embedding.dimension)
+            .await
+            .map_err(|e| Self::make_error(-32603, e.to_string()))?;
+
+        let recorder = if config.database.run_log.enabled {
+            RunRecorder::start(
+                RunLog::new(client, &config.database.run_log),
+                &repo_url,
+                Some(commit_hash.clone()),
+                runs::config_hash(&config),
+                None,
+            )
+            .await
+            .map_err(|e| warn!("Failed to start run log: {}", e))
+            .ok()
+            .map(Arc::new)
+        } else {
+            None
+        };
 
-        // Get max file size and embedding config from app config
-        let config_guard = self.read_config().await?;
-        let max_file_size_bytes = config_guard.pipeline.max_file_size_mb * 1024 * 1024;
-        let embedding = Arc::new(EmbeddingClient::new(config_guard.embedding.clone()));
-        let allow_fallback = config_guard.embedding.allow_fallback;
-        drop(config_guard);
+        context.recorder = recorder.clone();
+        let result = process_files(client, &config, files, context).await;
 
-        for file in files.iter().take(limit) {
-            // Enforce file size limit
-            if file.size > max_file_size_bytes as u64 {
-                warn!(
-                    "Skipping {}: file size {} MB exceeds limit of {} MB",
-                    file.relative_path,
-                    file.size / (1024 * 1024),
-                    max_file_size_bytes / (1024 * 1024)
-                );
-                failed += 1;
-                continue;
+        if let Some(recorder) = &recorder {
+            let status = if result.is_ok() {
+                RunStatus::Completed
+            } else {
+                RunStatus::Failed
+            };
+            if let Err(e) = recorder.finish(status).await {
+                warn!("Failed to record run: {}", e);
             }
+        }
+        let IngestReport { processed, failed } =
+            result.map_err(|e| Self::make_error(-32603, format!("Ingestion failed: {}", e)))?;
 
-            let content = match std::fs::read_to_string(&file.path) {
-                Ok(c) => c,
-                Err(e) => {
-                    error!("Failed to read {}: {}", file.relative_path, e);
-                    failed += 1;
-                    continue;
-                }
-            };
+        maintain_after_ingest(client, &config).await;
 
-            let inserter = BatchInserter::new(client, Arc::clone(&embedding))
-                .with_options(crate::parser::ChunkOptions::default(), allow_fallback);
-            match inserter
-                .insert_file(
-                    &file.path.display().to_string(),
-                    &file.relative_path,
-                    &content,
-                    file.modified,
-                    &repo_url,
-                    false,
-                )
+        if config.repository.history.enabled {
+            match CommitStore::new(client, &config.repository.history)
+                .index(&config, force.unwrap_or(false))
                 .await
             {
-                Ok(_) => {
-                    processed += 1;
-                    if processed % 10 == 0 {
-                        info!("MCP: Processed {}/{}", processed, limit);
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to insert {}: {}", file.relative_path, e);
-                    failed += 1;
-                }
+                Ok(commits) => info!("MCP: Indexed {} commit(s)", commits),
+                Err(e) => warn!("Failed to index commit history: {}", e),
             }
         }
 
-        // Store repository metadata
+        // Store repository metadata, keeping the refs ingested before
         let repo_key = Self::get_repo_key(&repo_url);
+        let mut refs = self
+            .read_repositories()
+            .await?
+            .get(&repo_key)
+            .map(|meta| meta.refs.clone())
+            .unwrap_or_default();
+        if resolved.kind != ReferenceKind::Directory {
+            refs.insert(resolved.name.clone(), commit_hash.clone());
+        }
         let metadata = RepositoryMetadata {
             url: repo_url.clone(),
             branch: branch_display.clone(),
@@ -370,6 +482,7 @@ impl GitSummarizeMcp {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or(std::time::Duration::from_secs(0))
                 .as_secs(),
+            refs,
         };
 
         self.write_repositories().await?.insert(repo_key, metadata);
@@ -379,22 +492,31 @@ impl GitSummarizeMcp {
         let metrics = PerformanceMetrics::new("document_ingestion", processed, duration);
         info!("Performance: {}", metrics.format());
 
+        let sync_display = match (report.reference.kind, &report.previous_commit) {
+            (ReferenceKind::Worktree, _) if report.dirty => "local checkout (dirty)".to_string(),
+            (ReferenceKind::Worktree, _) => "local checkout".to_string(),
+            (ReferenceKind::Directory, _) => "local directory".to_string(),
+            (_, None) => "cloned".to_string(),
+            _ if report.diverged => format!("{} (local branch diverged; kept)", report.changes),
+            _ => report.changes.to_string(),
+        };
         let result_text = format!(
             "Repository ingestion complete:\n\
              \n\
             Repository: {}\n\
-            Reference: {}\n\
+            Reference: {} ({})\n\
              Commit: {}\n\
+             Sync: {}\n\
              Subdirectories: {}\n\
              Total files found: {}\n\
              Files processed: {}\n\
              Files failed: {}\n\
-             Success rate: {:.1}%\n\
-             \n\
-             Note: Limited to first 100 files per request.",
+             Success rate: {:.1}%",
             repo_url,
             branch_display,
-            &commit_hash[..8.min(commit_hash.len())],
+            resolved.kind.as_str(),
+            commit_hash,
+            sync_display,
             subdir_display,
             file_count,
             processed,
@@ -430,6 +552,15 @@ impl GitSummarizeMcp {
                 .as_ref()
                 .map(|s| s.join(", "))
                 .unwrap_or_else(|| "all".to_string());
+            let refs = if meta.refs.is_empty() {
+                "none".to_string()
+            } else {
+                meta.refs
+                    .iter()
+                    .map(|(name, commit)| format!("{} ({})", name, &commit[..8.min(commit.len())]))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
 
             result.push_str(&format!(
                 "• {} ({})\n\
@@ -437,6 +568,7 @@ impl GitSummarizeMcp {
                    Local path: {}\n\
                    Branch: {}\n\
                    Commit: {}\n\
+                   Refs: {}\n\
                    Subdirs: {}\n\
                    Files: {}\n\
                    Ingested: {}\n\n",
@@ -450,6 +582,7 @@ impl GitSummarizeMcp {
                 meta.local_path.display(),
                 meta.branch,
                 &meta.commit_hash[..8.min(meta.commit_hash.len())],
+                refs,
                 subdirs,
                 meta.file_count,
                 chrono::DateTime::from_timestamp(meta.ingested_at as i64, 0)
@@ -581,46 +714,77 @@ impl GitSummarizeMcp {
         .await
     }
 
-    #[tool(description = "Get statistics about the ingested documents in the RAG pipeline")]
-    async fn get_stats(&self) -> Result<CallToolResult, McpError> {
+    #[tool(
+        description = "Get statistics about the indexed documents: chunks and files per repository, language, extension and chunk size, embedding model, table version and size, and files without chunks"
+    )]
+    async fn get_stats(
+        &self,
+        Parameters(params): Parameters<GetStatsParams>,
+    ) -> Result<CallToolResult, McpError> {
         info!("MCP: Getting statistics");
 
         self.ensure_db_connected().await?;
 
+        let pipeline_config = self.read_config().await?.pipeline.clone();
+        let sources: Vec<(String, std::path::PathBuf, Option<Vec<String>>)> = self
+            .read_repositories()
+            .await?
+            .values()
+            .map(|repo| {
+                (
+                    repo.url.clone(),
+                    repo.local_path.clone(),
+                    repo.subdirectories.clone(),
+                )
+            })
+            .collect();
+
         let db_guard = self.lock_db_client().await?;
         let client = db_guard
             .as_ref()
             .ok_or_else(|| Self::make_error(-32603, "Database not connected"))?;
 
-        let doc_count = client.get_document_count().await.map_err(|e| {
-            Self::make_error(-32603, format!("Failed to get document count: {}", e))
-        })?;
-
-        let repos = self.read_repositories().await?;
-        let repo_count = repos.len();
+        let scanner = FileScanner::new(pipeline_config);
+        let mut collector = StatsCollector::new(client);
+        for (url, local_path, subdirectories) in sources {
+            if !local_path.exists() {
+                continue;
+            }
+            let files = match scanner.scan_directory(&local_path) {
+                Ok(files) => files,
+                Err(e) => {
+                    warn!("MCP: Failed to scan {}: {}", local_path.display(), e);
+                    continue;
+                }
+            };
+            let paths = files
+                .into_iter()
+                .map(|f| f.relative_path)
+                .filter(|path| {
+                    subdirectories.as_ref().is_none_or(|subdirs| {
+                        subdirs
+                            .iter()
+                            .any(|subdir| path.starts_with(subdir.as_str()))
+                    })
+                })
+                .collect();
+            collector = collector.with_source_files(&url, paths);
+        }
 
-        let storage_uri = client.get_connection().uri().to_string();
+        let stats = collector.collect().await.map_err(|e| {
+            Self::make_error(-32603, format!("Failed to collect statistics: {}", e))
+        })?;
+        drop(db_guard);
 
-        let stats_text = format!(
-            "RAG Pipeline Statistics:\n\
-             \n\
-             Documents:\n\
-             - Total documents: {}\n\
-             \n\
-             Repositories:\n\
-             - Tracked repositories: {}\n\
-             \n\
-             Database:\n\
-             - Backend: LanceDB\n\
-             - Storage: {}\n\
-             - Table: {}",
-            doc_count,
-            repo_count,
-            storage_uri,
-            client.table_name()
-        );
+        let text = if params.format.as_deref() == Some("json") {
+            serde_json::to_string_pretty(&stats).map_err(|e| {
+                Self::make_error(-32603, format!("Failed to serialize statistics: {}", e))
+            })?
+        } else {
+            stats.to_string()
+        };
 
-        Ok(CallToolResult::success(vec![Content::text(stats_text)]))
+        Ok(CallToolResult::success(vec![Content::text(text)]))
     }
 
     #[tool(description = "Search for documents by semantic similarity using vector embeddings")]
@@ -632,14 +796,17 @@ impl GitSummarizeMcp {
             query,
             limit,
             repository_filter,
+            filter,
+            at,
+            recency,
         } = params;
         info!("MCP: Searching for documents with query: {}", query);
+        let filter = Self::parse_filter(filter.as_deref(), at.as_deref())?;
 
         self.ensure_db_connected().await?;
 
         let search_limit = limit.unwrap_or(5);
-
-        let query_embedding = self.embed_query(&query).await?;
+        let config = self.read_config().await?.clone();
 
         // Get database client
         let db_guard = self.lock_db_client().await?;
@@ -648,8 +815,11 @@ impl GitSummarizeMcp {
             .ok_or_else(|| Self::make_error(-32603, "Database not connected"))?;
 
         // Perform vector search
-        let results = client
-            .vector_search(query_embedding, search_limit, repository_filter.as_deref())
+        let results = Retriever::new(client, &config)
+            .map_err(|e| Self::make_error(-32603, e.to_string()))?
+            .with_filter(filter)
+            .with_recency(recency)
+            .retrieve(&query, search_limit, repository_filter.as_deref())
             .await
             .map_err(|e| Self::make_error(-32603, format!("Vector search failed: {}", e)))?;
 
@@ -662,7 +832,7 @@ impl GitSummarizeMcp {
                  \n\
                  Try:\n\
                  - Using different search terms\n\
-                 - Removing repository filter\n\
+                 - Removing the repository or metadata filter\n\
                  - Checking that documents have been ingested",
                 query
             );
@@ -694,27 +864,6 @@ impl GitSummarizeMcp {
         Ok(CallToolResult::success(vec![Content::text(result_text)]))
     }
 
-    /// Embed a query using the configured embedding provider, honoring the
-    /// `allow_fallback` flag.
-    async fn embed_query(&self, query: &str) -> Result<Vec<f32>, McpError> {
-        let config = self.read_config().await?;
-        let embedding = EmbeddingClient::new(config.embedding.clone());
-        match embedding.generate_embedding(query).await {
-            Ok(v) => Ok(v),
-            Err(e) if config.embedding.allow_fallback => {
-                warn!("Query embedding failed ({e}); using non-semantic fallback (degraded)");
-                Ok(EmbeddingClient::generate_fallback_embedding(
-                    query,
-                    config.embedding.dimension,
-                ))
-            }
-            Err(e) => Err(Self::make_error(
-                -32603,
-                format!("Failed to embed query: {e}"),
-            )),
-        }
-    }
-
     #[tool(
         description = "Ask a natural-language question; retrieves relevant context and returns a cited answer"
     )]
@@ -726,22 +875,28 @@ impl GitSummarizeMcp {
             question,
             limit,
             repository_filter,
+            filter,
+            at,
+            recency,
         } = params;
         info!("MCP: Answering question: {}", question);
+        let filter = Self::parse_filter(filter.as_deref(), at.as_deref())?;
 
         self.ensure_db_connected().await?;
 
-        let config = self.read_config().await?;
+        let config = self.read_config().await?.clone();
         let retrieve_limit = limit.unwrap_or(config.generation.max_context_chunks);
 
-        let query_embedding = self.embed_query(&question).await?;
-
         let db_guard = self.lock_db_client().await?;
         let client = db_guard
             .as_ref()
             .ok_or_else(|| Self::make_error(-32603, "Database not connected"))?;
-        let results = client
-            .vector_search(query_embedding, retrieve_limit, repository_filter.as_deref())
+        let results = Retriever::new(client, &config)
+            .map_err(|e| Self::make_error(-32603, e.to_string()))?
+            .with_filter(filter)
+            .with_recency(recency)
+            .with_history(config.repository.history.context_results)
+            .retrieve(&question, retrieve_limit, repository_filter.as_deref())
             .await
             .map_err(|e| Self::make_error(-32603, format!("Vector search failed: {}", e)))?;
         drop(db_guard);
@@ -752,7 +907,8 @@ impl GitSummarizeMcp {
             )]));
         }
 
-        let generator = AnswerGenerator::new(config.generation.clone());
+        let generator = AnswerGenerator::new(config.generation.clone())
+            .map_err(|e| Self::make_error(-32603, e.to_string()))?;
         let answer = generator
             .answer(&question, results)
             .await
@@ -768,10 +924,114 @@ impl GitSummarizeMcp {
                 source.score
             ));
         }
+        out.push_str(&format!("\nContext tokens: {}\n", answer.context_tokens));
+        if let Some(usage) = answer.usage {
+            out.push_str(&format!(
+                "Usage: {} prompt + {} completion tokens\n",
+                usage.prompt_tokens, usage.completion_tokens
+            ));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(
+        description = "Compare the documentation ingested at two refs: sections added, removed, modified or moved, optionally with a changelog"
+    )]
+    async fn diff_versions(
+        &self,
+        Parameters(params): Parameters<DiffVersionsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let DiffVersionsParams {
+            from,
+            to,
+            repository_filter,
+            move_threshold,
+            changelog,
+        } = params;
+        info!("MCP: Comparing {} with {}", from, to);
+
+        self.ensure_db_connected().await?;
+
+        let (generation, repository) = {
+            let config = self.read_config().await?;
+            (
+                config.generation.clone(),
+                repository_filter.unwrap_or_else(|| config.repository.repository_id()),
+            )
+        };
+        let db_guard = self.lock_db_client().await?;
+        let client = db_guard
+            .as_ref()
+            .ok_or_else(|| Self::make_error(-32603, "Database not connected"))?;
+        let mut diff = RefDiff::new(client, &repository);
+        if let Some(threshold) = move_threshold {
+            diff = diff.with_move_threshold(threshold);
+        }
+        let report = diff
+            .compare(&from, &to)
+            .await
+            .map_err(|e| Self::make_error(-32603, format!("Diff failed: {}", e)))?;
+        drop(db_guard);
+
+        let mut out = report.to_string();
+        if changelog.unwrap_or(false) {
+            let generator = AnswerGenerator::new(generation)
+                .map_err(|e| Self::make_error(-32603, e.to_string()))?;
+            let changelog = report.changelog(&generator).await.map_err(|e| {
+                Self::make_error(-32603, format!("Changelog generation failed: {}", e))
+            })?;
+            out.push_str(&format!("\nChangelog:\n{}\n", changelog));
+        }
 
         Ok(CallToolResult::success(vec![Content::text(out)]))
     }
 
+    #[tool(
+        description = "List documentation sections whose lines no commit has changed in a number of months, oldest first"
+    )]
+    async fn stale_documents(
+        &self,
+        Parameters(params): Parameters<StaleDocumentsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let StaleDocumentsParams {
+            months,
+            repository_filter,
+            filter,
+            at,
+        } = params;
+        let months = months.unwrap_or(6);
+        info!("MCP: Looking for sections unchanged in {} month(s)", months);
+        let filter = Self::parse_filter(filter.as_deref(), at.as_deref())?;
+        let filter = SearchFilter::all(
+            repository_filter
+                .as_deref()
+                .map(SearchFilter::repository)
+                .into_iter()
+                .chain(filter),
+        );
+        let cutoff = chrono::Utc::now()
+            .checked_sub_months(chrono::Months::new(months))
+            .map_or(0, |t| t.timestamp().max(0) as u64);
+
+        self.ensure_db_connected().await?;
+
+        let db_guard = self.lock_db_client().await?;
+        let client = db_guard
+            .as_ref()
+            .ok_or_else(|| Self::make_error(-32603, "Database not connected"))?;
+        let report = StaleFinder::new(client)
+            .with_filter(filter)
+            .find(cutoff)
+            .await
+            .map_err(|e| Self::make_error(-32603, format!("Stale report failed: {}", e)))?;
+        drop(db_guard);
+
+        Ok(CallToolResult::success(vec![Content::text(
+            report.to_string(),
+        )]))
+    }
+
     #[tool(description = "Get configuration information about the RAG pipeline")]
     async fn get_config(&self) -> Result<CallToolResult, McpError> {
         info!("MCP: Getting configuration");
@@ -798,9 +1058,13 @@ impl GitSummarizeMcp {
              - Dimension: {}\n\
              \n\
              Generation:\n\
+             - Backend: {:?}\n\
              - Provider URL: {}\n\
              - Model: {}\n\
              \n\
+             Retrieval:\n\
+             - Query strategy: {:?}\n\
+             \n\
              Pipeline:\n\
              - Parallel workers: {}\n\
              - Max file size: {} MB\n\
@@ -815,8 +1079,10 @@ impl GitSummarizeMcp {
             config.embedding.base_url,
             config.embedding.model,
             config.embedding.dimension,
+            config.generation.backend,
             config.generation.base_url,
             config.generation.model,
+            config.retrieval.strategy,
             config.pipeline.parallel_workers,
             config.pipeline.max_file_size_mb,
             config.pipeline.force_reprocess
@@ -847,10 +1113,33 @@ impl GitSummarizeMcp {
             .await
             .map_err(|e| Self::make_error(-32603, format!("Schema verification failed: {}", e)))?;
 
+        let index_status = schema_manager
+            .vector_index_status()
+            .await
+            .map_err(|e| Self::make_error(-32603, format!("Failed to read index status: {}", e)))?
+            .map(|status| status.to_string())
+            .unwrap_or_else(|| "none (brute-force search)".to_string());
+
+        let dimension = self.read_config().await?.embedding.dimension;
+        let migrations = schema_manager
+            .migration_plan(dimension)
+            .await
+            .map_err(|e| Self::make_error(-32603, format!("Failed to diff schema: {}", e)))?
+            .map(|plan| {
+                if plan.is_up_to_date() {
+                    format!("up to date (version {})", plan.to_version)
+                } else {
+                    plan.to_string()
+                }
+            })
+            .unwrap_or_else(|| "no table yet".to_string());
+
         let result_text = format!(
             "Database Verification:\n\
              - Connection: {}\n\
              - Schema: {}\n\
+             - Migrations: {}\n\
+             - Vector index: {}\n\
              - Status: Ready for operations",
             if ping_result {
                 "✓ Success"
@@ -861,7 +1150,9 @@ impl GitSummarizeMcp {
                 "✓ Valid"
             } else {
                 "✗ Invalid"
-            }
+            },
+            migrations,
+            index_status
         );
 
         Ok(CallToolResult::success(vec![Content::text(result_text)]))
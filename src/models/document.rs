@@ -2,16 +2,19 @@
 // description: core document/chunk model with validation and serialization
 // reference: internal data structures
 
-use crate::parser::Chunk;
+use crate::parser::{Chunk, FrontmatterParser};
+use crate::repository::{FileClassifier, LineCommit, ResolvedReference, SubmoduleInfo};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// A single retrievable unit stored in the vector index. One source file is
 /// split into many `Document` rows (one per heading-aware chunk).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document {
-    /// Stable, unique id per chunk: hash(repository_url + relative_path + chunk_index).
+    /// Stable, unique id per chunk: hash(repository_url + git_ref +
+    /// relative_path + chunk_index), without the ref for directory sources.
     pub id: String,
     pub file_path: String,
     pub relative_path: String,
@@ -28,6 +31,86 @@ pub struct Document {
     pub parsed_at: u64,
     pub normalized: bool,
     pub repository_url: String,
+    /// Lower-cased language code from frontmatter (`lang`/`language`).
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Category from frontmatter or the configured path rules.
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Path of the submodule the file belongs to, if any.
+    #[serde(default)]
+    pub submodule: Option<String>,
+    /// Commit checked out in that submodule.
+    #[serde(default)]
+    pub submodule_commit: Option<String>,
+    /// Branch, tag or commit the file was ingested from. Each ref of a
+    /// repository has its own rows.
+    #[serde(default)]
+    pub git_ref: Option<String>,
+    /// Commit `git_ref` pointed at when the file was ingested.
+    #[serde(default)]
+    pub git_commit: Option<String>,
+    /// Newest commit among the chunk's lines, from git blame. Unlike
+    /// `last_modified` (the file's mtime) it survives a fresh clone.
+    #[serde(default)]
+    pub last_commit: Option<String>,
+    /// Author of `last_commit`.
+    #[serde(default)]
+    pub last_author: Option<String>,
+    /// Author time of `last_commit`, seconds since the epoch.
+    #[serde(default)]
+    pub last_changed_at: Option<u64>,
+}
+
+/// File-level metadata shared by every chunk of a file, used by search filters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentMetadata {
+    pub language: Option<String>,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl DocumentMetadata {
+    /// Read `lang`/`language`, `category` and `tags` from a file's
+    /// frontmatter. Without a frontmatter category the classifier's path rules
+    /// decide, and its topic (if any) is added as a tag.
+    pub fn from_file(
+        relative_path: &str,
+        content: &str,
+        classifier: Option<&FileClassifier>,
+    ) -> Self {
+        let frontmatter = FrontmatterParser::new()
+            .extract(content)
+            .ok()
+            .flatten()
+            .map(|(frontmatter, _)| frontmatter)
+            .unwrap_or_default();
+        let field = |key: &str| {
+            frontmatter
+                .fields
+                .get(key)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+
+        let path = Path::new(relative_path);
+        let mut tags = frontmatter.list("tags");
+        if let Some(topic) = classifier.and_then(|c| c.extract_topic(path)) {
+            tags.push(topic);
+        }
+        let mut seen = std::collections::HashSet::new();
+        tags.retain(|t| seen.insert(t.to_lowercase()));
+
+        Self {
+            language: field("lang")
+                .or_else(|| field("language"))
+                .map(|l| l.to_lowercase()),
+            category: field("category").or_else(|| classifier.map(|c| c.extract_category(path))),
+            tags,
+        }
+    }
 }
 
 impl Document {
@@ -57,7 +140,54 @@ impl Document {
             parsed_at: now_secs(),
             normalized,
             repository_url: repository_url.to_string(),
+            language: None,
+            category: None,
+            tags: Vec::new(),
+            submodule: None,
+            submodule_commit: None,
+            git_ref: None,
+            git_commit: None,
+            last_commit: None,
+            last_author: None,
+            last_changed_at: None,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: &DocumentMetadata) -> Self {
+        self.language = metadata.language.clone();
+        self.category = metadata.category.clone();
+        self.tags = metadata.tags.clone();
+        self
+    }
+
+    pub fn with_submodule(mut self, submodule: Option<&SubmoduleInfo>) -> Self {
+        self.submodule = submodule.map(|s| s.path.clone());
+        self.submodule_commit = submodule.map(|s| s.commit.clone());
+        self
+    }
+
+    /// Record the ref the chunk was read from and key the chunk by it, so
+    /// the same file at several refs is stored side by side.
+    pub fn with_reference(mut self, reference: Option<&ResolvedReference>) -> Self {
+        if let Some(reference) = reference {
+            self.id = Self::versioned_chunk_id(
+                &self.repository_url,
+                &reference.name,
+                &self.relative_path,
+                self.chunk_index as usize,
+            );
+            self.git_ref = Some(reference.name.clone());
+            self.git_commit = Some(reference.commit.clone());
         }
+        self
+    }
+
+    /// Record the last commit that changed the chunk's lines.
+    pub fn with_last_change(mut self, change: Option<&LineCommit>) -> Self {
+        self.last_commit = change.map(|c| c.commit.clone());
+        self.last_author = change.map(|c| c.author.clone());
+        self.last_changed_at = change.map(|c| c.authored_at);
+        self
     }
 
     pub fn chunk_id(repository_url: &str, relative_path: &str, chunk_index: usize) -> String {
@@ -65,6 +195,16 @@ impl Document {
         Self::compute_hash(&key)
     }
 
+    pub fn versioned_chunk_id(
+        repository_url: &str,
+        git_ref: &str,
+        relative_path: &str,
+        chunk_index: usize,
+    ) -> String {
+        let key = format!("{repository_url}\u{0}{git_ref}\u{0}{relative_path}\u{0}{chunk_index}");
+        Self::compute_hash(&key)
+    }
+
     fn compute_hash(content: &str) -> String {
         let mut hasher = Sha256::new();
         hasher.update(content.as_bytes());
@@ -123,6 +263,67 @@ mod tests {
         assert_ne!(a, c);
     }
 
+    #[test]
+    fn test_metadata_prefers_frontmatter() {
+        use crate::config::{CategoryRule, TopicRule};
+
+        let classifier = FileClassifier::new(
+            vec![CategoryRule {
+                keywords: vec!["guides/".to_string()],
+                category: "guide".to_string(),
+            }],
+            vec![TopicRule {
+                keyword: "install".to_string(),
+                topic: "setup".to_string(),
+            }],
+        );
+
+        let metadata = DocumentMetadata::from_file(
+            "guides/install.md",
+            "---\nlang: EN\ntags: [cli, Setup]\n---\n# Install",
+            Some(&classifier),
+        );
+        assert_eq!(metadata.language.as_deref(), Some("en"));
+        assert_eq!(metadata.category.as_deref(), Some("guide"));
+        assert_eq!(metadata.tags, vec!["cli", "Setup"]);
+
+        let metadata = DocumentMetadata::from_file(
+            "guides/faq.md",
+            "---\ncategory: faq\n---\nBody",
+            Some(&classifier),
+        );
+        assert_eq!(metadata.category.as_deref(), Some("faq"));
+        assert!(metadata.tags.is_empty());
+
+        assert_eq!(
+            DocumentMetadata::from_file("a.md", "# No frontmatter", None),
+            DocumentMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_reference_keys_the_chunk_per_ref() {
+        use crate::repository::ReferenceKind;
+
+        let doc = |name: &str| {
+            Document::from_chunk("/r/a.md", "a.md", &sample_chunk(), 0, "repo", false)
+                .with_reference(Some(&ResolvedReference {
+                    name: name.to_string(),
+                    kind: ReferenceKind::Tag,
+                    commit: "abc123".to_string(),
+                }))
+        };
+        assert_ne!(doc("v1.4").id, doc("v1.5").id);
+        assert_eq!(doc("v1.4").content_hash, doc("v1.5").content_hash);
+        assert_eq!(doc("v1.4").git_commit.as_deref(), Some("abc123"));
+
+        let unversioned =
+            Document::from_chunk("/r/a.md", "a.md", &sample_chunk(), 0, "repo", false)
+                .with_reference(None);
+        assert_eq!(unversioned.id, Document::chunk_id("repo", "a.md", 2));
+        assert!(unversioned.git_ref.is_none());
+    }
+
     #[test]
     fn test_hash_consistency() {
         let id1 = Document::chunk_id("repo", "a.md", 0);
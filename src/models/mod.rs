@@ -5,7 +5,7 @@
 pub mod document;
 pub mod search_result;
 
-pub use document::Document;
+pub use document::{Document, DocumentMetadata};
 pub use search_result::{
     SearchResult, SearchResultFileMetadata, SearchResultPaths, SearchResultScoring,
 };
@@ -2,6 +2,7 @@
 // description: Search result model with similarity scores
 // reference: Used for vector similarity search results
 
+use crate::repository::history::format_date;
 use serde::{Deserialize, Serialize};
 
 /// Truncate to at most `max` chars (not bytes), appending an ellipsis if cut.
@@ -28,6 +29,9 @@ pub struct SearchResult {
     /// Heading breadcrumb of the matched chunk, e.g. "Setup > From source".
     pub heading_path: String,
 
+    /// Ordinal of the chunk within its source file
+    pub chunk_index: u32,
+
     /// Document content
     pub content: String,
 
@@ -45,14 +49,41 @@ pub struct SearchResult {
 
     /// Last modified timestamp
     pub last_modified: u64,
+
+    /// Branch, tag or commit the chunk was ingested from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+
+    /// Commit `git_ref` pointed at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
+
+    /// Last commit that changed the chunk's lines, from git blame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_commit: Option<String>,
+
+    /// Author of `last_commit`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_author: Option<String>,
+
+    /// Author time of `last_commit`, seconds since the epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_changed_at: Option<u64>,
+
+    /// The chunk's stored vector, when the search returned it. Used for
+    /// diversity-aware selection; never serialized.
+    #[serde(skip)]
+    pub embedding: Option<Vec<f32>>,
 }
 
-/// Groups disk path information to keep constructor arguments manageable.
+/// Groups path and in-file location information to keep constructor arguments
+/// manageable.
 #[derive(Debug, Clone)]
 pub struct SearchResultPaths {
     pub file_path: String,
     pub relative_path: String,
     pub heading_path: String,
+    pub chunk_index: u32,
 }
 
 /// Groups scoring metadata (score and optional distance).
@@ -84,38 +115,168 @@ impl SearchResult {
             file_path: paths.file_path,
             relative_path: paths.relative_path,
             heading_path: paths.heading_path,
+            chunk_index: paths.chunk_index,
             content,
             repository_url,
             score: scoring.score,
             distance: scoring.distance,
             file_size: metadata.file_size,
             last_modified: metadata.last_modified,
+            git_ref: None,
+            git_commit: None,
+            last_commit: None,
+            last_author: None,
+            last_changed_at: None,
+            embedding: None,
         }
     }
 
-    /// A human-readable location: "relative/path.md # Heading > Subheading".
+    /// Attach the ref and commit the chunk was ingested from.
+    pub fn with_reference(mut self, git_ref: Option<String>, git_commit: Option<String>) -> Self {
+        self.git_ref = git_ref;
+        self.git_commit = git_commit;
+        self
+    }
+
+    /// Attach the last commit that changed the chunk, with its author and
+    /// author time.
+    pub fn with_last_change(
+        mut self,
+        commit: Option<String>,
+        author: Option<String>,
+        changed_at: Option<u64>,
+    ) -> Self {
+        self.last_commit = commit;
+        self.last_author = author;
+        self.last_changed_at = changed_at;
+        self
+    }
+
+    /// Attach the chunk's stored vector.
+    pub fn with_embedding(mut self, embedding: Vec<f32>) -> Self {
+        self.embedding = Some(embedding);
+        self
+    }
+
+    /// Key identifying the source file this chunk came from, at its ref: the
+    /// same path at two indexed refs is two files.
+    pub fn file_key(&self) -> (&str, &str, Option<&str>) {
+        (
+            &self.repository_url,
+            &self.relative_path,
+            self.git_ref.as_deref(),
+        )
+    }
+
+    /// Key identifying the chunk's text in its file, the same for every ref
+    /// the section is unchanged in.
+    pub fn content_key(&self) -> (&str, &str, &str) {
+        (&self.repository_url, &self.relative_path, &self.content)
+    }
+
+    /// `v1.4 (0123456789)`, or the ref alone when the commit is unknown.
+    pub fn version(&self) -> Option<String> {
+        let git_ref = self.git_ref.as_deref()?;
+        Some(match self.git_commit.as_deref() {
+            Some(commit) if !commit.starts_with(git_ref) => {
+                format!("{} ({})", git_ref, &commit[..commit.len().min(10)])
+            }
+            _ => git_ref.to_string(),
+        })
+    }
+
+    /// A human-readable location: "relative/path.md # Heading > Subheading",
+    /// followed by "@ v1.4 (0123456789)" for chunks ingested at a ref.
     pub fn location(&self) -> String {
-        if self.heading_path.is_empty() {
+        let location = if self.heading_path.is_empty() {
             self.relative_path.clone()
         } else {
             format!("{} # {}", self.relative_path, self.heading_path)
+        };
+        match self.version() {
+            Some(version) => format!("{} @ {}", location, version),
+            None => location,
         }
     }
 
+    /// "changed 2024-03-01 by Ada (0123456789)", when blame is known.
+    pub fn last_change(&self) -> Option<String> {
+        let changed_at = self.last_changed_at?;
+        let mut text = format!("changed {}", format_date(changed_at));
+        if let Some(author) = &self.last_author {
+            text.push_str(&format!(" by {}", author));
+        }
+        if let Some(commit) = &self.last_commit {
+            text.push_str(&format!(" ({})", &commit[..commit.len().min(10)]));
+        }
+        Some(text)
+    }
+
     /// Format as a summary string for display
     pub fn format_summary(&self, max_content_len: usize) -> String {
         let content_preview = truncate_chars(&self.content, max_content_len);
+        let changed = self
+            .last_change()
+            .map(|change| format!(" | {}", change))
+            .unwrap_or_default();
 
         format!(
-            "Score: {:.4} | {} ({})\n{}\n",
+            "Score: {:.4} | {} ({}){}\n{}\n",
             self.score,
             self.location(),
             self.repository_url,
+            changed,
             content_preview
         )
     }
 }
 
+/// Search results for tests in other modules: start from
+/// [`SearchResult::fixture`] and set what the test is about.
+#[cfg(test)]
+impl SearchResult {
+    /// Chunk 0 of `{id}.md` in a fixed repository, without content, scoring
+    /// 0.5.
+    pub(crate) fn fixture(id: &str) -> Self {
+        Self::new(
+            id.to_string(),
+            SearchResultPaths {
+                file_path: format!("/repo/{id}.md"),
+                relative_path: format!("{id}.md"),
+                heading_path: String::new(),
+                chunk_index: 0,
+            },
+            String::new(),
+            "https://github.com/x/y".to_string(),
+            SearchResultScoring {
+                score: 0.5,
+                distance: None,
+            },
+            SearchResultFileMetadata {
+                file_size: 0,
+                last_modified: 0,
+            },
+        )
+    }
+
+    pub(crate) fn with_score(mut self, score: f32) -> Self {
+        self.score = score;
+        self
+    }
+
+    pub(crate) fn with_content(mut self, content: &str) -> Self {
+        self.content = content.to_string();
+        self
+    }
+
+    /// Another chunk of the same file, with the id `{relative_path}#{index}`.
+    pub(crate) fn with_chunk_index(mut self, index: u32) -> Self {
+        self.id = format!("{}#{}", self.relative_path, index);
+        self.chunk_index = index;
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,6 +289,7 @@ mod tests {
                 file_path: "/path/to/file.md".to_string(),
                 relative_path: "file.md".to_string(),
                 heading_path: String::new(),
+                chunk_index: 0,
             },
             "Test content".to_string(),
             "https://github.com/example/repo".to_string(),
@@ -154,6 +316,7 @@ mod tests {
                 file_path: "/path/to/file.md".to_string(),
                 relative_path: "docs/readme.md".to_string(),
                 heading_path: String::new(),
+                chunk_index: 0,
             },
             "This is a very long content that will be truncated".to_string(),
             "https://github.com/example/repo".to_string(),
@@ -171,5 +334,56 @@ mod tests {
         assert!(summary.contains("0.8700"));
         assert!(summary.contains("docs/readme.md"));
         assert!(summary.contains("..."));
+        assert!(!summary.contains("changed"));
+
+        let blamed = result.with_last_change(
+            Some("0123456789abcdef0123456789abcdef01234567".to_string()),
+            Some("Ada".to_string()),
+            Some(1_700_000_000),
+        );
+        assert!(
+            blamed
+                .format_summary(20)
+                .contains(" | changed 2023-11-14 by Ada (0123456789)\n")
+        );
+    }
+
+    #[test]
+    fn test_location_names_the_version() {
+        let result = SearchResult::new(
+            "abc123".to_string(),
+            SearchResultPaths {
+                file_path: "/path/to/file.md".to_string(),
+                relative_path: "docs/setup.md".to_string(),
+                heading_path: "Install".to_string(),
+                chunk_index: 0,
+            },
+            "Run the installer".to_string(),
+            "https://github.com/example/repo".to_string(),
+            SearchResultScoring {
+                score: 0.5,
+                distance: None,
+            },
+            SearchResultFileMetadata {
+                file_size: 100,
+                last_modified: 1_234_567_890,
+            },
+        );
+        assert_eq!(result.location(), "docs/setup.md # Install");
+
+        let tagged = result.clone().with_reference(
+            Some("v1.4".to_string()),
+            Some("0123456789abcdef0123456789abcdef01234567".to_string()),
+        );
+        assert_eq!(
+            tagged.location(),
+            "docs/setup.md # Install @ v1.4 (0123456789)"
+        );
+
+        let pinned = result.with_reference(
+            Some("0123456".to_string()),
+            Some("0123456789abcdef0123456789abcdef01234567".to_string()),
+        );
+        assert_eq!(pinned.location(), "docs/setup.md # Install @ 0123456");
     }
 }
@@ -46,10 +46,11 @@ impl FrontmatterParser {
 
         if let Yaml::Hash(hash) = &docs[0] {
             for (key, value) in hash {
-                if let (Yaml::String(k), Yaml::String(v)) = (key, value) {
-                    fields.insert(k.clone(), v.clone());
-                } else if let Yaml::String(k) = key {
-                    fields.insert(k.clone(), format!("{:?}", value));
+                if let Yaml::String(k) = key {
+                    let rendered = scalar(value)
+                        .or_else(|| list(value))
+                        .unwrap_or_else(|| format!("{:?}", value));
+                    fields.insert(k.clone(), rendered);
                 }
             }
         }
@@ -65,6 +66,44 @@ impl FrontmatterParser {
     }
 }
 
+impl Frontmatter {
+    /// A comma-separated field (or YAML list) split into trimmed items.
+    pub fn list(&self, key: &str) -> Vec<String> {
+        self.fields
+            .get(key)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn scalar(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// YAML sequences of scalars are stored comma-separated.
+fn list(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::Array(items) => items
+            .iter()
+            .map(scalar)
+            .collect::<Option<Vec<_>>>()
+            .map(|items| items.join(", ")),
+        _ => None,
+    }
+}
+
 impl Default for FrontmatterParser {
     fn default() -> Self {
         Self::new()
@@ -97,6 +136,18 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_list_fields() {
+        let parser = FrontmatterParser::new();
+        let content = "---\ntags: [rust, CLI]\nkeywords: search, docs\nversion: 2\n---\nBody";
+
+        let (frontmatter, _) = parser.extract(content).unwrap().unwrap();
+        assert_eq!(frontmatter.list("tags"), vec!["rust", "CLI"]);
+        assert_eq!(frontmatter.list("keywords"), vec!["search", "docs"]);
+        assert_eq!(frontmatter.fields.get("version"), Some(&"2".to_string()));
+        assert!(frontmatter.list("missing").is_empty());
+    }
+
     #[test]
     fn test_get_field() {
         let parser = FrontmatterParser::new();
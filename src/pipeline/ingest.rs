@@ -0,0 +1,390 @@
+// file: src/pipeline/ingest.rs
+// description: concurrent file ingestion shared by the cli and the mcp server
+// reference: https://docs.rs/tokio/latest/tokio/sync/mpsc/fn.channel.html
+
+use crate::config::{Config, RepositoryConfig};
+use crate::database::runs::{FileEvent, FileStatus, RunRecorder};
+use crate::database::{
+    BatchInserter, DocumentWriter, Embedder, LanceDbClient, MaintenanceOptions, PreparedFile,
+    SchemaManager, TableMaintainer,
+};
+use crate::error::{PipelineError, Result};
+use crate::parser::{ChunkOptions, MarkdownNormalizer, MarkdownParser};
+use crate::repository::{
+    BlameIndex, Blamer, FileClassifier, ReferenceKind, ResolvedReference, ScannedFile,
+    SubmoduleIndex,
+};
+use crate::utils::validation::Validator;
+use futures::stream::{self, StreamExt};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+/// What every file of one ingest shares besides the database and the
+/// configuration.
+#[derive(Default)]
+pub struct IngestContext {
+    /// The ref the files were read from; `None` for a plain directory.
+    pub reference: Option<ResolvedReference>,
+    pub submodules: Option<Arc<SubmoduleIndex>>,
+    pub blame: Option<Arc<BlameIndex>>,
+    pub recorder: Option<Arc<RunRecorder>>,
+}
+
+impl IngestContext {
+    /// Chunks are stored per ref; a plain directory has none, so its
+    /// reference is dropped.
+    pub fn new(reference: Option<ResolvedReference>) -> Self {
+        Self {
+            reference: reference.filter(|reference| reference.kind != ReferenceKind::Directory),
+            ..Self::default()
+        }
+    }
+
+    /// With `submodules.enabled`, attribute files to the submodules checked
+    /// out under `workdir` and drop the files their filters exclude.
+    pub fn with_submodules(
+        mut self,
+        config: &RepositoryConfig,
+        workdir: &Path,
+        files: &mut Vec<ScannedFile>,
+    ) -> Result<Self> {
+        if config.submodules.enabled {
+            let index = SubmoduleIndex::discover(workdir, &config.submodules)?;
+            info!(
+                "Found {} checked-out submodule(s)",
+                index.submodules().len()
+            );
+            files.retain(|file| index.includes(&file.relative_path));
+            self.submodules = Some(Arc::new(index));
+        }
+        Ok(self)
+    }
+
+    /// With `blame.enabled`, blame `files` at the reference's commit. Blame
+    /// is read from history, so a plain directory has none; a failed blame
+    /// only costs the chunks their commit dates.
+    pub fn with_blame(
+        mut self,
+        config: &RepositoryConfig,
+        workdir: &Path,
+        files: &[ScannedFile],
+    ) -> Self {
+        if let Some(reference) = &self.reference
+            && config.blame.enabled
+        {
+            let paths = files.iter().map(|file| file.relative_path.as_str());
+            match Blamer::new(config.blame.clone()).blame(workdir, &reference.commit, paths) {
+                Ok(index) => self.blame = Some(Arc::new(index)),
+                Err(e) => warn!(
+                    "Failed to blame files; storing chunks without commit dates: {}",
+                    e
+                ),
+            }
+        }
+        self
+    }
+}
+
+/// Files that made it into the table and files that did not.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IngestReport {
+    pub processed: usize,
+    pub failed: usize,
+}
+
+/// Workers read, chunk and embed files concurrently and send the prepared
+/// documents over a bounded channel to a single writer, which batches them
+/// into `database.batch_size`-row upserts.
+pub async fn process_files(
+    client: &LanceDbClient,
+    config: &Config,
+    files: Vec<ScannedFile>,
+    context: IngestContext,
+) -> Result<IngestReport> {
+    let IngestContext {
+        reference,
+        submodules,
+        blame,
+        recorder,
+    } = context;
+    let markdown_parser = Arc::new(MarkdownParser::new());
+    let normalizer = Arc::new(MarkdownNormalizer::new());
+    // One scheduler for the whole run, so chunks from concurrently processed
+    // files share embedding requests and a single set of rate limits.
+    let embedder = Embedder::from_config(client, &config.embedding);
+    let dimension = embedder.client.dimension();
+    let classifier = Arc::new(FileClassifier::new(
+        config.extraction.categories.clone(),
+        config.extraction.topics.clone(),
+    ));
+
+    // Sections unchanged since another stored ref copy that ref's vectors.
+    let reuse_vectors = match &reference {
+        Some(reference) => SchemaManager::new(client)
+            .stored_vectors_reusable(&config.repository.repository_id(), &reference.name)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to check for stored vectors; embedding every chunk: {}",
+                    e
+                );
+                false
+            }),
+        None => false,
+    };
+
+    let parallel_workers = config.pipeline.parallel_workers.max(1);
+    // Enough headroom that workers rarely wait on the writer, while bounding
+    // how many embedded files can pile up in memory.
+    let (tx, mut rx) = mpsc::channel::<PreparedFile>(parallel_workers * 2);
+
+    let producer = async {
+        // Stop taking new files once interrupted; in-flight ones finish.
+        let pending = files
+            .into_iter()
+            .take_while(|_| !gix::interrupt::is_triggered());
+        let results = stream::iter(pending.map(|file| {
+            let markdown_parser = Arc::clone(&markdown_parser);
+            let normalizer = Arc::clone(&normalizer);
+            let embedder = embedder.clone();
+            let classifier = Arc::clone(&classifier);
+            let submodules = submodules.clone();
+            let reference = reference.clone();
+            let blame = blame.clone();
+            let recorder = recorder.clone();
+            let tx = tx.clone();
+
+            async move {
+                let file_start = Instant::now();
+                let mut inserter = BatchInserter::from_embedder(client, &embedder)
+                    .with_options(ChunkOptions::default(), config.embedding.allow_fallback)
+                    .with_classifier(classifier)
+                    .with_stored_vectors(reuse_vectors);
+                if let Some(submodules) = submodules {
+                    inserter = inserter.with_submodules(submodules);
+                }
+                if let Some(reference) = reference {
+                    inserter = inserter.with_reference(reference);
+                }
+                if let Some(blame) = blame {
+                    inserter = inserter.with_blame(blame);
+                }
+                if let Some(recorder) = recorder {
+                    inserter = inserter.with_run_recorder(recorder);
+                }
+
+                let prepared = process_single_file(
+                    &inserter,
+                    markdown_parser.as_ref(),
+                    normalizer.as_ref(),
+                    config,
+                    &file,
+                )
+                .await;
+                let processing_time = file_start.elapsed().as_millis() as u32;
+
+                let result = match prepared {
+                    Ok(prepared) => {
+                        let chunks = prepared.documents.len();
+                        // Recorded before the writer sees the file: recording
+                        // resets the file's commit, so it must not come after
+                        // the writer has committed it.
+                        log_file_event(&inserter, &file, processing_time, &Ok(chunks)).await;
+                        let sent = tx.send(prepared).await.map(|_| chunks).map_err(|_| {
+                            PipelineError::Database(
+                                "Writer stopped before the file was queued".to_string(),
+                            )
+                        });
+                        if sent.is_err() {
+                            log_file_event(&inserter, &file, processing_time, &sent).await;
+                        }
+                        sent
+                    }
+                    Err(e) => {
+                        let failed = Err(e);
+                        log_file_event(&inserter, &file, processing_time, &failed).await;
+                        failed
+                    }
+                };
+
+                (file, result, processing_time)
+            }
+        }))
+        .buffer_unordered(parallel_workers)
+        .collect::<Vec<_>>()
+        .await;
+
+        // Close the channel so the writer drains and finishes.
+        drop(tx);
+        results
+    };
+
+    let consumer = async {
+        let mut writer = DocumentWriter::new(client, dimension);
+        if let Some(recorder) = &recorder {
+            writer = writer.with_run_recorder(Arc::clone(recorder));
+        }
+        // Write failures are recorded per file in the writer's stats.
+        while let Some(prepared) = rx.recv().await {
+            let _ = writer.push(prepared).await;
+        }
+        let _ = writer.flush().await;
+        writer.into_stats()
+    };
+
+    let (results, write_stats) = tokio::join!(producer, consumer);
+
+    let mut report = IngestReport::default();
+
+    for (file, result, processing_time) in results {
+        match result {
+            Ok(_) => {
+                report.processed += 1;
+                info!("Processed: {} ({} ms)", file.relative_path, processing_time);
+            }
+            Err(e) => {
+                report.failed += 1;
+                error!("Failed to process {}: {}", file.relative_path, e);
+            }
+        }
+    }
+
+    for (relative_path, e) in &write_stats.failed_files {
+        error!("Failed to write {}: {}", relative_path, e);
+        if let Some(recorder) = &recorder {
+            recorder.mark_failed(relative_path, e).await;
+        }
+    }
+    let write_failures = write_stats.failed_files.len().min(report.processed);
+    report.processed -= write_failures;
+    report.failed += write_failures;
+
+    info!(
+        "Wrote {} chunk(s) from {} file(s) in {} batch(es)",
+        write_stats.rows_written, write_stats.files_written, write_stats.batches
+    );
+
+    if write_stats.degraded_files > 0 {
+        warn!(
+            "{} file(s) were written with non-semantic fallback vectors",
+            write_stats.degraded_files
+        );
+    }
+    if write_stats.files_written > 0
+        && let Err(e) = SchemaManager::new(client)
+            .update_embedding_provenance(&config.embedding, write_stats.degraded_files > 0)
+            .await
+    {
+        warn!("Failed to record embedding provenance: {}", e);
+    }
+
+    embedder.flush_cache().await;
+
+    Ok(report)
+}
+
+/// Compact, prune and reindex the table after an ingest when
+/// `database.maintenance.after_ingest` is set; otherwise only build the
+/// vector index once the table is large enough. Failures are logged, since
+/// the ingested rows are already committed.
+pub async fn maintain_after_ingest(client: &LanceDbClient, config: &Config) {
+    if config.database.maintenance.after_ingest {
+        // Maintenance's reindex step also covers automatic index creation.
+        let options = MaintenanceOptions {
+            compact: true,
+            prune: true,
+            reindex: true,
+            retention_days: config.database.maintenance.retention_days,
+        };
+        match TableMaintainer::new(client).run(options).await {
+            Ok(report) => info!("Post-ingest maintenance:\n{}", report),
+            Err(e) => warn!("Post-ingest maintenance failed: {}", e),
+        }
+    } else {
+        match SchemaManager::new(client)
+            .ensure_vector_index(&config.database.index)
+            .await
+        {
+            Ok(Some(status)) => info!("Vector index: {}", status),
+            Ok(None) => {}
+            Err(e) => warn!("Automatic vector indexing failed: {}", e),
+        }
+    }
+}
+
+/// Record a file's outcome in the log and the run recorder.
+async fn log_file_event(
+    inserter: &BatchInserter<'_>,
+    file: &ScannedFile,
+    processing_time: u32,
+    result: &Result<usize>,
+) {
+    let event = FileEvent {
+        relative_path: file.relative_path.clone(),
+        status: if result.is_ok() {
+            FileStatus::Success
+        } else {
+            FileStatus::Failed
+        },
+        error: result.as_ref().err().map(|err| err.to_string()),
+        chunks: *result.as_ref().unwrap_or(&0) as u32,
+        duration_ms: processing_time as u64,
+        bytes: file.size,
+    };
+
+    if let Err(log_err) = inserter.log_processing(event).await {
+        error!(
+            "Failed to log processing result for {}: {}",
+            file.relative_path, log_err
+        );
+    }
+}
+
+async fn process_single_file(
+    inserter: &BatchInserter<'_>,
+    markdown_parser: &MarkdownParser,
+    normalizer: &MarkdownNormalizer,
+    config: &Config,
+    file: &ScannedFile,
+) -> Result<PreparedFile> {
+    Validator::validate_file_path(&file.path)?;
+
+    let content =
+        std::fs::read_to_string(&file.path).map_err(|source| PipelineError::FileOperation {
+            path: file.path.clone(),
+            source,
+        })?;
+
+    Validator::validate_content_not_empty(&content)?;
+
+    let normalized_content = if config.extraction.normalize_markdown {
+        normalizer.normalize(&content)?
+    } else {
+        content
+    };
+
+    // Validate markdown structure
+    markdown_parser.parse(&normalized_content)?;
+
+    let prepared = inserter
+        .prepare_file(
+            &file.path.display().to_string(),
+            &file.relative_path,
+            &normalized_content,
+            file.modified,
+            &config.repository.repository_id(),
+            config.extraction.normalize_markdown,
+        )
+        .await?;
+
+    debug!(
+        "Prepared {} chunk(s) for {}",
+        prepared.documents.len(),
+        file.relative_path
+    );
+
+    Ok(prepared)
+}
@@ -5,8 +5,10 @@
 // These modules are currently disabled as they depend on removed infosec extractors
 // mod orchestrator;
 // mod processor;
+mod ingest;
 mod progress;
 
 // pub use orchestrator::PipelineOrchestrator;
 // pub use processor::{FileProcessor, ProcessingResult};
+pub use ingest::{IngestContext, IngestReport, maintain_after_ingest, process_files};
 pub use progress::{PipelineStats, ProgressTracker};
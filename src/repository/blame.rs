@@ -0,0 +1,413 @@
+// file: src/repository/blame.rs
+// description: attribute every line of the ingested files to the commit that last changed it
+// reference: https://git-scm.com/docs/git-blame
+
+use crate::config::BlameConfig;
+use crate::error::{PipelineError, Result};
+use crate::repository::history::{BINARY_PROBE_BYTES, tree_changes};
+use crate::utils::diff::{LineChange, line_ops};
+use gix::bstr::ByteSlice;
+use gix::diff::tree::recorder::Change;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// The commit a line was last changed in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LineCommit {
+    pub commit: String,
+    pub author: String,
+    /// Author time, seconds since the epoch.
+    pub authored_at: u64,
+}
+
+/// The lines of one file with the commit each was last changed in.
+#[derive(Debug, Clone)]
+pub struct FileBlame {
+    /// Trimmed, non-blank line text to the (0-based) lines holding it.
+    positions: HashMap<String, Vec<usize>>,
+    /// Index into `commits` per line; `None` for lines older than the walk.
+    owners: Vec<Option<usize>>,
+    commits: Arc<Vec<LineCommit>>,
+}
+
+impl FileBlame {
+    fn new(lines: &[String], owners: Vec<Option<usize>>, commits: Arc<Vec<LineCommit>>) -> Self {
+        let mut positions: HashMap<String, Vec<usize>> = HashMap::new();
+        for (idx, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            if !line.is_empty() {
+                positions.entry(line.to_string()).or_default().push(idx);
+            }
+        }
+        Self {
+            positions,
+            owners,
+            commits,
+        }
+    }
+
+    /// The newest commit among the lines of `content`, a chunk of the file.
+    ///
+    /// Chunks may be normalized, so they are located by their lines rather
+    /// than by offset: the span between the first and last chunk line that
+    /// occurs once in the file (or, failing that, at all). A chunk none of
+    /// whose lines are found is attributed like the whole file. Undated
+    /// lines predate every dated one, so only a span without any dated line
+    /// is `None`.
+    pub fn last_change(&self, content: &str) -> Option<&LineCommit> {
+        let matches: Vec<&Vec<usize>> = content
+            .lines()
+            .filter_map(|line| self.positions.get(line.trim()))
+            .collect();
+        let unique: Vec<usize> = matches
+            .iter()
+            .filter(|lines| lines.len() == 1)
+            .map(|lines| lines[0])
+            .collect();
+        let span: Vec<usize> = if unique.is_empty() {
+            matches.into_iter().flatten().copied().collect()
+        } else {
+            unique
+        };
+        let lines = match (span.iter().min(), span.iter().max()) {
+            (Some(&first), Some(&last)) => &self.owners[first..=last],
+            _ => &self.owners[..],
+        };
+        lines
+            .iter()
+            .flatten()
+            .map(|&owner| &self.commits[owner])
+            .max_by_key(|commit| commit.authored_at)
+    }
+
+    /// The newest commit of any line of the file.
+    pub fn newest(&self) -> Option<&LineCommit> {
+        self.last_change("")
+    }
+}
+
+/// Blame of every file that could be attributed, keyed by path relative to
+/// the repository root.
+#[derive(Debug, Clone, Default)]
+pub struct BlameIndex {
+    files: HashMap<String, FileBlame>,
+}
+
+impl BlameIndex {
+    pub fn get(&self, relative_path: &str) -> Option<&FileBlame> {
+        self.files.get(relative_path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// A file whose lines are still being followed back through history.
+struct Pending {
+    /// The file as of the commit being visited.
+    lines: Vec<String>,
+    /// For each line of `lines`, the line of the blamed version it is.
+    origins: Vec<usize>,
+}
+
+/// Line-level blame along the first-parent history, like
+/// `git blame --first-parent`: changes merged from a side branch are
+/// attributed to the merge. Renames are not followed, so the lines of a
+/// renamed file belong to the commit that renamed it.
+pub struct Blamer {
+    config: BlameConfig,
+}
+
+impl Blamer {
+    pub fn new(config: BlameConfig) -> Self {
+        Self { config }
+    }
+
+    /// Blame `paths` as of `commit` in the repository at `repo_path`, with a
+    /// single walk for all files. Paths that are missing, binary, too large
+    /// or inside a submodule are left out.
+    pub fn blame<'p>(
+        &self,
+        repo_path: &Path,
+        commit: &str,
+        paths: impl IntoIterator<Item = &'p str>,
+    ) -> Result<BlameIndex> {
+        let repo = gix::open(repo_path)?;
+        let tip = repo
+            .rev_parse_single(commit)
+            .map_err(|e| {
+                PipelineError::GitReference(format!("Cannot resolve '{}': {}", commit, e))
+            })?
+            .object()?
+            .peel_to_commit()
+            .map_err(|e| {
+                PipelineError::GitReference(format!("'{}' is not a commit: {}", commit, e))
+            })?;
+        let tree = tip.tree().map_err(git_error)?;
+
+        let mut blamed: HashMap<String, Vec<String>> = HashMap::new();
+        let mut pending: HashMap<String, Pending> = HashMap::new();
+        for path in paths {
+            // A path below a submodule leads to a commit this repository
+            // does not have, which fails the lookup.
+            let entry = match tree.lookup_entry_by_path(path) {
+                Ok(Some(entry)) => entry,
+                Ok(None) => continue,
+                Err(e) => {
+                    debug!("Not blaming {}: {}", path, e);
+                    continue;
+                }
+            };
+            if !entry.mode().is_blob() {
+                continue;
+            }
+            let Some(lines) = self.text_lines(&repo, entry.object_id())? else {
+                continue;
+            };
+            pending.insert(
+                path.to_string(),
+                Pending {
+                    origins: (0..lines.len()).collect(),
+                    lines: lines.clone(),
+                },
+            );
+            blamed.insert(path.to_string(), lines);
+        }
+
+        let mut owners: HashMap<String, Vec<Option<usize>>> = blamed
+            .iter()
+            .map(|(path, lines)| (path.clone(), vec![None; lines.len()]))
+            .collect();
+        let mut commits: Vec<LineCommit> = Vec::new();
+        let mut commit_index: HashMap<gix::ObjectId, usize> = HashMap::new();
+        let mut owner_of = |id: gix::ObjectId| -> Result<usize> {
+            if let Some(&idx) = commit_index.get(&id) {
+                return Ok(idx);
+            }
+            commits.push(line_commit(&repo, id)?);
+            commit_index.insert(id, commits.len() - 1);
+            Ok(commits.len() - 1)
+        };
+
+        let mut current = tip.id;
+        let mut visited = 0;
+        while !pending.is_empty() {
+            visited += 1;
+            let Some(parent) = first_parent(&repo, current)? else {
+                // Root commit: whatever is left was added here.
+                let owner = owner_of(current)?;
+                for (path, file) in pending.drain() {
+                    let slots = owners.get_mut(&path).expect("blamed path");
+                    for origin in file.origins {
+                        slots[origin] = Some(owner);
+                    }
+                }
+                break;
+            };
+            if visited >= self.config.max_commits || !repo.has_object(parent) {
+                // Walk limit or shallow boundary: whatever is left was last
+                // changed here or earlier, so it stays undated.
+                debug!(
+                    "Stopped blaming at {}; {} file(s) keep undated lines",
+                    current,
+                    pending.len()
+                );
+                break;
+            }
+
+            let old_tree = commit_tree(&repo, parent)?;
+            let new_tree = commit_tree(&repo, current)?;
+            for change in tree_changes(&repo, Some(old_tree), new_tree)? {
+                let (path, previous) = match change {
+                    Change::Addition { path, .. } => (path, None),
+                    Change::Modification {
+                        path,
+                        previous_entry_mode,
+                        previous_oid,
+                        ..
+                    } if previous_entry_mode.is_blob() => (path, Some(previous_oid)),
+                    Change::Modification { path, .. } => (path, None),
+                    Change::Deletion { .. } => continue,
+                };
+                let path = path.to_str_lossy();
+                let Some(file) = pending.get_mut(path.as_ref()) else {
+                    continue;
+                };
+                let old_lines = match previous {
+                    Some(oid) => self.text_lines(&repo, oid)?,
+                    None => None,
+                };
+                let owner = owner_of(current)?;
+                let slots = owners.get_mut(path.as_ref()).expect("blamed path");
+                let Some(old_lines) = old_lines else {
+                    // Added here (or was binary before): every line is new.
+                    for &origin in &file.origins {
+                        slots[origin] = Some(owner);
+                    }
+                    pending.remove(path.as_ref());
+                    continue;
+                };
+
+                let old: Vec<&str> = old_lines.iter().map(String::as_str).collect();
+                let new: Vec<&str> = file.lines.iter().map(String::as_str).collect();
+                let mut origins = vec![0; old.len()];
+                let mut kept = vec![false; old.len()];
+                for op in line_ops(&old, &new) {
+                    match (op.change, op.old, op.new) {
+                        (LineChange::Equal, Some(old), Some(new)) => {
+                            origins[old] = file.origins[new];
+                            kept[old] = true;
+                        }
+                        (LineChange::Added, _, Some(new)) => {
+                            slots[file.origins[new]] = Some(owner);
+                        }
+                        _ => {}
+                    }
+                }
+                let (lines, origins): (Vec<String>, Vec<usize>) = old_lines
+                    .into_iter()
+                    .zip(origins)
+                    .zip(kept)
+                    .filter(|(_, kept)| *kept)
+                    .map(|(pair, _)| pair)
+                    .unzip();
+                if origins.is_empty() {
+                    pending.remove(path.as_ref());
+                } else {
+                    *file = Pending { lines, origins };
+                }
+            }
+            current = parent;
+        }
+
+        let commits = Arc::new(commits);
+        let files: HashMap<String, FileBlame> = blamed
+            .into_iter()
+            .map(|(path, lines)| {
+                let slots = owners.remove(&path).unwrap_or_default();
+                let blame = FileBlame::new(&lines, slots, Arc::clone(&commits));
+                (path, blame)
+            })
+            .filter(|(_, blame)| blame.owners.iter().any(Option::is_some))
+            .collect();
+        info!(
+            "Blamed {} file(s) over {} commit(s), {} distinct",
+            files.len(),
+            visited,
+            commits.len()
+        );
+        Ok(BlameIndex { files })
+    }
+
+    /// A blob's lines; `None` for binary and oversized blobs.
+    fn text_lines(
+        &self,
+        repo: &gix::Repository,
+        oid: gix::ObjectId,
+    ) -> Result<Option<Vec<String>>> {
+        let blob = repo.find_object(oid)?;
+        if blob.data.len() as u64 > self.config.max_file_bytes
+            || blob.data[..blob.data.len().min(BINARY_PROBE_BYTES)].contains(&0)
+        {
+            debug!("Not blaming binary or oversized blob {}", oid);
+            return Ok(None);
+        }
+        Ok(Some(
+            String::from_utf8_lossy(&blob.data)
+                .lines()
+                .map(str::to_string)
+                .collect(),
+        ))
+    }
+}
+
+/// The first parent of `id`; `None` for a root commit. At the boundary of a
+/// shallow clone the parent is not in the object database.
+fn first_parent(repo: &gix::Repository, id: gix::ObjectId) -> Result<Option<gix::ObjectId>> {
+    let commit = repo.find_object(id)?.try_into_commit()?;
+    Ok(commit.parent_ids().next().map(|parent| parent.detach()))
+}
+
+fn commit_tree(repo: &gix::Repository, id: gix::ObjectId) -> Result<gix::ObjectId> {
+    let commit = repo.find_object(id)?.try_into_commit()?;
+    Ok(commit.tree_id().map_err(git_error)?.detach())
+}
+
+fn line_commit(repo: &gix::Repository, id: gix::ObjectId) -> Result<LineCommit> {
+    let commit = repo.find_object(id)?.try_into_commit()?;
+    let author = commit.author().map_err(git_error)?.trim();
+    Ok(LineCommit {
+        commit: id.to_string(),
+        author: author.name.to_str_lossy().into_owned(),
+        authored_at: author.seconds().max(0) as u64,
+    })
+}
+
+fn git_error(e: impl std::fmt::Display) -> PipelineError {
+    PipelineError::GitObject(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(name: &str, authored_at: u64) -> LineCommit {
+        LineCommit {
+            commit: name.to_string(),
+            author: "Ada".to_string(),
+            authored_at,
+        }
+    }
+
+    #[test]
+    fn chunks_take_the_newest_commit_of_their_lines() {
+        let lines: Vec<String> =
+            "# Guide\nintro\n\n## Install\nrun make\n```\n\n## Usage\nrun it\n```"
+                .lines()
+                .map(str::to_string)
+                .collect();
+        let commits = Arc::new(vec![commit("old", 100), commit("new", 200)]);
+        let owners = [0, 0, 0, 0, 0, 0, 0, 1, 1, 0].map(Some).to_vec();
+        let blame = FileBlame::new(&lines, owners, commits);
+
+        // "```" occurs twice in the file, so only unique lines locate the chunk.
+        assert_eq!(
+            blame
+                .last_change("## Install\nrun make\n```")
+                .unwrap()
+                .commit,
+            "old"
+        );
+        assert_eq!(
+            blame.last_change("## Usage\n  run it\n").unwrap().commit,
+            "new"
+        );
+        assert_eq!(
+            blame.last_change("rewritten entirely").unwrap().commit,
+            "new"
+        );
+        assert_eq!(blame.newest().unwrap().authored_at, 200);
+    }
+
+    #[test]
+    fn lines_older_than_the_walk_are_undated() {
+        let lines: Vec<String> = "# Guide\nintro\n## Usage\nrun it"
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let commits = Arc::new(vec![commit("new", 200)]);
+        let blame = FileBlame::new(&lines, vec![None, None, Some(0), None], commits);
+
+        assert!(blame.last_change("# Guide\nintro").is_none());
+        assert_eq!(blame.last_change("intro\n## Usage").unwrap().commit, "new");
+        assert_eq!(blame.newest().unwrap().commit, "new");
+    }
+}
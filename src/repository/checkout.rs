@@ -0,0 +1,295 @@
+// file: src/repository/checkout.rs
+// description: worktree checkout and update of a resolved commit, optionally sparse
+// reference: https://docs.rs/gix-worktree-state
+
+use crate::error::{PipelineError, Result};
+use crate::repository::sparse::{self, SparseCone};
+use gix::bstr::ByteSlice;
+use gix::index::entry::{Flags, Mode};
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+/// Files a worktree update wrote or removed, as repository-relative paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WorktreeChanges {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+impl WorktreeChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+}
+
+impl fmt::Display for WorktreeChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} modified, {} deleted",
+            self.added.len(),
+            self.modified.len(),
+            self.deleted.len()
+        )
+    }
+}
+
+/// Write the tree of `commit` into the (empty) worktree of a freshly cloned
+/// `repo` and replace its index. With a `cone`, files outside it are marked
+/// skip-worktree instead of being written.
+pub fn checkout_commit(
+    repo: &gix::Repository,
+    commit: gix::ObjectId,
+    cone: Option<&SparseCone>,
+) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| PipelineError::GitWorktree("Repository has no worktree".to_string()))?;
+    let tree_id = repo.find_object(commit)?.peel_to_tree()?.id;
+
+    let mut index = repo
+        .index_from_tree(&tree_id)
+        .map_err(|e| PipelineError::GitWorktree(format!("Failed to build index: {}", e)))?;
+    let mut skipped = 0;
+    if let Some(cone) = cone {
+        for (entry, path) in index.entries_mut_with_paths() {
+            if !cone.contains(&path.to_string()) {
+                entry.flags |= Flags::SKIP_WORKTREE | Flags::EXTENDED;
+                skipped += 1;
+            }
+        }
+    }
+
+    let files_updated = write_entries(repo, &mut index, workdir, true)?;
+    write_index(&mut index)?;
+
+    match cone {
+        Some(cone) => {
+            sparse::write_sparse_config(repo.git_dir(), cone)?;
+            info!(
+                "Sparse checkout of {}: {} file(s) written, {} skipped",
+                cone.directories().join(", "),
+                files_updated,
+                skipped
+            );
+        }
+        None => info!("Checked out {} file(s)", files_updated),
+    }
+    Ok(())
+}
+
+/// Write the tree of `commit` into the empty directory `destination`
+/// without touching the repository's own worktree or index. With a `cone`,
+/// only files inside it are written. Returns the number of files written.
+pub fn export_tree(
+    repo: &gix::Repository,
+    commit: gix::ObjectId,
+    destination: &Path,
+    cone: Option<&SparseCone>,
+) -> Result<usize> {
+    let tree_id = repo.find_object(commit)?.peel_to_tree()?.id;
+    let mut index = repo
+        .index_from_tree(&tree_id)
+        .map_err(|e| PipelineError::GitWorktree(format!("Failed to build index: {}", e)))?;
+    for (entry, path) in index.entries_mut_with_paths() {
+        let outside_cone = cone.is_some_and(|cone| !cone.contains(&path.to_string()));
+        if outside_cone || entry.mode == Mode::COMMIT {
+            entry.flags |= Flags::SKIP_WORKTREE | Flags::EXTENDED;
+        }
+    }
+
+    std::fs::create_dir_all(destination)?;
+    let files_written = write_entries(repo, &mut index, destination, true)?;
+    debug!(
+        "Exported {} file(s) of {} to {}",
+        files_written,
+        commit,
+        destination.display()
+    );
+    Ok(files_written)
+}
+
+/// Bring an existing worktree from what its index records to the tree of
+/// `commit`: write added and modified files, remove deleted ones and
+/// replace the index. Files the update does not touch are left as they are.
+///
+/// With a `cone`, files outside it are skip-worktree; without one, files
+/// that were skip-worktree before stay that way.
+pub fn update_worktree(
+    repo: &gix::Repository,
+    commit: gix::ObjectId,
+    cone: Option<&SparseCone>,
+) -> Result<WorktreeChanges> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| PipelineError::GitWorktree("Repository has no worktree".to_string()))?;
+    let tree_id = repo.find_object(commit)?.peel_to_tree()?.id;
+
+    let previous = repo
+        .index_or_empty()
+        .map_err(|e| PipelineError::GitWorktree(format!("Failed to read index: {}", e)))?;
+    let mut index = repo
+        .index_from_tree(&tree_id)
+        .map_err(|e| PipelineError::GitWorktree(format!("Failed to build index: {}", e)))?;
+
+    let mut changes = WorktreeChanges::default();
+    // Entries already up to date on disk are hidden from the checkout and
+    // get their previous stat back afterwards.
+    let mut unchanged = Vec::new();
+    for (position, (entry, path)) in index.entries_mut_with_paths().enumerate() {
+        let old = previous.entry_by_path(path);
+        let outside_cone = match cone {
+            Some(cone) => !cone.contains(&path.to_string()),
+            None => old.is_some_and(|old| old.flags.contains(Flags::SKIP_WORKTREE)),
+        };
+        if outside_cone {
+            entry.flags |= Flags::SKIP_WORKTREE | Flags::EXTENDED;
+            continue;
+        }
+        match old.filter(|old| !old.flags.contains(Flags::SKIP_WORKTREE)) {
+            Some(old) if old.id == entry.id && old.mode == entry.mode => {
+                entry.flags |= Flags::SKIP_WORKTREE | Flags::EXTENDED;
+                unchanged.push((position, old.stat));
+            }
+            Some(_) => changes.modified.push(path.to_string()),
+            None => changes.added.push(path.to_string()),
+        }
+    }
+
+    if !changes.added.is_empty() || !changes.modified.is_empty() {
+        write_entries(repo, &mut index, workdir, false)?;
+    }
+    let entries = index.entries_mut();
+    for (position, stat) in unchanged {
+        let entry = &mut entries[position];
+        entry.flags.remove(Flags::SKIP_WORKTREE);
+        entry.stat = stat;
+    }
+
+    for old in previous.entries() {
+        if old.flags.contains(Flags::SKIP_WORKTREE) {
+            continue;
+        }
+        let path = old.path(&previous);
+        let kept = index
+            .entry_by_path(path)
+            .is_some_and(|entry| !entry.flags.contains(Flags::SKIP_WORKTREE));
+        if kept {
+            continue;
+        }
+        if old.mode == Mode::COMMIT {
+            // Submodule checkouts are directories of their own; leave them.
+            warn!("Not removing submodule {}", path);
+            continue;
+        }
+        remove_file(workdir, path.to_str_lossy().as_ref())?;
+        changes.deleted.push(path.to_string());
+    }
+
+    write_index(&mut index)?;
+    if let Some(cone) = cone {
+        sparse::write_sparse_config(repo.git_dir(), cone)?;
+    }
+
+    for path in &changes.added {
+        debug!("A {}", path);
+    }
+    for path in &changes.modified {
+        debug!("M {}", path);
+    }
+    for path in &changes.deleted {
+        debug!("D {}", path);
+    }
+    Ok(changes)
+}
+
+/// Write every entry of `index` that is not skip-worktree into `workdir`,
+/// recording the new stat information. Returns the number of files written.
+fn write_entries(
+    repo: &gix::Repository,
+    index: &mut gix::index::File,
+    workdir: &Path,
+    destination_is_initially_empty: bool,
+) -> Result<usize> {
+    let mut options = repo
+        .checkout_options(gix_worktree::stack::state::attributes::Source::IdMapping)
+        .map_err(|e| PipelineError::GitWorktree(format!("Invalid checkout options: {}", e)))?;
+    options.destination_is_initially_empty = destination_is_initially_empty;
+    options.overwrite_existing = !destination_is_initially_empty;
+
+    let objects =
+        repo.objects.clone().into_arc().map_err(|e| {
+            PipelineError::GitWorktree(format!("Failed to share object store: {}", e))
+        })?;
+    let outcome = gix_worktree_state::checkout(
+        index,
+        workdir,
+        objects,
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        &gix::interrupt::IS_INTERRUPTED,
+        options,
+    )?;
+    Ok(outcome.files_updated)
+}
+
+fn write_index(index: &mut gix::index::File) -> Result<()> {
+    index
+        .write(Default::default())
+        .map_err(|e| PipelineError::GitWorktree(format!("Failed to write index: {}", e)))
+}
+
+/// Remove a file deleted upstream along with directories it leaves empty.
+fn remove_file(workdir: &Path, relative_path: &str) -> Result<()> {
+    let path = workdir.join(relative_path);
+    match std::fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+    let mut dir = path.parent();
+    while let Some(current) = dir
+        && current != workdir
+        && std::fs::remove_dir(current).is_ok()
+    {
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn removing_a_file_prunes_empty_directories() {
+        let temp = TempDir::new().unwrap();
+        let workdir = temp.path();
+        std::fs::create_dir_all(workdir.join("docs/old/deep")).unwrap();
+        std::fs::write(workdir.join("docs/old/deep/page.md"), "gone").unwrap();
+        std::fs::write(workdir.join("docs/index.md"), "kept").unwrap();
+
+        remove_file(workdir, "docs/old/deep/page.md").unwrap();
+        assert!(!workdir.join("docs/old").exists());
+        assert!(workdir.join("docs/index.md").exists());
+
+        // Already gone is not an error.
+        remove_file(workdir, "docs/old/deep/page.md").unwrap();
+    }
+
+    #[test]
+    fn changes_summarize_counts() {
+        let changes = WorktreeChanges {
+            added: vec!["a.md".to_string()],
+            modified: vec!["b.md".to_string(), "c.md".to_string()],
+            deleted: Vec::new(),
+        };
+        assert!(!changes.is_empty());
+        assert_eq!(changes.to_string(), "1 added, 2 modified, 0 deleted");
+        assert!(WorktreeChanges::default().is_empty());
+    }
+}
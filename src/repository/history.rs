@@ -0,0 +1,371 @@
+// file: src/repository/history.rs
+// description: walk commit history with changed paths and per-file diff hunks
+// reference: https://git-scm.com/docs/git-log
+
+use crate::config::HistoryConfig;
+use crate::error::{PipelineError, Result};
+use crate::utils::diff::diff_hunks;
+use gix::bstr::ByteSlice;
+use gix::diff::tree::recorder::Change;
+use gix::revision::walk::Sorting;
+use gix::traverse::commit::simple::CommitTimeOrder;
+use glob::Pattern;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::{debug, info};
+
+/// Unchanged lines kept around each change, as in `git diff`.
+const DIFF_CONTEXT: usize = 3;
+
+/// Longer hunks are cut so a single hunk stays one embedding input.
+const MAX_HUNK_CHARS: usize = 4000;
+
+/// Git treats blobs with a NUL byte in their first 8000 bytes as binary.
+pub(crate) const BINARY_PROBE_BYTES: usize = 8000;
+
+/// A commit with the paths it changed and, optionally, its diff hunks.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitEntry {
+    pub commit: String,
+    pub author: String,
+    pub author_email: String,
+    /// Author time, seconds since the epoch.
+    pub authored_at: u64,
+    pub message: String,
+    /// Paths changed relative to the first parent. Empty when the parent is
+    /// missing, i.e. at the boundary of a shallow clone.
+    pub changed_paths: Vec<String>,
+    pub hunks: Vec<FileHunk>,
+}
+
+/// One hunk of one file's diff.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileHunk {
+    pub path: String,
+    /// Unified diff text, starting with the `@@` header.
+    pub text: String,
+}
+
+impl CommitEntry {
+    /// First line of the message.
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or_default().trim()
+    }
+
+    pub fn short_id(&self) -> &str {
+        &self.commit[..self.commit.len().min(10)]
+    }
+
+    /// Text embedded for the commit: header, message and changed paths.
+    pub fn message_text(&self) -> String {
+        let mut text = format!(
+            "commit {}\nAuthor: {} <{}>\nDate: {}\n\n{}",
+            self.commit,
+            self.author,
+            self.author_email,
+            format_date(self.authored_at),
+            self.message
+        );
+        if !self.changed_paths.is_empty() {
+            text.push_str("\n\nChanged files:\n");
+            text.push_str(&self.changed_paths.join("\n"));
+        }
+        text
+    }
+
+    /// Text embedded for one hunk, prefixed with the commit it belongs to.
+    pub fn hunk_text(&self, hunk: &FileHunk) -> String {
+        format!(
+            "commit {} {}\nFile: {}\n\n{}",
+            self.short_id(),
+            self.summary(),
+            hunk.path,
+            hunk.text
+        )
+    }
+}
+
+pub(crate) fn format_date(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+pub struct HistoryWalker {
+    config: HistoryConfig,
+    diff_paths: Vec<Pattern>,
+}
+
+impl HistoryWalker {
+    /// Fails with [`PipelineError::Validation`] naming the first
+    /// `diff_paths` glob that does not compile.
+    pub fn new(config: HistoryConfig) -> Result<Self> {
+        let diff_paths = config
+            .diff_paths
+            .iter()
+            .map(|p| {
+                Pattern::new(p).map_err(|e| {
+                    PipelineError::Validation(format!(
+                        "repository.history.diff_paths has an invalid glob '{}': {}",
+                        p, e
+                    ))
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { config, diff_paths })
+    }
+
+    /// Walk the configured range of the repository at `path`, newest first,
+    /// visiting at most `max_commits` commits. Commits in `known` count
+    /// towards that limit but are not returned.
+    pub fn walk(&self, path: &Path, known: &HashSet<String>) -> Result<Vec<CommitEntry>> {
+        let repo = gix::open(path)?;
+        let (tip, hidden) = self.range(&repo)?;
+        let walk = repo
+            .rev_walk([tip])
+            .with_hidden(hidden)
+            .sorting(Sorting::ByCommitTime(CommitTimeOrder::NewestFirst))
+            .all()
+            .map_err(|e| PipelineError::GitObject(format!("Failed to walk history: {}", e)))?;
+
+        let mut entries = Vec::new();
+        let mut visited = 0;
+        for info in walk.take(self.config.max_commits) {
+            let info = info
+                .map_err(|e| PipelineError::GitObject(format!("Failed to walk history: {}", e)))?;
+            visited += 1;
+            if known.contains(&info.id.to_string()) {
+                continue;
+            }
+            entries.push(self.entry(&repo, info.id)?);
+        }
+        info!(
+            "Walked {} commit(s), {} not yet indexed",
+            visited,
+            entries.len()
+        );
+        Ok(entries)
+    }
+
+    /// The tip to walk from and the commits to stop at, from `range`:
+    /// `from..to`, `..to`, `from..` (up to HEAD) or a single revision.
+    fn range(&self, repo: &gix::Repository) -> Result<(gix::ObjectId, Vec<gix::ObjectId>)> {
+        let spec = self.config.range.as_deref().unwrap_or("HEAD").trim();
+        let (from, to) = match spec.split_once("..") {
+            Some((from, to)) => (Some(from).filter(|f| !f.is_empty()), to),
+            None => (None, spec),
+        };
+        let to = if to.is_empty() { "HEAD" } else { to };
+        let hidden = from.map(|from| peel(repo, from)).transpose()?;
+        Ok((peel(repo, to)?, hidden.into_iter().collect()))
+    }
+
+    fn entry(&self, repo: &gix::Repository, id: gix::ObjectId) -> Result<CommitEntry> {
+        let commit = repo.find_object(id)?.try_into_commit()?;
+        let decode = |e: gix::objs::decode::Error| {
+            PipelineError::GitObject(format!("Failed to decode commit {}: {}", id, e))
+        };
+        let author = commit.author().map_err(decode)?.trim();
+        let tree = commit.tree_id().map_err(decode)?.detach();
+        let parent_tree = commit.parent_ids().next().map(|parent| {
+            repo.find_object(parent)
+                .ok()
+                .and_then(|object| object.try_into_commit().ok())
+                .and_then(|parent| parent.tree_id().ok().map(|id| id.detach()))
+        });
+
+        let mut entry = CommitEntry {
+            commit: id.to_string(),
+            author: author.name.to_str_lossy().into_owned(),
+            author_email: author.email.to_str_lossy().into_owned(),
+            authored_at: author.seconds().max(0) as u64,
+            message: commit
+                .message_raw_sloppy()
+                .to_str_lossy()
+                .trim()
+                .to_string(),
+            changed_paths: Vec::new(),
+            hunks: Vec::new(),
+        };
+
+        let old_tree = match parent_tree {
+            // Root commit: everything was added.
+            None => None,
+            Some(Some(tree)) => Some(tree),
+            Some(None) => {
+                debug!("Parent of {} is not available (shallow clone)", id);
+                return Ok(entry);
+            }
+        };
+        for change in tree_changes(repo, old_tree, tree)? {
+            let (path, old, new) = match change {
+                Change::Addition {
+                    entry_mode,
+                    oid,
+                    path,
+                    ..
+                } if !entry_mode.is_tree() => (path, None, Some((entry_mode, oid))),
+                Change::Deletion {
+                    entry_mode,
+                    oid,
+                    path,
+                    ..
+                } if !entry_mode.is_tree() => (path, Some((entry_mode, oid)), None),
+                Change::Modification {
+                    previous_entry_mode,
+                    previous_oid,
+                    entry_mode,
+                    oid,
+                    path,
+                } if !entry_mode.is_tree() => (
+                    path,
+                    Some((previous_entry_mode, previous_oid)),
+                    Some((entry_mode, oid)),
+                ),
+                _ => continue,
+            };
+            let path = path.to_str_lossy().into_owned();
+            if self.config.include_diffs && self.should_diff(&path) {
+                let blob = |side: Option<(gix::objs::tree::EntryMode, gix::ObjectId)>| {
+                    self.text_blob(repo, side)
+                };
+                if let (Some(old), Some(new)) = (blob(old)?, blob(new)?) {
+                    entry
+                        .hunks
+                        .extend(
+                            diff_hunks(&old, &new, DIFF_CONTEXT)
+                                .into_iter()
+                                .map(|hunk| FileHunk {
+                                    path: path.clone(),
+                                    text: truncate(hunk.to_string()),
+                                }),
+                        );
+                }
+            }
+            entry.changed_paths.push(path);
+        }
+        entry.changed_paths.sort();
+        Ok(entry)
+    }
+
+    fn should_diff(&self, path: &str) -> bool {
+        self.diff_paths.is_empty() || self.diff_paths.iter().any(|p| p.matches(path))
+    }
+
+    /// Content of one side of a change: empty when the file does not exist
+    /// on that side, `None` for gitlinks, binary and oversized files.
+    fn text_blob(
+        &self,
+        repo: &gix::Repository,
+        side: Option<(gix::objs::tree::EntryMode, gix::ObjectId)>,
+    ) -> Result<Option<String>> {
+        let Some((mode, oid)) = side else {
+            return Ok(Some(String::new()));
+        };
+        if !mode.is_blob() {
+            return Ok(None);
+        }
+        let blob = repo.find_object(oid)?;
+        if blob.data.len() as u64 > self.config.max_diff_bytes
+            || blob.data[..blob.data.len().min(BINARY_PROBE_BYTES)].contains(&0)
+        {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&blob.data).into_owned()))
+    }
+}
+
+fn peel(repo: &gix::Repository, name: &str) -> Result<gix::ObjectId> {
+    let id = repo
+        .rev_parse_single(name)
+        .map_err(|e| PipelineError::GitReference(format!("Cannot resolve '{}': {}", name, e)))?;
+    let commit = id
+        .object()?
+        .peel_to_commit()
+        .map_err(|e| PipelineError::GitReference(format!("'{}' is not a commit: {}", name, e)))?;
+    Ok(commit.id)
+}
+
+/// Files and directories that differ between two trees; `None` is the
+/// empty tree.
+pub(crate) fn tree_changes(
+    repo: &gix::Repository,
+    old: Option<gix::ObjectId>,
+    new: gix::ObjectId,
+) -> Result<Vec<Change>> {
+    let old = old.map(|id| repo.find_object(id)).transpose()?;
+    let new = repo.find_object(new)?;
+    let hash_kind = repo.object_hash();
+    let mut recorder = gix::diff::tree::Recorder::default();
+    gix::diff::tree(
+        gix::objs::TreeRefIter::from_bytes(old.as_ref().map_or(&[][..], |o| &o.data), hash_kind),
+        gix::objs::TreeRefIter::from_bytes(&new.data, hash_kind),
+        gix::diff::tree::State::default(),
+        &repo.objects,
+        &mut recorder,
+    )
+    .map_err(|e| PipelineError::GitObject(format!("Failed to diff trees: {}", e)))?;
+    Ok(recorder.records)
+}
+
+fn truncate(mut text: String) -> String {
+    if text.len() > MAX_HUNK_CHARS {
+        let mut end = MAX_HUNK_CHARS;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n...");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_text_includes_metadata_and_paths() {
+        let entry = CommitEntry {
+            commit: "0123456789abcdef0123456789abcdef01234567".to_string(),
+            author: "Ada".to_string(),
+            author_email: "ada@example.com".to_string(),
+            authored_at: 1_700_000_000,
+            message: "Drop Python 3.8\n\nIt is end of life.".to_string(),
+            changed_paths: vec!["docs/install.md".to_string()],
+            hunks: vec![FileHunk {
+                path: "docs/install.md".to_string(),
+                text: "@@ -1,1 +1,1 @@\n-3.8+\n+3.9+".to_string(),
+            }],
+        };
+
+        assert_eq!(entry.summary(), "Drop Python 3.8");
+        let text = entry.message_text();
+        assert!(text.starts_with("commit 0123456789abcdef"));
+        assert!(text.contains("Author: Ada <ada@example.com>\nDate: 2023-11-14"));
+        assert!(text.ends_with("Changed files:\ndocs/install.md"));
+        assert!(
+            entry
+                .hunk_text(&entry.hunks[0])
+                .starts_with("commit 0123456789 Drop Python 3.8\nFile: docs/install.md\n\n@@")
+        );
+        assert_eq!(
+            truncate("é".repeat(MAX_HUNK_CHARS)).len(),
+            MAX_HUNK_CHARS + 4
+        );
+    }
+
+    #[test]
+    fn invalid_diff_path_globs_are_rejected() {
+        let config = HistoryConfig {
+            diff_paths: vec!["docs/**".to_string(), "docs/[draft".to_string()],
+            ..Default::default()
+        };
+        let error = HistoryWalker::new(config).err().unwrap();
+        assert!(
+            matches!(&error, PipelineError::Validation(message) if message.contains("docs/[draft")),
+            "{error}"
+        );
+    }
+}
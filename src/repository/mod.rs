@@ -2,10 +2,28 @@
 // description: repository operations module exports
 // reference: internal module structure
 
+pub mod blame;
+pub mod checkout;
 pub mod classifier;
+pub mod history;
+pub mod reference;
 pub mod scanner;
+pub mod snapshot;
+pub mod source;
+pub mod sparse;
+pub mod submodule;
 pub mod sync;
+#[cfg(test)]
+pub(crate) mod testing;
 
+pub use blame::{BlameIndex, Blamer, FileBlame, LineCommit};
+pub use checkout::WorktreeChanges;
 pub use classifier::FileClassifier;
+pub use history::{CommitEntry, FileHunk, HistoryWalker};
+pub use reference::{ReferenceKind, ResolvedReference};
 pub use scanner::{FileScanner, ScannedFile};
-pub use sync::RepositorySync;
+pub use snapshot::RefSnapshot;
+pub use source::{LocalVersion, RepositorySource};
+pub use sparse::SparseCone;
+pub use submodule::{SubmoduleIndex, SubmoduleInfo};
+pub use sync::{RepositorySync, SyncReport};
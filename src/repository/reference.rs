@@ -0,0 +1,205 @@
+// file: src/repository/reference.rs
+// description: resolve a configured branch, tag or commit sha to a commit
+// reference: https://git-scm.com/docs/gitrevisions
+
+use crate::error::{PipelineError, Result};
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceKind {
+    Branch,
+    Tag,
+    Commit,
+    /// A local git checkout, indexed as it is on disk.
+    Worktree,
+    /// A local directory without git.
+    Directory,
+}
+
+impl ReferenceKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Branch => "branch",
+            Self::Tag => "tag",
+            Self::Commit => "commit",
+            Self::Worktree => "worktree",
+            Self::Directory => "directory",
+        }
+    }
+
+    /// Tags and commits are pinned: they are checked out with a detached HEAD
+    /// and never move on sync.
+    pub fn is_pinned(self) -> bool {
+        matches!(self, Self::Tag | Self::Commit)
+    }
+}
+
+/// What `repository.branch` resolved to in a local clone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResolvedReference {
+    /// The name as configured (for a commit, the sha as given). For local
+    /// sources, the checked-out branch or the directory.
+    pub name: String,
+    pub kind: ReferenceKind,
+    /// Full hex id of the commit, or the snapshot version of a directory.
+    pub commit: String,
+}
+
+impl fmt::Display for ResolvedReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.kind == ReferenceKind::Commit {
+            write!(f, "commit {}", self.commit)
+        } else {
+            write!(f, "{} {} at {}", self.kind.as_str(), self.name, self.commit)
+        }
+    }
+}
+
+/// Whether `name` could be a full or abbreviated commit sha.
+pub fn looks_like_commit(name: &str) -> bool {
+    (4..=40).contains(&name.len()) && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Look `name` up as a remote-tracking branch, a tag, a local branch and
+/// finally a commit sha, in that order. Returns `None` when nothing matches,
+/// which usually means the reference has not been fetched yet.
+pub fn resolve(
+    repo: &gix::Repository,
+    remote_name: &str,
+    name: &str,
+) -> Result<Option<(ReferenceKind, gix::ObjectId)>> {
+    let candidates = [
+        (
+            format!("refs/remotes/{}/{}", remote_name, name),
+            ReferenceKind::Branch,
+        ),
+        (format!("refs/tags/{}", name), ReferenceKind::Tag),
+        (format!("refs/heads/{}", name), ReferenceKind::Branch),
+    ];
+    for (full_name, kind) in candidates {
+        if let Some(mut reference) = repo.try_find_reference(full_name.as_str())? {
+            return Ok(Some((kind, peel_to_commit(&mut reference, &full_name)?)));
+        }
+    }
+
+    if looks_like_commit(name)
+        && let Ok(id) = repo.rev_parse_single(name)
+    {
+        let commit = id.object()?.peel_to_commit().map_err(|e| {
+            PipelineError::GitReference(format!("'{}' is not a commit: {}", name, e))
+        })?;
+        return Ok(Some((ReferenceKind::Commit, commit.id)));
+    }
+
+    Ok(None)
+}
+
+/// Refspecs that fetch `name` whether it is a branch, a tag or a full sha.
+pub fn fetch_refspecs(remote_name: &str, name: &str) -> Vec<String> {
+    if name.len() == 40 && looks_like_commit(name) {
+        return vec![name.to_string()];
+    }
+    vec![
+        format!("+refs/heads/{0}:refs/remotes/{1}/{0}", name, remote_name),
+        format!("+refs/tags/{0}:refs/tags/{0}", name),
+    ]
+}
+
+fn peel_to_commit(reference: &mut gix::Reference<'_>, full_name: &str) -> Result<gix::ObjectId> {
+    let id = reference
+        .peel_to_id()
+        .map_err(|e| PipelineError::GitReference(format!("Failed to peel {}: {}", full_name, e)))?;
+    let commit = id.object()?.peel_to_commit().map_err(|e| {
+        PipelineError::GitReference(format!("{} does not point to a commit: {}", full_name, e))
+    })?;
+    Ok(commit.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::testing::GitFixture;
+    use tempfile::TempDir;
+
+    fn id(hex: &str) -> gix::ObjectId {
+        gix::ObjectId::from_hex(hex.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn branches_tags_and_shas_resolve_to_commits() {
+        let temp = TempDir::new().unwrap();
+        let upstream = GitFixture::init(&temp.path().join("upstream"));
+        let first = upstream.commit(&[("a.md", Some("one"))], "first");
+        upstream.git(&["tag", "--annotate", "v1", "--message", "release 1"]);
+        upstream.git(&["branch", "feature"]);
+        let second = upstream.commit(&[("a.md", Some("two"))], "second");
+        let clone = upstream.clone_to(&temp.path().join("clone"));
+        clone.git(&["branch", "local-only", &first]);
+        let repo = gix::open(&clone.path).unwrap();
+
+        assert_eq!(
+            resolve(&repo, "origin", "main").unwrap(),
+            Some((ReferenceKind::Branch, id(&second)))
+        );
+        assert_eq!(
+            resolve(&repo, "origin", "feature").unwrap(),
+            Some((ReferenceKind::Branch, id(&first)))
+        );
+        assert_eq!(
+            resolve(&repo, "origin", "local-only").unwrap(),
+            Some((ReferenceKind::Branch, id(&first)))
+        );
+
+        // An annotated tag peels to its commit, not the tag object.
+        assert_ne!(clone.git(&["rev-parse", "v1"]), first);
+        assert_eq!(
+            resolve(&repo, "origin", "v1").unwrap(),
+            Some((ReferenceKind::Tag, id(&first)))
+        );
+
+        assert_eq!(
+            resolve(&repo, "origin", &first[..7]).unwrap(),
+            Some((ReferenceKind::Commit, id(&first)))
+        );
+        assert_eq!(
+            resolve(&repo, "origin", &second).unwrap(),
+            Some((ReferenceKind::Commit, id(&second)))
+        );
+
+        // Unknown names are left for the caller to fetch or report.
+        assert_eq!(resolve(&repo, "origin", "nope").unwrap(), None);
+        assert_eq!(resolve(&repo, "origin", "0000000").unwrap(), None);
+
+        // A sha of something other than a commit is an error.
+        let tree = clone.git(&["rev-parse", "HEAD^{tree}"]);
+        assert!(matches!(
+            resolve(&repo, "origin", &tree[..10]),
+            Err(PipelineError::GitReference(_))
+        ));
+    }
+
+    #[test]
+    fn commit_shas_are_recognized() {
+        assert!(looks_like_commit("a1b2c3d"));
+        assert!(looks_like_commit(
+            "0123456789abcdef0123456789abcdef01234567"
+        ));
+        assert!(!looks_like_commit("main"));
+        assert!(!looks_like_commit("v1.2.0"));
+        assert!(!looks_like_commit("abc"));
+
+        assert_eq!(
+            fetch_refspecs("origin", "0123456789abcdef0123456789abcdef01234567"),
+            vec!["0123456789abcdef0123456789abcdef01234567"]
+        );
+        assert_eq!(
+            fetch_refspecs("origin", "v1.2.0"),
+            vec![
+                "+refs/heads/v1.2.0:refs/remotes/origin/v1.2.0",
+                "+refs/tags/v1.2.0:refs/tags/v1.2.0"
+            ]
+        );
+    }
+}
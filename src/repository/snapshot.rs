@@ -0,0 +1,56 @@
+// file: src/repository/snapshot.rs
+// description: temporary export of a branch, tag or commit for ingesting past versions
+// reference: https://git-scm.com/docs/git-archive
+
+use crate::error::{PipelineError, Result};
+use crate::repository::checkout::export_tree;
+use crate::repository::reference::ResolvedReference;
+use crate::repository::sparse::SparseCone;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// The files of a ref exported to a temporary directory, so it can be
+/// ingested next to the checked-out version. The directory is removed when
+/// the snapshot is dropped.
+#[derive(Debug)]
+pub struct RefSnapshot {
+    pub reference: ResolvedReference,
+    path: PathBuf,
+}
+
+impl RefSnapshot {
+    /// Export the tree of `reference.commit`, limited to `cone` if given.
+    pub fn export(
+        repo: &gix::Repository,
+        reference: ResolvedReference,
+        cone: Option<&SparseCone>,
+    ) -> Result<Self> {
+        let commit = gix::ObjectId::from_hex(reference.commit.as_bytes())
+            .map_err(|e| PipelineError::GitObject(format!("Invalid commit id: {}", e)))?;
+        let path = std::env::temp_dir().join(format!(
+            "git_summarize-{}-{}",
+            commit.to_hex_with_len(12),
+            uuid::Uuid::new_v4().simple()
+        ));
+        // Constructed first so a failed export is cleaned up on drop.
+        let snapshot = Self { reference, path };
+        let files = export_tree(repo, commit, &snapshot.path, cone)?;
+        info!("Exported {} file(s) of {}", files, snapshot.reference);
+        Ok(snapshot)
+    }
+
+    /// The directory holding the exported files.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RefSnapshot {
+    fn drop(&mut self) {
+        match std::fs::remove_dir_all(&self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove snapshot {}: {}", self.path.display(), e),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+// file: src/repository/source.rs
+// description: remote and local repository sources, and the state of local ones
+// reference: https://git-scm.com/docs/git-clone#_git_urls
+
+use crate::error::{PipelineError, Result};
+use gix::index::entry::{Flags, Mode};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Where `repository.source_url` points.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepositorySource {
+    /// A URL cloned into `repository.local_path` and fetched from on sync.
+    Remote(String),
+    /// A directory read in place: a git checkout or a plain folder.
+    Local(PathBuf),
+}
+
+impl RepositorySource {
+    /// `file://` URLs and filesystem paths are local; URLs with any other
+    /// scheme and scp-like `user@host:path` addresses are remote.
+    pub fn parse(source_url: &str) -> Self {
+        let source_url = source_url.trim();
+        if let Some(path) = source_url.strip_prefix("file://") {
+            let path = path.strip_prefix("localhost").unwrap_or(path);
+            return Self::Local(PathBuf::from(path));
+        }
+        if source_url.contains("://") || is_scp_like(source_url) {
+            return Self::Remote(source_url.to_string());
+        }
+        match (source_url.strip_prefix("~/"), std::env::var_os("HOME")) {
+            (Some(rest), Some(home)) => Self::Local(PathBuf::from(home).join(rest)),
+            _ => Self::Local(PathBuf::from(source_url)),
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        matches!(self, Self::Local(_))
+    }
+
+    /// The value stored in the `repository_url` column: the URL of a remote,
+    /// or a `file://` URL of the absolute, canonical path of a local source,
+    /// so `./docs` and `/home/me/docs` index into the same repository.
+    pub fn id(&self) -> String {
+        match self {
+            Self::Remote(url) => url.clone(),
+            Self::Local(path) => {
+                let path = path
+                    .canonicalize()
+                    .or_else(|_| std::path::absolute(path))
+                    .unwrap_or_else(|_| path.clone());
+                format!("file://{}", path.display())
+            }
+        }
+    }
+}
+
+fn is_scp_like(source_url: &str) -> bool {
+    match source_url.split_once(':') {
+        // A single letter before the colon is a Windows drive, not a host.
+        Some((host, _)) => host.len() > 1 && !host.contains('/'),
+        None => false,
+    }
+}
+
+/// What a local source looks like right now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocalVersion {
+    /// A git checkout at `commit`; `dirty` when tracked files differ from
+    /// the index. Untracked files are indexed but don't make it dirty.
+    Git {
+        branch: Option<String>,
+        commit: String,
+        dirty: bool,
+    },
+    /// A directory without git, identified by a hash over the paths, sizes
+    /// and modification times of its files.
+    Snapshot { id: String, files: usize },
+}
+
+impl LocalVersion {
+    /// Inspect `path`: a git checkout if it contains `.git`, otherwise a
+    /// plain directory.
+    pub fn inspect(path: &Path) -> Result<Self> {
+        if !path.is_dir() {
+            return Err(PipelineError::RepositorySync(format!(
+                "Local source {} is not a directory",
+                path.display()
+            )));
+        }
+        if !path.join(".git").exists() {
+            return snapshot(path);
+        }
+
+        let repo = gix::open(path)?;
+        let commit = repo
+            .head_id()
+            .map_err(|e| PipelineError::GitReference(format!("HEAD has no commit: {}", e)))?
+            .detach();
+        let branch = repo.head_name()?.map(|name| name.shorten().to_string());
+        Ok(Self::Git {
+            branch,
+            commit: commit.to_string(),
+            dirty: is_dirty(&repo)?,
+        })
+    }
+
+    /// Stored as the commit of a run: the sha, `<sha>-dirty` for a checkout
+    /// with local changes, or `snapshot-<hash>`.
+    pub fn version(&self) -> String {
+        match self {
+            Self::Git {
+                commit,
+                dirty: true,
+                ..
+            } => format!("{}-dirty", commit),
+            Self::Git { commit, .. } => commit.clone(),
+            Self::Snapshot { id, .. } => format!("snapshot-{}", id),
+        }
+    }
+}
+
+/// Whether any tracked file differs from the index. Files whose size and
+/// mtime match the index are assumed unchanged, like git's stat check;
+/// the rest are hashed.
+fn is_dirty(repo: &gix::Repository) -> Result<bool> {
+    let Some(workdir) = repo.workdir() else {
+        return Ok(false);
+    };
+    let index = repo
+        .index_or_empty()
+        .map_err(|e| PipelineError::GitWorktree(format!("Failed to read index: {}", e)))?;
+
+    for entry in index.entries() {
+        if entry.flags.contains(Flags::SKIP_WORKTREE) || entry.mode == Mode::COMMIT {
+            continue;
+        }
+        let path = workdir.join(gix::path::from_bstr(entry.path(&index)));
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(true),
+            Err(e) => return Err(e.into()),
+        };
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|time| time.as_secs() as u32);
+        if metadata.len() as u32 == entry.stat.size && mtime == Some(entry.stat.mtime.secs) {
+            continue;
+        }
+
+        let data = if entry.mode == Mode::SYMLINK {
+            gix::path::into_bstr(std::fs::read_link(&path)?).to_vec()
+        } else {
+            std::fs::read(&path)?
+        };
+        let id = gix::objs::compute_hash(repo.object_hash(), gix::objs::Kind::Blob, &data)
+            .map_err(|e| {
+                PipelineError::GitObject(format!("Failed to hash {}: {}", path.display(), e))
+            })?;
+        if id != entry.id {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn snapshot(path: &Path) -> Result<LocalVersion> {
+    let mut hasher = Sha256::new();
+    let mut files = 0;
+    for entry in WalkDir::new(path)
+        .follow_links(false)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let metadata = entry.metadata().map_err(std::io::Error::from)?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|time| time.as_nanos())
+            .unwrap_or_default();
+        let relative = entry.path().strip_prefix(path).unwrap_or(entry.path());
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(metadata.len().to_le_bytes());
+        hasher.update(mtime.to_le_bytes());
+        files += 1;
+    }
+    let digest = hasher.finalize();
+    Ok(LocalVersion::Snapshot {
+        id: digest[..8].iter().map(|b| format!("{:02x}", b)).collect(),
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn sources_are_classified_by_form() {
+        assert_eq!(
+            RepositorySource::parse("https://github.com/user/repo"),
+            RepositorySource::Remote("https://github.com/user/repo".to_string())
+        );
+        assert!(!RepositorySource::parse("git@github.com:user/repo.git").is_local());
+        assert_eq!(
+            RepositorySource::parse("file:///srv/docs"),
+            RepositorySource::Local(PathBuf::from("/srv/docs"))
+        );
+        assert_eq!(
+            RepositorySource::parse("./notes"),
+            RepositorySource::Local(PathBuf::from("./notes"))
+        );
+        assert!(RepositorySource::parse("C:/work/docs").is_local());
+
+        let temp = TempDir::new().unwrap();
+        let id = RepositorySource::Local(temp.path().join(".")).id();
+        assert_eq!(
+            id,
+            format!("file://{}", temp.path().canonicalize().unwrap().display())
+        );
+    }
+
+    #[test]
+    fn snapshot_changes_with_files() {
+        let temp = TempDir::new().unwrap();
+        std::fs::write(temp.path().join("a.md"), "# A").unwrap();
+
+        let first = LocalVersion::inspect(temp.path()).unwrap();
+        assert!(matches!(first, LocalVersion::Snapshot { files: 1, .. }));
+        assert_eq!(first, LocalVersion::inspect(temp.path()).unwrap());
+        assert!(first.version().starts_with("snapshot-"));
+
+        std::fs::write(temp.path().join("b.md"), "# B").unwrap();
+        assert_ne!(first, LocalVersion::inspect(temp.path()).unwrap());
+    }
+}
@@ -0,0 +1,183 @@
+// file: src/repository/sparse.rs
+// description: cone-mode sparse checkout of selected directories
+// reference: https://git-scm.com/docs/git-sparse-checkout#_internalscone_mode_handling
+
+use crate::error::{PipelineError, Result};
+
+/// Directories to materialize, with git's cone-mode semantics: everything
+/// below a listed directory, plus the files (not subdirectories) of the root
+/// and of every directory on the way to a listed one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseCone {
+    directories: Vec<String>,
+}
+
+impl SparseCone {
+    /// Build a cone from directory paths such as `docs` or `/guides/api/`.
+    /// Returns `None` for an empty list, meaning a full checkout.
+    pub fn new<S: AsRef<str>>(paths: &[S]) -> Option<Self> {
+        let mut directories: Vec<String> = paths
+            .iter()
+            .map(|p| p.as_ref().trim().trim_matches('/').to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+        directories.sort();
+        directories.dedup();
+        (!directories.is_empty()).then_some(Self { directories })
+    }
+
+    pub fn directories(&self) -> &[String] {
+        &self.directories
+    }
+
+    /// Whether a repository-relative file path is checked out.
+    pub fn contains(&self, path: &str) -> bool {
+        let parent = match path.rsplit_once('/') {
+            Some((parent, _)) => parent,
+            None => return true,
+        };
+        self.directories.iter().any(|dir| {
+            (path.starts_with(dir.as_str()) && path.as_bytes().get(dir.len()) == Some(&b'/'))
+                || (dir.starts_with(parent) && dir.as_bytes().get(parent.len()) == Some(&b'/'))
+        })
+    }
+
+    /// The `info/sparse-checkout` file git writes for the same cone.
+    pub fn patterns(&self) -> String {
+        let mut lines = vec!["/*".to_string(), "!/*/".to_string()];
+        let mut parents: Vec<&str> = Vec::new();
+        for dir in &self.directories {
+            let mut end = 0;
+            while let Some(offset) = dir[end..].find('/') {
+                end += offset;
+                let parent = &dir[..end];
+                if !parents.contains(&parent) {
+                    parents.push(parent);
+                    lines.push(format!("/{}/", parent));
+                    lines.push(format!("!/{}/*/", parent));
+                }
+                end += 1;
+            }
+            lines.push(format!("/{}/", dir));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Parse an `info/sparse-checkout` file written in cone mode. Returns
+    /// `None` when it lists no directories or uses non-cone patterns.
+    pub fn from_patterns(patterns: &str) -> Option<Self> {
+        let mut included = Vec::new();
+        let mut parents = Vec::new();
+        for line in patterns
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            match line {
+                "/*" | "!/*/" => {}
+                _ if line.starts_with("!/") && line.ends_with("/*/") => {
+                    parents.push(&line[2..line.len() - 3])
+                }
+                _ if line.starts_with('/') && line.ends_with('/') && !line.contains('*') => {
+                    included.push(line.trim_matches('/'))
+                }
+                _ => return None,
+            }
+        }
+        // Parents are listed too, but only for their direct files.
+        included.retain(|dir| !parents.contains(dir));
+        Self::new(&included)
+    }
+}
+
+/// The cone recorded in `info/sparse-checkout`, if the clone is sparse.
+pub(crate) fn read_sparse_config(git_dir: &std::path::Path) -> Result<Option<SparseCone>> {
+    match std::fs::read_to_string(git_dir.join("info").join("sparse-checkout")) {
+        Ok(patterns) => Ok(SparseCone::from_patterns(&patterns)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Record the cone in `info/sparse-checkout` and enable cone mode, so the
+/// git CLI treats the checkout the same way. Runs on every pull, so the keys
+/// are set in place rather than appended.
+pub(crate) fn write_sparse_config(git_dir: &std::path::Path, cone: &SparseCone) -> Result<()> {
+    let info_dir = git_dir.join("info");
+    std::fs::create_dir_all(&info_dir)?;
+    std::fs::write(info_dir.join("sparse-checkout"), cone.patterns())?;
+
+    let config_error = |e: &dyn std::fmt::Display| {
+        PipelineError::GitWorktree(format!("Failed to update git config: {}", e))
+    };
+    let path = git_dir.join("config");
+    let mut config =
+        gix::config::File::from_path_no_includes(path.clone(), gix::config::Source::Local)
+            .map_err(|e| config_error(&e))?;
+    for key in ["core.sparseCheckout", "core.sparseCheckoutCone"] {
+        config
+            .set_raw_value(key, "true")
+            .map_err(|e| config_error(&e))?;
+    }
+    let mut buf = Vec::new();
+    config.write_to(&mut buf)?;
+    std::fs::write(path, buf)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cone_includes_listed_directories_and_parent_files() {
+        let cone = SparseCone::new(&["/guides/api/", "docs", " "]).unwrap();
+        assert_eq!(cone.directories(), ["docs", "guides/api"]);
+
+        assert!(cone.contains("README.md"));
+        assert!(cone.contains("docs/intro.md"));
+        assert!(cone.contains("docs/deep/nested.md"));
+        assert!(cone.contains("guides/index.md"));
+        assert!(cone.contains("guides/api/auth.md"));
+
+        assert!(!cone.contains("src/lib.rs"));
+        assert!(!cone.contains("docsite/page.md"));
+        assert!(!cone.contains("guides/cli/usage.md"));
+
+        assert!(SparseCone::new::<&str>(&[]).is_none());
+    }
+
+    #[test]
+    fn patterns_match_git_cone_format() {
+        let cone = SparseCone::new(&["docs", "guides/api"]).unwrap();
+        assert_eq!(
+            cone.patterns(),
+            "/*\n!/*/\n/docs/\n/guides/\n!/guides/*/\n/guides/api/\n"
+        );
+        assert_eq!(SparseCone::from_patterns(&cone.patterns()), Some(cone));
+        assert_eq!(SparseCone::from_patterns("/*\n!/*/\n"), None);
+        assert_eq!(SparseCone::from_patterns("*.md\n"), None);
+    }
+
+    #[test]
+    fn config_keys_are_set_once_across_pulls() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = dir.path().join("config");
+        std::fs::write(
+            &config,
+            "[core]\n\tbare = false\n[remote \"origin\"]\n\turl = x\n",
+        )
+        .unwrap();
+
+        let cone = SparseCone::new(&["docs"]).unwrap();
+        write_sparse_config(dir.path(), &cone).unwrap();
+        write_sparse_config(dir.path(), &cone).unwrap();
+
+        let written = std::fs::read_to_string(&config).unwrap();
+        assert_eq!(written.matches("[core]").count(), 1);
+        assert_eq!(written.matches("sparseCheckout =").count(), 1);
+        assert_eq!(written.matches("sparseCheckoutCone =").count(), 1);
+        assert!(written.contains("bare = false"));
+        assert_eq!(read_sparse_config(dir.path()).unwrap(), Some(cone));
+    }
+}
@@ -0,0 +1,271 @@
+// file: src/repository/submodule.rs
+// description: submodules of a checkout and per-submodule file filters
+// reference: https://git-scm.com/docs/gitsubmodules
+
+use crate::config::{SubmoduleConfig, SubmoduleFilter};
+use crate::error::{PipelineError, Result};
+use glob::Pattern;
+use serde::Serialize;
+use std::path::Path;
+use tracing::debug;
+
+/// The error for a submodule filter glob that does not compile.
+fn invalid_glob(path: &str, glob: &str, error: glob::PatternError) -> PipelineError {
+    PipelineError::Validation(format!(
+        "repository.submodules.filters.\"{}\" has an invalid glob '{}': {}",
+        path, glob, error
+    ))
+}
+
+/// A checked-out submodule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubmoduleInfo {
+    /// Path relative to the top-level repository, e.g. `vendor/sdk`.
+    pub path: String,
+    /// Commit checked out in the submodule.
+    pub commit: String,
+}
+
+/// The submodules of a checkout, used to tag and filter scanned files.
+#[derive(Debug, Clone, Default)]
+pub struct SubmoduleIndex {
+    /// Deepest paths first, so nested submodules match before their parents.
+    submodules: Vec<SubmoduleInfo>,
+    filters: Vec<(String, Vec<Pattern>, Vec<Pattern>)>,
+}
+
+impl SubmoduleIndex {
+    /// Fails with [`PipelineError::Validation`] naming the first filter glob
+    /// that does not compile.
+    pub fn new(mut submodules: Vec<SubmoduleInfo>, config: &SubmoduleConfig) -> Result<Self> {
+        submodules.sort_by(|a, b| b.path.len().cmp(&a.path.len()).then(a.path.cmp(&b.path)));
+        let filters = config
+            .filters
+            .iter()
+            .map(|(path, SubmoduleFilter { include, exclude })| {
+                let compile = |globs: &[String]| {
+                    globs
+                        .iter()
+                        .map(|g| Pattern::new(g).map_err(|e| invalid_glob(path, g, e)))
+                        .collect::<Result<Vec<_>>>()
+                };
+                Ok((
+                    path.trim_matches('/').to_string(),
+                    compile(include)?,
+                    compile(exclude)?,
+                ))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            submodules,
+            filters,
+        })
+    }
+
+    /// Find the checked-out submodules below `workdir`, descending into
+    /// nested ones when `config.recursive` is set.
+    pub fn discover(workdir: &Path, config: &SubmoduleConfig) -> Result<Self> {
+        let mut submodules = Vec::new();
+        if workdir.join(".git").exists() {
+            collect(&gix::open(workdir)?, "", config.recursive, &mut submodules)?;
+        }
+        Self::new(submodules, config)
+    }
+
+    pub fn submodules(&self) -> &[SubmoduleInfo] {
+        &self.submodules
+    }
+
+    /// The innermost submodule containing a repository-relative path.
+    pub fn find(&self, relative_path: &str) -> Option<&SubmoduleInfo> {
+        self.submodules.iter().find(|submodule| {
+            relative_path.starts_with(submodule.path.as_str())
+                && relative_path.as_bytes().get(submodule.path.len()) == Some(&b'/')
+        })
+    }
+
+    /// Whether the filters of the submodule containing `relative_path` keep
+    /// it. Files outside submodules are always kept.
+    pub fn includes(&self, relative_path: &str) -> bool {
+        let Some(submodule) = self.find(relative_path) else {
+            return true;
+        };
+        let Some((_, include, exclude)) = self
+            .filters
+            .iter()
+            .find(|(path, _, _)| *path == submodule.path)
+        else {
+            return true;
+        };
+        let inner = &relative_path[submodule.path.len() + 1..];
+        (include.is_empty() || include.iter().any(|p| p.matches(inner)))
+            && !exclude.iter().any(|p| p.matches(inner))
+    }
+}
+
+fn collect(
+    repo: &gix::Repository,
+    prefix: &str,
+    recursive: bool,
+    out: &mut Vec<SubmoduleInfo>,
+) -> Result<()> {
+    let Some(submodules) = repo
+        .submodules()
+        .map_err(|e| PipelineError::RepositorySync(format!("Failed to read .gitmodules: {}", e)))?
+    else {
+        return Ok(());
+    };
+    for submodule in submodules {
+        let Some(sub_repo) = submodule.open().ok().flatten() else {
+            debug!("Submodule {} is not checked out", submodule.name());
+            continue;
+        };
+        let Ok(commit) = sub_repo.head_id() else {
+            continue;
+        };
+        let path = format!("{}{}", prefix, submodule_path(&submodule)?);
+        out.push(SubmoduleInfo {
+            path: path.clone(),
+            commit: commit.to_string(),
+        });
+        if recursive {
+            collect(&sub_repo, &format!("{}/", path), recursive, out)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn submodule_path(submodule: &gix::Submodule<'_>) -> Result<String> {
+    submodule.path().map(|path| path.to_string()).map_err(|e| {
+        PipelineError::RepositorySync(format!(
+            "Invalid path for submodule {}: {}",
+            submodule.name(),
+            e
+        ))
+    })
+}
+
+/// Resolve a `./` or `../` submodule URL against the parent's remote URL,
+/// as git does. Other URLs are returned unchanged.
+pub(crate) fn resolve_url(parent_url: &str, url: &str) -> String {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return url.to_string();
+    }
+    let mut base = parent_url.trim_end_matches('/').to_string();
+    // scp-like parents (`git@host:repo`) may lose their `:` separator.
+    let mut separator = '/';
+    let mut rest = url;
+    loop {
+        if let Some(stripped) = rest.strip_prefix("./") {
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("../") {
+            rest = stripped;
+            if let Some(index) = base.rfind(['/', ':']) {
+                separator = if base[index..].starts_with(':') {
+                    ':'
+                } else {
+                    '/'
+                };
+                base.truncate(index);
+            }
+        } else {
+            break;
+        }
+    }
+    format!("{}{}{}", base, separator, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn relative_urls_resolve_against_the_parent() {
+        assert_eq!(
+            resolve_url("https://github.com/org/app.git", "../sdk.git"),
+            "https://github.com/org/sdk.git"
+        );
+        assert_eq!(
+            resolve_url("https://github.com/org/app/", "./docs"),
+            "https://github.com/org/app/docs"
+        );
+        assert_eq!(
+            resolve_url("git@github.com:org/app.git", "../sdk.git"),
+            "git@github.com:org/sdk.git"
+        );
+        assert_eq!(
+            resolve_url("git@github.com:app.git", "../sdk.git"),
+            "git@github.com:sdk.git"
+        );
+        assert_eq!(
+            resolve_url("https://github.com/org/app", "https://example.com/x.git"),
+            "https://example.com/x.git"
+        );
+    }
+
+    #[test]
+    fn files_are_matched_to_the_innermost_submodule_and_filtered() {
+        let config = SubmoduleConfig {
+            enabled: true,
+            filters: BTreeMap::from([(
+                "vendor/sdk".to_string(),
+                SubmoduleFilter {
+                    include: vec!["docs/**".to_string()],
+                    exclude: vec!["docs/internal/**".to_string()],
+                },
+            )]),
+            ..Default::default()
+        };
+        let index = SubmoduleIndex::new(
+            vec![
+                SubmoduleInfo {
+                    path: "vendor/sdk".to_string(),
+                    commit: "a".repeat(40),
+                },
+                SubmoduleInfo {
+                    path: "vendor/sdk/third_party/spec".to_string(),
+                    commit: "b".repeat(40),
+                },
+            ],
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(index.find("README.md"), None);
+        assert_eq!(index.find("vendor/sdkx/a.md"), None);
+        assert_eq!(
+            index.find("vendor/sdk/docs/a.md").unwrap().commit,
+            "a".repeat(40)
+        );
+        assert_eq!(
+            index.find("vendor/sdk/third_party/spec/a.md").unwrap().path,
+            "vendor/sdk/third_party/spec"
+        );
+
+        assert!(index.includes("README.md"));
+        assert!(index.includes("vendor/sdk/docs/guide/a.md"));
+        assert!(!index.includes("vendor/sdk/docs/internal/a.md"));
+        assert!(!index.includes("vendor/sdk/CHANGELOG.md"));
+        assert!(index.includes("vendor/sdk/third_party/spec/a.md"));
+    }
+
+    #[test]
+    fn invalid_filter_globs_are_rejected() {
+        let config = SubmoduleConfig {
+            filters: BTreeMap::from([(
+                "vendor/sdk".to_string(),
+                SubmoduleFilter {
+                    include: vec!["docs/**".to_string()],
+                    exclude: vec!["docs/[internal".to_string()],
+                },
+            )]),
+            ..Default::default()
+        };
+        let error = SubmoduleIndex::new(Vec::new(), &config).unwrap_err();
+        assert!(
+            matches!(&error, PipelineError::Validation(message) if message.contains("docs/[internal")),
+            "{error}"
+        );
+    }
+}
@@ -2,13 +2,39 @@
 // description: repository synchronization using gix (pure rust git implementation)
 // reference: https://docs.rs/gix
 
-use crate::config::RepositoryConfig;
+use crate::config::{DivergencePolicy, RepositoryConfig};
 use crate::error::{PipelineError, Result};
+use crate::repository::checkout::{WorktreeChanges, checkout_commit, update_worktree};
+use crate::repository::reference::{self, ReferenceKind, ResolvedReference};
+use crate::repository::snapshot::RefSnapshot;
+use crate::repository::source::{LocalVersion, RepositorySource};
+use crate::repository::sparse::{self, SparseCone};
+use crate::repository::submodule::{self, SubmoduleInfo, submodule_path};
+use gix::index::entry::{Flags, Mode};
 use gix::remote::Name;
 use gix::repository::merge_base;
+use serde::Serialize;
 use std::path::Path;
 use tracing::{debug, info, warn};
 
+/// The outcome of [`RepositorySync::sync`].
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncReport {
+    /// What `repository.branch` resolved to; `commit` is what is checked out.
+    pub reference: ResolvedReference,
+    /// The commit checked out before the sync, `None` after a fresh clone.
+    pub previous_commit: Option<String>,
+    /// Files written or removed in the worktree (empty after a clone).
+    pub changes: WorktreeChanges,
+    /// The local branch diverged from the remote and was kept
+    /// (`on_divergence = "skip"`).
+    pub diverged: bool,
+    /// A local git source has uncommitted changes to tracked files.
+    pub dirty: bool,
+    /// Submodules checked out by this sync (`repository.submodules`).
+    pub submodules: Vec<SubmoduleInfo>,
+}
+
 pub struct RepositorySync {
     config: RepositoryConfig,
 }
@@ -18,79 +44,199 @@ impl RepositorySync {
         Self { config }
     }
 
-    pub fn sync(&self) -> Result<()> {
+    /// Clone or update the repository, checking out what `repository.branch`
+    /// (a branch, tag or commit sha) resolves to.
+    /// Local sources are never fetched or modified; their current state is
+    /// reported instead.
+    pub fn sync(&self) -> Result<SyncReport> {
+        if let RepositorySource::Local(path) = self.config.source() {
+            return self.inspect_local(&path);
+        }
         let path = &self.config.local_path;
 
-        if path.exists() {
+        let mut report = if path.exists() {
             info!("Repository exists, pulling latest changes");
-            self.pull(path)?;
+            self.pull(path)?
         } else {
             info!("Repository does not exist, cloning");
-            self.clone()?;
+            SyncReport {
+                reference: self.clone()?,
+                previous_commit: None,
+                changes: WorktreeChanges::default(),
+                diverged: false,
+                dirty: false,
+                submodules: Vec::new(),
+            }
+        };
+
+        if self.config.submodules.enabled {
+            let repo = gix::open(path)?;
+            report.submodules = self.update_submodules(&repo, "")?;
         }
 
-        Ok(())
+        info!("Checked out {}", report.reference);
+        Ok(report)
     }
 
-    fn clone(&self) -> Result<()> {
+    fn clone(&self) -> Result<ResolvedReference> {
         info!("Cloning repository from {}", self.config.source_url);
 
         let mut prepare =
             gix::prepare_clone(self.config.source_url.clone(), &self.config.local_path)?;
 
-        if !self.config.branch.is_empty() {
-            let branch_ref = <&gix::refs::PartialNameRef>::try_from(self.config.branch.as_str())
-                .map_err(|e| {
-                    PipelineError::RepositorySync(format!("Invalid branch name: {}", e))
-                })?;
-            prepare = prepare
-                .with_ref_name(Some(branch_ref))
-                .expect("with_ref_name should not fail");
+        if let Some(shallow) = self.shallow() {
+            info!(
+                "Shallow clone (depth {})",
+                self.config.depth.unwrap_or_default()
+            );
+            prepare = prepare.with_shallow(shallow);
         }
 
-        let (mut checkout, _outcome) = prepare
+        let (checkout, _outcome) = prepare
             .fetch_then_checkout(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
             .map_err(|e| {
                 PipelineError::RepositorySync(format!("Failed to fetch and checkout: {}", e))
             })?;
 
-        let (_repo, _outcome) = checkout
-            .main_worktree(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
-            .map_err(|e| {
-                PipelineError::RepositorySync(format!("Failed to checkout main worktree: {}", e))
-            })?;
-
-        info!("Repository cloned successfully");
-        Ok(())
+        // The worktree is written below, once the configured reference (which
+        // need not be the remote's HEAD) is resolved.
+        let repo = checkout.persist();
+        let result = self.resolve_or_fetch(&repo).and_then(|resolved| {
+            let commit = gix::ObjectId::from_hex(resolved.commit.as_bytes())
+                .map_err(|e| PipelineError::GitObject(format!("Invalid commit id: {}", e)))?;
+            let cone = SparseCone::new(&self.config.sparse_paths);
+            checkout_commit(&repo, commit, cone.as_ref())?;
+            self.update_head(&repo, &resolved, commit)?;
+            Ok(resolved)
+        });
+
+        if result.is_err() {
+            // Don't leave a clone without a worktree behind for `pull` to find.
+            drop(repo);
+            if let Err(e) = std::fs::remove_dir_all(&self.config.local_path) {
+                warn!("Failed to remove incomplete clone: {}", e);
+            }
+        } else {
+            info!("Repository cloned successfully");
+        }
+        result
     }
 
-    fn pull(&self, path: &Path) -> Result<()> {
-        let repo = gix::open(path)?;
-
-        let remote = repo.find_fetch_remote(None).map_err(|e| {
-            PipelineError::RepositorySync(format!("Failed to resolve remote: {}", e))
-        })?;
+    fn pull(&self, path: &Path) -> Result<SyncReport> {
+        let repo = with_reflog_identity(gix::open(path)?)?;
+        let previous_commit = repo.head_id().ok().map(|id| id.detach());
+        let remote_name = remote_name(&repo)?;
+        let name = self.reference_name(&repo)?;
 
         info!("Fetching latest changes");
+        let outcome = self.fetch(&repo, &reference::fetch_refspecs(&remote_name, &name))?;
+        debug!("Fetched {} refs", outcome);
+
+        let (kind, remote_commit_id) =
+            reference::resolve(&repo, &remote_name, &name)?.ok_or_else(|| self.not_found(&name))?;
+        let resolved = ResolvedReference {
+            name: name.clone(),
+            kind,
+            commit: remote_commit_id.to_string(),
+        };
 
-        let outcome = remote
-            .connect(gix::remote::Direction::Fetch)?
-            .prepare_fetch(gix::progress::Discard, Default::default())?
-            .receive(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
-            .map_err(|e| {
-                PipelineError::RepositorySync(format!("Failed to fetch from remote: {}", e))
-            })?;
+        let (commit, diverged) = if kind.is_pinned() {
+            if previous_commit != Some(remote_commit_id) {
+                self.update_head(&repo, &resolved, remote_commit_id)?;
+                info!("Moved HEAD to {}", resolved);
+            }
+            (remote_commit_id, false)
+        } else {
+            self.advance_branch(&repo, &resolved, remote_commit_id)?
+        };
+
+        // Compared against the index rather than the previous commit, so a
+        // worktree left behind by an interrupted sync is repaired as well.
+        let cone = sparse::read_sparse_config(repo.git_dir())?;
+        let changes = update_worktree(&repo, commit, cone.as_ref())?;
+        if changes.is_empty() {
+            info!("Repository is up to date");
+        } else {
+            info!("Worktree updated: {}", changes);
+        }
 
-        debug!("Fetched {} refs", outcome.ref_map.mappings.len());
+        Ok(SyncReport {
+            reference: ResolvedReference {
+                commit: commit.to_string(),
+                ..resolved
+            },
+            previous_commit: previous_commit.map(|id| id.to_string()),
+            changes,
+            diverged,
+            dirty: false,
+            submodules: Vec::new(),
+        })
+    }
 
-        let remote_name = remote_symbolic_name(&remote).unwrap_or_else(|| "origin".to_string());
+    fn inspect_local(&self, path: &Path) -> Result<SyncReport> {
+        let version = LocalVersion::inspect(path)?;
+        let (reference, dirty) = match &version {
+            LocalVersion::Git {
+                branch,
+                commit,
+                dirty,
+            } => (
+                ResolvedReference {
+                    name: branch.clone().unwrap_or_else(|| "HEAD".to_string()),
+                    kind: ReferenceKind::Worktree,
+                    commit: commit.clone(),
+                },
+                *dirty,
+            ),
+            LocalVersion::Snapshot { files, .. } => {
+                info!(
+                    "{} is not a git repository; {} file(s) snapshotted",
+                    path.display(),
+                    files
+                );
+                (
+                    ResolvedReference {
+                        name: path.display().to_string(),
+                        kind: ReferenceKind::Directory,
+                        commit: version.version(),
+                    },
+                    false,
+                )
+            }
+        };
+        if dirty {
+            warn!(
+                "{} has uncommitted changes; indexing the files as they are",
+                path.display()
+            );
+        }
 
-        let local_branch_ref = format!("refs/heads/{}", self.config.branch);
-        let mut local_ref = repo.find_reference(&local_branch_ref).map_err(|e| {
-            PipelineError::GitReference(format!(
-                "Failed to open local branch {local_branch_ref}: {e}"
-            ))
-        })?;
+        info!("Using local source {}", reference);
+        Ok(SyncReport {
+            reference,
+            previous_commit: None,
+            changes: WorktreeChanges::default(),
+            diverged: false,
+            dirty,
+            submodules: Vec::new(),
+        })
+    }
+
+    /// Move the local branch to the remote commit if it fast-forwards,
+    /// otherwise apply `repository.on_divergence`. Returns the commit to
+    /// check out and whether local commits were kept over the remote ones.
+    fn advance_branch(
+        &self,
+        repo: &gix::Repository,
+        resolved: &ResolvedReference,
+        remote_commit_id: gix::ObjectId,
+    ) -> Result<(gix::ObjectId, bool)> {
+        let local_branch_ref = format!("refs/heads/{}", resolved.name);
+        let Some(mut local_ref) = repo.try_find_reference(local_branch_ref.as_str())? else {
+            info!("Creating local branch {}", resolved.name);
+            self.update_head(repo, resolved, remote_commit_id)?;
+            return Ok((remote_commit_id, false));
+        };
         let local_commit_id = local_ref
             .peel_to_id()
             .map_err(|e| {
@@ -100,30 +246,23 @@ impl RepositorySync {
             })?
             .detach();
 
-        let remote_branch_ref = format!("refs/remotes/{}/{}", remote_name, self.config.branch);
-        let mut remote_ref = repo.find_reference(&remote_branch_ref).map_err(|e| {
-            PipelineError::GitReference(format!(
-                "Failed to open remote tracking branch {remote_branch_ref}: {e}"
-            ))
-        })?;
-        let remote_commit_id = remote_ref
-            .peel_to_id()
-            .map_err(|e| {
-                PipelineError::GitReference(format!(
-                    "Failed to peel remote tracking branch {remote_branch_ref}: {e}"
-                ))
-            })?
-            .detach();
-
         if local_commit_id == remote_commit_id {
-            info!("Repository is up to date");
-            return Ok(());
+            if repo
+                .head_name()?
+                .is_none_or(|head| head.as_bstr() != local_branch_ref.as_str())
+            {
+                self.update_head(repo, resolved, remote_commit_id)?;
+            }
+            return Ok((remote_commit_id, false));
         }
 
         let is_fast_forward = match repo.merge_base(remote_commit_id, local_commit_id) {
             Ok(base) => base == local_commit_id,
             Err(merge_base::Error::NotFound { .. }) => {
-                warn!("No merge base found; manual merge required");
+                warn!(
+                    "No merge base found between local and remote {}",
+                    resolved.name
+                );
                 false
             }
             Err(err) => {
@@ -133,26 +272,340 @@ impl RepositorySync {
             }
         };
 
-        if is_fast_forward {
-            info!("Fast-forward merge");
+        let message = if is_fast_forward {
+            info!("Fast-forward {} to {}", resolved.name, remote_commit_id);
+            "Fast-forward"
+        } else {
+            match self.config.on_divergence {
+                DivergencePolicy::Skip => {
+                    warn!(
+                        "Local branch {} ({}) has diverged from the remote ({}); keeping the local \
+                         commit (set repository.on_divergence = \"reset\" to follow the remote)",
+                        resolved.name, local_commit_id, remote_commit_id
+                    );
+                    return Ok((local_commit_id, true));
+                }
+                DivergencePolicy::Fail => {
+                    return Err(PipelineError::RepositorySync(format!(
+                        "Local branch {} ({}) has diverged from the remote ({})",
+                        resolved.name, local_commit_id, remote_commit_id
+                    )));
+                }
+                DivergencePolicy::Reset => {
+                    warn!(
+                        "Local branch {} has diverged from the remote; resetting {} to {}",
+                        resolved.name, local_commit_id, remote_commit_id
+                    );
+                    "Reset to remote"
+                }
+            }
+        };
 
-            local_ref
-                .set_target_id(remote_commit_id, "Fast-forward")
-                .map_err(|e| {
-                    PipelineError::RepositorySync(format!(
-                        "Failed to update local branch {local_branch_ref}: {e}"
-                    ))
-                })?;
+        local_ref
+            .set_target_id(remote_commit_id, message)
+            .map_err(|e| {
+                PipelineError::RepositorySync(format!(
+                    "Failed to update local branch {local_branch_ref}: {e}"
+                ))
+            })?;
+        if repo
+            .head_name()?
+            .is_none_or(|head| head.as_bstr() != local_branch_ref.as_str())
+        {
+            self.update_head(repo, resolved, remote_commit_id)?;
+        }
+        Ok((remote_commit_id, false))
+    }
+
+    /// Resolve the configured reference in a fresh clone, fetching it
+    /// explicitly when the clone's default refspecs did not bring it in.
+    fn resolve_or_fetch(&self, repo: &gix::Repository) -> Result<ResolvedReference> {
+        let remote_name = remote_name(repo)?;
+        let name = self.reference_name(repo)?;
+
+        let found = match reference::resolve(repo, &remote_name, &name)? {
+            Some(found) => Some(found),
+            None => {
+                info!("Fetching {}", name);
+                self.fetch(repo, &reference::fetch_refspecs(&remote_name, &name))?;
+                reference::resolve(repo, &remote_name, &name)?
+            }
+        };
+        let (kind, commit) = found.ok_or_else(|| self.not_found(&name))?;
+
+        Ok(ResolvedReference {
+            name,
+            kind,
+            commit: commit.to_string(),
+        })
+    }
+
+    /// The configured reference, or the branch HEAD is on when none is set.
+    fn reference_name(&self, repo: &gix::Repository) -> Result<String> {
+        if !self.config.branch.is_empty() {
+            return Ok(self.config.branch.clone());
+        }
+        repo.head_name()?
+            .map(|name| name.shorten().to_string())
+            .ok_or_else(|| {
+                PipelineError::GitReference(
+                    "HEAD is detached; set repository.branch to a branch, tag or commit"
+                        .to_string(),
+                )
+            })
+    }
+
+    /// Fetch with the remote's refspecs plus `refspecs`, keeping the configured
+    /// depth. Returns the number of mapped refs.
+    fn fetch(&self, repo: &gix::Repository, refspecs: &[String]) -> Result<usize> {
+        fetch_remote(repo, refspecs, self.shallow())
+    }
+
+    /// Clone or update each submodule of `repo` to the commit recorded in
+    /// its index. Submodules that fail are logged and left out.
+    fn update_submodules(
+        &self,
+        repo: &gix::Repository,
+        prefix: &str,
+    ) -> Result<Vec<SubmoduleInfo>> {
+        let Some(submodules) = repo.submodules().map_err(|e| {
+            PipelineError::RepositorySync(format!("Failed to read .gitmodules: {}", e))
+        })?
+        else {
+            return Ok(Vec::new());
+        };
+        let index = repo
+            .index_or_empty()
+            .map_err(|e| PipelineError::GitWorktree(format!("Failed to read index: {}", e)))?;
+        let parent_url = repo
+            .find_fetch_remote(None)
+            .ok()
+            .and_then(|remote| {
+                remote
+                    .url(gix::remote::Direction::Fetch)
+                    .map(|url| url.to_bstring())
+            })
+            .map(|url| url.to_string());
+
+        let mut updated = Vec::new();
+        for submodule in submodules {
+            let relative = submodule_path(&submodule)?;
+            let path = format!("{}{}", prefix, relative);
+            if self
+                .config
+                .submodules
+                .skip
+                .iter()
+                .any(|skip| skip.trim_matches('/') == path)
+            {
+                info!("Skipping submodule {}", path);
+                continue;
+            }
+            let Some(entry) = index
+                .entry_by_path(relative.as_str().into())
+                .filter(|entry| entry.mode == Mode::COMMIT)
+            else {
+                debug!("Submodule {} is not recorded in the index", path);
+                continue;
+            };
+            if entry.flags.contains(Flags::SKIP_WORKTREE) {
+                debug!("Submodule {} is outside the sparse checkout", path);
+                continue;
+            }
+
+            match self.update_submodule(&submodule, parent_url.as_deref(), entry.id) {
+                Ok(sub_repo) => {
+                    info!("Submodule {} at {}", path, entry.id);
+                    updated.push(SubmoduleInfo {
+                        path: path.clone(),
+                        commit: entry.id.to_string(),
+                    });
+                    if self.config.submodules.recursive {
+                        updated.extend(self.update_submodules(&sub_repo, &format!("{}/", path))?);
+                    }
+                }
+                Err(e) => warn!("Failed to update submodule {}: {}", path, e),
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Clone the submodule if needed, fetch `commit` if it is missing and
+    /// check it out with a detached HEAD.
+    fn update_submodule(
+        &self,
+        submodule: &gix::Submodule<'_>,
+        parent_url: Option<&str>,
+        commit: gix::ObjectId,
+    ) -> Result<gix::Repository> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            PipelineError::RepositorySync(format!("Submodule {}: {}", submodule.name(), e))
+        };
+
+        let (repo, fresh) = match submodule.open().map_err(|e| invalid(&e))? {
+            Some(repo) => (with_reflog_identity(repo)?, false),
+            None => {
+                let url = submodule
+                    .url()
+                    .map_err(|e| invalid(&e))?
+                    .to_bstring()
+                    .to_string();
+                let url = match parent_url {
+                    Some(parent) => submodule::resolve_url(parent, &url),
+                    None => url,
+                };
+                let dir = submodule.work_dir().map_err(|e| invalid(&e))?;
+                info!("Cloning submodule {} from {}", submodule.name(), url);
+                std::fs::create_dir_all(&dir)?;
+                let (repo, _outcome) = gix::prepare_clone(url, &dir)?
+                    .fetch_only(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
+                    .map_err(|e| invalid(&e))?;
+                (repo, true)
+            }
+        };
 
-            info!("Repository updated successfully");
+        if repo.find_object(commit).is_err() {
+            fetch_remote(&repo, &[], None)?;
+        }
+        if repo.find_object(commit).is_err() {
+            // Not reachable from any branch; ask for the commit itself.
+            fetch_remote(&repo, &[commit.to_string()], None)?;
+        }
+
+        if fresh {
+            checkout_commit(&repo, commit, None)?;
         } else {
-            warn!("Repository requires manual merge");
+            let changes = update_worktree(&repo, commit, None)?;
+            if !changes.is_empty() {
+                info!("Submodule {} updated: {}", submodule.name(), changes);
+            }
         }
+        let pinned = ResolvedReference {
+            name: commit.to_string(),
+            kind: ReferenceKind::Commit,
+            commit: commit.to_string(),
+        };
+        self.update_head(&repo, &pinned, commit)?;
+        Ok(repo)
+    }
+
+    /// Point HEAD at the resolved reference: the local branch (created or
+    /// moved to `commit`) for a branch, otherwise `commit` itself, detached.
+    fn update_head(
+        &self,
+        repo: &gix::Repository,
+        resolved: &ResolvedReference,
+        commit: gix::ObjectId,
+    ) -> Result<()> {
+        use gix::refs::transaction::{Change, LogChange, PreviousValue, RefEdit};
+
+        let message = format!("git_summarize: checkout {}", resolved.name);
+        let target = if resolved.kind.is_pinned() {
+            gix::refs::Target::Object(commit)
+        } else {
+            let branch = format!("refs/heads/{}", resolved.name);
+            repo.reference(
+                branch.as_str(),
+                commit,
+                PreviousValue::Any,
+                message.as_str(),
+            )?;
+            gix::refs::Target::Symbolic(branch.as_str().try_into().map_err(|e| {
+                PipelineError::GitReference(format!("Invalid branch name {}: {}", branch, e))
+            })?)
+        };
 
+        repo.edit_reference(RefEdit {
+            change: Change::Update {
+                log: LogChange {
+                    message: message.into(),
+                    ..Default::default()
+                },
+                expected: PreviousValue::Any,
+                new: target,
+            },
+            name: "HEAD".try_into().expect("HEAD is a valid reference name"),
+            deref: false,
+        })?;
         Ok(())
     }
 
+    fn not_found(&self, name: &str) -> PipelineError {
+        PipelineError::GitReference(format!(
+            "'{}' is not a branch, tag or commit of {}",
+            name, self.config.source_url
+        ))
+    }
+
+    /// Keeps fetches at the configured depth, if any.
+    fn shallow(&self) -> Option<gix::remote::fetch::Shallow> {
+        self.config
+            .depth
+            .and_then(std::num::NonZeroU32::new)
+            .map(gix::remote::fetch::Shallow::DepthAtRemote)
+    }
+
+    /// What is checked out now: the configured reference (or HEAD's branch)
+    /// at the current commit, or the state of a local source.
+    pub fn current_reference(&self) -> Result<ResolvedReference> {
+        if let RepositorySource::Local(path) = self.config.source() {
+            return Ok(self.inspect_local(&path)?.reference);
+        }
+        let repo = gix::open(&self.config.local_path)?;
+        let name = self.reference_name(&repo)?;
+        let kind = reference::resolve(&repo, &remote_name(&repo)?, &name)?
+            .map(|(kind, _)| kind)
+            .unwrap_or(ReferenceKind::Commit);
+        Ok(ResolvedReference {
+            name,
+            kind,
+            commit: self.get_current_commit()?,
+        })
+    }
+
+    /// Export the files of `name` (a branch, tag or commit) to a temporary
+    /// directory, leaving the checkout as it is. A clone fetches `name`
+    /// first when it does not have it; a local checkout must already have it.
+    pub fn snapshot(&self, name: &str) -> Result<RefSnapshot> {
+        let (repo, remote) = match self.config.source() {
+            RepositorySource::Local(path) => {
+                let repo = gix::open(&path).map_err(|_| {
+                    PipelineError::GitReference(format!(
+                        "{} is not a git repository; it has no refs to ingest",
+                        path.display()
+                    ))
+                })?;
+                (repo, false)
+            }
+            RepositorySource::Remote(_) => (gix::open(&self.config.local_path)?, true),
+        };
+        let remote_name = remote_name(&repo).unwrap_or_else(|_| "origin".to_string());
+
+        let found = match reference::resolve(&repo, &remote_name, name)? {
+            Some(found) => Some(found),
+            None if remote => {
+                info!("Fetching {}", name);
+                self.fetch(&repo, &reference::fetch_refspecs(&remote_name, name))?;
+                reference::resolve(&repo, &remote_name, name)?
+            }
+            None => None,
+        };
+        let (kind, commit) = found.ok_or_else(|| self.not_found(name))?;
+
+        let resolved = ResolvedReference {
+            name: name.to_string(),
+            kind,
+            commit: commit.to_string(),
+        };
+        let cone = SparseCone::new(&self.config.sparse_paths);
+        RefSnapshot::export(&repo, resolved, cone.as_ref())
+    }
+
+    /// The checked-out commit; for local sources, [`LocalVersion::version`].
     pub fn get_current_commit(&self) -> Result<String> {
+        if let RepositorySource::Local(path) = self.config.source() {
+            return Ok(LocalVersion::inspect(&path)?.version());
+        }
         let repo = gix::open(&self.config.local_path)?;
 
         let mut head = repo.head()?;
@@ -165,18 +618,242 @@ impl RepositorySync {
     }
 }
 
-fn remote_symbolic_name(remote: &gix::Remote<'_>) -> Option<String> {
-    match remote.name()? {
-        Name::Symbol(symbol) => Some(symbol.to_string()),
-        Name::Url(_) => None,
-    }
+/// Fetch from the default remote with its refspecs plus `refspecs`.
+/// Returns the number of mapped refs.
+fn fetch_remote(
+    repo: &gix::Repository,
+    refspecs: &[String],
+    shallow: Option<gix::remote::fetch::Shallow>,
+) -> Result<usize> {
+    let remote = repo
+        .find_fetch_remote(None)
+        .map_err(|e| PipelineError::RepositorySync(format!("Failed to resolve remote: {}", e)))?;
+
+    let extra_refspecs = refspecs
+        .iter()
+        .map(|spec| {
+            gix::refspec::parse(spec.as_str().into(), gix::refspec::parse::Operation::Fetch)
+                .map(|spec| spec.to_owned())
+                .map_err(|e| {
+                    PipelineError::GitReference(format!("Invalid refspec {}: {}", spec, e))
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let options = gix::remote::ref_map::Options {
+        extra_refspecs,
+        ..Default::default()
+    };
+
+    let outcome = remote
+        .connect(gix::remote::Direction::Fetch)?
+        .prepare_fetch(gix::progress::Discard, options)?
+        .with_shallow(shallow.unwrap_or_default())
+        .receive(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
+        .map_err(|e| {
+            PipelineError::RepositorySync(format!("Failed to fetch from remote: {}", e))
+        })?;
+    Ok(outcome.ref_map.mappings.len())
+}
+
+/// Branch and HEAD updates write reflog entries, which need a committer.
+/// Like a clone, fall back to a generic one when no identity is configured.
+fn with_reflog_identity(mut repo: gix::Repository) -> Result<gix::Repository> {
+    repo.committer_or_set_generic_fallback().map_err(|e| {
+        PipelineError::RepositorySync(format!("Invalid committer configuration: {}", e))
+    })?;
+    Ok(repo)
+}
+
+/// Name of the fetch remote, as used in `refs/remotes/<name>/...`.
+fn remote_name(repo: &gix::Repository) -> Result<String> {
+    let remote = repo
+        .find_fetch_remote(None)
+        .map_err(|e| PipelineError::RepositorySync(format!("Failed to resolve remote: {}", e)))?;
+    Ok(match remote.name() {
+        Some(Name::Symbol(symbol)) => symbol.to_string(),
+        _ => "origin".to_string(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
+    use crate::repository::testing::GitFixture;
     use tempfile::TempDir;
 
+    /// Syncs the existing clone at `local_path`; fetches go to its `origin`.
+    fn clone_config(local_path: &Path, on_divergence: DivergencePolicy) -> RepositoryConfig {
+        RepositoryConfig {
+            source_url: "https://example.com/docs.git".to_string(),
+            local_path: local_path.to_path_buf(),
+            branch: "main".to_string(),
+            on_divergence,
+            ..Config::default_config().repository
+        }
+    }
+
+    /// A clone with a local commit on `main` while `origin/main` gained
+    /// another. Returns the clone and the local and remote commits.
+    fn diverged_clone(dir: &Path) -> (GitFixture, String, String) {
+        let upstream = GitFixture::init(&dir.join("upstream"));
+        upstream.commit(&[("a.md", Some("base"))], "base");
+        let clone = upstream.clone_to(&dir.join("clone"));
+        let local = clone.commit(&[("local.md", Some("local"))], "local");
+        let remote = upstream.commit(&[("remote.md", Some("remote"))], "remote");
+        (clone, local, remote)
+    }
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn pinned_refs_are_checked_out_with_a_detached_head() {
+        let temp = TempDir::new().unwrap();
+        let upstream = GitFixture::init(&temp.path().join("upstream"));
+        let first = upstream.commit(&[("a.md", Some("one"))], "first");
+        upstream.git(&["tag", "--annotate", "v1", "--message", "release 1"]);
+        let second = upstream.commit(&[("a.md", Some("two"))], "second");
+        let clone = upstream.clone_to(&temp.path().join("clone"));
+        let sync = |branch: &str| {
+            RepositorySync::new(RepositoryConfig {
+                branch: branch.to_string(),
+                ..clone_config(&clone.path, DivergencePolicy::Fail)
+            })
+            .sync()
+        };
+        let head_name = || {
+            gix::open(&clone.path)
+                .unwrap()
+                .head_name()
+                .unwrap()
+                .map(|name| name.shorten().to_string())
+        };
+
+        let report = sync("v1").unwrap();
+        assert_eq!(report.reference.kind, ReferenceKind::Tag);
+        assert_eq!(report.reference.commit, first);
+        assert_eq!(head_name(), None);
+        assert_eq!(clone.head(), first);
+        assert_eq!(clone.read("a.md").as_deref(), Some("one"));
+
+        // Back on the branch, HEAD is attached again.
+        let report = sync("main").unwrap();
+        assert_eq!(report.reference.commit, second);
+        assert_eq!(head_name().as_deref(), Some("main"));
+        assert_eq!(clone.read("a.md").as_deref(), Some("two"));
+
+        let report = sync(&first[..7]).unwrap();
+        assert_eq!(report.reference.kind, ReferenceKind::Commit);
+        assert_eq!(report.reference.name, &first[..7]);
+        assert_eq!(report.reference.commit, first);
+        assert_eq!(head_name(), None);
+        assert_eq!(clone.read("a.md").as_deref(), Some("one"));
+
+        // Nothing by that name upstream either: an error, and HEAD stays put.
+        let err = sync("nope").unwrap_err();
+        assert!(matches!(err, PipelineError::GitReference(_)));
+        assert_eq!(clone.head(), first);
+    }
+
+    #[test]
+    fn fast_forward_updates_added_modified_and_deleted_files() {
+        let temp = TempDir::new().unwrap();
+        let upstream = GitFixture::init(&temp.path().join("upstream"));
+        let first = upstream.commit(
+            &[
+                ("a.md", Some("one")),
+                ("docs/old/b.md", Some("two")),
+                ("c.md", Some("three")),
+            ],
+            "first",
+        );
+        let clone = upstream.clone_to(&temp.path().join("clone"));
+        let second = upstream.commit(
+            &[
+                ("a.md", Some("one, revised")),
+                ("docs/old/b.md", None),
+                ("d.md", Some("four")),
+            ],
+            "second",
+        );
+
+        let report = RepositorySync::new(clone_config(&clone.path, DivergencePolicy::Fail))
+            .sync()
+            .unwrap();
+        assert_eq!(report.previous_commit.as_deref(), Some(first.as_str()));
+        assert_eq!(report.reference.kind, ReferenceKind::Branch);
+        assert_eq!(report.reference.commit, second);
+        assert!(!report.diverged);
+        assert_eq!(
+            report.changes,
+            WorktreeChanges {
+                added: paths(&["d.md"]),
+                modified: paths(&["a.md"]),
+                deleted: paths(&["docs/old/b.md"]),
+            }
+        );
+
+        assert_eq!(clone.git(&["rev-parse", "main"]), second);
+        assert_eq!(clone.read("a.md").as_deref(), Some("one, revised"));
+        assert_eq!(clone.read("c.md").as_deref(), Some("three"));
+        assert_eq!(clone.read("d.md").as_deref(), Some("four"));
+        assert!(!clone.path.join("docs").exists());
+        // The index matches the worktree, as after a `git pull`.
+        assert_eq!(clone.git(&["status", "--porcelain"]), "");
+
+        // Nothing new upstream: nothing to change.
+        let report = RepositorySync::new(clone_config(&clone.path, DivergencePolicy::Fail))
+            .sync()
+            .unwrap();
+        assert_eq!(report.previous_commit.as_deref(), Some(second.as_str()));
+        assert!(report.changes.is_empty());
+    }
+
+    #[test]
+    fn diverged_branches_follow_on_divergence() {
+        let temp = TempDir::new().unwrap();
+
+        // A non-fast-forward is rejected and leaves the clone alone.
+        let (clone, local, _) = diverged_clone(&temp.path().join("fail"));
+        let err = RepositorySync::new(clone_config(&clone.path, DivergencePolicy::Fail))
+            .sync()
+            .unwrap_err();
+        assert!(matches!(err, PipelineError::RepositorySync(_)));
+        assert_eq!(clone.head(), local);
+        assert!(clone.read("local.md").is_some());
+        assert!(clone.read("remote.md").is_none());
+
+        let (clone, local, _) = diverged_clone(&temp.path().join("skip"));
+        let report = RepositorySync::new(clone_config(&clone.path, DivergencePolicy::Skip))
+            .sync()
+            .unwrap();
+        assert!(report.diverged);
+        assert_eq!(report.reference.commit, local);
+        assert!(report.changes.is_empty());
+        assert_eq!(clone.head(), local);
+        assert!(clone.read("remote.md").is_none());
+
+        let (clone, _, remote) = diverged_clone(&temp.path().join("reset"));
+        let report = RepositorySync::new(clone_config(&clone.path, DivergencePolicy::Reset))
+            .sync()
+            .unwrap();
+        assert!(!report.diverged);
+        assert_eq!(report.reference.commit, remote);
+        assert_eq!(
+            report.changes,
+            WorktreeChanges {
+                added: paths(&["remote.md"]),
+                modified: Vec::new(),
+                deleted: paths(&["local.md"]),
+            }
+        );
+        assert_eq!(clone.head(), remote);
+        assert!(clone.read("local.md").is_none());
+        assert_eq!(clone.git(&["status", "--porcelain"]), "");
+    }
+
     #[test]
     fn test_sync_creation() {
         let temp = TempDir::new().unwrap();
@@ -185,6 +862,12 @@ mod tests {
             local_path: temp.path().to_path_buf(),
             branch: "main".to_string(),
             sync_on_start: true,
+            depth: None,
+            sparse_paths: Vec::new(),
+            on_divergence: Default::default(),
+            submodules: Default::default(),
+            history: Default::default(),
+            blame: Default::default(),
         };
 
         let sync = RepositorySync::new(config);
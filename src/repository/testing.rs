@@ -0,0 +1,83 @@
+// file: src/repository/testing.rs
+// description: throwaway git repositories for tests, built with the git cli
+// reference: https://git-scm.com/docs/git
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A repository on disk that tests commit to with the `git` command line,
+/// isolated from the user's and the system's git configuration.
+pub struct GitFixture {
+    pub path: PathBuf,
+}
+
+impl GitFixture {
+    /// An empty repository whose initial branch is `main`.
+    pub fn init(path: &Path) -> Self {
+        std::fs::create_dir_all(path).unwrap();
+        let fixture = Self {
+            path: path.to_path_buf(),
+        };
+        fixture.git(&["init", "--quiet", "--initial-branch=main"]);
+        fixture
+    }
+
+    /// A clone of this repository at `path`, with `origin` pointing here.
+    pub fn clone_to(&self, path: &Path) -> Self {
+        let source = self.path.display().to_string();
+        let destination = path.display().to_string();
+        run_git(&self.path, &["clone", "--quiet", &source, &destination]);
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Run `git` in the repository and return its trimmed standard output.
+    pub fn git(&self, args: &[&str]) -> String {
+        run_git(&self.path, args)
+    }
+
+    /// Write (`Some`) or delete (`None`) the files, commit everything and
+    /// return the new commit's id.
+    pub fn commit(&self, files: &[(&str, Option<&str>)], message: &str) -> String {
+        for (relative_path, content) in files {
+            let path = self.path.join(relative_path);
+            match content {
+                Some(content) => {
+                    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                    std::fs::write(&path, content).unwrap();
+                }
+                None => std::fs::remove_file(&path).unwrap(),
+            }
+        }
+        self.git(&["add", "--all"]);
+        self.git(&["commit", "--quiet", "--message", message]);
+        self.head()
+    }
+
+    pub fn head(&self) -> String {
+        self.git(&["rev-parse", "HEAD"])
+    }
+
+    pub fn read(&self, relative_path: &str) -> Option<String> {
+        std::fs::read_to_string(self.path.join(relative_path)).ok()
+    }
+}
+
+fn run_git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .output()
+        .expect("git is installed");
+    assert!(
+        output.status.success(),
+        "git {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
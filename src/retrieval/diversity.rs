@@ -0,0 +1,248 @@
+// file: src/retrieval/diversity.rs
+// description: result diversification: adjacent-chunk merging, per-file caps and MMR
+// reference: https://www.cs.cmu.edu/~jgc/publication/The_Use_MMR_Diversity_Based_LTMIR_1998.pdf
+
+use crate::models::SearchResult;
+use std::collections::HashMap;
+
+/// Shortest suffix/prefix match treated as real window overlap when stitching
+/// adjacent chunks; anything shorter is joined with a paragraph break instead.
+const MIN_STITCH_OVERLAP: usize = 20;
+
+/// Merge runs of consecutive chunks (same [`SearchResult::file_key`],
+/// consecutive `chunk_index`) into a single result. The overlap `window_text`
+/// leaves between neighbouring windows is removed. A merged result keeps the
+/// first chunk's id, location and index, and the best score of the run.
+pub fn merge_adjacent(results: Vec<SearchResult>) -> Vec<SearchResult> {
+    let mut by_file: HashMap<FileKey, Vec<SearchResult>> = HashMap::new();
+    for result in results {
+        let (repository_url, relative_path, git_ref) = result.file_key();
+        let key = (
+            repository_url.to_string(),
+            relative_path.to_string(),
+            git_ref.map(str::to_string),
+        );
+        by_file.entry(key).or_default().push(result);
+    }
+
+    let mut merged = Vec::new();
+    for (_, mut chunks) in by_file {
+        chunks.sort_by_key(|c| c.chunk_index);
+
+        let mut run: Option<(SearchResult, u32)> = None;
+        for chunk in chunks {
+            run = Some(match run {
+                Some((mut head, last_index)) if chunk.chunk_index == last_index + 1 => {
+                    head.content = stitch(&head.content, &chunk.content);
+                    head.score = head.score.max(chunk.score);
+                    head.distance = match (head.distance, chunk.distance) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                    (head, chunk.chunk_index)
+                }
+                Some((head, _)) => {
+                    merged.push(head);
+                    let index = chunk.chunk_index;
+                    (chunk, index)
+                }
+                None => {
+                    let index = chunk.chunk_index;
+                    (chunk, index)
+                }
+            });
+        }
+        if let Some((head, _)) = run {
+            merged.push(head);
+        }
+    }
+
+    merged.sort_by(|a, b| b.score.total_cmp(&a.score));
+    merged
+}
+
+/// Keep at most `max_per_file` results from any one source file (at one
+/// ref), preserving the incoming order.
+pub fn cap_per_file(results: Vec<SearchResult>, max_per_file: usize) -> Vec<SearchResult> {
+    let mut counts: HashMap<FileKey, usize> = HashMap::new();
+    results
+        .into_iter()
+        .filter(|r| {
+            let (repository_url, relative_path, git_ref) = r.file_key();
+            let key = (
+                repository_url.to_string(),
+                relative_path.to_string(),
+                git_ref.map(str::to_string),
+            );
+            let count = counts.entry(key).or_insert(0);
+            *count += 1;
+            *count <= max_per_file
+        })
+        .collect()
+}
+
+/// [`SearchResult::file_key`], owned.
+type FileKey = (String, String, Option<String>);
+
+/// Select `limit` results by maximal marginal relevance: repeatedly take the
+/// candidate maximizing `lambda * score - (1 - lambda) * max_sim_to_selected`.
+/// `lambda = 1.0` is pure relevance order; lower values favour novelty.
+/// Candidates without a stored vector incur no redundancy penalty.
+pub fn mmr(candidates: Vec<SearchResult>, limit: usize, lambda: f32) -> Vec<SearchResult> {
+    let mut remaining = candidates;
+    let mut selected: Vec<SearchResult> = Vec::with_capacity(limit.min(remaining.len()));
+
+    while selected.len() < limit && !remaining.is_empty() {
+        let best = remaining
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| {
+                let redundancy = selected
+                    .iter()
+                    .filter_map(|s| similarity(candidate, s))
+                    .fold(0.0_f32, f32::max);
+                (i, lambda * candidate.score - (1.0 - lambda) * redundancy)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i);
+
+        match best {
+            Some(i) => selected.push(remaining.swap_remove(i)),
+            None => break,
+        }
+    }
+
+    selected
+}
+
+fn similarity(a: &SearchResult, b: &SearchResult) -> Option<f32> {
+    Some(cosine(a.embedding.as_deref()?, b.embedding.as_deref()?))
+}
+
+pub(crate) fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Concatenate two neighbouring windows, dropping the longest suffix of `a`
+/// that is also a prefix of `b`.
+fn stitch(a: &str, b: &str) -> String {
+    let max = a.len().min(b.len());
+    let overlap = b
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(b.len()))
+        .filter(|&k| k >= MIN_STITCH_OVERLAP && k <= max && a.ends_with(&b[..k]))
+        .max();
+
+    match overlap {
+        Some(k) => format!("{a}{}", &b[k..]),
+        None => format!("{a}\n\n{b}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjacent_chunks_are_stitched_without_overlap() {
+        let shared = "the overlapping tail shared by both windows";
+        let a = format!("First window body. {shared}");
+        let b = format!("{shared} and the second window continues.");
+        let merged = merge_adjacent(vec![
+            SearchResult::fixture("a")
+                .with_chunk_index(1)
+                .with_content(&b)
+                .with_score(0.4),
+            SearchResult::fixture("a")
+                .with_chunk_index(0)
+                .with_content(&a)
+                .with_score(0.8),
+            SearchResult::fixture("a")
+                .with_chunk_index(5)
+                .with_content("far away")
+                .with_score(0.3),
+        ]);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].chunk_index, 0);
+        assert_eq!(merged[0].score, 0.8);
+        assert_eq!(merged[0].content.matches(shared).count(), 1);
+        assert!(merged[0].content.ends_with("continues."));
+    }
+
+    #[test]
+    fn cap_limits_results_per_file() {
+        let capped = cap_per_file(
+            vec![
+                SearchResult::fixture("a")
+                    .with_chunk_index(0)
+                    .with_score(0.9),
+                SearchResult::fixture("a")
+                    .with_chunk_index(1)
+                    .with_score(0.8),
+                SearchResult::fixture("b")
+                    .with_chunk_index(0)
+                    .with_score(0.7),
+                SearchResult::fixture("a")
+                    .with_chunk_index(2)
+                    .with_score(0.6),
+            ],
+            2,
+        );
+        let ids: Vec<_> = capped.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a.md#0", "a.md#1", "b.md#0"]);
+    }
+
+    #[test]
+    fn the_same_file_at_two_refs_is_kept_apart() {
+        let at = |git_ref: &str, index, score| {
+            SearchResult::fixture("a")
+                .with_chunk_index(index)
+                .with_content(git_ref)
+                .with_score(score)
+                .with_reference(Some(git_ref.to_string()), None)
+        };
+        let merged = merge_adjacent(vec![at("v1", 0, 0.9), at("v2", 1, 0.8)]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].content, "v1");
+
+        let capped = cap_per_file(
+            vec![at("v1", 0, 0.9), at("v2", 0, 0.8), at("v1", 1, 0.7)],
+            1,
+        );
+        let refs: Vec<_> = capped.iter().map(|r| r.git_ref.as_deref()).collect();
+        assert_eq!(refs, vec![Some("v1"), Some("v2")]);
+    }
+
+    #[test]
+    fn mmr_prefers_novel_candidates() {
+        let selected = mmr(
+            vec![
+                SearchResult::fixture("a")
+                    .with_chunk_index(0)
+                    .with_score(0.90)
+                    .with_embedding(vec![1.0, 0.0]),
+                SearchResult::fixture("a")
+                    .with_chunk_index(1)
+                    .with_score(0.89)
+                    .with_embedding(vec![1.0, 0.01]),
+                SearchResult::fixture("b")
+                    .with_chunk_index(0)
+                    .with_score(0.70)
+                    .with_embedding(vec![0.0, 1.0]),
+            ],
+            2,
+            0.5,
+        );
+        let ids: Vec<_> = selected.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a.md#0", "b.md#0"]);
+    }
+}
@@ -0,0 +1,135 @@
+// file: src/retrieval/expansion.rs
+// description: LLM-driven query rewriting (multi-query, HyDE, step-back)
+// reference: https://arxiv.org/abs/2212.10496, https://arxiv.org/abs/2310.06117
+
+use crate::config::{QueryStrategy, RetrievalConfig};
+use crate::error::Result;
+use crate::generation::AnswerGenerator;
+use tracing::{debug, warn};
+
+const MULTI_QUERY_PROMPT: &str = "You rewrite search queries for a documentation search engine. \
+Given a question, produce alternative phrasings that use different vocabulary a technical \
+document might use. Output one query per line with no numbering, bullets, or commentary.";
+
+const HYDE_PROMPT: &str = "You write short passages of software documentation. Given a question, \
+write the paragraph from a project's docs that would answer it. Be concrete and technical. \
+If you are unsure of details, write a plausible passage anyway; it is only used for search.";
+
+const STEP_BACK_PROMPT: &str = "You help a documentation search engine. Given a specific question, \
+rewrite it as a single broader question about the underlying concept, component, or mechanism. \
+Output only the rewritten question.";
+
+/// Turns one user query into the set of texts that get embedded and searched.
+pub struct QueryExpander<'a> {
+    generator: &'a AnswerGenerator,
+    config: &'a RetrievalConfig,
+}
+
+impl<'a> QueryExpander<'a> {
+    pub fn new(generator: &'a AnswerGenerator, config: &'a RetrievalConfig) -> Self {
+        Self { generator, config }
+    }
+
+    /// Return the queries to search with. The raw query is always first; if the
+    /// rewriting call fails the raw query is searched alone rather than failing
+    /// the whole request.
+    pub async fn expand(&self, query: &str) -> Vec<String> {
+        let mut queries = vec![query.to_string()];
+
+        let extra = match self.config.strategy {
+            QueryStrategy::Direct => return queries,
+            QueryStrategy::MultiQuery => self.multi_query(query).await,
+            QueryStrategy::Hyde => self.hypothetical_answer(query).await.map(|a| vec![a]),
+            QueryStrategy::StepBack => self.step_back(query).await.map(|q| vec![q]),
+        };
+
+        match extra {
+            Ok(extra) => {
+                for q in extra {
+                    if !q.is_empty() && !queries.iter().any(|e| e.eq_ignore_ascii_case(&q)) {
+                        queries.push(q);
+                    }
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Query expansion ({:?}) failed ({e}); searching with the raw query only",
+                    self.config.strategy
+                );
+            }
+        }
+
+        debug!(
+            "Expanded query into {} search(es): {:?}",
+            queries.len(),
+            queries
+        );
+        queries
+    }
+
+    async fn multi_query(&self, query: &str) -> Result<Vec<String>> {
+        let user = format!(
+            "Write {} alternative search queries for:\n\n{query}",
+            self.config.num_queries
+        );
+        let text = self
+            .generator
+            .complete(MULTI_QUERY_PROMPT, &user, 0.7)
+            .await?;
+        Ok(parse_query_lines(&text, self.config.num_queries))
+    }
+
+    async fn hypothetical_answer(&self, query: &str) -> Result<String> {
+        let text = self.generator.complete(HYDE_PROMPT, query, 0.7).await?;
+        Ok(text.trim().to_string())
+    }
+
+    async fn step_back(&self, query: &str) -> Result<String> {
+        let text = self
+            .generator
+            .complete(STEP_BACK_PROMPT, query, 0.2)
+            .await?;
+        Ok(parse_query_lines(&text, 1)
+            .into_iter()
+            .next()
+            .unwrap_or_default())
+    }
+}
+
+/// Parse a model's line-per-query output, stripping list markers and quotes
+/// models tend to add despite instructions.
+fn parse_query_lines(text: &str, max: usize) -> Vec<String> {
+    text.lines()
+        .map(|line| {
+            line.trim()
+                .trim_start_matches(|c: char| {
+                    c.is_ascii_digit() || matches!(c, '.' | ')' | '-' | '*' | '•')
+                })
+                .trim()
+                .trim_matches('"')
+                .trim()
+                .to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .take(max)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_numbered_and_bulleted_lines() {
+        let text = "1. how to install\n- \"setup guide\"\n\n* build from source\n4) extra";
+        assert_eq!(
+            parse_query_lines(text, 3),
+            vec!["how to install", "setup guide", "build from source"]
+        );
+    }
+
+    #[test]
+    fn empty_output_yields_no_queries() {
+        assert!(parse_query_lines("\n  \n", 3).is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+// file: src/retrieval/mod.rs
+// description: retrieval layer between query embedding and LanceDB vector search
+// reference: internal RAG pipeline
+
+pub mod diversity;
+pub mod expansion;
+pub mod recency;
+pub mod rerank;
+
+pub use expansion::QueryExpander;
+pub use rerank::{HttpReranker, LexicalReranker, LlmJudgeReranker, Reranker, build_reranker};
+
+use crate::config::{Config, EmbeddingConfig, RetrievalConfig};
+use crate::database::{
+    CommitStore, EmbeddingClient, LanceDbClient, SchemaManager, SearchFilter, VectorSearchOptions,
+};
+use crate::error::Result;
+use crate::generation::AnswerGenerator;
+use crate::models::SearchResult;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// Runs a query through the configured retrieval strategy: expand it into one or
+/// more search texts, embed them in a single batch, search the index for each,
+/// merge the hits into one de-duplicated list, then optionally rerank, boost
+/// recently changed sections and diversify. With history enabled, commits are
+/// searched with the same query vectors and ranked in with the document chunks.
+pub struct Retriever<'a> {
+    client: &'a LanceDbClient,
+    embedding: EmbeddingClient,
+    embedding_config: EmbeddingConfig,
+    generator: AnswerGenerator,
+    reranker: Option<Box<dyn Reranker>>,
+    config: RetrievalConfig,
+    search_options: VectorSearchOptions,
+    filter: Option<SearchFilter>,
+    history: CommitStore,
+    history_limit: usize,
+}
+
+impl<'a> Retriever<'a> {
+    pub fn new(client: &'a LanceDbClient, config: &Config) -> Result<Self> {
+        Ok(Self {
+            client,
+            embedding: EmbeddingClient::new(config.embedding.clone()),
+            embedding_config: config.embedding.clone(),
+            generator: AnswerGenerator::new(config.generation.clone())?,
+            reranker: build_reranker(config)?,
+            config: config.retrieval.clone(),
+            search_options: VectorSearchOptions::default(),
+            filter: None,
+            history: CommitStore::new(client, &config.repository.history),
+            history_limit: 0,
+        })
+    }
+
+    /// Override the ANN tuning (`nprobes`, `refine_factor`) for this retriever.
+    pub fn with_search_options(mut self, options: VectorSearchOptions) -> Self {
+        self.search_options = options;
+        self
+    }
+
+    /// Restrict every search to chunks matching `filter`.
+    pub fn with_filter(mut self, filter: Option<SearchFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Override `retrieval.recency_weight`; `None` keeps the configured value.
+    pub fn with_recency(mut self, weight: Option<f32>) -> Self {
+        if weight.is_some() {
+            self.config.recency_weight = weight;
+        }
+        self
+    }
+
+    /// Also retrieve up to `limit` commit messages or diff hunks from the
+    /// commits table. Metadata filters do not apply to them; the repository
+    /// filter does.
+    pub fn with_history(mut self, limit: usize) -> Self {
+        self.history_limit = limit;
+        self
+    }
+
+    /// Retrieve up to `limit` chunks for `query`. With a reranker or diversity
+    /// option configured, `limit * overfetch_factor` candidates are fetched and
+    /// narrowed down to `limit`.
+    pub async fn retrieve(
+        &self,
+        query: &str,
+        limit: usize,
+        repository_filter: Option<&str>,
+    ) -> Result<Vec<SearchResult>> {
+        // Query vectors from a different model would match garbage.
+        SchemaManager::new(self.client)
+            .check_embedding_provenance(&self.embedding_config)
+            .await?;
+
+        let fetch_limit = if self.config.needs_overfetch() {
+            limit.saturating_mul(self.config.rerank.overfetch_factor)
+        } else {
+            limit
+        };
+
+        let queries = QueryExpander::new(&self.generator, &self.config)
+            .expand(query)
+            .await;
+        let embeddings = self.embed_queries(&queries).await?;
+        let filter = SearchFilter::all(
+            repository_filter
+                .map(SearchFilter::repository)
+                .into_iter()
+                .chain(self.filter.clone()),
+        );
+
+        let mut result_sets = Vec::with_capacity(embeddings.len());
+        for embedding in &embeddings {
+            result_sets.push(
+                self.client
+                    .vector_search(
+                        embedding.clone(),
+                        fetch_limit,
+                        filter.as_ref(),
+                        self.search_options,
+                    )
+                    .await?,
+            );
+        }
+
+        let mut merged = merge_results(result_sets, fetch_limit);
+        info!(
+            "Retrieved {} unique chunk(s) from {} search(es)",
+            merged.len(),
+            queries.len()
+        );
+
+        merged = self.rerank(query, merged).await;
+        if let Some(weight) = self.config.recency_weight {
+            let now = chrono::Utc::now().timestamp().max(0) as u64;
+            merged = recency::boost(merged, weight, self.config.recency_half_life_days, now);
+        }
+        let mut results = self.diversify(merged, limit);
+
+        if self.history_limit > 0 {
+            let history = self
+                .search_history(query, embeddings, repository_filter)
+                .await;
+            info!("Retrieved {} commit(s) from history", history.len());
+            results = interleave_by_score(results, history);
+        }
+        Ok(results)
+    }
+
+    async fn rerank(&self, query: &str, results: Vec<SearchResult>) -> Vec<SearchResult> {
+        let Some(reranker) = &self.reranker else {
+            return results;
+        };
+        match reranker.rerank(query, results.clone()).await {
+            Ok(reranked) => reranked,
+            Err(e) => {
+                warn!("Reranking failed ({e}); keeping vector search order");
+                results
+            }
+        }
+    }
+
+    /// Best commit hits over all query vectors, reranked like the documents
+    /// so their scores stay comparable. Failures only cost the history part.
+    async fn search_history(
+        &self,
+        query: &str,
+        embeddings: Vec<Vec<f32>>,
+        repository_filter: Option<&str>,
+    ) -> Vec<SearchResult> {
+        let mut result_sets = Vec::with_capacity(embeddings.len());
+        for embedding in embeddings {
+            match self
+                .history
+                .search(embedding, self.history_limit, repository_filter)
+                .await
+            {
+                Ok(results) => result_sets.push(results),
+                Err(e) => {
+                    warn!("History search failed: {}", e);
+                    return Vec::new();
+                }
+            }
+        }
+        let merged = merge_results(result_sets, self.history_limit);
+        self.rerank(query, merged).await
+    }
+
+    /// Apply adjacent-chunk merging, the per-file cap and MMR (each only when
+    /// configured), then cut to `limit`.
+    fn diversify(&self, mut results: Vec<SearchResult>, limit: usize) -> Vec<SearchResult> {
+        if self.config.merge_adjacent {
+            results = diversity::merge_adjacent(results);
+        }
+        if let Some(max_per_file) = self.config.max_per_file {
+            results = diversity::cap_per_file(results, max_per_file);
+        }
+        if let Some(lambda) = self.config.mmr_lambda {
+            results = diversity::mmr(results, limit, lambda);
+        }
+        results.truncate(limit);
+        results
+    }
+
+    /// Embed all search texts, honoring the `allow_fallback` flag; otherwise a
+    /// failed call surfaces as an error.
+    async fn embed_queries(&self, queries: &[String]) -> Result<Vec<Vec<f32>>> {
+        match self.embedding.embed_batch(queries).await {
+            Ok(v) => Ok(v),
+            Err(e) if self.embedding_config.allow_fallback => {
+                warn!("Query embedding failed ({e}); using non-semantic fallback (degraded)");
+                Ok(queries
+                    .iter()
+                    .map(|q| {
+                        EmbeddingClient::generate_fallback_embedding(
+                            q,
+                            self.embedding_config.dimension,
+                        )
+                    })
+                    .collect())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Union several ranked result lists, keeping each chunk once with its best
+/// score, then order by score and cut to `limit`. Chunks are keyed by file and
+/// text, so a section unchanged across several indexed refs appears once.
+pub fn merge_results(result_sets: Vec<Vec<SearchResult>>, limit: usize) -> Vec<SearchResult> {
+    let mut best: HashMap<(String, String, String), SearchResult> = HashMap::new();
+    for result in result_sets.into_iter().flatten() {
+        let (repository_url, relative_path, content) = result.content_key();
+        let key = (
+            repository_url.to_string(),
+            relative_path.to_string(),
+            content.to_string(),
+        );
+        match best.get(&key) {
+            Some(existing) if existing.score >= result.score => {}
+            _ => {
+                best.insert(key, result);
+            }
+        }
+    }
+
+    let mut merged: Vec<SearchResult> = best.into_values().collect();
+    merged.sort_by(|a, b| b.score.total_cmp(&a.score));
+    merged.truncate(limit);
+    merged
+}
+
+/// Merge `extra` (best first) into `results` by score without reordering
+/// `results`, whose order may come from diversification rather than score.
+fn interleave_by_score(results: Vec<SearchResult>, extra: Vec<SearchResult>) -> Vec<SearchResult> {
+    let mut merged = Vec::with_capacity(results.len() + extra.len());
+    let mut extra = extra.into_iter().peekable();
+    for result in results {
+        while let Some(next) = extra.next_if(|next| next.score > result.score) {
+            merged.push(next);
+        }
+        merged.push(result);
+    }
+    merged.extend(extra);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_dedups_by_content_keeping_best_score() {
+        let merged = merge_results(
+            vec![
+                vec![
+                    SearchResult::fixture("a").with_score(0.5),
+                    SearchResult::fixture("b").with_score(0.4),
+                ],
+                vec![
+                    SearchResult::fixture("a").with_score(0.9),
+                    SearchResult::fixture("c").with_score(0.3),
+                ],
+            ],
+            10,
+        );
+        let ids: Vec<_> = merged.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert_eq!(merged[0].score, 0.9);
+    }
+
+    #[test]
+    fn merge_collapses_a_section_unchanged_across_refs() {
+        let at = |git_ref: &str, score| {
+            let mut result = SearchResult::fixture(&format!("a@{git_ref}"))
+                .with_score(score)
+                .with_content("Unchanged");
+            result.relative_path = "a.md".to_string();
+            result.with_reference(Some(git_ref.to_string()), None)
+        };
+        let merged = merge_results(vec![vec![at("v1", 0.5), at("v2", 0.6)]], 10);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].git_ref.as_deref(), Some("v2"));
+
+        let mut changed = at("v3", 0.4);
+        changed.content = "Changed".to_string();
+        let merged = merge_results(vec![vec![at("v1", 0.5)], vec![changed]], 10);
+        let refs: Vec<_> = merged.iter().map(|r| r.git_ref.as_deref()).collect();
+        assert_eq!(refs, vec![Some("v1"), Some("v3")]);
+    }
+
+    #[test]
+    fn history_is_interleaved_without_reordering_documents() {
+        let merged = interleave_by_score(
+            vec![
+                SearchResult::fixture("a").with_score(0.9),
+                SearchResult::fixture("b").with_score(0.5),
+                SearchResult::fixture("c").with_score(0.7),
+            ],
+            vec![
+                SearchResult::fixture("h1").with_score(0.8),
+                SearchResult::fixture("h2").with_score(0.6),
+                SearchResult::fixture("h3").with_score(0.1),
+            ],
+        );
+        let ids: Vec<_> = merged.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "h1", "h2", "b", "c", "h3"]);
+    }
+
+    #[test]
+    fn merge_truncates_to_limit() {
+        let merged = merge_results(
+            vec![vec![
+                SearchResult::fixture("a").with_score(0.5),
+                SearchResult::fixture("b").with_score(0.4),
+            ]],
+            1,
+        );
+        assert_eq!(merged.len(), 1);
+    }
+}
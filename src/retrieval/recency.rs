@@ -0,0 +1,74 @@
+// file: src/retrieval/recency.rs
+// description: recency boosting by the date of the last commit touching a chunk
+// reference: https://en.wikipedia.org/wiki/Half-life
+
+use crate::models::SearchResult;
+
+const SECONDS_PER_DAY: f32 = 86_400.0;
+
+/// Freshness in `(0, 1]`: 1 for a chunk changed at `now`, halving every
+/// `half_life_days`.
+pub fn freshness(changed_at: u64, now: u64, half_life_days: f32) -> f32 {
+    let age_days = now.saturating_sub(changed_at) as f32 / SECONDS_PER_DAY;
+    0.5f32.powf(age_days / half_life_days.max(f32::EPSILON))
+}
+
+/// Multiply each score by `1 + weight * freshness` and re-sort, so recently
+/// changed sections win close calls. Results without a blame date (plain
+/// directories, tables from before blame) get no boost.
+pub fn boost(
+    mut results: Vec<SearchResult>,
+    weight: f32,
+    half_life_days: f32,
+    now: u64,
+) -> Vec<SearchResult> {
+    for result in &mut results {
+        if let Some(changed_at) = result.last_changed_at {
+            result.score *= 1.0 + weight * freshness(changed_at, now, half_life_days);
+        }
+    }
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+    const DAY: u64 = 86_400;
+
+    #[test]
+    fn freshness_halves_every_half_life() {
+        assert_eq!(freshness(NOW, NOW, 180.0), 1.0);
+        assert!((freshness(NOW - 180 * DAY, NOW, 180.0) - 0.5).abs() < 1e-4);
+        assert!((freshness(NOW - 360 * DAY, NOW, 180.0) - 0.25).abs() < 1e-4);
+        // Clock skew: commits dated in the future count as changed now.
+        assert_eq!(freshness(NOW + DAY, NOW, 180.0), 1.0);
+    }
+
+    #[test]
+    fn recent_sections_win_close_calls() {
+        let boosted = boost(
+            vec![
+                SearchResult::fixture("stale")
+                    .with_score(0.80)
+                    .with_last_change(None, None, Some(NOW - 1000 * DAY)),
+                SearchResult::fixture("fresh")
+                    .with_score(0.75)
+                    .with_last_change(None, None, Some(NOW - DAY)),
+                // A fresh mtime alone earns no boost.
+                SearchResult {
+                    last_modified: NOW,
+                    ..SearchResult::fixture("unknown").with_score(0.78)
+                },
+            ],
+            0.3,
+            180.0,
+            NOW,
+        );
+        let ids: Vec<_> = boosted.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["fresh", "stale", "unknown"]);
+        assert_eq!(boosted[2].score, 0.78);
+    }
+}
@@ -0,0 +1,362 @@
+// file: src/retrieval/rerank.rs
+// description: pluggable rerankers applied between vector search and generation
+// reference: https://docs.cohere.com/reference/rerank
+
+use crate::config::{Config, RerankConfig, RerankerKind};
+use crate::error::{PipelineError, Result};
+use crate::generation::AnswerGenerator;
+use crate::models::SearchResult;
+use crate::utils::text::significant_terms;
+use futures::future::BoxFuture;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::debug;
+
+/// Weight of the lexical overlap score when blended with the vector score.
+const LEXICAL_WEIGHT: f32 = 0.5;
+
+/// Max chars of each candidate shown to the LLM judge; keeps the prompt bounded
+/// when reranking dozens of candidates.
+const JUDGE_EXCERPT_CHARS: usize = 800;
+
+const JUDGE_PROMPT: &str = "You grade how useful documentation excerpts are for answering a \
+question. For every numbered excerpt output exactly one line in the form `n: score`, where \
+score is an integer from 0 (irrelevant) to 10 (directly answers the question). \
+Output nothing else.";
+
+/// Re-scores retrieval candidates against the query. Implementations return the
+/// candidates best-first with `score` replaced by their own relevance score;
+/// `distance` is left untouched so the original vector distance stays visible.
+pub trait Reranker: Send + Sync {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        candidates: Vec<SearchResult>,
+    ) -> BoxFuture<'a, Result<Vec<SearchResult>>>;
+}
+
+/// Build the reranker selected by `retrieval.rerank.kind`, if any.
+pub fn build_reranker(config: &Config) -> Result<Option<Box<dyn Reranker>>> {
+    let rerank = &config.retrieval.rerank;
+    Ok(match rerank.kind {
+        RerankerKind::None => None,
+        RerankerKind::Http => Some(Box::new(HttpReranker::new(rerank.clone()))),
+        RerankerKind::Llm => Some(Box::new(LlmJudgeReranker::new(AnswerGenerator::new(
+            config.generation.clone(),
+        )?))),
+        RerankerKind::Lexical => Some(Box::new(LexicalReranker)),
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct RerankRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+    query: &'a str,
+    documents: Vec<&'a str>,
+    top_n: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct RerankResponse {
+    results: Vec<RerankItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RerankItem {
+    index: usize,
+    relevance_score: f32,
+}
+
+/// Client for a Cohere/Jina-compatible `/rerank` endpoint (cross-encoder).
+pub struct HttpReranker {
+    client: Client,
+    config: RerankConfig,
+}
+
+impl HttpReranker {
+    pub fn new(config: RerankConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+
+    fn endpoint(&self) -> String {
+        format!(
+            "{}/rerank",
+            self.config
+                .base_url
+                .as_deref()
+                .unwrap_or_default()
+                .trim_end_matches('/')
+        )
+    }
+
+    async fn scores(&self, query: &str, candidates: &[SearchResult]) -> Result<Vec<(usize, f32)>> {
+        let request = RerankRequest {
+            model: self.config.model.as_deref(),
+            query,
+            documents: candidates.iter().map(|c| c.content.as_str()).collect(),
+            top_n: candidates.len(),
+        };
+
+        let mut builder = self
+            .client
+            .post(self.endpoint())
+            .header("Content-Type", "application/json")
+            .json(&request);
+        if let Some(api_key) = &self.config.api_key {
+            builder = builder.header("Authorization", format!("Bearer {}", api_key));
+        }
+
+        let response = builder.send().await.map_err(|e| {
+            PipelineError::Database(format!("Failed to send rerank request: {}", e))
+        })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(PipelineError::Database(format!(
+                "Rerank request failed with status {}: {}",
+                status, error_text
+            )));
+        }
+
+        let parsed: RerankResponse = response.json().await.map_err(|e| {
+            PipelineError::Database(format!("Failed to parse rerank response: {}", e))
+        })?;
+
+        Ok(parsed
+            .results
+            .into_iter()
+            .map(|r| (r.index, r.relevance_score))
+            .collect())
+    }
+}
+
+impl Reranker for HttpReranker {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        candidates: Vec<SearchResult>,
+    ) -> BoxFuture<'a, Result<Vec<SearchResult>>> {
+        Box::pin(async move {
+            if candidates.is_empty() {
+                return Ok(candidates);
+            }
+            debug!(
+                "Reranking {} candidate(s) via {}",
+                candidates.len(),
+                self.endpoint()
+            );
+            let scores = self.scores(query, &candidates).await?;
+            Ok(apply_scores(candidates, scores))
+        })
+    }
+}
+
+/// Uses the generation model as a relevance judge. Slower and costlier than a
+/// cross-encoder, but needs no extra provider.
+pub struct LlmJudgeReranker {
+    generator: AnswerGenerator,
+}
+
+impl LlmJudgeReranker {
+    pub fn new(generator: AnswerGenerator) -> Self {
+        Self { generator }
+    }
+}
+
+impl Reranker for LlmJudgeReranker {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        candidates: Vec<SearchResult>,
+    ) -> BoxFuture<'a, Result<Vec<SearchResult>>> {
+        Box::pin(async move {
+            if candidates.is_empty() {
+                return Ok(candidates);
+            }
+
+            let excerpts = candidates
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let excerpt: String = c.content.chars().take(JUDGE_EXCERPT_CHARS).collect();
+                    format!("[{}] {}\n{}", i + 1, c.location(), excerpt)
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let user = format!("Question: {query}\n\nExcerpts:\n\n{excerpts}");
+
+            let text = self.generator.complete(JUDGE_PROMPT, &user, 0.0).await?;
+            Ok(apply_scores(candidates, parse_judge_scores(&text)))
+        })
+    }
+}
+
+/// Blends the vector score with the fraction of query terms that appear in the
+/// chunk (content, heading breadcrumb and path). Cheap, local, and good at
+/// promoting exact identifier and keyword matches embeddings tend to blur.
+pub struct LexicalReranker;
+
+impl Reranker for LexicalReranker {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        candidates: Vec<SearchResult>,
+    ) -> BoxFuture<'a, Result<Vec<SearchResult>>> {
+        Box::pin(async move {
+            let query_terms = significant_terms(query);
+            let scores = candidates
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let overlap = term_overlap(&query_terms, c);
+                    (
+                        i,
+                        (1.0 - LEXICAL_WEIGHT) * c.score + LEXICAL_WEIGHT * overlap,
+                    )
+                })
+                .collect();
+            Ok(apply_scores(candidates, scores))
+        })
+    }
+}
+
+fn term_overlap(query_terms: &HashSet<String>, candidate: &SearchResult) -> f32 {
+    if query_terms.is_empty() {
+        return 0.0;
+    }
+    let doc_terms = significant_terms(&format!(
+        "{} {} {}",
+        candidate.relative_path, candidate.heading_path, candidate.content
+    ));
+    let hits = query_terms.intersection(&doc_terms).count();
+    hits as f32 / query_terms.len() as f32
+}
+
+/// Parse `n: score` lines (1-based `n`, score 0-10) into `(index, score)` with
+/// scores normalized to 0.0-1.0. Malformed lines are ignored.
+fn parse_judge_scores(text: &str) -> Vec<(usize, f32)> {
+    text.lines()
+        .filter_map(|line| {
+            let (n, score) = line.split_once(':')?;
+            let n: usize = n
+                .trim()
+                .trim_matches(|c| c == '[' || c == ']')
+                .parse()
+                .ok()?;
+            let score: f32 = score.split_whitespace().next()?.parse().ok()?;
+            Some((n.checked_sub(1)?, (score / 10.0).clamp(0.0, 1.0)))
+        })
+        .collect()
+}
+
+/// Apply `(candidate_index, score)` pairs and order best-first. Candidates the
+/// reranker did not score keep their relative order after the scored ones.
+/// Their vector scores are on another scale, so they are replaced by scores
+/// spaced below the lowest reranked one; later steps that sort by score keep
+/// the two tiers apart.
+fn apply_scores(candidates: Vec<SearchResult>, scores: Vec<(usize, f32)>) -> Vec<SearchResult> {
+    let mut slots: Vec<Option<f32>> = vec![None; candidates.len()];
+    for (index, score) in scores {
+        if let Some(slot) = slots.get_mut(index) {
+            *slot = Some(score);
+        }
+    }
+
+    let mut scored = Vec::new();
+    let mut unscored = Vec::new();
+    for (mut candidate, slot) in candidates.into_iter().zip(slots) {
+        match slot {
+            Some(score) => {
+                candidate.score = score;
+                scored.push(candidate);
+            }
+            None => unscored.push(candidate),
+        }
+    }
+
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+    if let Some(floor) = scored.last().map(|c| c.score) {
+        let count = unscored.len();
+        for (rank, candidate) in unscored.iter_mut().enumerate() {
+            candidate.score = if floor > 0.0 {
+                floor * (count - rank) as f32 / (count + 1) as f32
+            } else {
+                floor - (rank + 1) as f32
+            };
+        }
+    }
+    scored.extend(unscored);
+    scored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn judge_scores_are_parsed_and_normalized() {
+        let scores = parse_judge_scores("1: 7\n[2]: 10 (exact)\nnoise\n3: x\n0: 5");
+        assert_eq!(scores, vec![(0, 0.7), (1, 1.0)]);
+    }
+
+    #[test]
+    fn apply_scores_orders_and_keeps_unscored_last() {
+        let mut candidates = vec![
+            SearchResult::fixture("a").with_score(0.9),
+            SearchResult::fixture("b").with_score(0.8),
+            SearchResult::fixture("c").with_score(0.7),
+        ];
+        candidates[2].distance = Some(1.0);
+        let reranked = apply_scores(candidates, vec![(2, 0.95), (0, 0.1)]);
+        let ids: Vec<_> = reranked.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "a", "b"]);
+        assert_eq!(reranked[0].distance, Some(1.0));
+        // "b" kept its 0.8 vector score before, outranking "a" on any re-sort.
+        assert!(reranked[2].score > 0.0 && reranked[2].score < reranked[1].score);
+    }
+
+    #[test]
+    fn unscored_candidates_stay_below_reranked_ones() {
+        let reranked = apply_scores(
+            vec![
+                SearchResult::fixture("a").with_score(0.9),
+                SearchResult::fixture("b").with_score(0.8),
+                SearchResult::fixture("c").with_score(0.7),
+            ],
+            vec![(1, -2.0)],
+        );
+        let scores: Vec<f32> = reranked.iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![-2.0, -3.0, -4.0]);
+
+        let untouched = apply_scores(vec![SearchResult::fixture("a").with_score(0.9)], Vec::new());
+        assert_eq!(untouched[0].score, 0.9);
+    }
+
+    #[tokio::test]
+    async fn lexical_reranker_promotes_keyword_matches() {
+        let reranked = LexicalReranker
+            .rerank(
+                "configure retry backoff",
+                vec![
+                    SearchResult::fixture("a")
+                        .with_content("General overview of the project.")
+                        .with_score(0.6),
+                    SearchResult::fixture("b")
+                        .with_content("Set the retry backoff in the client config.")
+                        .with_score(0.5),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(reranked[0].id, "b");
+    }
+}
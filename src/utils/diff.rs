@@ -0,0 +1,233 @@
+// file: src/utils/diff.rs
+// description: line diff of two texts, grouped into unified-format hunks
+// reference: https://www.gnu.org/software/diffutils/manual/html_node/Detailed-Unified.html
+
+use std::fmt;
+
+/// Above this many cells (old lines x new lines, after trimming the common
+/// prefix and suffix) the changed region is reported as a single
+/// replacement instead of running the quadratic LCS.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Equal,
+    Removed,
+    Added,
+}
+
+/// One step of a line diff. `old` is set for equal and removed lines, `new`
+/// for equal and added ones (0-based line numbers).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineOp {
+    pub change: LineChange,
+    pub old: Option<usize>,
+    pub new: Option<usize>,
+}
+
+/// A run of changes with surrounding context, as in `diff -u`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// 1-based first line in the old text (the line before, when empty).
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<(LineChange, String)>,
+}
+
+impl Hunk {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_lines, self.new_start, self.new_lines
+        )
+    }
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.header())?;
+        for (change, line) in &self.lines {
+            let prefix = match change {
+                LineChange::Equal => ' ',
+                LineChange::Removed => '-',
+                LineChange::Added => '+',
+            };
+            write!(f, "\n{}{}", prefix, line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Line-by-line edit script turning `old` into `new`: a longest common
+/// subsequence of lines, with removals listed before additions.
+pub fn line_ops(old: &[&str], new: &[&str]) -> Vec<LineOp> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let equal = |i: usize, j: usize| LineOp {
+        change: LineChange::Equal,
+        old: Some(i),
+        new: Some(j),
+    };
+    let removed = |i: usize| LineOp {
+        change: LineChange::Removed,
+        old: Some(i),
+        new: None,
+    };
+    let added = |j: usize| LineOp {
+        change: LineChange::Added,
+        old: None,
+        new: Some(j),
+    };
+
+    let mut ops: Vec<LineOp> = (0..prefix).map(|i| equal(i, i)).collect();
+    if a.len().saturating_mul(b.len()) > MAX_LCS_CELLS {
+        ops.extend((0..a.len()).map(|i| removed(prefix + i)));
+        ops.extend((0..b.len()).map(|j| added(prefix + j)));
+    } else {
+        // lengths[i][j]: LCS length of a[i..] and b[j..]
+        let width = b.len() + 1;
+        let mut lengths = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lengths[i * width + j] = if a[i] == b[j] {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push(equal(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if j == b.len()
+                || (i < a.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+            {
+                ops.push(removed(prefix + i));
+                i += 1;
+            } else {
+                ops.push(added(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    let (old_tail, new_tail) = (old.len() - suffix, new.len() - suffix);
+    ops.extend((0..suffix).map(|k| equal(old_tail + k, new_tail + k)));
+    ops
+}
+
+/// Diff two texts into hunks with `context` unchanged lines around each
+/// change. Identical texts produce no hunks.
+pub fn diff_hunks(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = line_ops(&old_lines, &new_lines);
+
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| op.change != LineChange::Equal)
+        .map(|(index, _)| index)
+        .collect();
+    let Some(&first) = changed.first() else {
+        return Vec::new();
+    };
+
+    // Op ranges of each hunk; changes closer than 2 * context share one.
+    let mut ranges = vec![(first.saturating_sub(context), first)];
+    for &index in &changed[1..] {
+        let last = ranges.last_mut().expect("ranges is not empty");
+        if index - last.1 <= 2 * context + 1 {
+            last.1 = index;
+        } else {
+            ranges.push((index.saturating_sub(context), index));
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, last)| {
+            let end = (last + context + 1).min(ops.len());
+            let slice = &ops[start..end];
+            // Lines of each side consumed before the hunk.
+            let old_before = ops[..start].iter().filter(|op| op.old.is_some()).count();
+            let new_before = ops[..start].iter().filter(|op| op.new.is_some()).count();
+            let old_count = slice.iter().filter(|op| op.old.is_some()).count();
+            let new_count = slice.iter().filter(|op| op.new.is_some()).count();
+            Hunk {
+                old_start: old_before + usize::from(old_count > 0),
+                old_lines: old_count,
+                new_start: new_before + usize::from(new_count > 0),
+                new_lines: new_count,
+                lines: slice
+                    .iter()
+                    .map(|op| {
+                        let line = match op.change {
+                            LineChange::Added => new_lines[op.new.unwrap_or_default()],
+                            _ => old_lines[op.old.unwrap_or_default()],
+                        };
+                        (op.change, line.to_string())
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ops_keep_the_longest_common_subsequence() {
+        let ops = line_ops(&["a", "b", "c", "d"], &["a", "c", "x", "d"]);
+        let changes: Vec<LineChange> = ops.iter().map(|op| op.change).collect();
+        assert_eq!(
+            changes,
+            vec![
+                LineChange::Equal,
+                LineChange::Removed,
+                LineChange::Equal,
+                LineChange::Added,
+                LineChange::Equal,
+            ]
+        );
+        assert_eq!(ops[2].old, Some(2));
+        assert_eq!(ops[2].new, Some(1));
+    }
+
+    #[test]
+    fn hunks_are_grouped_with_context() {
+        let old: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+        let new = old
+            .replace("line 3\n", "line three\n")
+            .replace("line 17\n", "");
+
+        let hunks = diff_hunks(&old, &new, 2);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].header(), "@@ -1,5 +1,5 @@");
+        assert_eq!(
+            hunks[0].to_string(),
+            "@@ -1,5 +1,5 @@\n line 1\n line 2\n-line 3\n+line three\n line 4\n line 5"
+        );
+        assert_eq!(hunks[1].header(), "@@ -15,5 +15,4 @@");
+
+        assert_eq!(diff_hunks(&old, &new, 10).len(), 1);
+        assert!(diff_hunks(&old, &old, 3).is_empty());
+        assert_eq!(diff_hunks("", "a\nb\n", 3)[0].header(), "@@ -0,0 +1,2 @@");
+    }
+}
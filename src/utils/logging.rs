@@ -3,6 +3,8 @@
 
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
+/// Logs go to stderr so stdout carries only command output (e.g.
+/// `stats --json`, or the MCP protocol over stdio).
 pub fn init_logger(colored_output: bool, verbose: bool) {
     let level = if verbose { "debug" } else { "info" };
     let filter = EnvFilter::new(level);
@@ -14,7 +16,8 @@ pub fn init_logger(colored_output: bool, verbose: bool) {
         .with_file(true)
         .with_line_number(true)
         .compact()
-        .with_ansi(colored_output);
+        .with_ansi(colored_output)
+        .with_writer(std::io::stderr);
 
     tracing_subscriber::registry()
         .with(filter)
@@ -2,9 +2,11 @@
 // description: utility functions module exports
 // reference: internal module structure
 
+pub mod diff;
 pub mod logging;
 pub mod telemetry;
 pub mod template;
+pub mod text;
 pub mod validation;
 
 pub use telemetry::{HealthCheck, HealthReport, HealthStatus, OperationTimer, PerformanceMetrics};